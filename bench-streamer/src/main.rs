@@ -1,11 +1,10 @@
 use clap::{App, Arg};
-use solana::packet::{Packet, SharedPackets, BLOB_SIZE, PACKET_DATA_SIZE};
+use solana::packet::{Packet, PacketsRecycler, SharedPackets, BLOB_SIZE, PACKET_DATA_SIZE};
 use solana::result::Result;
-use solana::streamer::{receiver, PacketReceiver};
+use solana::streamer::{packet_channel, receiver, PacketReceiver, PACKET_CHANNEL_MAX_PACKETS};
 use std::cmp::max;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::mpsc::channel;
 use std::sync::Arc;
 use std::thread::sleep;
 use std::thread::{spawn, JoinHandle};
@@ -72,6 +71,7 @@ fn main() -> Result<()> {
 
     let mut read_channels = Vec::new();
     let mut read_threads = Vec::new();
+    let recycler = PacketsRecycler::default();
     for _ in 0..num_sockets {
         let read = solana_netutil::bind_to(port, false).unwrap();
         read.set_read_timeout(Some(Duration::new(1, 0))).unwrap();
@@ -79,12 +79,13 @@ fn main() -> Result<()> {
         addr = read.local_addr().unwrap();
         port = addr.port();
 
-        let (s_reader, r_reader) = channel();
+        let (s_reader, r_reader) = packet_channel(PACKET_CHANNEL_MAX_PACKETS);
         read_channels.push(r_reader);
         read_threads.push(receiver(
             Arc::new(read),
             exit.clone(),
             s_reader,
+            &recycler,
             "bench-streamer",
         ));
     }
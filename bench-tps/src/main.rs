@@ -9,7 +9,7 @@ use solana::gen_keys::GenKeys;
 use solana::service::Service;
 use solana::thin_client::poll_gossip_for_leader;
 use solana_metrics;
-use solana_sdk::signature::KeypairUtil;
+use solana_sdk::signature::{Keypair, KeypairUtil};
 
 use std::collections::VecDeque;
 use std::process::exit;
@@ -35,9 +35,10 @@ fn converge(
     num_nodes: usize,
 ) -> (Vec<NodeInfo>, Option<NodeInfo>, GossipService) {
     //lets spy on the network
-    let (node, gossip_socket) = ClusterInfo::spy_node();
+    let keypair = Arc::new(Keypair::new());
+    let (node, gossip_socket) = ClusterInfo::spy_node(keypair.pubkey());
     println!("Spy node: {}", node.id);
-    let mut spy_cluster_info = ClusterInfo::new(node);
+    let mut spy_cluster_info = ClusterInfo::new_with_keypair(node, keypair);
     spy_cluster_info.insert_info(leader.clone());
     spy_cluster_info.set_leader(leader.id);
     let spy_ref = Arc::new(RwLock::new(spy_cluster_info));
@@ -15,9 +15,9 @@ fn main() {
 
     let chacha = !env::var("CARGO_FEATURE_CHACHA").is_err();
     let cuda = !env::var("CARGO_FEATURE_CUDA").is_err();
-    let erasure = !env::var("CARGO_FEATURE_ERASURE").is_err();
+    let jerasure = !env::var("CARGO_FEATURE_JERASURE").is_err();
 
-    if chacha || cuda || erasure {
+    if chacha || cuda || jerasure {
         println!("cargo:rerun-if-changed=target/perf-libs");
         println!("cargo:rustc-link-search=native=target/perf-libs");
     }
@@ -37,7 +37,7 @@ fn main() {
         println!("cargo:rustc-link-lib=dylib=cuda");
         println!("cargo:rustc-link-lib=dylib=cudadevrt");
     }
-    if erasure {
+    if jerasure {
         #[cfg(any(target_os = "macos", target_os = "ios"))]
         {
             println!("cargo:rerun-if-changed=target/perf-libs/libgf_complete.dylib");
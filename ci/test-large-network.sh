@@ -39,4 +39,4 @@ fi
 
 set -x
 export SOLANA_DYNAMIC_NODES=120
-exec cargo test --release --features=erasure test_multi_node_dynamic_network -- --ignored
+exec cargo test --release --features=jerasure test_multi_node_dynamic_network -- --ignored
@@ -26,7 +26,7 @@ make -C programs/bpf/c tests
 # Must be built out of band
 make -C programs/bpf/rust/noop/ all
 
-FEATURES=bpf_c,bpf_rust,erasure,chacha
+FEATURES=bpf_c,bpf_rust,jerasure,chacha
 if [[ $(uname) = Darwin ]]; then
   ./build-perf-libs.sh
 else
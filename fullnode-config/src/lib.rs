@@ -1,3 +1,4 @@
+use log::warn;
 use serde_derive::{Deserialize, Serialize};
 use solana_sdk::signature::Keypair;
 use std::net::SocketAddr;
@@ -9,7 +10,7 @@ pub struct Config {
     /// Bind to port or address
     pub bind_port_or_address: Option<String>,
 
-    /// Detect public network address using public servers
+    /// Detect public network address using the cluster entrypoint
     pub use_public_address: bool,
 
     /// Detect network address from local machine configuration
@@ -20,7 +21,9 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn bind_addr(&self, default_port: u16) -> SocketAddr {
+    /// `entrypoint` is the gossip address of the cluster being joined, which is asked for
+    /// our public address when `use_public_address` is set
+    pub fn bind_addr(&self, default_port: u16, entrypoint: Option<&SocketAddr>) -> SocketAddr {
         let mut bind_addr =
             solana_netutil::parse_port_or_addr(&self.bind_port_or_address, default_port);
         if self.use_local_address {
@@ -28,7 +31,14 @@ impl Config {
             bind_addr.set_ip(ip);
         }
         if self.use_public_address {
-            let ip = solana_netutil::get_public_ip_addr().unwrap();
+            let ip = match entrypoint {
+                Some(entrypoint) => solana_netutil::get_public_ip_addr(entrypoint)
+                    .unwrap_or_else(|err| panic!("Unable to determine public address: {}", err)),
+                None => {
+                    warn!("No cluster entrypoint to ask for our public address, using the local address");
+                    solana_netutil::get_ip_addr().unwrap()
+                }
+            };
             bind_addr.set_ip(ip);
         }
         bind_addr
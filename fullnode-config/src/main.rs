@@ -24,7 +24,7 @@ fn main() {
                 .short("p")
                 .long("public")
                 .takes_value(false)
-                .help("Detect public network address using public servers"),
+                .help("Detect public network address using the cluster entrypoint"),
         )
         .arg(
             clap::Arg::with_name("bind")
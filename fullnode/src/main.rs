@@ -3,8 +3,11 @@ use log::*;
 use solana::client::mk_client;
 use solana::cluster_info::{Node, NodeInfo, FULLNODE_PORT_RANGE};
 use solana::fullnode::{Fullnode, FullnodeConfig};
-use solana::leader_scheduler::LeaderScheduler;
+use solana::genesis_block::GenesisBlock;
+use solana::gossip_peer_score::PeerFilter;
+use solana::leader_scheduler::{LeaderScheduler, LeaderSchedulerConfig, DEFAULT_SLOTS_PER_EPOCH};
 use solana::local_vote_signer_service::LocalVoteSignerService;
+use solana::sigverify::VerifyMode;
 use solana::socketaddr;
 use solana::thin_client::{poll_gossip_for_leader, ThinClient};
 use solana::voting_keypair::{RemoteVoteSigner, VotingKeypair};
@@ -15,14 +18,18 @@ use solana_sdk::vote_transaction::VoteTransaction;
 use solana_vote_signer::rpc::{LocalVoteSigner, VoteSigner};
 use std::fs::File;
 use std::io::{Error, ErrorKind, Result};
-use std::net::{Ipv4Addr, SocketAddr};
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::process::exit;
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::thread::sleep;
 use std::time::Duration;
 
-fn parse_identity(matches: &ArgMatches<'_>) -> (Keypair, SocketAddr) {
+fn parse_identity(
+    matches: &ArgMatches<'_>,
+    cluster_entrypoint: Option<&SocketAddr>,
+) -> (Keypair, SocketAddr) {
     if let Some(i) = matches.value_of("identity") {
         let path = i.to_string();
         if let Ok(file) = File::open(path.clone()) {
@@ -33,7 +40,7 @@ fn parse_identity(matches: &ArgMatches<'_>) -> (Keypair, SocketAddr) {
                 let keypair = config_data.keypair();
                 let node_info = NodeInfo::new_with_pubkey_socketaddr(
                     keypair.pubkey(),
-                    &config_data.bind_addr(FULLNODE_PORT_RANGE.0),
+                    &config_data.bind_addr(FULLNODE_PORT_RANGE.0, cluster_entrypoint),
                 );
 
                 (keypair, node_info.gossip)
@@ -50,6 +57,36 @@ fn parse_identity(matches: &ArgMatches<'_>) -> (Keypair, SocketAddr) {
     }
 }
 
+fn parse_gossip_peer_filter(matches: &ArgMatches<'_>) -> PeerFilter {
+    let parse_ips = |name: &str| -> Vec<IpAddr> {
+        matches
+            .values_of(name)
+            .map(|values| {
+                values
+                    .map(|ip| ip.parse().expect("failed to parse gossip peer address"))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let mut filter = PeerFilter::default();
+    filter.allowed.extend(parse_ips("gossip_allow_ip"));
+    filter.denied.extend(parse_ips("gossip_deny_ip"));
+    if let Some(ids) = matches.values_of("gossip_deny_id") {
+        for id in ids {
+            let pubkey_vec = bs58::decode(id)
+                .into_vec()
+                .expect("base58-encoded public key");
+            if pubkey_vec.len() != mem::size_of::<Pubkey>() {
+                eprintln!("Invalid gossip peer id: {}", id);
+                exit(1);
+            }
+            filter.denied_ids.insert(Pubkey::new(&pubkey_vec));
+        }
+    }
+    filter
+}
+
 fn create_and_fund_vote_account(
     client: &mut ThinClient,
     vote_account: Pubkey,
@@ -138,6 +175,30 @@ fn main() {
                 .value_name("UNIX DOMAIN SOCKET")
                 .help("Open entry stream at this unix domain socket location")
         )
+        .arg(
+            Arg::with_name("gossip_allow_ip")
+                .long("gossip-allow-ip")
+                .value_name("IP")
+                .takes_value(true)
+                .multiple(true)
+                .help("Never rate limit gossip traffic from this address"),
+        )
+        .arg(
+            Arg::with_name("gossip_deny_id")
+                .long("gossip-deny-id")
+                .value_name("PUBKEY")
+                .takes_value(true)
+                .multiple(true)
+                .help("Ignore gossip values signed by this node"),
+        )
+        .arg(
+            Arg::with_name("gossip_deny_ip")
+                .long("gossip-deny-ip")
+                .value_name("IP")
+                .takes_value(true)
+                .multiple(true)
+                .help("Ignore gossip traffic from this address"),
+        )
         .arg(
             Arg::with_name("identity")
                 .short("i")
@@ -188,6 +249,22 @@ fn main() {
                 .long("no-sigverify")
                 .help("Run without signature verification"),
         )
+        .arg(
+            Arg::with_name("sigverify")
+                .long("sigverify")
+                .value_name("MODE")
+                .takes_value(true)
+                .possible_values(&["disabled", "cpu", "cpu-batch", "cuda"])
+                .conflicts_with("no_sigverify")
+                .help("How to verify transaction signatures [default: cuda when built with it, cpu otherwise]"),
+        )
+        .arg(
+            Arg::with_name("observer")
+                .long("observer")
+                .takes_value(false)
+                .conflicts_with("signer")
+                .help("Replay the ledger and serve RPC without voting, accepting transactions or becoming leader"),
+        )
         .arg(
             Arg::with_name("rpc_port")
                 .long("rpc-port")
@@ -206,15 +283,25 @@ fn main() {
         .get_matches();
 
     let mut fullnode_config = FullnodeConfig::default();
-    fullnode_config.sigverify_disabled = matches.is_present("no_sigverify");
-    let no_signer = matches.is_present("no_signer");
+    if matches.is_present("no_sigverify") {
+        fullnode_config.sigverify_mode = VerifyMode::Disabled;
+    } else if let Some(mode) = matches.value_of("sigverify") {
+        fullnode_config.sigverify_mode = mode.parse().unwrap_or_else(|err| {
+            eprintln!("Invalid --sigverify: {}", err);
+            exit(1);
+        });
+    }
+    let observer = matches.is_present("observer");
+    let no_signer = matches.is_present("no_signer") || observer;
     fullnode_config.voting_disabled = no_signer;
+    fullnode_config.observer = observer;
+    fullnode_config.gossip_peer_filter = parse_gossip_peer_filter(&matches);
     let use_only_bootstrap_leader = matches.is_present("no_leader_rotation");
-    let (keypair, gossip) = parse_identity(&matches);
-    let ledger_path = matches.value_of("ledger").unwrap();
-    let cluster_entrypoint = matches
+    let cluster_entrypoint: Option<SocketAddr> = matches
         .value_of("network")
         .map(|network| network.parse().expect("failed to parse network address"));
+    let (keypair, gossip) = parse_identity(&matches, cluster_entrypoint.as_ref());
+    let ledger_path = matches.value_of("ledger").unwrap();
     let (_signer_service, signer_addr) = if let Some(signer_addr) = matches.value_of("signer") {
         (
             None,
@@ -248,7 +335,27 @@ fn main() {
     node.info.rpc.set_port(rpc_port);
     node.info.rpc_pubsub.set_port(rpc_pubsub_port);
 
-    let mut leader_scheduler = LeaderScheduler::default();
+    // Catch NAT/firewall misconfigurations before joining rather than silently receiving
+    // nothing from the cluster
+    if let Some(ref cluster_entrypoint) = cluster_entrypoint {
+        let mut udp_sockets = vec![&node.sockets.repair];
+        udp_sockets.extend(node.sockets.tvu.iter());
+        if !observer {
+            udp_sockets.extend(node.sockets.tpu.iter());
+        }
+        if let Err(err) = solana_netutil::verify_reachable_ports(cluster_entrypoint, &udp_sockets) {
+            error!("{}", err);
+            exit(1);
+        }
+    }
+
+    // Slots are as long as the genesis block says they are
+    let genesis_block = GenesisBlock::load(ledger_path).expect("Unable to load genesis block");
+    let mut leader_scheduler = LeaderScheduler::new(&LeaderSchedulerConfig {
+        leader_rotation_interval: genesis_block.ticks_per_slot,
+        seed_rotation_interval: DEFAULT_SLOTS_PER_EPOCH * genesis_block.ticks_per_slot,
+        ..LeaderSchedulerConfig::default()
+    });
     leader_scheduler.use_only_bootstrap_leader = use_only_bootstrap_leader;
 
     let vote_signer: Box<dyn VoteSigner + Sync + Send> = if !no_signer {
@@ -1,10 +1,15 @@
 //! A command-line executable for generating the chain's genesis block.
 
-use clap::{crate_version, value_t_or_exit, App, Arg};
+use clap::{crate_version, value_t, value_t_or_exit, App, Arg};
 use solana::db_ledger::create_new_ledger;
+use solana::erasure::{ErasureConfig, NUM_CODING, NUM_DATA};
 use solana::genesis_block::GenesisBlock;
+use solana::leader_scheduler::DEFAULT_TICKS_PER_SLOT;
+use solana::poh::{compute_hashes_per_second, compute_hashes_per_tick};
+use solana::poh_service::NUM_TICKS_PER_SECOND;
 use solana_sdk::signature::{read_keypair, KeypairUtil};
 use std::error;
+use std::time::Duration;
 
 /**
  * Bootstrap leader gets two tokens:
@@ -56,12 +61,69 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                 .required(true)
                 .help("Path to file containing keys of the mint"),
         )
+        .arg(
+            Arg::with_name("num_erasure_data")
+                .long("num-erasure-data")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Number of data blobs in an erasure set [default: 16]"),
+        )
+        .arg(
+            Arg::with_name("num_erasure_coding")
+                .long("num-erasure-coding")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Number of coding blobs in an erasure set [default: 4]"),
+        )
+        .arg(
+            Arg::with_name("hashes_per_tick")
+                .long("hashes-per-tick")
+                .value_name("NUM|auto|sleep")
+                .takes_value(true)
+                .help(
+                    "Number of PoH hashes between ticks.  \"auto\" measures the hash rate of \
+                     this machine, \"sleep\" produces ticks on a timer [default: sleep]",
+                ),
+        )
+        .arg(
+            Arg::with_name("ticks_per_slot")
+                .long("ticks-per-slot")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Number of ticks in a slot [default: 8]"),
+        )
         .get_matches();
 
     let bootstrap_leader_keypair_file = matches.value_of("bootstrap_leader_keypair_file").unwrap();
     let ledger_path = matches.value_of("ledger_path").unwrap();
     let mint_keypair_file = matches.value_of("mint_keypair_file").unwrap();
     let num_tokens = value_t_or_exit!(matches, "num_tokens", u64);
+    let num_erasure_data = value_t!(matches, "num_erasure_data", usize).unwrap_or(NUM_DATA);
+    let num_erasure_coding = value_t!(matches, "num_erasure_coding", usize).unwrap_or(NUM_CODING);
+    if num_erasure_coding == 0 || num_erasure_coding > num_erasure_data {
+        Err("--num-erasure-coding must be between 1 and --num-erasure-data")?;
+    }
+    let hashes_per_tick = match matches.value_of("hashes_per_tick").unwrap_or("sleep") {
+        "sleep" => None,
+        "auto" => {
+            let hashes_per_second = compute_hashes_per_second(Duration::from_secs(1));
+            let hashes_per_tick =
+                compute_hashes_per_tick(hashes_per_second, NUM_TICKS_PER_SECOND as u64);
+            println!(
+                "Measured {} hashes per second, using {} hashes per tick",
+                hashes_per_second, hashes_per_tick
+            );
+            Some(hashes_per_tick)
+        }
+        _ => Some(value_t_or_exit!(matches, "hashes_per_tick", u64)),
+    };
+    if hashes_per_tick == Some(0) {
+        Err("--hashes-per-tick must be at least 1")?;
+    }
+    let ticks_per_slot = value_t!(matches, "ticks_per_slot", u64).unwrap_or(DEFAULT_TICKS_PER_SLOT);
+    if ticks_per_slot == 0 {
+        Err("--ticks-per-slot must be at least 1")?;
+    }
 
     let bootstrap_leader_keypair = read_keypair(bootstrap_leader_keypair_file)?;
     let mint_keypair = read_keypair(mint_keypair_file)?;
@@ -71,6 +133,9 @@ fn main() -> Result<(), Box<dyn error::Error>> {
         tokens: num_tokens,
         bootstrap_leader_id: bootstrap_leader_keypair.pubkey(),
         bootstrap_leader_tokens: BOOTSTRAP_LEADER_TOKENS,
+        erasure_config: ErasureConfig::new(num_erasure_data, num_erasure_coding),
+        hashes_per_tick,
+        ticks_per_slot,
     };
 
     create_new_ledger(ledger_path, &genesis_block)?;
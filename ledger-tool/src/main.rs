@@ -1,8 +1,14 @@
+mod stats;
+
+use crate::stats::LedgerStats;
 use clap::{crate_version, App, Arg, SubCommand};
-use solana::bank::Bank;
+use solana::bank::{Bank, VERIFY_BLOCK_SIZE};
 use solana::db_ledger::DbLedger;
+use solana::entry::EntrySlice;
 use solana::genesis_block::GenesisBlock;
-use std::io::{stdout, Write};
+use solana::ledger_archive::{export_ledger, import_ledger};
+use std::fs::File;
+use std::io::{stdout, BufReader, BufWriter, Write};
 use std::process::exit;
 
 fn main() {
@@ -24,7 +30,7 @@ fn main() {
                 .long("head")
                 .value_name("NUM")
                 .takes_value(true)
-                .help("Limit to at most the first NUM entries in ledger\n  (only applies to verify, print, json commands)"),
+                .help("Limit to at most the first NUM entries in ledger\n  (only applies to verify, print, json, stats commands)"),
         )
         .arg(
             Arg::with_name("min-hashes")
@@ -43,10 +49,120 @@ fn main() {
         .subcommand(SubCommand::with_name("print").about("Print the ledger"))
         .subcommand(SubCommand::with_name("json").about("Print the ledger in JSON format"))
         .subcommand(SubCommand::with_name("verify").about("Verify the ledger's PoH"))
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Replay the ledger and report transaction statistics")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(&["table", "json"])
+                        .default_value("table")
+                        .help("Output format"),
+                )
+                .arg(
+                    Arg::with_name("top")
+                        .long("top")
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help("Number of accounts to list by lock count"),
+                )
+                .arg(
+                    Arg::with_name("ticks-per-slot")
+                        .long("ticks-per-slot")
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .help("Number of ticks in a slot [default: the genesis block's]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Export a range of ledger entries into an archive")
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .help("Index of the first entry to export [default: 0]"),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .help("Stop exporting before the entry at index NUM\n  [default: end of ledger]"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Archive file to write"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Create the ledger from an archive written by export")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Archive file to read"),
+                ),
+        )
         .get_matches();
 
     let ledger_path = matches.value_of("ledger").unwrap();
 
+    match matches.subcommand() {
+        ("export", Some(export_matches)) => {
+            let from = match export_matches.value_of("from") {
+                Some(from) => from.parse().expect("please pass a number for --from"),
+                None => 0,
+            };
+            let to = export_matches
+                .value_of("to")
+                .map(|to| to.parse().expect("please pass a number for --to"));
+            let archive_path = export_matches.value_of("file").unwrap();
+
+            let file = File::create(archive_path).unwrap_or_else(|err| {
+                eprintln!("Failed to create {}: {}", archive_path, err);
+                exit(1);
+            });
+            match export_ledger(ledger_path, from, to, BufWriter::new(file)) {
+                Ok(num_entries) => println!("Exported {} entries", num_entries),
+                Err(err) => {
+                    eprintln!("Failed to export ledger at {}: {:?}", ledger_path, err);
+                    exit(1);
+                }
+            }
+            return;
+        }
+        ("import", Some(import_matches)) => {
+            let archive_path = import_matches.value_of("file").unwrap();
+
+            let file = File::open(archive_path).unwrap_or_else(|err| {
+                eprintln!("Failed to open {}: {}", archive_path, err);
+                exit(1);
+            });
+            match import_ledger(BufReader::new(file), ledger_path) {
+                Ok(header) => println!(
+                    "Imported {} entries starting at entry {}",
+                    header.num_entries, header.start_index
+                ),
+                Err(err) => {
+                    eprintln!("Failed to import ledger to {}: {:?}", ledger_path, err);
+                    exit(1);
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
+
     let genesis_block = GenesisBlock::load(ledger_path).unwrap_or_else(|err| {
         eprintln!(
             "Failed to open ledger genesis_block at {}: {}",
@@ -113,31 +229,75 @@ fn main() {
         }
         ("verify", _) => {
             let bank = Bank::new(&genesis_block);
+            let continue_on_failure = matches.is_present("continue");
             let mut last_id = bank.last_id();
             let mut num_entries = 0;
-            for (i, entry) in entries.enumerate() {
-                if i >= head {
-                    break;
-                }
-
-                if !entry.verify(&last_id) {
-                    eprintln!("entry.verify() failed at entry[{}]", i + 2);
-                    if !matches.is_present("continue") {
+            let mut entries = entries.take(head).peekable();
+            while entries.peek().is_some() {
+                // Verify the PoH a block at a time, in parallel
+                let block: Vec<_> = entries.by_ref().take(VERIFY_BLOCK_SIZE).collect();
+                let mut start = 0;
+                let mut start_id = last_id;
+                while let Some(i) = block[start..].first_invalid_entry(&start_id) {
+                    eprintln!(
+                        "entry.verify() failed at entry[{}]",
+                        num_entries + start + i + 2
+                    );
+                    if !continue_on_failure {
                         exit(1);
                     }
+                    start += i + 1;
+                    start_id = block[start - 1].id;
                 }
-                last_id = entry.id;
-                num_entries += 1;
 
-                if let Err(e) = bank.process_entry(&entry) {
-                    eprintln!("verify failed at entry[{}], err: {:?}", i + 2, e);
-                    if !matches.is_present("continue") {
-                        exit(1);
+                for (i, entry) in block.iter().enumerate() {
+                    if let Err(e) = bank.process_entry(entry) {
+                        eprintln!(
+                            "verify failed at entry[{}], err: {:?}",
+                            num_entries + i + 2,
+                            e
+                        );
+                        if !continue_on_failure {
+                            exit(1);
+                        }
                     }
                 }
+                last_id = block.last().unwrap().id;
+                num_entries += block.len();
             }
             println!("{} entries.  last_id={:?}", num_entries, last_id);
         }
+        ("stats", Some(stats_matches)) => {
+            let num_top_accounts = stats_matches
+                .value_of("top")
+                .unwrap()
+                .parse()
+                .expect("please pass a number for --top");
+            let ticks_per_slot = match stats_matches.value_of("ticks-per-slot") {
+                Some(ticks) => match ticks.parse() {
+                    Ok(0) | Err(_) => {
+                        eprintln!("please pass a positive number for --ticks-per-slot");
+                        exit(1);
+                    }
+                    Ok(ticks) => ticks,
+                },
+                None => genesis_block.ticks_per_slot,
+            };
+
+            let bank = Bank::new(&genesis_block);
+            let mut stats = LedgerStats::default();
+            for entry in entries.take(head) {
+                stats.record_entry(&entry, &bank, ticks_per_slot);
+            }
+
+            if stats_matches.value_of("format") == Some("json") {
+                serde_json::to_writer_pretty(stdout(), &stats.to_json(num_top_accounts))
+                    .expect("serialize");
+                println!();
+            } else {
+                stats.print_table(num_top_accounts);
+            }
+        }
         ("", _) => {
             eprintln!("{}", matches.usage());
             exit(1);
@@ -0,0 +1,150 @@
+//! The `stats` module accumulates per-program, per-account and per-slot
+//! statistics while a ledger is replayed through a `Bank`.
+
+use serde_json::{json, Value};
+use solana::bank::Bank;
+use solana::entry::Entry;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[derive(Default)]
+pub struct LedgerStats {
+    pub num_entries: u64,
+    pub num_ticks: u64,
+    pub num_transaction_entries: u64,
+    pub total_hashes: u64,
+    pub num_succeeded: u64,
+    pub num_failed: u64,
+    /// Number of transactions that invoke each program at least once
+    pub transactions_per_program: HashMap<Pubkey, u64>,
+    /// Number of transactions that lock each account.  Every account key of a
+    /// transaction is locked by `Accounts::lock_accounts`.
+    pub account_locks: HashMap<Pubkey, u64>,
+    pub entries_per_slot: BTreeMap<u64, u64>,
+}
+
+// Sort by descending count, breaking ties by key so the output is stable
+fn sorted_counts(counts: &HashMap<Pubkey, u64>) -> Vec<(Pubkey, u64)> {
+    let mut counts: Vec<_> = counts.iter().map(|(key, count)| (*key, *count)).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts
+}
+
+impl LedgerStats {
+    /// Replay `entry` through `bank` and account for it.  `ticks_per_slot`
+    /// maps the entry's tick height to a slot.
+    pub fn record_entry(&mut self, entry: &Entry, bank: &Bank, ticks_per_slot: u64) {
+        self.num_entries += 1;
+        self.total_hashes += entry.num_hashes;
+        *self
+            .entries_per_slot
+            .entry(entry.tick_height / ticks_per_slot)
+            .or_insert(0) += 1;
+
+        if entry.is_tick() {
+            self.num_ticks += 1;
+            if let Err(err) = bank.process_entry(entry) {
+                eprintln!("failed to register tick {:?}: {:?}", entry.id, err);
+            }
+            return;
+        }
+
+        self.num_transaction_entries += 1;
+        for tx in &entry.transactions {
+            let program_ids: HashSet<_> = tx.program_ids.iter().collect();
+            for program_id in program_ids {
+                *self
+                    .transactions_per_program
+                    .entry(*program_id)
+                    .or_insert(0) += 1;
+            }
+            for key in &tx.account_keys {
+                *self.account_locks.entry(*key).or_insert(0) += 1;
+            }
+        }
+
+        for result in bank.process_transactions(&entry.transactions) {
+            if result.is_ok() {
+                self.num_succeeded += 1;
+            } else {
+                self.num_failed += 1;
+            }
+        }
+    }
+
+    pub fn average_num_hashes(&self) -> f64 {
+        if self.num_entries == 0 {
+            0.0
+        } else {
+            self.total_hashes as f64 / self.num_entries as f64
+        }
+    }
+
+    pub fn top_programs(&self) -> Vec<(Pubkey, u64)> {
+        sorted_counts(&self.transactions_per_program)
+    }
+
+    pub fn top_accounts(&self, num: usize) -> Vec<(Pubkey, u64)> {
+        let mut accounts = sorted_counts(&self.account_locks);
+        accounts.truncate(num);
+        accounts
+    }
+
+    pub fn to_json(&self, num_top_accounts: usize) -> Value {
+        let programs: Vec<_> = self
+            .top_programs()
+            .into_iter()
+            .map(|(id, count)| json!({"program_id": id.to_string(), "transactions": count}))
+            .collect();
+        let accounts: Vec<_> = self
+            .top_accounts(num_top_accounts)
+            .into_iter()
+            .map(|(id, count)| json!({"account": id.to_string(), "locks": count}))
+            .collect();
+        let slots: Vec<_> = self
+            .entries_per_slot
+            .iter()
+            .map(|(slot, count)| json!({"slot": slot, "entries": count}))
+            .collect();
+
+        json!({
+            "entries": self.num_entries,
+            "ticks": self.num_ticks,
+            "transaction_entries": self.num_transaction_entries,
+            "average_num_hashes": self.average_num_hashes(),
+            "transactions_succeeded": self.num_succeeded,
+            "transactions_failed": self.num_failed,
+            "programs": programs,
+            "top_accounts": accounts,
+            "entries_per_slot": slots,
+        })
+    }
+
+    pub fn print_table(&self, num_top_accounts: usize) {
+        println!("Entries:               {}", self.num_entries);
+        println!("  Ticks:               {}", self.num_ticks);
+        println!("  Transaction entries: {}", self.num_transaction_entries);
+        println!("Average num_hashes:    {:.2}", self.average_num_hashes());
+        println!("Transactions:");
+        println!("  Succeeded:           {}", self.num_succeeded);
+        println!("  Failed:              {}", self.num_failed);
+
+        println!();
+        println!("{:<46} {:>12}", "Program Id", "Transactions");
+        for (id, count) in self.top_programs() {
+            println!("{:<46} {:>12}", id.to_string(), count);
+        }
+
+        println!();
+        println!("{:<46} {:>12}", "Account", "Locks");
+        for (id, count) in self.top_accounts(num_top_accounts) {
+            println!("{:<46} {:>12}", id.to_string(), count);
+        }
+
+        println!();
+        println!("{:>12} {:>12}", "Slot", "Entries");
+        for (slot, count) in &self.entries_per_slot {
+            println!("{:>12} {:>12}", slot, count);
+        }
+    }
+}
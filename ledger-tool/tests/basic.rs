@@ -1,5 +1,10 @@
-use solana::db_ledger::create_tmp_sample_ledger;
+use solana::db_ledger::{
+    create_tmp_sample_ledger, get_tmp_ledger_path, DbLedger, DEFAULT_SLOT_HEIGHT,
+};
+use solana::entry::Entry;
 use solana_sdk::signature::{Keypair, KeypairUtil};
+use solana_sdk::system_program;
+use solana_sdk::system_transaction::SystemTransaction;
 
 use assert_cmd::prelude::*;
 use std::process::Command;
@@ -59,3 +64,107 @@ fn nominal() {
     assert!(output.status.success());
     assert_eq!(count_newlines(&output.stdout), 0);
 }
+
+#[test]
+fn export_import() {
+    let keypair = Arc::new(Keypair::new());
+    let (_, ledger_path, _, _) = create_tmp_sample_ledger(
+        "test_ledger_tool_export_import",
+        100,
+        9,
+        keypair.pubkey(),
+        50,
+    );
+    let archive_path = format!("{}.archive", ledger_path);
+
+    // Export everything but the first two entries
+    let output = run_ledger_tool(&["-l", &ledger_path, "export", "--from", "2", &archive_path]);
+    assert!(output.status.success());
+
+    let import_path = get_tmp_ledger_path("test_ledger_tool_export_import_import");
+    let output = run_ledger_tool(&["-l", &import_path, "import", &archive_path]);
+    assert!(output.status.success());
+
+    let output = run_ledger_tool(&["-l", &import_path, "print"]);
+    assert!(output.status.success());
+    assert_eq!(count_newlines(&output.stdout), 8);
+
+    // A second import over the same ledger is refused
+    let output = run_ledger_tool(&["-l", &import_path, "import", &archive_path]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn stats() {
+    let keypair = Arc::new(Keypair::new());
+    let (mint_keypair, ledger_path, entry_height, last_id) =
+        create_tmp_sample_ledger("test_ledger_tool_stats", 100, 9, keypair.pubkey(), 50);
+
+    // Append an entry with a transfer from the mint, which succeeds, and one from an
+    // account that doesn't exist, which fails
+    let to = Keypair::new().pubkey();
+    let unfunded_keypair = Keypair::new();
+    let unfunded_to = Keypair::new().pubkey();
+    let transactions = vec![
+        SystemTransaction::new_account(&mint_keypair, to, 10, last_id, 0),
+        SystemTransaction::new_move(&unfunded_keypair, unfunded_to, 10, last_id, 0),
+    ];
+    let db_ledger = DbLedger::open(&ledger_path).unwrap();
+    db_ledger
+        .write_entries(
+            DEFAULT_SLOT_HEIGHT,
+            entry_height,
+            &[Entry::new(&last_id, 0, 1, transactions)],
+        )
+        .unwrap();
+    drop(db_ledger);
+
+    let output = run_ledger_tool(&["-l", &ledger_path, "stats"]);
+    assert!(output.status.success());
+    let table = String::from_utf8(output.stdout).unwrap();
+    assert!(table.contains(&system_program::id().to_string()));
+    assert!(table.contains(&mint_keypair.pubkey().to_string()));
+
+    let output = run_ledger_tool(&["-l", &ledger_path, "stats", "--format", "json"]);
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["entries"], 11);
+    assert_eq!(stats["ticks"], 10);
+    assert_eq!(stats["transaction_entries"], 1);
+    assert_eq!(stats["transactions_succeeded"], 1);
+    assert_eq!(stats["transactions_failed"], 1);
+
+    // Both transactions invoke the system program, and lock their two accounts each
+    let programs = stats["programs"].as_array().unwrap();
+    assert_eq!(programs.len(), 1);
+    assert_eq!(programs[0]["program_id"], system_program::id().to_string());
+    assert_eq!(programs[0]["transactions"], 2);
+
+    let top_accounts = stats["top_accounts"].as_array().unwrap();
+    let mut accounts: Vec<_> = top_accounts
+        .iter()
+        .map(|account| {
+            assert_eq!(account["locks"], 1);
+            account["account"].as_str().unwrap().to_string()
+        })
+        .collect();
+    accounts.sort();
+    let mut expected_accounts: Vec<_> = [
+        mint_keypair.pubkey(),
+        to,
+        unfunded_keypair.pubkey(),
+        unfunded_to,
+    ]
+    .iter()
+    .map(|id| id.to_string())
+    .collect();
+    expected_accounts.sort();
+    assert_eq!(accounts, expected_accounts);
+
+    // Only replay the first 5 items
+    let output = run_ledger_tool(&["-l", &ledger_path, "-n", "5", "stats", "--format", "json"]);
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["entries"], 5);
+    assert_eq!(stats["transaction_entries"], 0);
+}
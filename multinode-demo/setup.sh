@@ -21,7 +21,7 @@ Creates a fullnode configuration
  -n num_tokens  - Number of tokens to create
  -l             - Detect network address from local machine configuration, which
                   may be a private IP address unaccessible on the Intenet (default)
- -p             - Detect public address using public Internet servers
+ -p             - Detect public address using the cluster entrypoint
  -t node_type   - Create configuration files only for this kind of node.  Valid
                   options are bootstrap-leader or fullnode.  Creates configuration files
                   for both by default
@@ -0,0 +1,262 @@
+//! The `ip_echo_server` module implements a tiny TCP service, run by every fullnode on its
+//! gossip port, that tells a connecting node which IP address it was seen from and
+//! optionally sends a datagram to a handful of the node's UDP ports so that it can confirm
+//! they are reachable from the outside.
+use bincode::{deserialize_from, serialize_into};
+use log::*;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{sleep, Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Maximum number of UDP ports the server will probe for a single request
+pub const MAX_PORT_COUNT_PER_MESSAGE: usize = 4;
+
+/// How long either side waits on the other before giving up
+const IP_ECHO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most connections the server handles at once, further ones are dropped
+const MAX_CONCURRENT_CONNECTIONS: usize = 64;
+
+/// Payload of the datagram sent to each requested UDP port
+const PROBE_DATAGRAM: [u8; 4] = [0; 4];
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct IpEchoServerMessage {
+    /// UDP ports to probe, zero entries are ignored
+    pub udp_ports: [u16; MAX_PORT_COUNT_PER_MESSAGE],
+}
+
+impl IpEchoServerMessage {
+    pub fn new(udp_ports: &[u16]) -> Self {
+        assert!(udp_ports.len() <= MAX_PORT_COUNT_PER_MESSAGE);
+        let mut msg = Self::default();
+        msg.udp_ports[..udp_ports.len()].copy_from_slice(udp_ports);
+        msg
+    }
+}
+
+fn process_connection(mut stream: TcpStream, peer_addr: SocketAddr) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(IP_ECHO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IP_ECHO_TIMEOUT))?;
+
+    let msg: IpEchoServerMessage = deserialize_from(&mut stream)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+    trace!("ip echo request from {}: {:?}", peer_addr, msg);
+
+    let udp_socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0))?;
+    for udp_port in msg.udp_ports.iter().filter(|port| **port != 0) {
+        let addr = SocketAddr::new(peer_addr.ip(), *udp_port);
+        if let Err(err) = udp_socket.send_to(&PROBE_DATAGRAM, addr) {
+            warn!("ip echo probe of {} failed: {}", addr, err);
+        }
+    }
+
+    serialize_into(&mut stream, &peer_addr.ip())
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))
+}
+
+/// Serves ip echo requests on `tcp_listener` until `exit` is set
+pub fn ip_echo_server(tcp_listener: TcpListener, exit: Arc<AtomicBool>) -> JoinHandle<()> {
+    tcp_listener
+        .set_nonblocking(true)
+        .expect("ip echo listener set_nonblocking");
+    let num_connections = Arc::new(AtomicUsize::new(0));
+    Builder::new()
+        .name("solana-ip-echo-server".to_string())
+        .spawn(move || loop {
+            if exit.load(Ordering::Relaxed) {
+                break;
+            }
+            match tcp_listener.accept() {
+                Ok((stream, peer_addr)) => {
+                    // Each connection gets its own thread so that a client that never
+                    // sends its request doesn't hold up everyone behind it
+                    if num_connections.fetch_add(1, Ordering::Relaxed) >= MAX_CONCURRENT_CONNECTIONS
+                    {
+                        num_connections.fetch_sub(1, Ordering::Relaxed);
+                        info!(
+                            "ip echo server busy, dropping connection from {}",
+                            peer_addr
+                        );
+                        continue;
+                    }
+                    let num_connections = num_connections.clone();
+                    let spawned = Builder::new()
+                        .name("solana-ip-echo-connection".to_string())
+                        .spawn(move || {
+                            if let Err(err) = process_connection(stream, peer_addr) {
+                                info!("ip echo request from {} failed: {}", peer_addr, err);
+                            }
+                            num_connections.fetch_sub(1, Ordering::Relaxed);
+                        });
+                    if let Err(err) = spawned {
+                        warn!("ip echo connection thread failed to start: {}", err);
+                        num_connections.fetch_sub(1, Ordering::Relaxed);
+                    }
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    sleep(Duration::from_millis(100));
+                }
+                Err(err) => {
+                    warn!("ip echo accept failed: {}", err);
+                    sleep(Duration::from_millis(100));
+                }
+            }
+        })
+        .unwrap()
+}
+
+fn ip_echo_server_request(
+    ip_echo_server_addr: &SocketAddr,
+    msg: &IpEchoServerMessage,
+) -> Result<IpAddr, String> {
+    let mut stream = TcpStream::connect_timeout(ip_echo_server_addr, IP_ECHO_TIMEOUT)
+        .map_err(|err| format!("Unable to connect to {}: {}", ip_echo_server_addr, err))?;
+    stream
+        .set_read_timeout(Some(IP_ECHO_TIMEOUT))
+        .map_err(|err| err.to_string())?;
+    stream
+        .set_write_timeout(Some(IP_ECHO_TIMEOUT))
+        .map_err(|err| err.to_string())?;
+
+    serialize_into(&mut stream, msg).map_err(|err| err.to_string())?;
+    deserialize_from(&mut stream)
+        .map_err(|err| format!("Invalid response from {}: {}", ip_echo_server_addr, err))
+}
+
+/// Asks the ip echo server at `ip_echo_server_addr` which IP address this machine
+/// connects from
+pub fn get_public_ip_addr(ip_echo_server_addr: &SocketAddr) -> Result<IpAddr, String> {
+    ip_echo_server_request(ip_echo_server_addr, &IpEchoServerMessage::default())
+}
+
+fn probe_udp_ports(
+    ip_echo_server_addr: &SocketAddr,
+    sockets_by_port: &BTreeMap<u16, Vec<&UdpSocket>>,
+) -> Result<BTreeSet<u16>, String> {
+    let ports: Vec<u16> = sockets_by_port.keys().cloned().collect();
+    let mut unreachable_ports = BTreeSet::new();
+    for ports in ports.chunks(MAX_PORT_COUNT_PER_MESSAGE) {
+        ip_echo_server_request(ip_echo_server_addr, &IpEchoServerMessage::new(ports))?;
+
+        let mut pending: BTreeSet<u16> = ports.iter().cloned().collect();
+        let deadline = Instant::now() + IP_ECHO_TIMEOUT;
+        let mut buf = PROBE_DATAGRAM;
+        while !pending.is_empty() && Instant::now() < deadline {
+            pending.retain(|port| {
+                !sockets_by_port[port]
+                    .iter()
+                    .any(|udp_socket| udp_socket.recv(&mut buf).is_ok())
+            });
+            sleep(Duration::from_millis(10));
+        }
+        unreachable_ports.extend(pending);
+    }
+    Ok(unreachable_ports)
+}
+
+/// Asks the ip echo server at `ip_echo_server_addr` to send a datagram to the port of each
+/// of `udp_sockets`, and fails with the list of ports that did not receive one.  Sockets
+/// sharing a port (see `multi_bind_in_range`) only need one of them to receive the probe.
+pub fn verify_reachable_ports(
+    ip_echo_server_addr: &SocketAddr,
+    udp_sockets: &[&UdpSocket],
+) -> Result<(), String> {
+    let mut sockets_by_port: BTreeMap<u16, Vec<&UdpSocket>> = BTreeMap::new();
+    for udp_socket in udp_sockets {
+        let port = udp_socket
+            .local_addr()
+            .map_err(|err| err.to_string())?
+            .port();
+        sockets_by_port.entry(port).or_default().push(udp_socket);
+        udp_socket
+            .set_nonblocking(true)
+            .map_err(|err| err.to_string())?;
+    }
+
+    let unreachable_ports = probe_udp_ports(ip_echo_server_addr, &sockets_by_port);
+    for udp_socket in udp_sockets {
+        udp_socket
+            .set_nonblocking(false)
+            .map_err(|err| err.to_string())?;
+    }
+
+    let unreachable_ports = unreachable_ports?;
+    if unreachable_ports.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "UDP port(s) {:?} are not reachable from {}",
+            unreachable_ports, ip_echo_server_addr
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bind_in_range;
+
+    fn start_server() -> (SocketAddr, Arc<AtomicBool>, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let exit = Arc::new(AtomicBool::new(false));
+        let t = ip_echo_server(listener, exit.clone());
+        (addr, exit, t)
+    }
+
+    #[test]
+    fn test_get_public_ip_addr() {
+        let (addr, exit, t) = start_server();
+        assert_eq!(
+            get_public_ip_addr(&addr),
+            Ok(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)))
+        );
+        exit.store(true, Ordering::Relaxed);
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn test_get_public_ip_addr_stalled_client() {
+        let (addr, exit, t) = start_server();
+
+        // A client that connects and never sends its request doesn't hold up the next one
+        let _stalled = TcpStream::connect(&addr).unwrap();
+        let start = Instant::now();
+        assert_eq!(
+            get_public_ip_addr(&addr),
+            Ok(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)))
+        );
+        assert!(start.elapsed() < IP_ECHO_TIMEOUT);
+
+        exit.store(true, Ordering::Relaxed);
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn test_verify_reachable_ports() {
+        let (addr, exit, t) = start_server();
+        let sockets: Vec<_> = (0..6)
+            .map(|_| bind_in_range((4000, 5000)).unwrap().1)
+            .collect();
+        let sockets: Vec<_> = sockets.iter().collect();
+        assert_eq!(verify_reachable_ports(&addr, &sockets), Ok(()));
+        exit.store(true, Ordering::Relaxed);
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn test_verify_reachable_ports_no_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert!(verify_reachable_ports(&addr, &[&socket]).is_err());
+    }
+}
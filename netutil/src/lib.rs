@@ -4,12 +4,14 @@ use nix::sys::socket::setsockopt;
 use nix::sys::socket::sockopt::{ReuseAddr, ReusePort};
 use pnet_datalink as datalink;
 use rand::{thread_rng, Rng};
-use reqwest;
 use socket2::{Domain, SockAddr, Socket, Type};
 use std::io;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, UdpSocket};
 use std::os::unix::io::AsRawFd;
 
+mod ip_echo_server;
+pub use ip_echo_server::*;
+
 /// A data type representing a public Udp socket
 pub struct UdpSocketPair {
     pub addr: SocketAddr,    // Public address of the socket
@@ -17,19 +19,6 @@ pub struct UdpSocketPair {
     pub sender: UdpSocket,   // Locally bound socket to send via public address
 }
 
-/// Tries to determine the public IP address of this machine
-pub fn get_public_ip_addr() -> Result<IpAddr, String> {
-    let body = reqwest::get("http://ifconfig.co/ip")
-        .map_err(|err| err.to_string())?
-        .text()
-        .map_err(|err| err.to_string())?;
-
-    match body.lines().next() {
-        Some(ip) => Result::Ok(ip.parse().unwrap()),
-        None => Result::Err("Empty response body".to_string()),
-    }
-}
-
 pub fn parse_port_or_addr(optstr: &Option<String>, default_port: u16) -> SocketAddr {
     let daddr = SocketAddr::from(([0, 0, 0, 0], default_port));
 
@@ -175,6 +164,38 @@ pub fn bind_to(port: u16, reuseaddr: bool) -> io::Result<UdpSocket> {
     }
 }
 
+// binds both a UdpSocket and a TcpListener to the same port
+pub fn bind_common(port: u16, reuseaddr: bool) -> io::Result<(UdpSocket, TcpListener)> {
+    let udp_socket = bind_to(port, reuseaddr)?;
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port);
+    let tcp_listener = TcpListener::bind(addr)?;
+    Ok((udp_socket, tcp_listener))
+}
+
+// like bind_in_range, but the port must also be available for a TcpListener
+pub fn bind_common_in_range(range: (u16, u16)) -> io::Result<(u16, (UdpSocket, TcpListener))> {
+    let (start, end) = range;
+    let mut tries_left = end - start;
+    let mut rand_port = thread_rng().gen_range(start, end);
+    loop {
+        match bind_common(rand_port, false) {
+            Ok(sockets) => {
+                break Result::Ok((rand_port, sockets));
+            }
+            Err(err) => {
+                if tries_left == 0 {
+                    return Err(err);
+                }
+            }
+        }
+        rand_port += 1;
+        if rand_port == end {
+            rand_port = start;
+        }
+        tries_left -= 1;
+    }
+}
+
 pub fn find_available_port_in_range(range: (u16, u16)) -> io::Result<u16> {
     let (start, end) = range;
     let mut tries_left = end - start;
@@ -305,6 +326,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_bind_common_in_range() {
+        let (port, (udp_socket, tcp_listener)) = bind_common_in_range((3100, 3150)).unwrap();
+        assert!(3100 <= port && port < 3150);
+        assert_eq!(udp_socket.local_addr().unwrap().port(), port);
+        assert_eq!(tcp_listener.local_addr().unwrap().port(), port);
+        assert!(bind_common(port, false).is_err());
+    }
+
     #[test]
     #[should_panic]
     fn test_bind_in_range_nil() {
@@ -10,19 +10,7 @@ use solana_sdk::native_program::ProgramError;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::solana_entrypoint;
 use solana_sdk::storage_program::*;
-
-pub const TOTAL_VALIDATOR_REWARDS: u64 = 1000;
-pub const TOTAL_REPLICATOR_REWARDS: u64 = 1000;
-
-fn count_valid_proofs(proofs: &[ProofStatus]) -> u64 {
-    let mut num = 0;
-    for proof in proofs {
-        if let ProofStatus::Valid = proof {
-            num += 1;
-        }
-    }
-    num
-}
+use solana_sdk::vote_program::{self, VoteState};
 
 solana_entrypoint!(entrypoint);
 fn entrypoint(
@@ -33,7 +21,7 @@ fn entrypoint(
 ) -> Result<(), ProgramError> {
     solana_logger::setup();
 
-    if keyed_accounts.len() != 2 {
+    if keyed_accounts.len() < 2 {
         // keyed_accounts[1] should be the main storage key
         // to access its userdata
         Err(ProgramError::InvalidArgument)?;
@@ -55,6 +43,16 @@ fn entrypoint(
     }
 
     if let Ok(syscall) = bincode::deserialize(data) {
+        // Reward claims take an extra account, the pool rewards are paid out of, and
+        // validations the validator's vote account
+        let num_accounts = match syscall {
+            StorageProgram::ClaimStorageReward { .. } | StorageProgram::ProofValidation { .. } => 3,
+            _ => 2,
+        };
+        if keyed_accounts.len() != num_accounts {
+            Err(ProgramError::InvalidArgument)?;
+        }
+
         let mut storage_account_state = if let Ok(storage_account_state) =
             bincode::deserialize(&keyed_accounts[1].account.userdata)
         {
@@ -90,6 +88,16 @@ fn entrypoint(
                     sha_state,
                     signature,
                 };
+
+                // The key has to be derived from the current challenge, otherwise
+                // one encryption could be resubmitted every epoch
+                if !proof_info.verify(&storage_account_state.id, entry_height) {
+                    info!(
+                        "{} didn't sign challenge {:?} for entry_height {}",
+                        proof_info.id, storage_account_state.id, entry_height
+                    );
+                    return Err(ProgramError::InvalidArgument);
+                }
                 storage_account_state.proofs[segment_index].push(proof_info);
             }
             StorageProgram::AdvertiseStorageLastId { id, entry_height } => {
@@ -104,8 +112,12 @@ fn entrypoint(
                 }
 
                 storage_account_state.entry_height = entry_height;
+                storage_account_state.previous_id = storage_account_state.id;
                 storage_account_state.id = id;
 
+                // the proofs validated last epoch can now be claimed for
+                storage_account_state.reward_proofs = storage_account_state.previous_proofs.clone();
+
                 // move the proofs to previous_proofs
                 storage_account_state.previous_proofs = storage_account_state.proofs.clone();
                 storage_account_state.proofs.clear();
@@ -120,6 +132,11 @@ fn entrypoint(
                 storage_account_state
                     .lockout_validations
                     .resize(segments as usize, Vec::new());
+
+                storage_account_state.reward_claims.clear();
+                storage_account_state
+                    .reward_claims
+                    .resize(storage_account_state.reward_validations.len(), Vec::new());
             }
             StorageProgram::ProofValidation {
                 entry_height,
@@ -130,40 +147,97 @@ fn entrypoint(
                 }
 
                 let segment_index = get_segment_from_entry(entry_height);
-                if storage_account_state.previous_proofs[segment_index].len() != proof_mask.len() {
+                if segment_index >= storage_account_state.previous_proofs.len()
+                    || storage_account_state.previous_proofs[segment_index].len()
+                        != proof_mask.len()
+                {
+                    return Err(ProgramError::InvalidArgument);
+                }
+
+                // Only validators staking a vote account get a say, weighted by
+                // their stake
+                let id = *keyed_accounts[0].signer_key().unwrap();
+                let is_staker = vote_program::check_id(&keyed_accounts[2].account.owner)
+                    && VoteState::deserialize(&keyed_accounts[2].account.userdata)
+                        .map(|vote_state| vote_state.staker_id == id)
+                        .unwrap_or(false);
+                let stake = keyed_accounts[0].account.tokens;
+                if !is_staker || stake == 0 {
+                    info!(
+                        "{} isn't staking vote account {:?}",
+                        id,
+                        keyed_accounts[2].unsigned_key()
+                    );
                     return Err(ProgramError::InvalidArgument);
                 }
 
-                // TODO: Check that each proof mask matches the signature
-                /*for (i, entry) in proof_mask.iter().enumerate() {
-                    if storage_account_state.previous_proofs[segment_index][i] != signature.as_ref[0] {
-                        return Err(ProgramError::InvalidArgument);
-                    }
-                }*/
+                // Replicators don't get to vouch for their own proofs
+                if storage_account_state.previous_proofs[segment_index]
+                    .iter()
+                    .zip(&proof_mask)
+                    .any(|(proof, status)| proof.id == id && *status != ProofStatus::Skipped)
+                {
+                    info!("{} can't validate its own proofs", id);
+                    return Err(ProgramError::InvalidArgument);
+                }
+
+                if storage_account_state.lockout_validations[segment_index]
+                    .iter()
+                    .any(|validation| validation.id == id)
+                {
+                    info!("segment {} already validated by {}", segment_index, id);
+                    return Err(ProgramError::InvalidArgument);
+                }
 
                 let info = ValidationInfo {
-                    id: *keyed_accounts[0].signer_key().unwrap(),
+                    id,
+                    stake,
                     proof_mask,
                 };
                 storage_account_state.lockout_validations[segment_index].push(info);
             }
             StorageProgram::ClaimStorageReward { entry_height } => {
+                if *keyed_accounts[2].unsigned_key() != rewards_pool_id() {
+                    info!(
+                        "invalid rewards pool: {:?}",
+                        keyed_accounts[2].unsigned_key()
+                    );
+                    return Err(ProgramError::InvalidArgument);
+                }
+
+                // Only proofs whose validations went through a full epoch of
+                // lockout can be claimed for
                 let claims_index = get_segment_from_entry(entry_height);
-                let account_key = keyed_accounts[0].signer_key().unwrap();
-                let mut num_validations = 0;
-                let mut total_validations = 0;
-                for validation in &storage_account_state.reward_validations[claims_index] {
-                    if *account_key == validation.id {
-                        num_validations += count_valid_proofs(&validation.proof_mask);
-                    } else {
-                        total_validations += count_valid_proofs(&validation.proof_mask);
-                    }
+                if claims_index >= storage_account_state.reward_validations.len() {
+                    return Err(ProgramError::InvalidArgument);
                 }
-                total_validations += num_validations;
-                if total_validations > 0 {
-                    keyed_accounts[0].account.tokens +=
-                        (TOTAL_VALIDATOR_REWARDS * num_validations) / total_validations;
+
+                let account_key = *keyed_accounts[0].signer_key().unwrap();
+                if storage_account_state.reward_claims[claims_index].contains(&account_key) {
+                    info!("{} already claimed segment {}", account_key, claims_index);
+                    return Err(ProgramError::InvalidArgument);
                 }
+
+                let proofs = storage_account_state
+                    .reward_proofs
+                    .get(claims_index)
+                    .map_or(&[][..], |proofs| &proofs[..]);
+                let reward = compute_reward(
+                    &account_key,
+                    proofs,
+                    &storage_account_state.reward_validations[claims_index],
+                );
+                if reward == 0 {
+                    return Err(ProgramError::InvalidArgument);
+                }
+                if keyed_accounts[2].account.tokens < reward {
+                    info!("rewards pool can't pay out {} tokens", reward);
+                    return Err(ProgramError::ResultWithNegativeTokens);
+                }
+
+                keyed_accounts[2].account.tokens -= reward;
+                keyed_accounts[0].account.tokens += reward;
+                storage_account_state.reward_claims[claims_index].push(account_key);
             }
         }
 
@@ -187,7 +261,7 @@ fn entrypoint(
 mod test {
     use super::*;
     use solana_sdk::account::{create_keyed_accounts, Account};
-    use solana_sdk::hash::Hash;
+    use solana_sdk::hash::{hash, Hash};
     use solana_sdk::signature::{Keypair, KeypairUtil, Signature};
     use solana_sdk::storage_program;
     use solana_sdk::storage_program::ProofStatus;
@@ -300,9 +374,10 @@ mod test {
         let mut accounts = [Account::default(), Account::default()];
         accounts[1].userdata.resize(16 * 1024, 0);
 
+        let storage_last_id = hash(b"storage_last_id");
         let tx = StorageTransaction::new_advertise_last_id(
             &keypair,
-            Hash::default(),
+            storage_last_id,
             Hash::default(),
             ENTRIES_PER_SEGMENT,
         );
@@ -314,66 +389,338 @@ mod test {
             Hash::default(),
             Hash::default(),
             0,
-            Signature::default(),
+            keypair.sign_message(&segment_message(&storage_last_id, 0)),
         );
 
         test_transaction(&tx, &mut accounts).unwrap();
     }
 
     #[test]
-    fn test_validate_mining() {
+    fn test_submit_mining_unsigned_challenge() {
         solana_logger::setup();
         let keypair = Keypair::new();
         let mut accounts = [Account::default(), Account::default()];
         accounts[1].userdata.resize(16 * 1024, 0);
 
-        let entry_height = 0;
-
+        let storage_last_id = hash(b"storage_last_id");
         let tx = StorageTransaction::new_advertise_last_id(
             &keypair,
+            storage_last_id,
             Hash::default(),
+            2 * ENTRIES_PER_SEGMENT,
+        );
+        test_transaction(&tx, &mut accounts).unwrap();
+
+        let signatures = [
+            Signature::default(),
+            // a key derived from an earlier challenge
+            keypair.sign_message(&segment_message(&Hash::default(), 0)),
+            // from another segment
+            keypair.sign_message(&segment_message(&storage_last_id, ENTRIES_PER_SEGMENT)),
+            // by another replicator
+            Keypair::new().sign_message(&segment_message(&storage_last_id, 0)),
+        ];
+        for signature in &signatures {
+            let tx = StorageTransaction::new_mining_proof(
+                &keypair,
+                Hash::default(),
+                Hash::default(),
+                0,
+                *signature,
+            );
+            assert_eq!(
+                test_transaction(&tx, &mut accounts),
+                Err(ProgramError::InvalidArgument)
+            );
+        }
+    }
+
+    // Validate the proofs of segment 0 as `validator`, staking `stake` tokens on
+    // a vote account
+    fn validate_proofs(
+        validator: &Keypair,
+        stake: u64,
+        accounts: &mut [Account],
+        proof_mask: Vec<ProofStatus>,
+    ) -> Result<(), ProgramError> {
+        let mut vote_account = Account::new(1, vote_program::get_max_size(), vote_program::id());
+        VoteState::new(validator.pubkey(), validator.pubkey())
+            .serialize(&mut vote_account.userdata)
+            .unwrap();
+        let mut validation_accounts = [
+            Account::new(stake, 0, Pubkey::default()),
+            accounts[1].clone(),
+            vote_account,
+        ];
+
+        let tx = StorageTransaction::new_proof_validation(
+            validator,
+            Keypair::new().pubkey(),
             Hash::default(),
-            ENTRIES_PER_SEGMENT,
+            0,
+            proof_mask,
         );
+        let result = test_transaction(&tx, &mut validation_accounts);
+        accounts[1] = validation_accounts[1].clone();
+        result
+    }
 
-        test_transaction(&tx, &mut accounts).unwrap();
+    // Submit a proof for segment 0 as `replicator` and roll over the epoch so
+    // that it can be validated
+    fn submit_mining_proof(replicator: &Keypair, accounts: &mut [Account]) {
+        let tx = StorageTransaction::new_advertise_last_id(
+            &replicator,
+            Hash::default(),
+            Hash::default(),
+            ENTRIES_PER_SEGMENT,
+        );
+        test_transaction(&tx, accounts).unwrap();
 
         let tx = StorageTransaction::new_mining_proof(
-            &keypair,
+            &replicator,
             Hash::default(),
             Hash::default(),
-            entry_height,
-            Signature::default(),
+            0,
+            replicator.sign_message(&segment_message(&Hash::default(), 0)),
         );
-        test_transaction(&tx, &mut accounts).unwrap();
+        test_transaction(&tx, accounts).unwrap();
 
         let tx = StorageTransaction::new_advertise_last_id(
-            &keypair,
+            &replicator,
             Hash::default(),
             Hash::default(),
             ENTRIES_PER_SEGMENT * 2,
         );
-        test_transaction(&tx, &mut accounts).unwrap();
+        test_transaction(&tx, accounts).unwrap();
+    }
 
-        let tx = StorageTransaction::new_proof_validation(
-            &keypair,
-            Hash::default(),
-            entry_height,
-            vec![ProofStatus::Valid],
-        );
-        test_transaction(&tx, &mut accounts).unwrap();
+    // Roll over the epoch so that the validations of segment 0 can be claimed for
+    fn end_lockout(replicator: &Keypair, accounts: &mut [Account]) {
+        // Can't claim before the validations went through lockout
+        let tx = StorageTransaction::new_reward_claim(&replicator, Hash::default(), 0);
+        assert!(test_transaction(&tx, accounts).is_err());
 
         let tx = StorageTransaction::new_advertise_last_id(
-            &keypair,
+            &replicator,
             Hash::default(),
             Hash::default(),
             ENTRIES_PER_SEGMENT * 3,
         );
+        test_transaction(&tx, accounts).unwrap();
+    }
+
+    // Submit a proof for segment 0 as `replicator`, have `validator` validate it
+    // with `status` and roll over the epoch so that it can be claimed for
+    fn validate_mining_proof(
+        replicator: &Keypair,
+        validator: &Keypair,
+        accounts: &mut [Account],
+        status: ProofStatus,
+    ) {
+        submit_mining_proof(replicator, accounts);
+        validate_proofs(validator, 1, accounts, vec![status]).unwrap();
+        end_lockout(replicator, accounts);
+    }
+
+    fn storage_accounts(pool_tokens: u64) -> [Account; 3] {
+        let mut accounts = [Account::default(), Account::default(), Account::default()];
+        accounts[1].userdata.resize(16 * 1024, 0);
+        accounts[2].tokens = pool_tokens;
+        accounts
+    }
+
+    #[test]
+    fn test_validate_mining() {
+        solana_logger::setup();
+        let replicator = Keypair::new();
+        let validator = Keypair::new();
+        let pool_tokens = TOTAL_VALIDATOR_REWARDS + TOTAL_REPLICATOR_REWARDS;
+        let mut accounts = storage_accounts(pool_tokens);
+
+        validate_mining_proof(&replicator, &validator, &mut accounts, ProofStatus::Valid);
+
+        let tx = StorageTransaction::new_reward_claim(&replicator, Hash::default(), 0);
         test_transaction(&tx, &mut accounts).unwrap();
+        assert_eq!(accounts[0].tokens, TOTAL_REPLICATOR_REWARDS);
 
-        let tx = StorageTransaction::new_reward_claim(&keypair, Hash::default(), entry_height);
+        // Rewards can only be claimed once
+        assert!(test_transaction(&tx, &mut accounts).is_err());
+        assert_eq!(accounts[0].tokens, TOTAL_REPLICATOR_REWARDS);
+
+        let tx = StorageTransaction::new_reward_claim(&validator, Hash::default(), 0);
         test_transaction(&tx, &mut accounts).unwrap();
+        assert_eq!(accounts[0].tokens, pool_tokens);
+        assert_eq!(accounts[2].tokens, 0);
+    }
+
+    #[test]
+    fn test_claim_invalid_proof() {
+        solana_logger::setup();
+        let replicator = Keypair::new();
+        let validator = Keypair::new();
+        let mut accounts = storage_accounts(TOTAL_REPLICATOR_REWARDS);
+
+        validate_mining_proof(
+            &replicator,
+            &validator,
+            &mut accounts,
+            ProofStatus::NotValid,
+        );
+
+        let tx = StorageTransaction::new_reward_claim(&replicator, Hash::default(), 0);
+        assert_eq!(
+            test_transaction(&tx, &mut accounts),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(accounts[0].tokens, 0);
+        assert_eq!(accounts[2].tokens, TOTAL_REPLICATOR_REWARDS);
+    }
+
+    #[test]
+    fn test_claim_underfunded_pool() {
+        solana_logger::setup();
+        let replicator = Keypair::new();
+        let validator = Keypair::new();
+        let mut accounts = storage_accounts(TOTAL_REPLICATOR_REWARDS - 1);
+
+        validate_mining_proof(&replicator, &validator, &mut accounts, ProofStatus::Valid);
+
+        let tx = StorageTransaction::new_reward_claim(&replicator, Hash::default(), 0);
+        assert_eq!(
+            test_transaction(&tx, &mut accounts),
+            Err(ProgramError::ResultWithNegativeTokens)
+        );
+        assert_eq!(accounts[0].tokens, 0);
+    }
+
+    #[test]
+    fn test_claim_wrong_pool() {
+        solana_logger::setup();
+        let replicator = Keypair::new();
+        let validator = Keypair::new();
+        let mut accounts = storage_accounts(TOTAL_VALIDATOR_REWARDS + TOTAL_REPLICATOR_REWARDS);
+
+        validate_mining_proof(&replicator, &validator, &mut accounts, ProofStatus::Valid);
+
+        let mut tx = StorageTransaction::new_reward_claim(&replicator, Hash::default(), 0);
+        tx.account_keys[2] = Keypair::new().pubkey();
+        assert_eq!(
+            test_transaction(&tx, &mut accounts),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_validate_twice() {
+        solana_logger::setup();
+        let replicator = Keypair::new();
+        let validator = Keypair::new();
+        let mut accounts = storage_accounts(0);
+
+        submit_mining_proof(&replicator, &mut accounts);
+        validate_proofs(&validator, 1, &mut accounts, vec![ProofStatus::Valid]).unwrap();
+        assert!(validate_proofs(&validator, 1, &mut accounts, vec![ProofStatus::Valid]).is_err());
 
-        assert!(accounts[0].tokens == TOTAL_VALIDATOR_REWARDS);
+        // The mask has to cover every proof of the segment
+        assert!(validate_proofs(&Keypair::new(), 1, &mut accounts, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_validate_own_proof() {
+        solana_logger::setup();
+        let replicator = Keypair::new();
+        let mut accounts = storage_accounts(0);
+
+        submit_mining_proof(&replicator, &mut accounts);
+        assert_eq!(
+            validate_proofs(&replicator, 1, &mut accounts, vec![ProofStatus::Valid]),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_validate_unstaked() {
+        solana_logger::setup();
+        let replicator = Keypair::new();
+        let validator = Keypair::new();
+        let mut accounts = storage_accounts(0);
+
+        submit_mining_proof(&replicator, &mut accounts);
+        assert_eq!(
+            validate_proofs(&validator, 0, &mut accounts, vec![ProofStatus::Valid]),
+            Err(ProgramError::InvalidArgument)
+        );
+
+        // Nor can a validator vouch with a vote account it doesn't stake
+        let vote_account = Account::new(1, vote_program::get_max_size(), vote_program::id());
+        let mut validation_accounts = [
+            Account::new(1, 0, Pubkey::default()),
+            accounts[1].clone(),
+            vote_account,
+        ];
+        let tx = StorageTransaction::new_proof_validation(
+            &validator,
+            Keypair::new().pubkey(),
+            Hash::default(),
+            0,
+            vec![ProofStatus::Valid],
+        );
+        assert_eq!(
+            test_transaction(&tx, &mut validation_accounts),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_validations_weighted_by_stake() {
+        solana_logger::setup();
+        let replicator = Keypair::new();
+        let mut accounts = storage_accounts(TOTAL_VALIDATOR_REWARDS + TOTAL_REPLICATOR_REWARDS);
+
+        submit_mining_proof(&replicator, &mut accounts);
+        validate_proofs(&Keypair::new(), 1, &mut accounts, vec![ProofStatus::Valid]).unwrap();
+        validate_proofs(&Keypair::new(), 1, &mut accounts, vec![ProofStatus::Valid]).unwrap();
+        validate_proofs(
+            &Keypair::new(),
+            3,
+            &mut accounts,
+            vec![ProofStatus::NotValid],
+        )
+        .unwrap();
+        end_lockout(&replicator, &mut accounts);
+
+        // Outvoted by stake, not by number of validators
+        let tx = StorageTransaction::new_reward_claim(&replicator, Hash::default(), 0);
+        assert_eq!(
+            test_transaction(&tx, &mut accounts),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_rubber_stamp_validation_unpaid() {
+        solana_logger::setup();
+        let replicator = Keypair::new();
+        let rubber_stamper = Keypair::new();
+        let validator = Keypair::new();
+        let mut accounts = storage_accounts(TOTAL_VALIDATOR_REWARDS + TOTAL_REPLICATOR_REWARDS);
+
+        submit_mining_proof(&replicator, &mut accounts);
+        validate_proofs(&rubber_stamper, 1, &mut accounts, vec![ProofStatus::Valid]).unwrap();
+        validate_proofs(&validator, 3, &mut accounts, vec![ProofStatus::NotValid]).unwrap();
+        end_lockout(&replicator, &mut accounts);
+
+        // Vouching for a proof the majority found invalid earns nothing
+        let tx = StorageTransaction::new_reward_claim(&rubber_stamper, Hash::default(), 0);
+        assert_eq!(
+            test_transaction(&tx, &mut accounts),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(accounts[0].tokens, 0);
+
+        // While the validator that caught it gets the validator rewards
+        let tx = StorageTransaction::new_reward_claim(&validator, Hash::default(), 0);
+        test_transaction(&tx, &mut accounts).unwrap();
+        assert_eq!(accounts[0].tokens, TOTAL_VALIDATOR_REWARDS);
     }
 }
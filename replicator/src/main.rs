@@ -1,12 +1,13 @@
 use clap::{crate_version, App, Arg};
 use serde_json;
 use solana::cluster_info::{Node, NodeInfo, FULLNODE_PORT_RANGE};
-use solana::replicator::Replicator;
+use solana::replicator::{Replicator, ReplicatorConfig};
 use solana::socketaddr;
 use solana_sdk::signature::{Keypair, KeypairUtil};
 use std::fs::File;
 use std::net::{Ipv4Addr, SocketAddr};
 use std::process::exit;
+use std::sync::Arc;
 
 fn main() {
     solana_logger::setup();
@@ -37,12 +38,33 @@ fn main() {
                 .value_name("DIR")
                 .takes_value(true)
                 .required(true)
-                .help("use DIR as persistent ledger location"),
+                .help("use DIR as persistent ledger location, holding the cluster's genesis.json"),
+        )
+        .arg(
+            Arg::with_name("segments")
+                .long("segments")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Number of ledger segments to store"),
         )
         .get_matches();
 
     let ledger_path = matches.value_of("ledger").unwrap();
 
+    let mut config = ReplicatorConfig::default();
+    if let Some(segments) = matches.value_of("segments") {
+        config.num_segments = segments.parse().expect("failed to parse segments");
+        if config.num_segments == 0 {
+            eprintln!("--segments must be at least 1");
+            exit(1);
+        }
+    }
+
+    let network_addr = matches
+        .value_of("network")
+        .map(|network| network.parse().expect("failed to parse network address"))
+        .unwrap();
+
     let (keypair, gossip) = if let Some(i) = matches.value_of("identity") {
         let path = i.to_string();
         if let Ok(file) = File::open(path.clone()) {
@@ -52,7 +74,7 @@ fn main() {
                 let keypair = config_data.keypair();
                 let node_info = NodeInfo::new_with_pubkey_socketaddr(
                     keypair.pubkey(),
-                    &config_data.bind_addr(FULLNODE_PORT_RANGE.0),
+                    &config_data.bind_addr(FULLNODE_PORT_RANGE.0, Some(&network_addr)),
                 );
                 (keypair, node_info.gossip)
             } else {
@@ -75,14 +97,11 @@ fn main() {
         gossip
     );
 
-    let network_addr = matches
-        .value_of("network")
-        .map(|network| network.parse().expect("failed to parse network address"))
-        .unwrap();
-
     let leader_info = NodeInfo::new_entry_point(&network_addr);
 
-    let replicator = Replicator::new(ledger_path, node, &leader_info, &keypair, None).unwrap();
+    let keypair = Arc::new(keypair);
+    let replicator =
+        Replicator::new(ledger_path, node, &leader_info, &keypair, None, &config).unwrap();
 
     replicator.join();
 }
@@ -2,6 +2,7 @@ use crate::hash::Hash;
 use crate::pubkey::Pubkey;
 use crate::signature::{Keypair, Signature};
 use crate::transaction::Transaction;
+use byteorder::{LittleEndian, WriteBytesExt};
 
 pub const ENTRIES_PER_SEGMENT: u64 = 16;
 
@@ -9,7 +10,7 @@ pub fn get_segment_from_entry(entry_height: u64) -> usize {
     (entry_height / ENTRIES_PER_SEGMENT) as usize
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum ProofStatus {
     Valid,
     NotValid,
@@ -23,9 +24,30 @@ pub struct ProofInfo {
     pub sha_state: Hash,
 }
 
+/// Message a replicator signs to derive the key it encrypts the segment starting at
+/// `entry_height` with, for the challenge identified by `storage_last_id`
+pub fn segment_message(storage_last_id: &Hash, entry_height: u64) -> Vec<u8> {
+    let mut message = storage_last_id.as_ref().to_vec();
+    message.write_u64::<LittleEndian>(entry_height).unwrap();
+    message
+}
+
+impl ProofInfo {
+    /// Whether the proof's key is its submitter's signature of the challenge
+    /// `storage_last_id` for the segment starting at `entry_height`
+    pub fn verify(&self, storage_last_id: &Hash, entry_height: u64) -> bool {
+        self.signature.verify(
+            self.id.as_ref(),
+            &segment_message(storage_last_id, entry_height),
+        )
+    }
+}
+
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 pub struct ValidationInfo {
     pub id: Pubkey,
+    /// Tokens the validator had staked when it submitted the validation
+    pub stake: u64,
     pub proof_mask: Vec<ProofStatus>,
 }
 
@@ -33,12 +55,118 @@ pub struct ValidationInfo {
 pub struct StorageProgramState {
     pub entry_height: u64,
     pub id: Hash,
+    /// Storage last_id `previous_proofs` answered
+    pub previous_id: Hash,
 
     pub proofs: Vec<Vec<ProofInfo>>,
     pub previous_proofs: Vec<Vec<ProofInfo>>,
 
     pub lockout_validations: Vec<Vec<ValidationInfo>>,
     pub reward_validations: Vec<Vec<ValidationInfo>>,
+
+    /// Proofs `reward_validations` refer to, which can be claimed for
+    pub reward_proofs: Vec<Vec<ProofInfo>>,
+    /// Accounts that already claimed their reward for each segment
+    pub reward_claims: Vec<Vec<Pubkey>>,
+}
+
+pub const TOTAL_VALIDATOR_REWARDS: u64 = 1000;
+pub const TOTAL_REPLICATOR_REWARDS: u64 = 1000;
+
+/// A proof is valid if more stake found it valid than not
+pub fn is_proof_valid(validations: &[ValidationInfo], proof_index: usize) -> bool {
+    let mut valid_stake = 0;
+    let mut not_valid_stake = 0;
+    for validation in validations {
+        match validation.proof_mask.get(proof_index) {
+            Some(ProofStatus::Valid) => valid_stake += validation.stake,
+            Some(ProofStatus::NotValid) => not_valid_stake += validation.stake,
+            _ => (),
+        }
+    }
+    valid_stake > not_valid_stake
+}
+
+/// Number of proofs in `proof_mask` whose status agrees with their `verdicts`
+fn count_agreeing_proofs(proof_mask: &[ProofStatus], verdicts: &[bool]) -> u64 {
+    proof_mask
+        .iter()
+        .zip(verdicts)
+        .filter(|(status, is_valid)| match status {
+            ProofStatus::Valid => **is_valid,
+            ProofStatus::NotValid => !**is_valid,
+            ProofStatus::Skipped => false,
+        })
+        .count() as u64
+}
+
+/// Tokens owed to `account_key` for the proofs and validations of a segment
+pub fn compute_reward(
+    account_key: &Pubkey,
+    proofs: &[ProofInfo],
+    validations: &[ValidationInfo],
+) -> u64 {
+    let mut reward = 0;
+
+    // Validators share the validator rewards by the number of proofs they
+    // marked the way the stake-weighted majority did, so marking every proof
+    // valid without checking it doesn't pay
+    let num_masked = validations
+        .iter()
+        .map(|validation| validation.proof_mask.len())
+        .max()
+        .unwrap_or(0);
+    let verdicts: Vec<_> = (0..num_masked)
+        .map(|i| is_proof_valid(validations, i))
+        .collect();
+    let mut num_validations = 0;
+    let mut total_validations = 0;
+    for validation in validations {
+        let count = count_agreeing_proofs(&validation.proof_mask, &verdicts);
+        if *account_key == validation.id {
+            num_validations += count;
+        }
+        total_validations += count;
+    }
+    if total_validations > 0 {
+        reward += (TOTAL_VALIDATOR_REWARDS * num_validations) / total_validations;
+    }
+
+    // Replicators share the replicator rewards by their number of valid proofs
+    let valid_proofs: Vec<_> = proofs
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| is_proof_valid(validations, *i))
+        .map(|(_, proof)| proof.id)
+        .collect();
+    let num_proofs = valid_proofs.iter().filter(|id| *id == account_key).count() as u64;
+    if !valid_proofs.is_empty() {
+        reward += (TOTAL_REPLICATOR_REWARDS * num_proofs) / valid_proofs.len() as u64;
+    }
+
+    reward
+}
+
+impl StorageProgramState {
+    /// Tokens `account_key` can still claim across all segments
+    pub fn pending_reward(&self, account_key: &Pubkey) -> u64 {
+        self.reward_validations
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| {
+                self.reward_claims
+                    .get(*i)
+                    .map_or(true, |claims| !claims.contains(account_key))
+            })
+            .map(|(i, validations)| {
+                let proofs = self
+                    .reward_proofs
+                    .get(i)
+                    .map_or(&[][..], |proofs| &proofs[..]);
+                compute_reward(account_key, proofs, validations)
+            })
+            .sum()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -71,6 +199,12 @@ pub const STORAGE_SYSTEM_ACCOUNT_ID: [u8; 32] = [
     0,
 ];
 
+/// Account owned by the storage program that rewards are paid out of
+pub const STORAGE_REWARDS_POOL_ID: [u8; 32] = [
+    134, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0,
+];
+
 pub fn check_id(program_id: &Pubkey) -> bool {
     program_id.as_ref() == STORAGE_PROGRAM_ID
 }
@@ -83,6 +217,10 @@ pub fn system_id() -> Pubkey {
     Pubkey::new(&STORAGE_SYSTEM_ACCOUNT_ID)
 }
 
+pub fn rewards_pool_id() -> Pubkey {
+    Pubkey::new(&STORAGE_REWARDS_POOL_ID)
+}
+
 pub struct StorageTransaction {}
 
 impl StorageTransaction {
@@ -128,8 +266,10 @@ impl StorageTransaction {
         )
     }
 
+    /// `vote_account` is the validator's vote account, staked by `from_keypair`
     pub fn new_proof_validation(
         from_keypair: &Keypair,
+        vote_account: Pubkey,
         last_id: Hash,
         entry_height: u64,
         proof_mask: Vec<ProofStatus>,
@@ -140,7 +280,7 @@ impl StorageTransaction {
         };
         Transaction::new(
             from_keypair,
-            &[Pubkey::new(&STORAGE_SYSTEM_ACCOUNT_ID)],
+            &[Pubkey::new(&STORAGE_SYSTEM_ACCOUNT_ID), vote_account],
             id(),
             &program,
             last_id,
@@ -156,7 +296,10 @@ impl StorageTransaction {
         let program = StorageProgram::ClaimStorageReward { entry_height };
         Transaction::new(
             from_keypair,
-            &[Pubkey::new(&STORAGE_SYSTEM_ACCOUNT_ID)],
+            &[
+                Pubkey::new(&STORAGE_SYSTEM_ACCOUNT_ID),
+                Pubkey::new(&STORAGE_REWARDS_POOL_ID),
+            ],
             id(),
             &program,
             last_id,
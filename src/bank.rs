@@ -9,7 +9,7 @@ use crate::entry::Entry;
 use crate::entry::EntrySlice;
 use crate::genesis_block::GenesisBlock;
 use crate::last_id_queue::{LastIdQueue, MAX_ENTRY_IDS};
-use crate::leader_scheduler::LeaderScheduler;
+use crate::leader_scheduler::{LeaderScheduler, DEFAULT_TICKS_PER_SLOT};
 use crate::poh_recorder::{PohRecorder, PohRecorderError};
 use crate::result::Error;
 use crate::runtime::{self, RuntimeError};
@@ -82,7 +82,8 @@ pub enum BankError {
 
 pub type Result<T> = result::Result<T, BankError>;
 
-pub const VERIFY_BLOCK_SIZE: usize = 16;
+/// Number of entries `process_ledger` verifies the PoH of in parallel at a time
+pub const VERIFY_BLOCK_SIZE: usize = 1024;
 
 pub trait BankSubscriptions {
     fn check_account(&self, pubkey: &Pubkey, account: &Account);
@@ -121,6 +122,12 @@ pub struct Bank {
     pub leader_scheduler: Arc<RwLock<LeaderScheduler>>,
 
     subscriptions: RwLock<Box<Arc<BankSubscriptions + Send + Sync>>>,
+
+    /// The number of hashes the genesis block requires between ticks, if any
+    hashes_per_tick: Option<u64>,
+
+    /// The number of ticks in each slot
+    ticks_per_slot: u64,
 }
 
 impl Default for Bank {
@@ -132,13 +139,17 @@ impl Default for Bank {
             confirmation_time: AtomicUsize::new(std::usize::MAX),
             leader_scheduler: Arc::new(RwLock::new(LeaderScheduler::default())),
             subscriptions: RwLock::new(Box::new(Arc::new(LocalSubscriptions::default()))),
+            hashes_per_tick: None,
+            ticks_per_slot: DEFAULT_TICKS_PER_SLOT,
         }
     }
 }
 
 impl Bank {
     pub fn new(genesis_block: &GenesisBlock) -> Self {
-        let bank = Self::default();
+        let mut bank = Self::default();
+        bank.hashes_per_tick = genesis_block.hashes_per_tick;
+        bank.ticks_per_slot = genesis_block.ticks_per_slot;
         bank.process_genesis_block(genesis_block);
         bank.add_builtin_programs();
         bank
@@ -158,9 +169,19 @@ impl Bank {
             confirmation_time: AtomicUsize::new(self.confirmation_time()),
             leader_scheduler: self.leader_scheduler.clone(),
             subscriptions: RwLock::new(Box::new(Arc::new(LocalSubscriptions::default()))),
+            hashes_per_tick: self.hashes_per_tick,
+            ticks_per_slot: self.ticks_per_slot,
         }
     }
 
+    pub fn hashes_per_tick(&self) -> Option<u64> {
+        self.hashes_per_tick
+    }
+
+    pub fn ticks_per_slot(&self) -> u64 {
+        self.ticks_per_slot
+    }
+
     fn process_genesis_block(&self, genesis_block: &GenesisBlock) {
         assert!(genesis_block.mint_id != Pubkey::default());
         assert!(genesis_block.tokens >= genesis_block.bootstrap_leader_tokens);
@@ -235,6 +256,20 @@ impl Bank {
         self.accounts
             .store_slow(true, &storage_program::system_id(), &storage_system_account);
 
+        // Storage rewards are paid out of this account, funded by transfers to it
+        let storage_rewards_pool_account = Account {
+            tokens: 1,
+            owner: storage_program::id(),
+            userdata: vec![],
+            executable: false,
+            loader: Pubkey::default(),
+        };
+        self.accounts.store_slow(
+            true,
+            &storage_program::rewards_pool_id(),
+            &storage_rewards_pool_account,
+        );
+
         // Bpf Loader
         let bpf_loader_account = Account {
             tokens: 1,
@@ -280,6 +315,11 @@ impl Bank {
             .expect("no last_id has been set")
     }
 
+    /// Ticks since `last_id` was registered, or None if the bank no longer tracks it
+    pub fn last_id_age(&self, last_id: &Hash) -> Option<u64> {
+        self.last_id_queue.read().unwrap().entry_id_age(last_id)
+    }
+
     pub fn get_storage_entry_height(&self) -> u64 {
         match self.get_account(&storage_program::system_id()) {
             Some(storage_system_account) => {
@@ -307,6 +347,12 @@ impl Bank {
         Hash::default()
     }
 
+    pub fn get_storage_program_state(&self) -> storage_program::StorageProgramState {
+        self.get_account(&storage_program::system_id())
+            .and_then(|account| deserialize(&account.userdata).ok())
+            .unwrap_or_default()
+    }
+
     /// Forget all signatures. Useful for benchmarking.
     pub fn clear_signatures(&self) {
         self.status_cache.write().unwrap().clear();
@@ -769,30 +815,44 @@ impl Bank {
     }
 
     /// Starting from the genesis block, append the provided entries to the ledger verifying them
-    /// along the way.
-    pub fn process_ledger<I>(&mut self, entries: I) -> Result<(u64, Hash)>
+    /// along the way.  Returns the entry height and last id of the ledger, along with the
+    /// hashes rolled since its last tick for replay to carry on counting from.
+    pub fn process_ledger<I>(&mut self, entries: I) -> Result<(u64, Hash, u64)>
     where
         I: IntoIterator<Item = Entry>,
     {
         let mut entry_height = 0;
         let mut last_id = self.last_id();
+        let mut tick_hash_count = 0;
 
         // Ledger verification needs to be parallelized, but we can't pull the whole
         // thing into memory. We therefore chunk it.
         for block in &entries.into_iter().chunks(VERIFY_BLOCK_SIZE) {
             let block: Vec<_> = block.collect();
 
-            if !block.verify(&last_id) {
-                warn!("Ledger proof of history failed at entry: {}", entry_height);
+            if let Some(i) = block.first_invalid_entry(&last_id) {
+                warn!(
+                    "Ledger proof of history failed at entry: {}",
+                    entry_height + i as u64
+                );
                 return Err(BankError::LedgerVerificationFailed);
             }
+            if let Some(hashes_per_tick) = self.hashes_per_tick {
+                if !block.verify_tick_hash_count(&mut tick_hash_count, hashes_per_tick) {
+                    warn!(
+                        "Ledger tick hash count mismatch after entry: {}",
+                        entry_height
+                    );
+                    return Err(BankError::LedgerVerificationFailed);
+                }
+            }
 
             self.process_block(&block)?;
 
             last_id = block.last().unwrap().id;
             entry_height += block.len() as u64;
         }
-        Ok((entry_height, last_id))
+        Ok((entry_height, last_id, tick_hash_count))
     }
 
     /// Create, sign, and process a Transaction from `keypair` to `to` of
@@ -903,6 +963,7 @@ impl Bank {
 mod tests {
     use super::*;
     use crate::entry::{next_entries, next_entry, Entry};
+    use crate::erasure::ErasureConfig;
     use crate::gen_keys::GenKeys;
     use bincode::serialize;
     use hashbrown::HashSet;
@@ -910,7 +971,7 @@ mod tests {
     use solana_sdk::native_program::ProgramError;
     use solana_sdk::signature::Keypair;
     use solana_sdk::signature::KeypairUtil;
-    use solana_sdk::storage_program::{StorageTransaction, ENTRIES_PER_SEGMENT};
+    use solana_sdk::storage_program::{segment_message, StorageTransaction, ENTRIES_PER_SEGMENT};
     use solana_sdk::system_instruction::SystemInstruction;
     use solana_sdk::system_transaction::SystemTransaction;
     use solana_sdk::transaction::Instruction;
@@ -1276,12 +1337,52 @@ mod tests {
             bootstrap_leader_tokens: 1,
             mint_id: mint_keypair.pubkey(),
             tokens,
+            erasure_config: ErasureConfig::default(),
+            hashes_per_tick: None,
+            ticks_per_slot: DEFAULT_TICKS_PER_SLOT,
         };
         let block =
             create_sample_block_with_ticks(&genesis_block, &mint_keypair, num_entries, num_entries);
         (genesis_block, mint_keypair, block)
     }
 
+    #[test]
+    fn test_process_ledger_bad_poh() {
+        let (genesis_block, _mint_keypair, ledger) = create_sample_ledger(100, 8);
+        let mut ledger: Vec<_> = ledger.collect();
+        ledger[5].num_hashes += 1;
+
+        let mut bank = Bank::default();
+        bank.process_genesis_block(&genesis_block);
+        bank.add_system_program();
+        assert_eq!(
+            bank.process_ledger(ledger),
+            Err(BankError::LedgerVerificationFailed)
+        );
+    }
+
+    #[test]
+    fn test_process_ledger_tick_hash_count() {
+        let (genesis_block, _mint_keypair, ledger) = create_sample_ledger(100, 4);
+        let ledger: Vec<_> = ledger.collect();
+
+        // Every entry in the sample ledger is a single hash
+        let mut bank = Bank::default();
+        bank.process_genesis_block(&genesis_block);
+        bank.add_system_program();
+        bank.hashes_per_tick = Some(1);
+        assert!(bank.process_ledger(ledger.clone()).is_ok());
+
+        let mut bank = Bank::default();
+        bank.process_genesis_block(&genesis_block);
+        bank.add_system_program();
+        bank.hashes_per_tick = Some(2);
+        assert_eq!(
+            bank.process_ledger(ledger),
+            Err(BankError::LedgerVerificationFailed)
+        );
+    }
+
     #[test]
     fn test_process_ledger_simple() {
         let (genesis_block, mint_keypair, ledger) = create_sample_ledger(100, 2);
@@ -1289,9 +1390,10 @@ mod tests {
         bank.process_genesis_block(&genesis_block);
         assert_eq!(bank.tick_height(), 0);
         bank.add_system_program();
-        let (ledger_height, last_id) = bank.process_ledger(ledger).unwrap();
+        let (ledger_height, last_id, tick_hash_count) = bank.process_ledger(ledger).unwrap();
         assert_eq!(bank.get_balance(&mint_keypair.pubkey()), 98);
         assert_eq!(ledger_height, 4);
+        assert_eq!(tick_hash_count, 0);
         assert_eq!(bank.tick_height(), 2);
         assert_eq!(bank.last_id(), last_id);
     }
@@ -1304,6 +1406,9 @@ mod tests {
             bootstrap_leader_tokens: 1,
             mint_id: mint_keypair.pubkey(),
             tokens: 2_000,
+            erasure_config: ErasureConfig::default(),
+            hashes_per_tick: None,
+            ticks_per_slot: DEFAULT_TICKS_PER_SLOT,
         };
         let seed = [0u8; 32];
         let mut rnd = GenKeys::new(seed);
@@ -1611,6 +1716,10 @@ mod tests {
             133, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
             0, 0, 0, 0,
         ]);
+        let storage_rewards_pool = Pubkey::new(&[
+            134, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0,
+        ]);
 
         assert_eq!(system_program::id(), system);
         assert_eq!(solana_native_loader::id(), native);
@@ -1620,6 +1729,7 @@ mod tests {
         assert_eq!(token_program::id(), token);
         assert_eq!(vote_program::id(), vote);
         assert_eq!(storage_program::system_id(), storage_system);
+        assert_eq!(storage_program::rewards_pool_id(), storage_rewards_pool);
     }
 
     #[test]
@@ -1634,6 +1744,7 @@ mod tests {
             token_program::id(),
             vote_program::id(),
             storage_program::system_id(),
+            storage_program::rewards_pool_id(),
         ];
         assert!(ids.into_iter().all(move |id| unique.insert(id)));
     }
@@ -1736,7 +1847,7 @@ mod tests {
             Hash::default(),
             last_id,
             entry_height,
-            Signature::default(),
+            jack.sign_message(&segment_message(&storage_last_id, entry_height)),
         );
 
         bank.process_transaction(&tx).unwrap();
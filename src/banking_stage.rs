@@ -256,6 +256,7 @@ impl BankingStage {
         );
         inc_new_counter_info!("banking_stage-process_packets", count);
         inc_new_counter_info!("banking_stage-process_transactions", new_tx_count);
+        inc_new_counter_info!("banking_stage-rejected_packets", count - new_tx_count);
         Ok(())
     }
 }
@@ -1,14 +1,12 @@
 //! The `broadcast_service` broadcasts data from a leader node to validators
 //!
-use crate::bank::Bank;
-use crate::cluster_info::{ClusterInfo, ClusterInfoError, NodeInfo, DATA_PLANE_FANOUT};
+use crate::cluster_info::{ClusterInfo, ClusterInfoError, NodeInfo};
 use crate::counter::Counter;
 use crate::entry::Entry;
-use crate::entry::EntrySlice;
-#[cfg(feature = "erasure")]
-use crate::erasure::CodingGenerator;
+use crate::erasure::{CodingGenerator, ErasureConfig};
 use crate::leader_scheduler::LeaderScheduler;
-use crate::packet::index_blobs;
+use crate::packet::{index_blobs, recycle_blobs, BlobRecycler};
+use crate::recycler::DEFAULT_RECYCLER_LIMIT;
 use crate::result::{Error, Result};
 use crate::service::Service;
 use crate::streamer::BlobSender;
@@ -35,9 +33,8 @@ struct Broadcast {
     id: Pubkey,
     max_tick_height: Option<u64>,
     blob_index: u64,
-
-    #[cfg(feature = "erasure")]
     coding_generator: CodingGenerator,
+    blob_recycler: BlobRecycler,
 }
 
 impl Broadcast {
@@ -79,9 +76,18 @@ impl Broadcast {
         //  this may span slots if this leader broadcasts for consecutive slots...
         let slots = generate_slots(&ventries, leader_scheduler);
 
+        let blob_recycler = &self.blob_recycler;
         let blobs: Vec<_> = ventries
             .into_par_iter()
-            .flat_map(|p| p.to_shared_blobs())
+            .flat_map(|p| {
+                p.iter()
+                    .map(|entry| {
+                        let blob = blob_recycler.allocate();
+                        entry.write_compressed_blob(&mut blob.write().unwrap());
+                        blob
+                    })
+                    .collect::<Vec<_>>()
+            })
             .collect();
 
         // TODO: blob_index should be slot-relative...
@@ -99,16 +105,32 @@ impl Broadcast {
         self.blob_index += blobs.len() as u64;
 
         // Send out data
-        ClusterInfo::broadcast(&self.id, last_tick, &broadcast_table, sock, &blobs)?;
+        ClusterInfo::broadcast(
+            &self.id,
+            last_tick,
+            &broadcast_table,
+            leader_scheduler,
+            sock,
+            &blobs,
+        )?;
 
         // Fill in the coding blob data from the window data blobs
-        #[cfg(feature = "erasure")]
-        {
-            let coding = self.coding_generator.next(&blobs)?;
+        let coding = self.coding_generator.next(&blobs)?;
 
-            // send out erasures
-            ClusterInfo::broadcast(&self.id, false, &broadcast_table, sock, &coding)?;
-        }
+        // send out erasures
+        ClusterInfo::broadcast(
+            &self.id,
+            false,
+            &broadcast_table,
+            leader_scheduler,
+            sock,
+            &coding,
+        )?;
+
+        // Coding blobs are done with, and data blobs are too once the window has stored
+        // them and the coding generator has used them
+        recycle_blobs(&self.blob_recycler, coding);
+        recycle_blobs(&self.blob_recycler, blobs);
 
         let broadcast_elapsed = duration_as_ms(&broadcast_start.elapsed());
 
@@ -186,8 +208,8 @@ pub struct BroadcastService {
 }
 
 impl BroadcastService {
+    #[allow(clippy::too_many_arguments)]
     fn run(
-        bank: &Arc<Bank>,
         sock: &UdpSocket,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         entry_height: u64,
@@ -196,6 +218,7 @@ impl BroadcastService {
         max_tick_height: Option<u64>,
         exit_signal: &Arc<AtomicBool>,
         blob_sender: &BlobSender,
+        erasure_config: ErasureConfig,
     ) -> BroadcastServiceReturnType {
         let me = cluster_info.read().unwrap().my_data().clone();
 
@@ -203,17 +226,16 @@ impl BroadcastService {
             id: me.id,
             max_tick_height,
             blob_index: entry_height,
-            #[cfg(feature = "erasure")]
-            coding_generator: CodingGenerator::new(),
+            coding_generator: CodingGenerator::new_with_config(erasure_config),
+            blob_recycler: BlobRecycler::new("broadcast-service", DEFAULT_RECYCLER_LIMIT),
         };
 
         loop {
             if exit_signal.load(Ordering::Relaxed) {
                 return BroadcastServiceReturnType::ExitSignal;
             }
-            let mut broadcast_table = cluster_info.read().unwrap().sorted_tvu_peers(&bank);
-            // Layer 1, leader nodes are limited to the fanout size.
-            broadcast_table.truncate(DATA_PLANE_FANOUT);
+            // Each blob is sent to the root of its retransmit tree, which spans every peer
+            let broadcast_table = cluster_info.read().unwrap().tvu_peers();
             inc_new_counter_info!("broadcast_service-num_peers", broadcast_table.len() + 1);
             if let Err(e) = broadcast.run(
                 &broadcast_table,
@@ -245,6 +267,7 @@ impl BroadcastService {
     /// * `cluster_info` - ClusterInfo structure
     /// * `window` - Cache of blobs that we have broadcast
     /// * `receiver` - Receive channel for blobs to be retransmitted to all the layer 1 nodes.
+    /// * `erasure_config` - Shape of the erasure sets to generate coding blobs for.
     /// * `exit_sender` - Set to true when this service exits, allows rest of Tpu to exit cleanly.
     /// Otherwise, when a Tpu closes, it only closes the stages that come after it. The stages
     /// that come before could be blocked on a receive, and never notice that they need to
@@ -252,8 +275,8 @@ impl BroadcastService {
     /// WriteStage is the last stage in the pipeline), which will then close Broadcast service,
     /// which will then close FetchStage in the Tpu, and then the rest of the Tpu,
     /// completing the cycle.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        bank: Arc<Bank>,
         sock: UdpSocket,
         cluster_info: Arc<RwLock<ClusterInfo>>,
         entry_height: u64,
@@ -262,6 +285,7 @@ impl BroadcastService {
         max_tick_height: Option<u64>,
         exit_sender: Arc<AtomicBool>,
         blob_sender: &BlobSender,
+        erasure_config: ErasureConfig,
     ) -> Self {
         let exit_signal = Arc::new(AtomicBool::new(false));
         let blob_sender = blob_sender.clone();
@@ -270,7 +294,6 @@ impl BroadcastService {
             .spawn(move || {
                 let _exit = Finalizer::new(exit_sender);
                 Self::run(
-                    &bank,
                     &sock,
                     &cluster_info,
                     entry_height,
@@ -279,6 +302,7 @@ impl BroadcastService {
                     max_tick_height,
                     &exit_signal,
                     &blob_sender,
+                    erasure_config,
                 )
             })
             .unwrap();
@@ -340,13 +364,10 @@ mod test {
         let cluster_info = Arc::new(RwLock::new(cluster_info));
 
         let exit_sender = Arc::new(AtomicBool::new(false));
-        let bank = Arc::new(Bank::default());
-
         let (blob_fetch_sender, _) = channel();
 
         // Start up the broadcast stage
         let broadcast_service = BroadcastService::new(
-            bank.clone(),
             leader_info.sockets.broadcast,
             cluster_info,
             entry_height,
@@ -355,6 +376,7 @@ mod test {
             Some(max_tick_height),
             exit_sender,
             &blob_fetch_sender,
+            db_ledger.erasure_config(),
         );
 
         MockBroadcastService {
@@ -376,7 +398,7 @@ mod test {
 
             // Mock the tick height to look like the tick height right after a leader transition
             leader_scheduler.last_seed_height = Some(leader_scheduler.bootstrap_height);
-            leader_scheduler.set_leader_schedule(vec![leader_keypair.pubkey()]);
+            leader_scheduler.set_leader_schedule(1, vec![leader_keypair.pubkey()]);
             leader_scheduler.use_only_bootstrap_leader = false;
             let start_tick_height = leader_scheduler.bootstrap_height;
             let max_tick_height = start_tick_height + leader_scheduler.last_seed_height.unwrap();
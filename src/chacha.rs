@@ -1,7 +1,10 @@
 use crate::db_ledger::{DbLedger, DEFAULT_SLOT_HEIGHT};
+use rayon::prelude::*;
+use solana_sdk::hash::{Hash, Hasher};
 use std::fs::File;
 use std::io;
 use std::io::{BufWriter, Write};
+use std::mem::size_of;
 use std::path::Path;
 use std::sync::Arc;
 
@@ -39,8 +42,18 @@ pub fn chacha_cbc_encrypt_ledger(
     out_path: &Path,
     ivec: &mut [u8; CHACHA_BLOCK_SIZE],
 ) -> io::Result<usize> {
-    let mut out_file =
+    let out_file =
         BufWriter::new(File::create(out_path).expect("Can't open ledger encrypted data file"));
+    chacha_cbc_encrypt_ledger_to(db_ledger, slice, out_file, ivec)
+}
+
+/// Same as `chacha_cbc_encrypt_ledger`, writing the encrypted segment to `out_file`
+pub fn chacha_cbc_encrypt_ledger_to<W: Write>(
+    db_ledger: &Arc<DbLedger>,
+    slice: u64,
+    mut out_file: W,
+    ivec: &mut [u8; CHACHA_BLOCK_SIZE],
+) -> io::Result<usize> {
     const BUFFER_SIZE: usize = 8 * 1024;
     let mut buffer = [0; BUFFER_SIZE];
     let mut encrypted_buffer = [0; BUFFER_SIZE];
@@ -89,21 +102,106 @@ pub fn chacha_cbc_encrypt_ledger(
             }
         }
     }
+    out_file.flush()?;
     Ok(total_size)
 }
 
+// Read the blobs of the segment starting at `slice` in the same buffer-sized
+// chunks, padded the same way, that `chacha_cbc_encrypt_ledger_to` encrypts
+fn read_segment_chunks(db_ledger: &Arc<DbLedger>, slice: u64) -> Vec<Vec<u8>> {
+    const BUFFER_SIZE: usize = 8 * 1024;
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut chunks = vec![];
+    let mut total_entries = 0;
+    let mut entry = slice;
+
+    while let Ok((num_entries, entry_len)) = db_ledger.read_blobs_bytes(
+        entry,
+        ENTRIES_PER_SEGMENT - total_entries,
+        &mut buffer,
+        DEFAULT_SLOT_HEIGHT,
+    ) {
+        let mut size = entry_len as usize;
+        if size == 0 {
+            break;
+        }
+        if size < BUFFER_SIZE {
+            size = (size + CHACHA_KEY_SIZE - 1) & !(CHACHA_KEY_SIZE - 1);
+        }
+        chunks.push(buffer[..size].to_vec());
+
+        total_entries += num_entries;
+        entry += num_entries;
+    }
+    chunks
+}
+
+// Encrypt a ledger segment with multiple starting IV states, determined by
+// ivecs.len(), in parallel on the CPU
+//
+// Then sample each encryption at the offsets provided by samples argument with
+// sha256 and return the vec of sha states. Produces the same output as the
+// CUDA `chacha_cuda::chacha_cbc_encrypt_file_many_keys`.
+pub fn chacha_cbc_encrypt_file_many_keys(
+    db_ledger: &Arc<DbLedger>,
+    segment: u64,
+    ivecs: &mut [u8],
+    samples: &[u64],
+) -> io::Result<Vec<Hash>> {
+    if ivecs.len() % CHACHA_BLOCK_SIZE != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "bad IV length({}) not divisible by {} ",
+                ivecs.len(),
+                CHACHA_BLOCK_SIZE,
+            ),
+        ));
+    }
+
+    let chunks = read_segment_chunks(db_ledger, segment);
+    let key = [0; CHACHA_KEY_SIZE];
+    let sample_size = size_of::<Hash>();
+
+    Ok(ivecs
+        .par_chunks_mut(CHACHA_BLOCK_SIZE)
+        .map(|ivec| {
+            let mut hasher = Hasher::default();
+            let mut encrypted = vec![];
+            let mut chunk_start = 0;
+            for chunk in &chunks {
+                encrypted.resize(chunk.len(), 0);
+                chacha_cbc_encrypt(chunk, &mut encrypted, &key, ivec);
+                let chunk_end = chunk_start + chunk.len();
+                for sample in samples {
+                    let offset = *sample as usize * sample_size;
+                    if offset >= chunk_start && offset + sample_size <= chunk_end {
+                        let start = offset - chunk_start;
+                        hasher.hash(&encrypted[start..start + sample_size]);
+                    }
+                }
+                chunk_start = chunk_end;
+            }
+            hasher.result()
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::chacha::chacha_cbc_encrypt_ledger;
+    use crate::chacha::{
+        chacha_cbc_encrypt_file_many_keys, chacha_cbc_encrypt_ledger, CHACHA_BLOCK_SIZE,
+    };
     use crate::db_ledger::get_tmp_ledger_path;
     use crate::db_ledger::{DbLedger, DEFAULT_SLOT_HEIGHT};
-    use crate::entry::Entry;
+    use crate::entry::{make_tiny_test_entries, Entry, EntrySlice};
+    use crate::replicator::sample_file;
     use ring::signature::Ed25519KeyPair;
     use solana_sdk::budget_transaction::BudgetTransaction;
     use solana_sdk::hash::{hash, Hash, Hasher};
     use solana_sdk::signature::KeypairUtil;
-    use std::fs::remove_file;
     use std::fs::File;
+    use std::fs::{remove_dir_all, remove_file};
     use std::io::Read;
     use std::path::Path;
     use std::sync::Arc;
@@ -147,10 +245,19 @@ mod tests {
         let db_ledger = Arc::new(DbLedger::open(&ledger_path).unwrap());
         let out_path = Path::new("test_chacha_encrypt_file_output.txt.enc");
 
+        // Write uncompressed blobs so the golden doesn't depend on the compressor
         let entries = make_tiny_deterministic_test_entries(32);
-        db_ledger
-            .write_entries(DEFAULT_SLOT_HEIGHT, 0, &entries)
-            .unwrap();
+        let blobs: Vec<_> = entries
+            .to_blobs()
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut blob)| {
+                blob.set_index(index as u64);
+                blob.set_slot(DEFAULT_SLOT_HEIGHT);
+                blob
+            })
+            .collect();
+        db_ledger.write_blobs(&blobs).unwrap();
 
         let mut key = hex!(
             "abcd1234abcd1234abcd1234abcd1234 abcd1234abcd1234abcd1234abcd1234
@@ -173,4 +280,38 @@ mod tests {
         assert_eq!(hasher.result(), golden,);
         remove_file(out_path).unwrap();
     }
+
+    #[test]
+    fn test_encrypt_file_many_keys_matches_single_key() {
+        solana_logger::setup();
+        let ledger_path = get_tmp_ledger_path("chacha_test_encrypt_file_many_keys");
+        let db_ledger = Arc::new(DbLedger::open(&ledger_path).unwrap());
+        db_ledger
+            .write_entries(DEFAULT_SLOT_HEIGHT, 0, &make_tiny_test_entries(32))
+            .unwrap();
+        let out_path = Path::new("test_chacha_encrypt_file_many_keys_cpu_output.txt.enc");
+
+        let samples = [0, 1, 3, 4, 5, 50];
+        let mut ivecs = vec![];
+        let mut ref_hashes = vec![];
+        for i in 0..3 {
+            let mut ivec = [i; CHACHA_BLOCK_SIZE];
+            ivecs.extend_from_slice(&ivec);
+            chacha_cbc_encrypt_ledger(&db_ledger, 0, out_path, &mut ivec).unwrap();
+            ref_hashes.push(sample_file(&out_path, &samples).unwrap());
+        }
+
+        let hashes =
+            chacha_cbc_encrypt_file_many_keys(&db_ledger, 0, &mut ivecs, &samples).unwrap();
+        assert_eq!(hashes, ref_hashes);
+
+        let mut bad_ivecs = [0; CHACHA_BLOCK_SIZE - 1];
+        assert!(
+            chacha_cbc_encrypt_file_many_keys(&db_ledger, 0, &mut bad_ivecs, &samples).is_err()
+        );
+
+        drop(db_ledger);
+        remove_dir_all(&ledger_path).unwrap();
+        remove_file(out_path).unwrap();
+    }
 }
@@ -109,7 +109,7 @@ pub fn chacha_cbc_encrypt_file_many_keys(
 
 #[cfg(test)]
 mod tests {
-    use crate::chacha::chacha_cbc_encrypt_ledger;
+    use crate::chacha::{self, chacha_cbc_encrypt_ledger, CHACHA_BLOCK_SIZE};
     use crate::chacha_cuda::chacha_cbc_encrypt_file_many_keys;
     use crate::db_ledger::get_tmp_ledger_path;
     use crate::db_ledger::{DbLedger, DEFAULT_SLOT_HEIGHT};
@@ -198,6 +198,42 @@ mod tests {
         let _ignored = remove_file(out_path);
     }
 
+    #[test]
+    fn test_encrypt_file_many_keys_cpu_equivalence() {
+        solana_logger::setup();
+
+        let entries = make_tiny_test_entries(64);
+        let ledger_dir = "test_encrypt_file_many_keys_cpu_equivalence";
+        let ledger_path = get_tmp_ledger_path(ledger_dir);
+        let db_ledger = Arc::new(DbLedger::open(&ledger_path).unwrap());
+        db_ledger
+            .write_entries(DEFAULT_SLOT_HEIGHT, 0, &entries)
+            .unwrap();
+
+        let samples = [0, 2, 7, 19, 50];
+        for segment in 0..2 {
+            let mut ivecs: Vec<u8> = (0..64 * CHACHA_BLOCK_SIZE)
+                .map(|i| (i % 251) as u8)
+                .collect();
+            let mut cpu_ivecs = ivecs.clone();
+
+            let hashes =
+                chacha_cbc_encrypt_file_many_keys(&db_ledger, segment, &mut ivecs, &samples)
+                    .unwrap();
+            let cpu_hashes = chacha::chacha_cbc_encrypt_file_many_keys(
+                &db_ledger,
+                segment,
+                &mut cpu_ivecs,
+                &samples,
+            )
+            .unwrap();
+
+            assert_eq!(hashes, cpu_hashes);
+        }
+
+        let _ignored = remove_dir_all(&ledger_path);
+    }
+
     #[test]
     fn test_encrypt_file_many_keys_bad_key_length() {
         let mut keys = hex!("abc123");
@@ -12,34 +12,43 @@
 //! * layer 2 - Everyone else, if layer 1 is `2^10`, layer 2 should be able to fit `2^20` number of nodes.
 //!
 //! Bank needs to provide an interface for us to query the stake weight
-use crate::bank::Bank;
 use crate::bloom::Bloom;
 use crate::contact_info::ContactInfo;
 use crate::counter::Counter;
 use crate::crds_gossip::CrdsGossip;
 use crate::crds_gossip_error::CrdsGossipError;
 use crate::crds_gossip_pull::CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS;
-use crate::crds_value::{CrdsValue, CrdsValueLabel, LeaderId, Vote};
+use crate::crds_value::{
+    feature_set, CrdsValue, CrdsValueLabel, EpochSlots, LeaderId, Root, Version, Vote,
+};
 use crate::db_ledger::DbLedger;
-use crate::packet::{to_shared_blob, Blob, SharedBlob, BLOB_SIZE};
+use crate::gossip_peer_score::{PeerFilter, PeerScores};
+use crate::leader_scheduler::LeaderScheduler;
+use crate::packet::{to_shared_blob, Blob, SharedBlob, BLOB_DATA_SIZE, BLOB_SIZE};
 use crate::result::Result;
 use crate::rpc::RPC_PORT;
 use crate::streamer::{BlobReceiver, BlobSender};
-use bincode::{deserialize, serialize};
+use bincode::{deserialize, serialize, serialized_size};
 use hashbrown::HashMap;
 use log::Level;
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
 use rayon::prelude::*;
 use solana_metrics::{influxdb, submit};
-use solana_netutil::{bind_in_range, bind_to, find_available_port_in_range, multi_bind_in_range};
-use solana_sdk::hash::Hash;
+use solana_netutil::{
+    bind_common, bind_common_in_range, bind_in_range, find_available_port_in_range,
+    multi_bind_in_range,
+};
+use solana_sdk::hash::{hash, Hash};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, KeypairUtil, Signable, Signature};
 use solana_sdk::timing::{duration_as_ms, timestamp};
 use solana_sdk::transaction::Transaction;
 use std::cmp::min;
+use std::collections::BTreeSet;
 use std::io;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, UdpSocket};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
 use std::thread::{sleep, Builder, JoinHandle};
@@ -47,6 +56,78 @@ use std::time::{Duration, Instant};
 
 pub type NodeInfo = ContactInfo;
 
+/// The nodes of a retransmit tree sorted by id, along with their stakes.  The tree of each
+/// blob is a shuffle weighted by stake and seeded by the blob, so each blob takes a different
+/// path through the cluster, while every node that knows the same nodes and stakes computes
+/// the same tree.  Building this sorts the nodes by id and looks up their stakes, so it's kept
+/// for as long as they and their stakes don't change.  Each blob still sorts the nodes by its
+/// own keys in `order`, only `root` avoids the sort.
+pub struct RetransmitTree {
+    /// Id of each node, its index in the nodes the tree was built from, and the exponent
+    /// of its key
+    nodes: Vec<(Pubkey, usize, f64)>,
+}
+
+impl RetransmitTree {
+    pub fn new(nodes: &[NodeInfo], stakes: &HashMap<Pubkey, u64>) -> Self {
+        let mut nodes: Vec<_> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let weight = stakes.get(&node.id).cloned().unwrap_or(0).saturating_add(1);
+                (node.id, i, 1.0 / weight as f64)
+            })
+            .collect();
+        // draw the keys in an order that doesn't depend on how the nodes were found
+        nodes.sort_by_key(|(id, _, _)| *id);
+        RetransmitTree { nodes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The keys of the blob at `index` of `slot`, one per node in id order.  Nodes are
+    /// ordered by descending key, a weighted random order, see Efraimidis and Spirakis,
+    /// "Weighted random sampling with a reservoir".
+    fn keys<'a>(&'a self, slot: u64, index: u64) -> impl Iterator<Item = f64> + 'a {
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(hash(&serialize(&(slot, index)).unwrap()).as_ref());
+        let mut rng = ChaChaRng::from_seed(seed);
+        self.nodes
+            .iter()
+            .map(move |(_, _, exponent)| rng.gen::<f64>().powf(*exponent))
+    }
+
+    /// Index of the root of the tree of the blob at `index` of `slot`, the first node of
+    /// `order`, found without sorting
+    pub fn root(&self, slot: u64, index: u64) -> Option<usize> {
+        // on a tie the smaller id comes first, and the nodes are visited in id order
+        self.keys(slot, index)
+            .zip(&self.nodes)
+            .fold(None, |root, (key, (_, i, _))| match root {
+                Some((root_key, _)) if root_key >= key => root,
+                _ => Some((key, *i)),
+            })
+            .map(|(_, i)| i)
+    }
+
+    /// Indices of the nodes in the order of the tree of the blob at `index` of `slot`
+    pub fn order(&self, slot: u64, index: u64) -> Vec<usize> {
+        let mut keyed: Vec<_> = self
+            .keys(slot, index)
+            .zip(&self.nodes)
+            .map(|(key, (id, i, _))| (key, id, *i))
+            .collect();
+        keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then_with(|| a.1.cmp(b.1)));
+        keyed.into_iter().map(|(_, _, i)| i).collect()
+    }
+}
+
 pub const FULLNODE_PORT_RANGE: (u16, u16) = (8000, 10_000);
 
 /// The fanout for Ledger Replication
@@ -58,6 +139,21 @@ pub const GROW_LAYER_CAPACITY: bool = false;
 /// milliseconds we sleep for between gossip requests
 pub const GOSSIP_SLEEP_MILLIS: u64 = 100;
 
+/// Version of the gossip wire format.  Bump it whenever `Protocol`, or any value it carries,
+/// changes in a way older nodes can't deserialize.
+pub const GOSSIP_PROTOCOL_VERSION: u32 = 2;
+/// Version reported for peers that send a bare `Protocol`, from before the envelope existed
+pub const UNVERSIONED_GOSSIP_PROTOCOL_VERSION: u32 = 1;
+/// First word of every envelope.  A bare `Protocol` starts with its variant tag instead, which
+/// is far too small to be mistaken for it.
+const GOSSIP_PROTOCOL_MAGIC: u32 = 0x5053_4f47;
+/// Bytes of a push message or pull response taken by the envelope, the message tag, the
+/// sender and the length of the values
+const PROTOCOL_HEADER_SIZE: u64 = 64;
+/// Max bytes of values carried by a single push message or pull response, larger batches
+/// are split into several messages
+pub const MAX_PROTOCOL_PAYLOAD_SIZE: u64 = BLOB_DATA_SIZE as u64 - PROTOCOL_HEADER_SIZE;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum ClusterInfoError {
     NoPeers,
@@ -72,6 +168,10 @@ pub struct ClusterInfo {
     pub gossip: CrdsGossip,
     /// set the keypair that will be used to sign crds values generated. It is unset only in tests.
     pub(crate) keypair: Arc<Keypair>,
+    /// stake of each node, used to favour staked peers in the push active set
+    stakes: HashMap<Pubkey, u64>,
+    /// per-peer accounting of the traffic handled by the listen thread
+    peer_scores: PeerScores,
 }
 
 #[derive(Default, Clone)]
@@ -148,6 +248,55 @@ enum Protocol {
     RequestWindowIndex(NodeInfo, u64),
 }
 
+/// Every `Protocol` message travels in this envelope so that a peer running an incompatible
+/// version can be told apart from a malformed message
+#[derive(Serialize, Deserialize, Debug)]
+struct VersionedProtocol {
+    magic: u32,
+    version: u32,
+    protocol: Protocol,
+}
+
+impl VersionedProtocol {
+    fn new(protocol: Protocol) -> Self {
+        VersionedProtocol {
+            magic: GOSSIP_PROTOCOL_MAGIC,
+            version: GOSSIP_PROTOCOL_VERSION,
+            protocol,
+        }
+    }
+}
+
+/// Serialize `protocol` in its envelope into a blob addressed to `addr`
+fn to_protocol_blob(protocol: Protocol, addr: SocketAddr) -> Option<SharedBlob> {
+    to_shared_blob(VersionedProtocol::new(protocol), addr).ok()
+}
+
+/// Split `values` into batches that each fit in a single push message or pull response.
+/// Values that can't fit in any message are dropped.
+fn split_gossip_messages(values: Vec<CrdsValue>) -> Vec<Vec<CrdsValue>> {
+    let mut batches = vec![];
+    let mut batch = vec![];
+    let mut batch_size = 0;
+    for value in values {
+        let size = serialized_size(&value).unwrap();
+        if size > MAX_PROTOCOL_PAYLOAD_SIZE {
+            inc_new_counter_info!("cluster_info-gossip_value_too_large", 1);
+            continue;
+        }
+        if batch_size + size > MAX_PROTOCOL_PAYLOAD_SIZE {
+            batches.push(mem::replace(&mut batch, vec![]));
+            batch_size = 0;
+        }
+        batch_size += size;
+        batch.push(value);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
 impl ClusterInfo {
     pub fn new(node_info: NodeInfo) -> Self {
         //Without a keypair, gossip will not function. Only useful for tests.
@@ -157,6 +306,8 @@ impl ClusterInfo {
         let mut me = ClusterInfo {
             gossip: CrdsGossip::default(),
             keypair,
+            stakes: HashMap::new(),
+            peer_scores: PeerScores::default(),
         };
         let id = node_info.id;
         me.gossip.set_self(id);
@@ -170,12 +321,16 @@ impl ClusterInfo {
         my_data.wallclock = now;
         let mut entry = CrdsValue::ContactInfo(my_data);
         entry.sign(&self.keypair);
-        self.gossip.refresh_push_active_set();
+        self.gossip.refresh_push_active_set(&self.stakes);
         self.gossip.process_push_message(&[entry], now);
     }
+    /// Insert a ContactInfo into the local table.  Only our own ContactInfo is signed,
+    /// anyone else's is stored as given and will fail verification if gossiped on
     pub fn insert_info(&mut self, node_info: NodeInfo) {
         let mut value = CrdsValue::ContactInfo(node_info);
-        value.sign(&self.keypair);
+        if value.pubkey() == self.id() {
+            value.sign(&self.keypair);
+        }
         let _ = self.gossip.crds.insert(value, timestamp());
     }
     pub fn id(&self) -> Pubkey {
@@ -253,7 +408,7 @@ impl ClusterInfo {
 
     pub fn push_vote(&mut self, vote: Transaction) {
         let now = timestamp();
-        let vote = Vote::new(vote, now);
+        let vote = Vote::new(self.id(), vote, now);
         let mut entry = CrdsValue::Vote(vote);
         entry.sign(&self.keypair);
         self.gossip.process_push_message(&[entry], now);
@@ -282,8 +437,68 @@ impl ClusterInfo {
         (txs, max_ts)
     }
 
+    /// Advertise the slots of `epoch` that are complete in our ledger
+    pub fn push_epoch_slots(&mut self, epoch: u64, slots: BTreeSet<u64>) {
+        let now = timestamp();
+        let epoch_slots = EpochSlots::new(self.id(), epoch, slots, now);
+        let mut entry = CrdsValue::EpochSlots(epoch_slots);
+        entry.sign(&self.keypair);
+        self.gossip.process_push_message(&[entry], now);
+    }
+
+    pub fn get_epoch_slots(&self, id: Pubkey) -> Option<&EpochSlots> {
+        let entry = CrdsValueLabel::EpochSlots(id);
+        self.gossip
+            .crds
+            .lookup(&entry)
+            .and_then(|x| x.epoch_slots())
+    }
+
+    /// Advertise the version of this build
+    pub fn push_version(&mut self) {
+        let now = timestamp();
+        let mut entry = CrdsValue::Version(Version::new(self.id(), now));
+        entry.sign(&self.keypair);
+        self.gossip.process_push_message(&[entry], now);
+    }
+
+    pub fn get_version(&self, id: Pubkey) -> Option<&Version> {
+        let entry = CrdsValueLabel::Version(id);
+        self.gossip.crds.lookup(&entry).and_then(|x| x.version())
+    }
+
+    /// Advertise the tick height of the last slot we replayed and verified
+    pub fn push_root(&mut self, root: u64) {
+        let now = timestamp();
+        let mut entry = CrdsValue::Root(Root::new(self.id(), root, now));
+        entry.sign(&self.keypair);
+        self.gossip.process_push_message(&[entry], now);
+    }
+
+    pub fn get_root(&self, id: Pubkey) -> Option<u64> {
+        let entry = CrdsValueLabel::Root(id);
+        self.gossip
+            .crds
+            .lookup(&entry)
+            .and_then(|x| x.root())
+            .map(|x| x.root)
+    }
+
+    /// Gossip peers that sent us packets using another protocol version, and that version
+    pub fn incompatible_protocol_peers(&self) -> Vec<(IpAddr, u32)> {
+        self.peer_scores.incompatible_peers()
+    }
+    pub fn set_stakes(&mut self, stakes: HashMap<Pubkey, u64>) {
+        self.stakes = stakes;
+    }
+    /// Configure the gossip peers that are always allowed or denied
+    pub fn set_peer_filter(&mut self, filter: PeerFilter) {
+        self.peer_scores.set_filter(filter);
+    }
+
     pub fn purge(&mut self, now: u64) {
         self.gossip.purge(now);
+        self.peer_scores.purge(now);
     }
     pub fn convergence(&self) -> usize {
         self.gossip_peers().len() + 1
@@ -352,30 +567,97 @@ impl ClusterInfo {
             .collect()
     }
 
-    fn sort_by_stake(peers: &[NodeInfo], bank: &Arc<Bank>) -> Vec<(u64, NodeInfo)> {
-        let mut peers_with_stakes: Vec<_> = peers
-            .iter()
-            .map(|c| (bank.get_balance(&c.id), c.clone()))
-            .collect();
-        peers_with_stakes.sort_unstable();
-        peers_with_stakes
+    /// Repair peers that advertise `slot` as complete in their ledger
+    pub fn repair_peers_for_slot(&self, slot: u64) -> Vec<NodeInfo> {
+        self.repair_peers()
+            .into_iter()
+            .filter(|x| {
+                self.get_epoch_slots(x.id)
+                    .map_or(false, |epoch_slots| epoch_slots.slots.contains(&slot))
+            })
+            .collect()
     }
 
-    pub fn sorted_retransmit_peers(&self, bank: &Arc<Bank>) -> Vec<NodeInfo> {
-        let peers = self.retransmit_peers();
-        let peers_with_stakes: Vec<_> = ClusterInfo::sort_by_stake(&peers, bank);
-        peers_with_stakes
-            .iter()
-            .map(|(_, peer)| (*peer).clone())
+    /// Peers that advertise a version whose feature set doesn't match this build's
+    pub fn incompatible_peers(&self) -> Vec<NodeInfo> {
+        let me = self.my_data().id;
+        let feature_set = feature_set();
+        self.gossip
+            .crds
+            .table
+            .values()
+            .filter_map(|x| x.value.contact_info())
+            .filter(|x| x.id != me)
+            .filter(|x| {
+                self.get_version(x.id)
+                    .map_or(false, |version| version.feature_set != feature_set)
+            })
+            .cloned()
             .collect()
     }
 
-    pub fn sorted_tvu_peers(&self, bank: &Arc<Bank>) -> Vec<NodeInfo> {
-        let peers = self.tvu_peers();
-        let peers_with_stakes: Vec<_> = ClusterInfo::sort_by_stake(&peers, bank);
-        peers_with_stakes
-            .iter()
-            .map(|(_, peer)| (*peer).clone())
+    /// The frozen stakes of `epoch`, which weight the retransmit trees of its blobs
+    pub fn epoch_stakes(leader_scheduler: &LeaderScheduler, epoch: u64) -> HashMap<Pubkey, u64> {
+        leader_scheduler
+            .get_epoch_stakes(epoch)
+            .map(|epoch_stakes| epoch_stakes.stakes.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// `nodes` in the order of the retransmit tree of the blob at `index` of `slot`, see
+    /// `RetransmitTree`.  The first node is the root the leader sends the blob to.
+    pub fn retransmit_tree_order(
+        nodes: &[NodeInfo],
+        stakes: &HashMap<Pubkey, u64>,
+        slot: u64,
+        index: u64,
+    ) -> Vec<NodeInfo> {
+        RetransmitTree::new(nodes, stakes)
+            .order(slot, index)
+            .into_iter()
+            .map(|i| nodes[i].clone())
+            .collect()
+    }
+
+    /// Positions in a retransmit tree of `num_nodes` nodes that the node at `index` forwards
+    /// a blob to.  The first node of each neighborhood forwards to the rest of its
+    /// neighborhood, and every node forwards to its peers in the next layer.
+    pub fn retransmit_tree_peer_indices(num_nodes: usize, index: usize) -> Vec<usize> {
+        let (num_layers, layer_indices) = ClusterInfo::describe_data_plane(
+            num_nodes,
+            DATA_PLANE_FANOUT,
+            NEIGHBORHOOD_SIZE,
+            GROW_LAYER_CAPACITY,
+        );
+        if num_layers <= 1 {
+            // single layer data plane, the root forwards to everyone
+            return if index == 0 {
+                (1..num_nodes).collect()
+            } else {
+                vec![]
+            };
+        }
+        let locality = ClusterInfo::localize(&layer_indices, NEIGHBORHOOD_SIZE, index);
+        let mut peers = vec![];
+        if index == locality.neighbor_bounds.0 {
+            let end = min(locality.neighbor_bounds.1, num_nodes);
+            peers.extend(index + 1..end);
+        }
+        peers.extend(
+            locality
+                .child_layer_peers
+                .iter()
+                .filter(|ix| **ix < num_nodes),
+        );
+        peers
+    }
+
+    /// The nodes that the node at `index` of the retransmit tree `nodes` forwards a blob to,
+    /// see `retransmit_tree_peer_indices`
+    pub fn retransmit_tree_peers(nodes: &[NodeInfo], index: usize) -> Vec<NodeInfo> {
+        Self::retransmit_tree_peer_indices(nodes.len(), index)
+            .into_iter()
+            .map(|i| nodes[i].clone())
             .collect()
     }
 
@@ -530,6 +812,7 @@ impl ClusterInfo {
         id: &Pubkey,
         contains_last_tick: bool,
         broadcast_table: &[NodeInfo],
+        leader_scheduler: &Arc<RwLock<LeaderScheduler>>,
         s: &UdpSocket,
         blobs: &[SharedBlob],
     ) -> Result<()> {
@@ -539,7 +822,12 @@ impl ClusterInfo {
             Err(ClusterInfoError::NoPeers)?;
         }
 
-        let orders = Self::create_broadcast_orders(contains_last_tick, blobs, broadcast_table);
+        let orders = Self::create_broadcast_orders(
+            contains_last_tick,
+            blobs,
+            broadcast_table,
+            &leader_scheduler.read().unwrap(),
+        );
 
         trace!("broadcast orders table {}", orders.len());
 
@@ -659,17 +947,31 @@ impl ClusterInfo {
         contains_last_tick: bool,
         blobs: &[SharedBlob],
         broadcast_table: &'a [NodeInfo],
+        leader_scheduler: &LeaderScheduler,
     ) -> Vec<(SharedBlob, Vec<&'a NodeInfo>)> {
-        // enumerate all the blobs in the window, those are the indices
-        // transmit them to nodes, starting from a different node.
+        // send each blob to the root of its retransmit tree
         if blobs.is_empty() {
             return vec![];
         }
         let mut orders = Vec::with_capacity(blobs.len());
 
-        let x = thread_rng().gen_range(0, broadcast_table.len());
-        for (i, blob) in blobs.iter().enumerate() {
-            let br_idx = (x + i) % broadcast_table.len();
+        // the nodes are only sorted once per epoch, each blob then just draws its root
+        let mut tree: Option<(u64, RetransmitTree)> = None;
+        for blob in blobs {
+            let (slot, index) = {
+                let blob = blob.read().unwrap();
+                (blob.slot(), blob.index())
+            };
+            let epoch = leader_scheduler.slot_to_epoch(slot);
+            if tree
+                .as_ref()
+                .map_or(true, |(tree_epoch, _)| *tree_epoch != epoch)
+            {
+                let stakes = Self::epoch_stakes(leader_scheduler, epoch);
+                tree = Some((epoch, RetransmitTree::new(broadcast_table, &stakes)));
+            }
+            let (_, tree) = tree.as_ref().unwrap();
+            let br_idx = tree.root(slot, index).unwrap();
 
             trace!("broadcast order data br_idx {}", br_idx);
 
@@ -694,7 +996,7 @@ impl ClusterInfo {
 
     pub fn window_index_request_bytes(&self, ix: u64) -> Result<Vec<u8>> {
         let req = Protocol::RequestWindowIndex(self.my_data().clone(), ix);
-        let out = serialize(&req)?;
+        let out = serialize(&VersionedProtocol::new(req))?;
         Ok(out)
     }
 
@@ -742,6 +1044,7 @@ impl ClusterInfo {
     fn new_push_requests(&mut self) -> Vec<(SocketAddr, Protocol)> {
         let self_id = self.gossip.id;
         let (_, peers, msgs) = self.gossip.new_push_messages(timestamp());
+        let batches = split_gossip_messages(msgs);
         peers
             .into_iter()
             .filter_map(|p| {
@@ -752,7 +1055,11 @@ impl ClusterInfo {
                     .and_then(|v| v.contact_info())
                     .map(|p| p.gossip)
             })
-            .map(|peer| (peer, Protocol::PushMessage(self_id, msgs.clone())))
+            .flat_map(|peer| {
+                batches
+                    .iter()
+                    .map(move |batch| (peer, Protocol::PushMessage(self_id, batch.clone())))
+            })
             .collect()
     }
 
@@ -767,7 +1074,7 @@ impl ClusterInfo {
         let reqs = obj.write().unwrap().gossip_request();
         let blobs = reqs
             .into_iter()
-            .filter_map(|(remote_gossip_addr, req)| to_shared_blob(req, remote_gossip_addr).ok())
+            .filter_map(|(remote_gossip_addr, req)| to_protocol_blob(req, remote_gossip_addr))
             .collect();
         blob_sender.send(blobs)?;
         Ok(())
@@ -873,12 +1180,46 @@ impl ClusterInfo {
         db_ledger: Option<&Arc<DbLedger>>,
         blob: &Blob,
     ) -> Vec<SharedBlob> {
-        deserialize(&blob.data[..blob.meta.size])
-            .into_iter()
-            .flat_map(|request| {
-                ClusterInfo::handle_protocol(obj, &blob.meta.addr(), db_ledger, request)
-            })
-            .collect()
+        let data = &blob.data[..blob.meta.size];
+        let from_addr = blob.meta.addr();
+        // Check the version before the rest of the envelope, whose layout may differ
+        let version = match deserialize::<(u32, u32)>(data) {
+            Ok((GOSSIP_PROTOCOL_MAGIC, version)) => Some(version),
+            _ if deserialize::<Protocol>(data).is_ok() => Some(UNVERSIONED_GOSSIP_PROTOCOL_VERSION),
+            _ => None,
+        };
+        let request = match version {
+            Some(GOSSIP_PROTOCOL_VERSION) => deserialize::<VersionedProtocol>(data).ok(),
+            Some(version) => {
+                inc_new_counter_info!("cluster_info-gossip_incompatible_version", 1);
+                if obj
+                    .write()
+                    .unwrap()
+                    .peer_scores
+                    .record_incompatible_version(&from_addr, version)
+                {
+                    warn!(
+                        "gossip peer {} uses protocol version {}, expected {}",
+                        from_addr, version, GOSSIP_PROTOCOL_VERSION
+                    );
+                }
+                return vec![];
+            }
+            None => None,
+        };
+        match request {
+            Some(request) => {
+                ClusterInfo::handle_protocol(obj, &from_addr, db_ledger, request.protocol)
+            }
+            None => {
+                inc_new_counter_info!("cluster_info-gossip_deserialize_fail", 1);
+                obj.write()
+                    .unwrap()
+                    .peer_scores
+                    .record_invalid(&from_addr, 1);
+                vec![]
+            }
+        }
     }
 
     fn handle_pull_request(
@@ -913,7 +1254,6 @@ impl ClusterInfo {
             trace!("no updates me {}", self_id);
             vec![]
         } else {
-            let rsp = Protocol::PullResponse(self_id, data);
             // the remote side may not know his public IP:PORT, record what he looks like to us
             //  this may or may not be correct for everybody but it's better than leaving him with
             //  an unspecified address in our table
@@ -922,18 +1262,29 @@ impl ClusterInfo {
                 from.gossip = *from_addr;
             }
             inc_new_counter_info!("cluster_info-pull_request-rsp", len);
-            to_shared_blob(rsp, from.gossip).ok().into_iter().collect()
+            split_gossip_messages(data)
+                .into_iter()
+                .filter_map(|batch| {
+                    to_protocol_blob(Protocol::PullResponse(self_id, batch), from.gossip)
+                })
+                .collect()
         }
     }
-    fn handle_pull_response(me: &Arc<RwLock<Self>>, from: Pubkey, data: Vec<CrdsValue>) {
+    fn handle_pull_response(
+        me: &Arc<RwLock<Self>>,
+        from: Pubkey,
+        data: Vec<CrdsValue>,
+        from_addr: &SocketAddr,
+    ) {
         let len = data.len();
         let now = Instant::now();
         let self_id = me.read().unwrap().gossip.id;
         trace!("PullResponse me: {} len={}", self_id, len);
-        me.write()
-            .unwrap()
-            .gossip
-            .process_pull_response(from, data, timestamp());
+        let mut me = me.write().unwrap();
+        let duplicates = me.gossip.process_pull_response(from, data, timestamp());
+        if duplicates > 0 {
+            me.peer_scores.record_duplicate(from_addr, duplicates);
+        }
         inc_new_counter_info!("cluster_info-pull_request_response", 1);
         inc_new_counter_info!("cluster_info-pull_request_response-size", len);
 
@@ -943,6 +1294,7 @@ impl ClusterInfo {
         me: &Arc<RwLock<Self>>,
         from: Pubkey,
         data: &[CrdsValue],
+        from_addr: &SocketAddr,
     ) -> Vec<SharedBlob> {
         let self_id = me.read().unwrap().gossip.id;
         inc_new_counter_info!("cluster_info-push_message", 1);
@@ -953,6 +1305,10 @@ impl ClusterInfo {
             .process_push_message(&data, timestamp());
         if !prunes.is_empty() {
             inc_new_counter_info!("cluster_info-push_message-prunes", prunes.len());
+            me.write()
+                .unwrap()
+                .peer_scores
+                .record_duplicate(from_addr, prunes.len());
             let ci = me.read().unwrap().lookup(from).cloned();
             let pushes: Vec<_> = me.write().unwrap().new_push_requests();
             inc_new_counter_info!("cluster_info-push_message-pushes", pushes.len());
@@ -967,15 +1323,13 @@ impl ClusterInfo {
                     };
                     prune_msg.sign(&me.read().unwrap().keypair);
                     let rsp = Protocol::PruneMessage(self_id, prune_msg);
-                    to_shared_blob(rsp, ci.gossip).ok()
+                    to_protocol_blob(rsp, ci.gossip)
                 })
                 .into_iter()
                 .collect();
             let mut blobs: Vec<_> = pushes
                 .into_iter()
-                .filter_map(|(remote_gossip_addr, req)| {
-                    to_shared_blob(req, remote_gossip_addr).ok()
-                })
+                .filter_map(|(remote_gossip_addr, req)| to_protocol_blob(req, remote_gossip_addr))
                 .collect();
             rsp.append(&mut blobs);
             rsp
@@ -994,7 +1348,6 @@ impl ClusterInfo {
 
         //TODO this doesn't depend on cluster_info module, could be moved
         //but we are using the listen thread to service these request
-        //TODO verify from is signed
 
         let self_id = me.read().unwrap().gossip.id;
         if from.id == me.read().unwrap().gossip.id {
@@ -1006,7 +1359,23 @@ impl ClusterInfo {
             return vec![];
         }
 
-        me.write().unwrap().insert_info(from.clone());
+        // Repairs are served to anyone but denied nodes, and only a ContactInfo signed by
+        // its owner is remembered
+        let verified = from.verify();
+        if verified && me.read().unwrap().peer_scores.is_denied_id(&from.id) {
+            inc_new_counter_info!("cluster_info-gossip_denied_id", 1);
+            return vec![];
+        }
+        if verified {
+            let _ = me
+                .write()
+                .unwrap()
+                .gossip
+                .crds
+                .insert(CrdsValue::ContactInfo(from.clone()), timestamp());
+        } else {
+            inc_new_counter_info!("cluster_info-window-request-verify-fail", 1);
+        }
         let my_info = me.read().unwrap().my_data().clone();
         inc_new_counter_info!("cluster_info-window-request-recv", 1);
         trace!(
@@ -1029,13 +1398,40 @@ impl ClusterInfo {
         db_ledger: Option<&Arc<DbLedger>>,
         request: Protocol,
     ) -> Vec<SharedBlob> {
+        let record_invalid = |count: usize| {
+            if count > 0 {
+                me.write()
+                    .unwrap()
+                    .peer_scores
+                    .record_invalid(from_addr, count);
+            }
+        };
+        // Only a verified signature says who a value came from, the sender named in the
+        // message itself can be anyone
+        let is_denied = |id: &Pubkey| {
+            let denied = me.read().unwrap().peer_scores.is_denied_id(id);
+            if denied {
+                inc_new_counter_info!("cluster_info-gossip_denied_id", 1);
+            }
+            denied
+        };
         match request {
             // TODO verify messages faster
             Protocol::PullRequest(filter, caller) => {
-                //Pulls don't need to be verified
+                // The caller's ContactInfo is inserted into the table, so a forged one
+                // would redirect the caller's traffic
+                if !caller.verify() {
+                    inc_new_counter_info!("cluster_info-gossip_pull_request_verify_fail", 1);
+                    record_invalid(1);
+                    return vec![];
+                }
+                if is_denied(&caller.label().pubkey()) {
+                    return vec![];
+                }
                 Self::handle_pull_request(me, filter, caller, from_addr)
             }
             Protocol::PullResponse(from, mut data) => {
+                let len = data.len();
                 data.retain(|v| {
                     let ret = v.verify();
                     if !ret {
@@ -1043,10 +1439,13 @@ impl ClusterInfo {
                     }
                     ret
                 });
-                Self::handle_pull_response(me, from, data);
+                record_invalid(len - data.len());
+                data.retain(|v| !is_denied(&v.label().pubkey()));
+                Self::handle_pull_response(me, from, data, from_addr);
                 vec![]
             }
             Protocol::PushMessage(from, mut data) => {
+                let len = data.len();
                 data.retain(|v| {
                     let ret = v.verify();
                     if !ret {
@@ -1054,10 +1453,15 @@ impl ClusterInfo {
                     }
                     ret
                 });
-                Self::handle_push_message(me, from, &data)
+                record_invalid(len - data.len());
+                data.retain(|v| !is_denied(&v.label().pubkey()));
+                Self::handle_push_message(me, from, &data, from_addr)
             }
             Protocol::PruneMessage(from, data) => {
-                if data.verify() {
+                if !data.verify() {
+                    inc_new_counter_info!("cluster_info-gossip_prune_msg_verify_fail", 1);
+                    record_invalid(1);
+                } else if !is_denied(&data.pubkey) {
                     inc_new_counter_info!("cluster_info-prune_message", 1);
                     inc_new_counter_info!("cluster_info-prune_message-size", data.prunes.len());
                     match me.write().unwrap().gossip.process_prune_msg(
@@ -1076,8 +1480,6 @@ impl ClusterInfo {
                         Err(_) => (),
                         Ok(_) => (),
                     }
-                } else {
-                    inc_new_counter_info!("cluster_info-gossip_prune_msg_verify_fail", 1);
                 }
                 vec![]
             }
@@ -1100,6 +1502,20 @@ impl ClusterInfo {
         while let Ok(mut more) = requests_receiver.try_recv() {
             reqs.append(&mut more);
         }
+        let len = reqs.len();
+        let reqs: Vec<_> = {
+            let now = timestamp();
+            let mut me = obj.write().unwrap();
+            reqs.into_iter()
+                .filter(|req| {
+                    let blob = req.read().unwrap();
+                    me.peer_scores.admit(&blob.meta.addr(), blob.meta.size, now)
+                })
+                .collect()
+        };
+        if reqs.len() < len {
+            inc_new_counter_info!("cluster_info-gossip_peer_dropped", len - reqs.len());
+        }
         let mut resps = Vec::new();
         for req in reqs {
             let mut resp = Self::handle_blob(obj, db_ledger, &req.read().unwrap());
@@ -1139,13 +1555,15 @@ impl ClusterInfo {
             .unwrap()
     }
 
-    pub fn spy_node() -> (NodeInfo, UdpSocket) {
+    /// A node that only gossips.  Its ClusterInfo needs the keypair of `id` for peers to
+    /// accept its pull requests
+    pub fn spy_node(id: Pubkey) -> (NodeInfo, UdpSocket) {
         let (_, gossip_socket) = bind_in_range(FULLNODE_PORT_RANGE).unwrap();
-        let pubkey = Keypair::new().pubkey();
         let daddr = socketaddr_any!();
 
         let node = NodeInfo::new(
-            pubkey,
+            id,
+            daddr,
             daddr,
             daddr,
             daddr,
@@ -1163,9 +1581,13 @@ pub struct Sockets {
     pub gossip: UdpSocket,
     pub tvu: Vec<UdpSocket>,
     pub tpu: Vec<UdpSocket>,
+    /// Accepts transactions over TCP, see `tcp_receiver`
+    pub tpu_tcp: TcpListener,
     pub broadcast: UdpSocket,
     pub repair: UdpSocket,
     pub retransmit: UdpSocket,
+    /// Serves the ip echo protocol on the gossip port, see `solana_netutil::ip_echo_server`
+    pub ip_echo: Option<TcpListener>,
 }
 
 #[derive(Debug)]
@@ -1181,6 +1603,7 @@ impl Node {
     }
     pub fn new_localhost_with_pubkey(pubkey: Pubkey) -> Self {
         let tpu = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tpu_tcp = TcpListener::bind("127.0.0.1:0").unwrap();
         let gossip = UdpSocket::bind("127.0.0.1:0").unwrap();
         let tvu = UdpSocket::bind("127.0.0.1:0").unwrap();
         let repair = UdpSocket::bind("127.0.0.1:0").unwrap();
@@ -1198,6 +1621,7 @@ impl Node {
             gossip.local_addr().unwrap(),
             tvu.local_addr().unwrap(),
             tpu.local_addr().unwrap(),
+            tpu_tcp.local_addr().unwrap(),
             storage.local_addr().unwrap(),
             rpc_addr,
             rpc_pubsub_addr,
@@ -1209,9 +1633,11 @@ impl Node {
                 gossip,
                 tvu: vec![tvu],
                 tpu: vec![tpu],
+                tpu_tcp,
                 broadcast,
                 repair,
                 retransmit,
+                ip_echo: None,
             },
         }
     }
@@ -1220,15 +1646,15 @@ impl Node {
             bind_in_range(FULLNODE_PORT_RANGE).expect("Failed to bind")
         };
 
-        let (gossip_port, gossip) = if gossip_addr.port() != 0 {
+        let (gossip_port, (gossip, ip_echo)) = if gossip_addr.port() != 0 {
             (
                 gossip_addr.port(),
-                bind_to(gossip_addr.port(), false).unwrap_or_else(|e| {
-                    panic!("gossip_addr bind_to port {}: {}", gossip_addr.port(), e)
+                bind_common(gossip_addr.port(), false).unwrap_or_else(|e| {
+                    panic!("gossip_addr bind_common port {}: {}", gossip_addr.port(), e)
                 }),
             )
         } else {
-            bind()
+            bind_common_in_range(FULLNODE_PORT_RANGE).expect("Failed to bind")
         };
 
         let (tvu_port, tvu_sockets) =
@@ -1237,6 +1663,9 @@ impl Node {
         let (tpu_port, tpu_sockets) =
             multi_bind_in_range(FULLNODE_PORT_RANGE, 32).expect("tpu multi_bind");
 
+        let (tpu_tcp_port, (_, tpu_tcp)) =
+            bind_common_in_range(FULLNODE_PORT_RANGE).expect("tpu_tcp bind");
+
         let (_, repair) = bind();
         let (_, broadcast) = bind();
         let (_, retransmit) = bind();
@@ -1247,6 +1676,7 @@ impl Node {
             SocketAddr::new(gossip_addr.ip(), gossip_port),
             SocketAddr::new(gossip_addr.ip(), tvu_port),
             SocketAddr::new(gossip_addr.ip(), tpu_port),
+            SocketAddr::new(gossip_addr.ip(), tpu_tcp_port),
             SocketAddr::new(gossip_addr.ip(), storage_port),
             SocketAddr::new(gossip_addr.ip(), RPC_PORT),
             SocketAddr::new(gossip_addr.ip(), RPC_PORT + 1),
@@ -1260,9 +1690,11 @@ impl Node {
                 gossip,
                 tvu: tvu_sockets,
                 tpu: tpu_sockets,
+                tpu_tcp,
                 broadcast,
                 repair,
                 retransmit,
+                ip_echo: Some(ip_echo),
             },
         }
     }
@@ -1281,7 +1713,7 @@ mod tests {
     use crate::crds_value::CrdsValueLabel;
     use crate::db_ledger::get_tmp_ledger_path;
     use crate::db_ledger::DbLedger;
-    use crate::packet::BLOB_HEADER_SIZE;
+    use crate::packet::{to_blob, BLOB_HEADER_SIZE};
     use crate::result::Error;
     use crate::test_tx::test_tx;
     use solana_sdk::signature::{Keypair, KeypairUtil};
@@ -1293,14 +1725,14 @@ mod tests {
     fn test_cluster_spy_gossip() {
         //check that gossip doesn't try to push to invalid addresses
         let node = Node::new_localhost();
-        let (spy, _) = ClusterInfo::spy_node();
+        let (spy, _) = ClusterInfo::spy_node(Keypair::new().pubkey());
         let cluster_info = Arc::new(RwLock::new(ClusterInfo::new(node.info)));
         cluster_info.write().unwrap().insert_info(spy);
         cluster_info
             .write()
             .unwrap()
             .gossip
-            .refresh_push_active_set();
+            .refresh_push_active_set(&HashMap::new());
         let reqs = cluster_info.write().unwrap().gossip_request();
         //assert none of the addrs are invalid.
         reqs.iter().all(|(addr, _)| {
@@ -1342,6 +1774,7 @@ mod tests {
             socketaddr!([127, 0, 0, 1], 1237),
             socketaddr!([127, 0, 0, 1], 1238),
             socketaddr!([127, 0, 0, 1], 1239),
+            socketaddr!([127, 0, 0, 1], 1240),
             0,
         );
         cluster_info.insert_info(nxt.clone());
@@ -1358,6 +1791,7 @@ mod tests {
             socketaddr!([127, 0, 0, 1], 1237),
             socketaddr!([127, 0, 0, 1], 1238),
             socketaddr!([127, 0, 0, 1], 1239),
+            socketaddr!([127, 0, 0, 1], 1240),
             0,
         );
         cluster_info.insert_info(nxt);
@@ -1391,6 +1825,7 @@ mod tests {
                 socketaddr!("127.0.0.1:1237"),
                 socketaddr!("127.0.0.1:1238"),
                 socketaddr!("127.0.0.1:1239"),
+                socketaddr!("127.0.0.1:1240"),
                 0,
             );
             let rv =
@@ -1481,6 +1916,16 @@ mod tests {
         assert_eq!(node.sockets.repair.local_addr().unwrap().ip(), ip);
 
         assert_eq!(node.sockets.gossip.local_addr().unwrap().port(), 8050);
+        assert_eq!(
+            node.sockets
+                .ip_echo
+                .as_ref()
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port(),
+            8050
+        );
         let tx_port = node.sockets.tvu[0].local_addr().unwrap().port();
         assert!(tx_port >= FULLNODE_PORT_RANGE.0);
         assert!(tx_port < FULLNODE_PORT_RANGE.1);
@@ -1675,6 +2120,78 @@ mod tests {
         assert!(!broadcast_set.contains(&(layer_indices.last().unwrap())));
     }
 
+    #[test]
+    fn test_retransmit_tree_order() {
+        let nodes: Vec<_> = (0..20)
+            .map(|_| NodeInfo::new_localhost(Keypair::new().pubkey(), 0))
+            .collect();
+        let stakes = HashMap::new();
+        let order = ClusterInfo::retransmit_tree_order(&nodes, &stakes, 1, 2);
+        assert_eq!(order.len(), nodes.len());
+
+        // every node computes the same tree, however it found its peers
+        let mut reversed = nodes.clone();
+        reversed.reverse();
+        let reversed_order = ClusterInfo::retransmit_tree_order(&reversed, &stakes, 1, 2);
+        assert_eq!(
+            order.iter().map(|node| node.id).collect::<Vec<_>>(),
+            reversed_order
+                .iter()
+                .map(|node| node.id)
+                .collect::<Vec<_>>()
+        );
+
+        // but each blob takes a different path
+        let roots: HashSet<_> = (0..20)
+            .map(|index| ClusterInfo::retransmit_tree_order(&nodes, &stakes, 1, index)[0].id)
+            .collect();
+        assert!(roots.len() > 1);
+
+        // and heavily staked nodes are found near the root
+        let mut stakes = HashMap::new();
+        stakes.insert(nodes[7].id, 1_000_000_000);
+        for index in 0..20 {
+            let order = ClusterInfo::retransmit_tree_order(&nodes, &stakes, 1, index);
+            assert_eq!(order[0].id, nodes[7].id);
+        }
+    }
+
+    #[test]
+    fn test_retransmit_tree_root() {
+        let nodes: Vec<_> = (0..50)
+            .map(|_| NodeInfo::new_localhost(Keypair::new().pubkey(), 0))
+            .collect();
+        let mut stakes = HashMap::new();
+        for (i, node) in nodes.iter().enumerate().step_by(3) {
+            stakes.insert(node.id, i as u64);
+        }
+        let tree = RetransmitTree::new(&nodes, &stakes);
+        assert_eq!(tree.len(), nodes.len());
+        for index in 0..100 {
+            let order = tree.order(5, index);
+            assert_eq!(tree.root(5, index), Some(order[0]));
+        }
+        assert!(RetransmitTree::new(&[], &stakes).root(5, 0).is_none());
+    }
+
+    #[test]
+    fn test_retransmit_tree_coverage() {
+        for num_nodes in &[1, 10, DATA_PLANE_FANOUT + 300] {
+            let nodes: Vec<_> = (0..*num_nodes)
+                .map(|_| NodeInfo::new_localhost(Keypair::new().pubkey(), 0))
+                .collect();
+            let order = ClusterInfo::retransmit_tree_order(&nodes, &HashMap::new(), 3, 4);
+            let mut reached = HashSet::new();
+            reached.insert(order[0].id);
+            for index in 0..order.len() {
+                let peers = ClusterInfo::retransmit_tree_peers(&order, index);
+                assert!(peers.iter().all(|peer| peer.id != order[index].id));
+                reached.extend(peers.into_iter().map(|peer| peer.id));
+            }
+            assert_eq!(reached.len(), nodes.len());
+        }
+    }
+
     #[test]
     fn test_push_vote() {
         let keys = Keypair::new();
@@ -1701,4 +2218,266 @@ mod tests {
         assert_eq!(votes, vec![]);
         assert_eq!(max_ts, new_max_ts);
     }
+
+    #[test]
+    fn test_push_epoch_slots_version_and_root() {
+        let keypair = Keypair::new();
+        let node_info = NodeInfo::new_localhost(keypair.pubkey(), 0);
+        let mut cluster_info = ClusterInfo::new_with_keypair(node_info, Arc::new(keypair));
+        let id = cluster_info.id();
+        assert!(cluster_info.get_epoch_slots(id).is_none());
+        assert!(cluster_info.get_version(id).is_none());
+        assert_eq!(cluster_info.get_root(id), None);
+
+        let slots: BTreeSet<u64> = (1..4).collect();
+        cluster_info.push_epoch_slots(1, slots.clone());
+        cluster_info.push_version();
+        cluster_info.push_root(24);
+        let epoch_slots = cluster_info.get_epoch_slots(id).unwrap();
+        assert_eq!(epoch_slots.epoch, 1);
+        assert_eq!(epoch_slots.slots, slots);
+        assert_eq!(
+            cluster_info.get_version(id).unwrap().feature_set,
+            feature_set()
+        );
+        assert_eq!(cluster_info.get_root(id), Some(24));
+
+        // Only peers that have a slot complete are asked to repair it, and peers that
+        // run an incompatible build are reported
+        let peer_keypair = Keypair::new();
+        let peer = NodeInfo::new_localhost(peer_keypair.pubkey(), timestamp());
+        cluster_info.insert_info(peer.clone());
+        assert!(cluster_info.repair_peers_for_slot(2).is_empty());
+        assert!(cluster_info.incompatible_peers().is_empty());
+
+        let epoch_slots = EpochSlots::new(peer.id, 1, slots, timestamp());
+        let mut epoch_slots = CrdsValue::EpochSlots(epoch_slots);
+        epoch_slots.sign(&peer_keypair);
+        let mut version = Version::new(peer.id, timestamp());
+        version.feature_set = feature_set().wrapping_add(1);
+        let mut version = CrdsValue::Version(version);
+        version.sign(&peer_keypair);
+        cluster_info
+            .gossip
+            .process_push_message(&[epoch_slots, version], timestamp());
+        assert_eq!(cluster_info.repair_peers_for_slot(2), vec![peer.clone()]);
+        assert!(cluster_info.repair_peers_for_slot(4).is_empty());
+        assert_eq!(cluster_info.incompatible_peers(), vec![peer]);
+    }
+
+    #[test]
+    fn test_handle_protocol_drops_forged_values() {
+        let keypair = Arc::new(Keypair::new());
+        let node_info = NodeInfo::new_localhost(keypair.pubkey(), 0);
+        let me = Arc::new(RwLock::new(ClusterInfo::new_with_keypair(
+            node_info, keypair,
+        )));
+        let from_addr = socketaddr!("127.0.0.1:1234");
+
+        // A ContactInfo of the victim, signed by someone else
+        let victim = Keypair::new();
+        let attacker = Keypair::new();
+        let mut value =
+            CrdsValue::ContactInfo(NodeInfo::new_localhost(victim.pubkey(), timestamp()));
+        value.sign(&attacker);
+        let requests = vec![
+            Protocol::PushMessage(attacker.pubkey(), vec![value.clone()]),
+            Protocol::PullResponse(attacker.pubkey(), vec![value.clone()]),
+            Protocol::PullRequest(Bloom::random(1, 0.1, 1024), value.clone()),
+            Protocol::RequestWindowIndex(value.contact_info().cloned().unwrap(), 0),
+        ];
+        for request in requests {
+            ClusterInfo::handle_protocol(&me, &from_addr, None, request);
+            assert!(me.read().unwrap().lookup(victim.pubkey()).is_none());
+        }
+
+        // The same ContactInfo signed by its owner is accepted
+        value.sign(&victim);
+        ClusterInfo::handle_protocol(
+            &me,
+            &from_addr,
+            None,
+            Protocol::PushMessage(victim.pubkey(), vec![value]),
+        );
+        assert!(me.read().unwrap().lookup(victim.pubkey()).is_some());
+    }
+
+    #[test]
+    fn test_handle_protocol_peer_scores() {
+        let keypair = Arc::new(Keypair::new());
+        let node_info = NodeInfo::new_localhost(keypair.pubkey(), 0);
+        let me = Arc::new(RwLock::new(ClusterInfo::new_with_keypair(
+            node_info, keypair,
+        )));
+        let from_addr = socketaddr!("127.0.0.1:1234");
+
+        // Forged values are counted, but don't keep the address they claim to come from
+        // from being admitted
+        let attacker = Keypair::new();
+        let mut value = CrdsValue::ContactInfo(NodeInfo::new_localhost(
+            Keypair::new().pubkey(),
+            timestamp(),
+        ));
+        value.sign(&attacker);
+        for _ in 0..1000 {
+            ClusterInfo::handle_protocol(
+                &me,
+                &from_addr,
+                None,
+                Protocol::PushMessage(attacker.pubkey(), vec![value.clone()]),
+            );
+        }
+        assert!(me
+            .write()
+            .unwrap()
+            .peer_scores
+            .admit(&from_addr, 1, timestamp()));
+        assert_eq!(
+            me.read()
+                .unwrap()
+                .peer_scores
+                .stats(&from_addr.ip())
+                .unwrap()
+                .invalid,
+            1000
+        );
+
+        // Values signed by denied node ids are ignored, even when relayed by another node
+        let denied = Keypair::new();
+        let mut filter = PeerFilter::default();
+        filter.denied_ids.insert(denied.pubkey());
+        me.write().unwrap().set_peer_filter(filter);
+        let mut value =
+            CrdsValue::ContactInfo(NodeInfo::new_localhost(denied.pubkey(), timestamp()));
+        value.sign(&denied);
+        for from in vec![denied.pubkey(), Keypair::new().pubkey()] {
+            ClusterInfo::handle_protocol(
+                &me,
+                &from_addr,
+                None,
+                Protocol::PushMessage(from, vec![value.clone()]),
+            );
+            assert!(me.read().unwrap().lookup(denied.pubkey()).is_none());
+        }
+
+        // Naming a denied node as the sender doesn't get other nodes' values dropped
+        let relayed = Keypair::new();
+        let mut value =
+            CrdsValue::ContactInfo(NodeInfo::new_localhost(relayed.pubkey(), timestamp()));
+        value.sign(&relayed);
+        ClusterInfo::handle_protocol(
+            &me,
+            &from_addr,
+            None,
+            Protocol::PushMessage(denied.pubkey(), vec![value]),
+        );
+        assert!(me.read().unwrap().lookup(relayed.pubkey()).is_some());
+    }
+
+    #[test]
+    fn test_protocol_header_size() {
+        let id = Keypair::new().pubkey();
+        for protocol in vec![
+            Protocol::PushMessage(id, vec![]),
+            Protocol::PullResponse(id, vec![]),
+        ] {
+            assert!(
+                serialized_size(&VersionedProtocol::new(protocol)).unwrap() <= PROTOCOL_HEADER_SIZE
+            );
+        }
+    }
+
+    #[test]
+    fn test_split_gossip_messages() {
+        let id = Keypair::new().pubkey();
+        let values: Vec<_> = (0..1000)
+            .map(|_| CrdsValue::ContactInfo(NodeInfo::new_localhost(Keypair::new().pubkey(), 0)))
+            .collect();
+        let batches = split_gossip_messages(values.clone());
+        assert!(batches.len() > 1);
+        for batch in &batches {
+            let protocol = VersionedProtocol::new(Protocol::PushMessage(id, batch.clone()));
+            assert!(serialized_size(&protocol).unwrap() <= BLOB_DATA_SIZE as u64);
+            assert!(to_protocol_blob(protocol.protocol, socketaddr!("127.0.0.1:1234")).is_some());
+        }
+        let split: Vec<_> = batches.into_iter().flatten().collect();
+        assert_eq!(split, values);
+        assert!(split_gossip_messages(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_handle_blob_incompatible_version() {
+        let keypair = Arc::new(Keypair::new());
+        let node_info = NodeInfo::new_localhost(keypair.pubkey(), 0);
+        let me = Arc::new(RwLock::new(ClusterInfo::new_with_keypair(
+            node_info, keypair,
+        )));
+        let from_addr = socketaddr!("127.0.0.1:1234");
+        let peer = Keypair::new();
+        let mut value = CrdsValue::ContactInfo(NodeInfo::new_localhost(peer.pubkey(), timestamp()));
+        value.sign(&peer);
+        let protocol = Protocol::PushMessage(peer.pubkey(), vec![value]);
+
+        let blob = to_blob(
+            (
+                GOSSIP_PROTOCOL_MAGIC,
+                GOSSIP_PROTOCOL_VERSION + 1,
+                &protocol,
+            ),
+            from_addr,
+        )
+        .unwrap();
+        assert!(ClusterInfo::handle_blob(&me, None, &blob).is_empty());
+        assert!(me.read().unwrap().lookup(peer.pubkey()).is_none());
+        assert_eq!(
+            me.read().unwrap().incompatible_protocol_peers(),
+            vec![(from_addr.ip(), GOSSIP_PROTOCOL_VERSION + 1)]
+        );
+
+        let blob = to_blob(VersionedProtocol::new(protocol), from_addr).unwrap();
+        ClusterInfo::handle_blob(&me, None, &blob);
+        assert!(me.read().unwrap().lookup(peer.pubkey()).is_some());
+    }
+
+    #[test]
+    fn test_handle_blob_unversioned() {
+        let keypair = Arc::new(Keypair::new());
+        let node_info = NodeInfo::new_localhost(keypair.pubkey(), 0);
+        let me = Arc::new(RwLock::new(ClusterInfo::new_with_keypair(
+            node_info, keypair,
+        )));
+        let from_addr = socketaddr!("127.0.0.1:1234");
+        let peer = Keypair::new();
+        let mut value = CrdsValue::ContactInfo(NodeInfo::new_localhost(peer.pubkey(), timestamp()));
+        value.sign(&peer);
+
+        // The PushMessage tag is the same number as the current version, a bare message
+        // must still not be taken for an envelope
+        for protocol in vec![
+            Protocol::PullResponse(peer.pubkey(), vec![value.clone()]),
+            Protocol::PushMessage(peer.pubkey(), vec![value.clone()]),
+        ] {
+            let blob = to_blob(protocol, from_addr).unwrap();
+            assert!(ClusterInfo::handle_blob(&me, None, &blob).is_empty());
+            assert!(me.read().unwrap().lookup(peer.pubkey()).is_none());
+        }
+        assert_eq!(
+            me.read().unwrap().incompatible_protocol_peers(),
+            vec![(from_addr.ip(), UNVERSIONED_GOSSIP_PROTOCOL_VERSION)]
+        );
+
+        // Garbage isn't mistaken for an old peer
+        let other_addr = socketaddr!("127.0.0.2:1234");
+        let blob = to_blob(vec![0xffu8; 16], other_addr).unwrap();
+        assert!(ClusterInfo::handle_blob(&me, None, &blob).is_empty());
+        assert_eq!(
+            me.read()
+                .unwrap()
+                .peer_scores
+                .stats(&other_addr.ip())
+                .unwrap()
+                .invalid,
+            1
+        );
+    }
 }
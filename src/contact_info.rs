@@ -18,6 +18,8 @@ pub struct ContactInfo {
     pub tvu: SocketAddr,
     /// transactions address
     pub tpu: SocketAddr,
+    /// transactions address for length-prefixed frames over TCP, see `tcp_receiver`
+    pub tpu_tcp: SocketAddr,
     /// storage data address
     pub storage_addr: SocketAddr,
     /// address to which to send JSON-RPC requests
@@ -72,6 +74,7 @@ impl Default for ContactInfo {
             gossip: socketaddr_any!(),
             tvu: socketaddr_any!(),
             tpu: socketaddr_any!(),
+            tpu_tcp: socketaddr_any!(),
             storage_addr: socketaddr_any!(),
             rpc: socketaddr_any!(),
             rpc_pubsub: socketaddr_any!(),
@@ -87,6 +90,7 @@ impl ContactInfo {
         gossip: SocketAddr,
         tvu: SocketAddr,
         tpu: SocketAddr,
+        tpu_tcp: SocketAddr,
         storage_addr: SocketAddr,
         rpc: SocketAddr,
         rpc_pubsub: SocketAddr,
@@ -98,6 +102,7 @@ impl ContactInfo {
             gossip,
             tvu,
             tpu,
+            tpu_tcp,
             storage_addr,
             rpc,
             rpc_pubsub,
@@ -114,6 +119,7 @@ impl ContactInfo {
             socketaddr!("127.0.0.1:1237"),
             socketaddr!("127.0.0.1:1238"),
             socketaddr!("127.0.0.1:1239"),
+            socketaddr!("127.0.0.1:1240"),
             now,
         )
     }
@@ -131,6 +137,7 @@ impl ContactInfo {
             addr,
             addr,
             addr,
+            addr,
             0,
         )
     }
@@ -150,6 +157,7 @@ impl ContactInfo {
             gossip_addr,
             tvu_addr,
             tpu_addr,
+            tpu_addr,
             "0.0.0.0:0".parse().unwrap(),
             rpc_addr,
             rpc_pubsub_addr,
@@ -171,6 +179,7 @@ impl ContactInfo {
             daddr,
             daddr,
             daddr,
+            daddr,
             timestamp(),
         )
     }
@@ -199,6 +208,7 @@ impl Signable for ContactInfo {
             gossip: SocketAddr,
             tvu: SocketAddr,
             tpu: SocketAddr,
+            tpu_tcp: SocketAddr,
             storage_addr: SocketAddr,
             rpc: SocketAddr,
             rpc_pubsub: SocketAddr,
@@ -211,6 +221,7 @@ impl Signable for ContactInfo {
             gossip: me.gossip,
             tvu: me.tvu,
             tpu: me.tpu,
+            tpu_tcp: me.tpu_tcp,
             storage_addr: me.storage_addr,
             rpc: me.rpc,
             rpc_pubsub: me.rpc_pubsub,
@@ -253,6 +264,7 @@ mod tests {
         assert!(ci.rpc.ip().is_unspecified());
         assert!(ci.rpc_pubsub.ip().is_unspecified());
         assert!(ci.tpu.ip().is_unspecified());
+        assert!(ci.tpu_tcp.ip().is_unspecified());
         assert!(ci.storage_addr.ip().is_unspecified());
     }
     #[test]
@@ -263,6 +275,7 @@ mod tests {
         assert!(ci.rpc.ip().is_multicast());
         assert!(ci.rpc_pubsub.ip().is_multicast());
         assert!(ci.tpu.ip().is_multicast());
+        assert!(ci.tpu_tcp.ip().is_multicast());
         assert!(ci.storage_addr.ip().is_multicast());
     }
     #[test]
@@ -281,6 +294,7 @@ mod tests {
         let addr = socketaddr!("127.0.0.1:10");
         let ci = ContactInfo::new_with_socketaddr(&addr);
         assert_eq!(ci.tpu, addr);
+        assert_eq!(ci.tpu_tcp, addr);
         assert_eq!(ci.gossip.port(), 11);
         assert_eq!(ci.tvu.port(), 12);
         assert_eq!(ci.rpc.port(), 8899);
@@ -9,6 +9,7 @@ use crate::crds_gossip_error::CrdsGossipError;
 use crate::crds_gossip_pull::CrdsGossipPull;
 use crate::crds_gossip_push::{CrdsGossipPush, CRDS_GOSSIP_NUM_ACTIVE};
 use crate::crds_value::CrdsValue;
+use hashbrown::HashMap;
 use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
 
@@ -90,10 +91,11 @@ impl CrdsGossip {
     }
 
     /// refresh the push active set
-    /// * ratio - number of actives to rotate
-    pub fn refresh_push_active_set(&mut self) {
+    /// * stakes - stake of each known node, staked nodes are favoured
+    pub fn refresh_push_active_set(&mut self, stakes: &HashMap<Pubkey, u64>) {
         self.push.refresh_push_active_set(
             &self.crds,
+            stakes,
             self.id,
             self.pull.pull_request_time.len(),
             CRDS_GOSSIP_NUM_ACTIVE,
@@ -267,7 +269,9 @@ mod test {
         // make sure there is someone in the active set
         let network_values: Vec<Node> = network.values().cloned().collect();
         network_values.par_iter().for_each(|node| {
-            node.lock().unwrap().refresh_push_active_set();
+            node.lock()
+                .unwrap()
+                .refresh_push_active_set(&hashbrown::HashMap::new());
         });
         let mut total_bytes = bytes_tx;
         for second in 1..num {
@@ -366,7 +370,9 @@ mod test {
             }
             if now % CRDS_GOSSIP_PUSH_MSG_TIMEOUT_MS == 0 && now > 0 {
                 network_values.par_iter().for_each(|node| {
-                    node.lock().unwrap().refresh_push_active_set();
+                    node.lock()
+                        .unwrap()
+                        .refresh_push_active_set(&hashbrown::HashMap::new());
                 });
             }
             total = network_values
@@ -527,7 +533,7 @@ mod test {
             .crds
             .insert(CrdsValue::ContactInfo(ci.clone()), 0)
             .unwrap();
-        crds_gossip.refresh_push_active_set();
+        crds_gossip.refresh_push_active_set(&hashbrown::HashMap::new());
         let now = timestamp();
         //incorrect dest
         let mut res = crds_gossip.process_prune_msg(
@@ -25,13 +25,15 @@ use std::cmp;
 use std::collections::VecDeque;
 
 pub const CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS: u64 = 15000;
+/// Max bytes of values in a single pull response, which is split into several messages
+pub const CRDS_GOSSIP_PULL_MAX_RESPONSE_BYTES: usize = 4 * BLOB_DATA_SIZE;
 
 pub struct CrdsGossipPull {
     /// timestamp of last request
     pub pull_request_time: HashMap<Pubkey, u64>,
     /// hash and insert time
     purged_values: VecDeque<(Hash, u64)>,
-    /// max bytes per message
+    /// max bytes per response
     pub max_bytes: usize,
     pub crds_timeout: u64,
 }
@@ -41,7 +43,7 @@ impl Default for CrdsGossipPull {
         Self {
             purged_values: VecDeque::new(),
             pull_request_time: HashMap::new(),
-            max_bytes: BLOB_DATA_SIZE,
+            max_bytes: CRDS_GOSSIP_PULL_MAX_RESPONSE_BYTES,
             crds_timeout: CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS,
         }
     }
@@ -16,10 +16,11 @@ use crate::crds_gossip_error::CrdsGossipError;
 use crate::crds_value::{CrdsValue, CrdsValueLabel};
 use crate::packet::BLOB_DATA_SIZE;
 use bincode::serialized_size;
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use indexmap::map::IndexMap;
 use rand;
 use rand::seq::SliceRandom;
+use rand::Rng;
 use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
 use std::cmp;
@@ -156,47 +157,52 @@ impl CrdsGossipPush {
 
     /// refresh the push active set
     /// * ratio - active_set.len()/ratio is the number of actives to rotate
+    /// * stakes - new peers are admitted with probability weighted by their stake, so staked
+    ///   peers are favoured and unstaked peers fill the remaining slots
     pub fn refresh_push_active_set(
         &mut self,
         crds: &Crds,
+        stakes: &HashMap<Pubkey, u64>,
         self_id: Pubkey,
         network_size: usize,
         ratio: usize,
     ) {
         let need = Self::compute_need(self.num_active, self.active_set.len(), ratio);
-        let mut new_items = HashMap::new();
-        let mut ixs: Vec<_> = (0..crds.table.len()).collect();
-        ixs.shuffle(&mut rand::thread_rng());
-
-        for ix in ixs {
-            let item = crds.table.get_index(ix);
-            if item.is_none() {
-                continue;
-            }
-            let val = item.unwrap();
-            if val.0.pubkey() == self_id {
-                continue;
-            }
-            if self.active_set.get(&val.0.pubkey()).is_some() {
-                continue;
-            }
-            if new_items.get(&val.0.pubkey()).is_some() {
-                continue;
-            }
-            if let Some(contact) = val.1.value.contact_info() {
-                if !ContactInfo::is_valid_address(&contact.gossip) {
-                    continue;
+        let mut rng = rand::thread_rng();
+        let mut seen = HashSet::new();
+        let mut candidates: Vec<(f64, Pubkey)> = crds
+            .table
+            .iter()
+            .filter_map(|(label, val)| {
+                let pubkey = label.pubkey();
+                if pubkey == self_id
+                    || self.active_set.get(&pubkey).is_some()
+                    || seen.contains(&pubkey)
+                {
+                    return None;
                 }
-            }
-            let size = cmp::max(CRDS_GOSSIP_BLOOM_SIZE, network_size);
-            let bloom = Bloom::random(size, 0.1, 1024 * 8 * 4);
-            new_items.insert(val.0.pubkey(), bloom);
-            if new_items.len() == need {
-                break;
-            }
-        }
+                if let Some(contact) = val.value.contact_info() {
+                    if !ContactInfo::is_valid_address(&contact.gossip) {
+                        return None;
+                    }
+                }
+                seen.insert(pubkey);
+                // weighted random order, see Efraimidis and Spirakis, "Weighted random
+                // sampling with a reservoir"
+                let weight = stakes.get(&pubkey).cloned().unwrap_or(0).saturating_add(1);
+                Some((rng.gen::<f64>().powf(1.0 / weight as f64), pubkey))
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+        let size = cmp::max(CRDS_GOSSIP_BLOOM_SIZE, network_size);
+        let new_items: Vec<_> = candidates
+            .into_iter()
+            .take(need)
+            .map(|(_, pubkey)| (pubkey, Bloom::random(size, 0.1, 1024 * 8 * 4)))
+            .collect();
         let mut keys: Vec<Pubkey> = self.active_set.keys().cloned().collect();
-        keys.shuffle(&mut rand::thread_rng());
+        keys.shuffle(&mut rng);
         let num = keys.len() / ratio;
         for k in &keys[..num] {
             self.active_set.remove(k);
@@ -348,14 +354,14 @@ mod test {
         let value1 = CrdsValue::ContactInfo(ContactInfo::new_localhost(Keypair::new().pubkey(), 0));
 
         assert_eq!(crds.insert(value1.clone(), 0), Ok(None));
-        push.refresh_push_active_set(&crds, Pubkey::default(), 1, 1);
+        push.refresh_push_active_set(&crds, &HashMap::new(), Pubkey::default(), 1, 1);
 
         assert!(push.active_set.get(&value1.label().pubkey()).is_some());
         let value2 = CrdsValue::ContactInfo(ContactInfo::new_localhost(Keypair::new().pubkey(), 0));
         assert!(push.active_set.get(&value2.label().pubkey()).is_none());
         assert_eq!(crds.insert(value2.clone(), 0), Ok(None));
         for _ in 0..30 {
-            push.refresh_push_active_set(&crds, Pubkey::default(), 1, 1);
+            push.refresh_push_active_set(&crds, &HashMap::new(), Pubkey::default(), 1, 1);
             if push.active_set.get(&value2.label().pubkey()).is_some() {
                 break;
             }
@@ -367,16 +373,35 @@ mod test {
                 CrdsValue::ContactInfo(ContactInfo::new_localhost(Keypair::new().pubkey(), 0));
             assert_eq!(crds.insert(value2.clone(), 0), Ok(None));
         }
-        push.refresh_push_active_set(&crds, Pubkey::default(), 1, 1);
+        push.refresh_push_active_set(&crds, &HashMap::new(), Pubkey::default(), 1, 1);
         assert_eq!(push.active_set.len(), push.num_active);
     }
     #[test]
+    fn test_refresh_active_set_favours_stake() {
+        let mut crds = Crds::default();
+        let mut push = CrdsGossipPush::default();
+        push.num_active = 1;
+        for _ in 0..10 {
+            let value =
+                CrdsValue::ContactInfo(ContactInfo::new_localhost(Keypair::new().pubkey(), 0));
+            assert_eq!(crds.insert(value, 0), Ok(None));
+        }
+        let staked = CrdsValue::ContactInfo(ContactInfo::new_localhost(Keypair::new().pubkey(), 0));
+        assert_eq!(crds.insert(staked.clone(), 0), Ok(None));
+        let mut stakes = HashMap::new();
+        stakes.insert(staked.label().pubkey(), u64::max_value() / 2);
+
+        push.refresh_push_active_set(&crds, &stakes, Pubkey::default(), 1, 1);
+        assert_eq!(push.active_set.len(), 1);
+        assert!(push.active_set.get(&staked.label().pubkey()).is_some());
+    }
+    #[test]
     fn test_new_push_messages() {
         let mut crds = Crds::default();
         let mut push = CrdsGossipPush::default();
         let peer = CrdsValue::ContactInfo(ContactInfo::new_localhost(Keypair::new().pubkey(), 0));
         assert_eq!(crds.insert(peer.clone(), 0), Ok(None));
-        push.refresh_push_active_set(&crds, Pubkey::default(), 1, 1);
+        push.refresh_push_active_set(&crds, &HashMap::new(), Pubkey::default(), 1, 1);
 
         let new_msg =
             CrdsValue::ContactInfo(ContactInfo::new_localhost(Keypair::new().pubkey(), 0));
@@ -396,7 +421,7 @@ mod test {
         let mut push = CrdsGossipPush::default();
         let peer = CrdsValue::ContactInfo(ContactInfo::new_localhost(Keypair::new().pubkey(), 0));
         assert_eq!(crds.insert(peer.clone(), 0), Ok(None));
-        push.refresh_push_active_set(&crds, Pubkey::default(), 1, 1);
+        push.refresh_push_active_set(&crds, &HashMap::new(), Pubkey::default(), 1, 1);
 
         let new_msg =
             CrdsValue::ContactInfo(ContactInfo::new_localhost(Keypair::new().pubkey(), 0));
@@ -416,7 +441,7 @@ mod test {
         let mut push = CrdsGossipPush::default();
         let peer = CrdsValue::ContactInfo(ContactInfo::new_localhost(Keypair::new().pubkey(), 0));
         assert_eq!(crds.insert(peer.clone(), 0), Ok(None));
-        push.refresh_push_active_set(&crds, Pubkey::default(), 1, 1);
+        push.refresh_push_active_set(&crds, &HashMap::new(), Pubkey::default(), 1, 1);
 
         let mut ci = ContactInfo::new_localhost(Keypair::new().pubkey(), 0);
         ci.wallclock = 1;
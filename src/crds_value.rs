@@ -1,9 +1,13 @@
 use crate::contact_info::ContactInfo;
 use bincode::serialize;
+use byteorder::{LittleEndian, ReadBytesExt};
+use solana_sdk::hash::hash;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, Signable, Signature};
 use solana_sdk::transaction::Transaction;
+use std::collections::BTreeSet;
 use std::fmt;
+use std::io::Cursor;
 
 /// CrdsValue that is replicated across the cluster
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -14,6 +18,12 @@ pub enum CrdsValue {
     Vote(Vote),
     /// * Merge Strategy - Latest wallclock is picked
     LeaderId(LeaderId),
+    /// * Merge Strategy - Latest wallclock is picked
+    EpochSlots(EpochSlots),
+    /// * Merge Strategy - Latest wallclock is picked
+    Version(Version),
+    /// * Merge Strategy - Latest wallclock is picked
+    Root(Root),
 }
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
@@ -26,11 +36,42 @@ pub struct LeaderId {
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Vote {
+    pub from: Pubkey,
     pub transaction: Transaction,
     pub signature: Signature,
     pub wallclock: u64,
 }
 
+/// The slots of an epoch that a node has complete in its ledger, and can serve repairs for
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EpochSlots {
+    pub from: Pubkey,
+    pub epoch: u64,
+    pub slots: BTreeSet<u64>,
+    pub signature: Signature,
+    pub wallclock: u64,
+}
+
+/// The software version a node runs
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Version {
+    pub from: Pubkey,
+    pub version: String,
+    /// Nodes can only interoperate if their feature sets match, see `feature_set()`
+    pub feature_set: u32,
+    pub signature: Signature,
+    pub wallclock: u64,
+}
+
+/// The tick height of the last slot a node has replayed and verified
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Root {
+    pub from: Pubkey,
+    pub root: u64,
+    pub signature: Signature,
+    pub wallclock: u64,
+}
+
 impl Signable for LeaderId {
     fn pubkey(&self) -> Pubkey {
         self.id
@@ -62,16 +103,18 @@ impl Signable for LeaderId {
 
 impl Signable for Vote {
     fn pubkey(&self) -> Pubkey {
-        self.transaction.account_keys[0]
+        self.from
     }
 
     fn signable_data(&self) -> Vec<u8> {
         #[derive(Serialize)]
         struct SignData {
+            from: Pubkey,
             transaction: Transaction,
             wallclock: u64,
         }
         let data = SignData {
+            from: self.from,
             transaction: self.transaction.clone(),
             wallclock: self.wallclock,
         };
@@ -87,6 +130,91 @@ impl Signable for Vote {
     }
 }
 
+impl Signable for EpochSlots {
+    fn pubkey(&self) -> Pubkey {
+        self.from
+    }
+
+    fn signable_data(&self) -> Vec<u8> {
+        #[derive(Serialize)]
+        struct SignData<'a> {
+            epoch: u64,
+            slots: &'a BTreeSet<u64>,
+            wallclock: u64,
+        }
+        let data = SignData {
+            epoch: self.epoch,
+            slots: &self.slots,
+            wallclock: self.wallclock,
+        };
+        serialize(&data).expect("unable to serialize EpochSlots")
+    }
+
+    fn get_signature(&self) -> Signature {
+        self.signature
+    }
+
+    fn set_signature(&mut self, signature: Signature) {
+        self.signature = signature
+    }
+}
+
+impl Signable for Version {
+    fn pubkey(&self) -> Pubkey {
+        self.from
+    }
+
+    fn signable_data(&self) -> Vec<u8> {
+        #[derive(Serialize)]
+        struct SignData<'a> {
+            version: &'a str,
+            feature_set: u32,
+            wallclock: u64,
+        }
+        let data = SignData {
+            version: &self.version,
+            feature_set: self.feature_set,
+            wallclock: self.wallclock,
+        };
+        serialize(&data).expect("unable to serialize Version")
+    }
+
+    fn get_signature(&self) -> Signature {
+        self.signature
+    }
+
+    fn set_signature(&mut self, signature: Signature) {
+        self.signature = signature
+    }
+}
+
+impl Signable for Root {
+    fn pubkey(&self) -> Pubkey {
+        self.from
+    }
+
+    fn signable_data(&self) -> Vec<u8> {
+        #[derive(Serialize)]
+        struct SignData {
+            root: u64,
+            wallclock: u64,
+        }
+        let data = SignData {
+            root: self.root,
+            wallclock: self.wallclock,
+        };
+        serialize(&data).expect("unable to serialize Root")
+    }
+
+    fn get_signature(&self) -> Signature {
+        self.signature
+    }
+
+    fn set_signature(&mut self, signature: Signature) {
+        self.signature = signature
+    }
+}
+
 /// Type of the replicated value
 /// These are labels for values in a record that is associated with `Pubkey`
 #[derive(PartialEq, Hash, Eq, Clone, Debug)]
@@ -94,6 +222,9 @@ pub enum CrdsValueLabel {
     ContactInfo(Pubkey),
     Vote(Pubkey),
     LeaderId(Pubkey),
+    EpochSlots(Pubkey),
+    Version(Pubkey),
+    Root(Pubkey),
 }
 
 impl fmt::Display for CrdsValueLabel {
@@ -102,6 +233,9 @@ impl fmt::Display for CrdsValueLabel {
             CrdsValueLabel::ContactInfo(_) => write!(f, "ContactInfo({})", self.pubkey()),
             CrdsValueLabel::Vote(_) => write!(f, "Vote({})", self.pubkey()),
             CrdsValueLabel::LeaderId(_) => write!(f, "LeaderId({})", self.pubkey()),
+            CrdsValueLabel::EpochSlots(_) => write!(f, "EpochSlots({})", self.pubkey()),
+            CrdsValueLabel::Version(_) => write!(f, "Version({})", self.pubkey()),
+            CrdsValueLabel::Root(_) => write!(f, "Root({})", self.pubkey()),
         }
     }
 }
@@ -112,6 +246,9 @@ impl CrdsValueLabel {
             CrdsValueLabel::ContactInfo(p) => *p,
             CrdsValueLabel::Vote(p) => *p,
             CrdsValueLabel::LeaderId(p) => *p,
+            CrdsValueLabel::EpochSlots(p) => *p,
+            CrdsValueLabel::Version(p) => *p,
+            CrdsValueLabel::Root(p) => *p,
         }
     }
 }
@@ -128,9 +265,12 @@ impl LeaderId {
 }
 
 impl Vote {
+    /// `from` is the id of the node gossiping the vote, whose keypair signs it, which
+    /// isn't necessarily the vote account that signed `transaction`
     // TODO: it might make sense for the transaction to encode the wallclock in the userdata
-    pub fn new(transaction: Transaction, wallclock: u64) -> Self {
+    pub fn new(from: Pubkey, transaction: Transaction, wallclock: u64) -> Self {
         Vote {
+            from,
             transaction,
             signature: Signature::default(),
             wallclock,
@@ -138,6 +278,60 @@ impl Vote {
     }
 }
 
+impl EpochSlots {
+    pub fn new(from: Pubkey, epoch: u64, slots: BTreeSet<u64>, wallclock: u64) -> Self {
+        EpochSlots {
+            from,
+            epoch,
+            slots,
+            signature: Signature::default(),
+            wallclock,
+        }
+    }
+}
+
+impl Version {
+    /// The version of this build
+    pub fn new(from: Pubkey, wallclock: u64) -> Self {
+        Version {
+            from,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            feature_set: feature_set(),
+            signature: Signature::default(),
+            wallclock,
+        }
+    }
+
+    pub fn is_compatible(&self, other: &Version) -> bool {
+        self.feature_set == other.feature_set
+    }
+}
+
+/// Identifies the wire protocol of this build.  Until 1.0 every minor release may change
+/// it.  Build features don't: the jerasure and Rust erasure coders produce the same coding
+/// blobs, and the erasure sets come from the genesis block.
+pub fn feature_set() -> u32 {
+    let features = format!(
+        "{}.{}",
+        env!("CARGO_PKG_VERSION_MAJOR"),
+        env!("CARGO_PKG_VERSION_MINOR")
+    );
+    let hash = hash(features.as_bytes());
+    let mut rdr = Cursor::new(hash.as_ref());
+    rdr.read_u32::<LittleEndian>().unwrap()
+}
+
+impl Root {
+    pub fn new(from: Pubkey, root: u64, wallclock: u64) -> Self {
+        Root {
+            from,
+            root,
+            signature: Signature::default(),
+            wallclock,
+        }
+    }
+}
+
 impl CrdsValue {
     /// Totally unsecure unverfiable wallclock of the node that generated this message
     /// Latest wallclock is always picked.
@@ -147,6 +341,9 @@ impl CrdsValue {
             CrdsValue::ContactInfo(contact_info) => contact_info.wallclock,
             CrdsValue::Vote(vote) => vote.wallclock,
             CrdsValue::LeaderId(leader_id) => leader_id.wallclock,
+            CrdsValue::EpochSlots(epoch_slots) => epoch_slots.wallclock,
+            CrdsValue::Version(version) => version.wallclock,
+            CrdsValue::Root(root) => root.wallclock,
         }
     }
     pub fn label(&self) -> CrdsValueLabel {
@@ -156,6 +353,9 @@ impl CrdsValue {
             }
             CrdsValue::Vote(vote) => CrdsValueLabel::Vote(vote.pubkey()),
             CrdsValue::LeaderId(leader_id) => CrdsValueLabel::LeaderId(leader_id.pubkey()),
+            CrdsValue::EpochSlots(epoch_slots) => CrdsValueLabel::EpochSlots(epoch_slots.pubkey()),
+            CrdsValue::Version(version) => CrdsValueLabel::Version(version.pubkey()),
+            CrdsValue::Root(root) => CrdsValueLabel::Root(root.pubkey()),
         }
     }
     pub fn contact_info(&self) -> Option<&ContactInfo> {
@@ -176,12 +376,33 @@ impl CrdsValue {
             _ => None,
         }
     }
+    pub fn epoch_slots(&self) -> Option<&EpochSlots> {
+        match self {
+            CrdsValue::EpochSlots(epoch_slots) => Some(epoch_slots),
+            _ => None,
+        }
+    }
+    pub fn version(&self) -> Option<&Version> {
+        match self {
+            CrdsValue::Version(version) => Some(version),
+            _ => None,
+        }
+    }
+    pub fn root(&self) -> Option<&Root> {
+        match self {
+            CrdsValue::Root(root) => Some(root),
+            _ => None,
+        }
+    }
     /// Return all the possible labels for a record identified by Pubkey.
-    pub fn record_labels(key: Pubkey) -> [CrdsValueLabel; 3] {
+    pub fn record_labels(key: Pubkey) -> [CrdsValueLabel; 6] {
         [
             CrdsValueLabel::ContactInfo(key),
             CrdsValueLabel::Vote(key),
             CrdsValueLabel::LeaderId(key),
+            CrdsValueLabel::EpochSlots(key),
+            CrdsValueLabel::Version(key),
+            CrdsValueLabel::Root(key),
         ]
     }
 }
@@ -192,6 +413,9 @@ impl Signable for CrdsValue {
             CrdsValue::ContactInfo(contact_info) => contact_info.sign(keypair),
             CrdsValue::Vote(vote) => vote.sign(keypair),
             CrdsValue::LeaderId(leader_id) => leader_id.sign(keypair),
+            CrdsValue::EpochSlots(epoch_slots) => epoch_slots.sign(keypair),
+            CrdsValue::Version(version) => version.sign(keypair),
+            CrdsValue::Root(root) => root.sign(keypair),
         };
     }
     fn verify(&self) -> bool {
@@ -199,6 +423,9 @@ impl Signable for CrdsValue {
             CrdsValue::ContactInfo(contact_info) => contact_info.verify(),
             CrdsValue::Vote(vote) => vote.verify(),
             CrdsValue::LeaderId(leader_id) => leader_id.verify(),
+            CrdsValue::EpochSlots(epoch_slots) => epoch_slots.verify(),
+            CrdsValue::Version(version) => version.verify(),
+            CrdsValue::Root(root) => root.verify(),
         }
     }
 
@@ -207,6 +434,9 @@ impl Signable for CrdsValue {
             CrdsValue::ContactInfo(contact_info) => contact_info.pubkey(),
             CrdsValue::Vote(vote) => vote.pubkey(),
             CrdsValue::LeaderId(leader_id) => leader_id.pubkey(),
+            CrdsValue::EpochSlots(epoch_slots) => epoch_slots.pubkey(),
+            CrdsValue::Version(version) => version.pubkey(),
+            CrdsValue::Root(root) => root.pubkey(),
         }
     }
 
@@ -233,13 +463,16 @@ mod test {
 
     #[test]
     fn test_labels() {
-        let mut hits = [false; 3];
+        let mut hits = [false; 6];
         // this method should cover all the possible labels
         for v in &CrdsValue::record_labels(Pubkey::default()) {
             match v {
                 CrdsValueLabel::ContactInfo(_) => hits[0] = true,
                 CrdsValueLabel::Vote(_) => hits[1] = true,
                 CrdsValueLabel::LeaderId(_) => hits[2] = true,
+                CrdsValueLabel::EpochSlots(_) => hits[3] = true,
+                CrdsValueLabel::Version(_) => hits[4] = true,
+                CrdsValueLabel::Root(_) => hits[5] = true,
             }
         }
         assert!(hits.iter().all(|x| *x));
@@ -256,10 +489,25 @@ mod test {
         let key = v.clone().contact_info().unwrap().id;
         assert_eq!(v.label(), CrdsValueLabel::ContactInfo(key));
 
-        let v = CrdsValue::Vote(Vote::new(test_tx(), 0));
+        let v = CrdsValue::Vote(Vote::new(Pubkey::default(), test_tx(), 0));
         assert_eq!(v.wallclock(), 0);
-        let key = v.clone().vote().unwrap().transaction.account_keys[0];
+        let key = v.clone().vote().unwrap().from;
         assert_eq!(v.label(), CrdsValueLabel::Vote(key));
+
+        let v = CrdsValue::EpochSlots(EpochSlots::new(Pubkey::default(), 1, BTreeSet::new(), 0));
+        assert_eq!(v.wallclock(), 0);
+        let key = v.clone().epoch_slots().unwrap().from;
+        assert_eq!(v.label(), CrdsValueLabel::EpochSlots(key));
+
+        let v = CrdsValue::Version(Version::new(Pubkey::default(), 0));
+        assert_eq!(v.wallclock(), 0);
+        let key = v.clone().version().unwrap().from;
+        assert_eq!(v.label(), CrdsValueLabel::Version(key));
+
+        let v = CrdsValue::Root(Root::new(Pubkey::default(), 5, 0));
+        assert_eq!(v.wallclock(), 0);
+        let key = v.clone().root().unwrap().from;
+        assert_eq!(v.label(), CrdsValueLabel::Root(key));
     }
     #[test]
     fn test_signature() {
@@ -271,6 +519,61 @@ mod test {
         assert!(v.verify());
         v.sign(&fake_keypair);
         assert!(!v.verify());
+
+        // Votes are signed by the node gossiping them, not by the vote account
+        let mut v = CrdsValue::Vote(Vote::new(keypair.pubkey(), test_tx(), timestamp()));
+        verify_signatures(&mut v, &keypair, &fake_keypair);
+
+        let mut v =
+            CrdsValue::ContactInfo(ContactInfo::new_localhost(keypair.pubkey(), timestamp()));
+        verify_signatures(&mut v, &keypair, &fake_keypair);
+
+        let slots = (1..5).collect();
+        let mut v = CrdsValue::EpochSlots(EpochSlots::new(keypair.pubkey(), 1, slots, timestamp()));
+        verify_signatures(&mut v, &keypair, &fake_keypair);
+
+        let mut v = CrdsValue::Version(Version::new(keypair.pubkey(), timestamp()));
+        verify_signatures(&mut v, &keypair, &fake_keypair);
+
+        let mut v = CrdsValue::Root(Root::new(keypair.pubkey(), 5, timestamp()));
+        verify_signatures(&mut v, &keypair, &fake_keypair);
+    }
+
+    #[test]
+    fn test_version_compatibility() {
+        let version = Version::new(Pubkey::default(), 0);
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(version.feature_set, feature_set());
+
+        let mut other_version = Version::new(Keypair::new().pubkey(), 0);
+        assert!(version.is_compatible(&other_version));
+        other_version.feature_set = feature_set().wrapping_add(1);
+        assert!(!version.is_compatible(&other_version));
+    }
+
+    fn verify_signatures(
+        value: &mut CrdsValue,
+        correct_keypair: &Keypair,
+        wrong_keypair: &Keypair,
+    ) {
+        assert!(!value.verify());
+        value.sign(correct_keypair);
+        assert!(value.verify());
+
+        // A value whose contents change after signing is rejected
+        let mut forged = value.clone();
+        match &mut forged {
+            CrdsValue::ContactInfo(contact_info) => contact_info.wallclock += 1,
+            CrdsValue::Vote(vote) => vote.wallclock += 1,
+            CrdsValue::LeaderId(leader_id) => leader_id.wallclock += 1,
+            CrdsValue::EpochSlots(epoch_slots) => epoch_slots.wallclock += 1,
+            CrdsValue::Version(version) => version.wallclock += 1,
+            CrdsValue::Root(root) => root.wallclock += 1,
+        }
+        assert!(!forged.verify());
+
+        value.sign(wrong_keypair);
+        assert!(!value.verify());
     }
 
 }
@@ -3,6 +3,7 @@
 //! access read to a persistent file-based ledger.
 
 use crate::entry::Entry;
+use crate::erasure::ErasureConfig;
 use crate::genesis_block::GenesisBlock;
 use crate::packet::{Blob, SharedBlob, BLOB_HEADER_SIZE};
 use crate::result::{Error, Result};
@@ -281,6 +282,8 @@ pub struct DbLedger {
     meta_cf: MetaCf,
     data_cf: DataCf,
     erasure_cf: ErasureCf,
+    // Shape of the erasure sets of the blobs stored in erasure_cf
+    erasure_config: ErasureConfig,
 }
 
 // TODO: Once we support a window that knows about different leader
@@ -297,6 +300,11 @@ pub const ERASURE_CF: &str = "erasure";
 impl DbLedger {
     // Opens a Ledger in directory, provides "infinite" window of blobs
     pub fn open(ledger_path: &str) -> Result<Self> {
+        Self::open_with_config(ledger_path, ErasureConfig::default())
+    }
+
+    // Opens a Ledger in directory for a cluster using the given erasure sets
+    pub fn open_with_config(ledger_path: &str, erasure_config: ErasureConfig) -> Result<Self> {
         fs::create_dir_all(&ledger_path)?;
         let ledger_path = Path::new(ledger_path).join(DB_LEDGER_DIRECTORY);
 
@@ -330,9 +338,14 @@ impl DbLedger {
             meta_cf,
             data_cf,
             erasure_cf,
+            erasure_config,
         })
     }
 
+    pub fn erasure_config(&self) -> ErasureConfig {
+        self.erasure_config
+    }
+
     pub fn meta(&self) -> Result<Option<SlotMeta>> {
         self.meta_cf.get(&MetaCf::key(DEFAULT_SLOT_HEIGHT))
     }
@@ -382,7 +395,7 @@ impl DbLedger {
             .into_iter()
             .enumerate()
             .map(|(idx, entry)| {
-                let mut b = entry.borrow().to_blob();
+                let mut b = entry.borrow().to_compressed_blob();
                 b.set_index(idx as u64 + index);
                 b.set_slot(slot);
                 b
@@ -473,10 +486,8 @@ impl DbLedger {
                     // look for the blob in the database.
                     if let Some(next_blob) = found_blob {
                         current_slot = next_blob.slot();
-                        let serialized_entry_data =
-                            &next_blob.data[BLOB_HEADER_SIZE..BLOB_HEADER_SIZE + next_blob.size()];
                         // Verify entries can actually be reconstructed
-                        deserialize(serialized_entry_data).expect(
+                        Entry::from_blob(next_blob).expect(
                             "Blob made it past validation, so must be deserializable at this point",
                         )
                     } else {
@@ -496,7 +507,7 @@ impl DbLedger {
                                 break 'outer;
                             }
                         };
-                        deserialize(&blob_data[BLOB_HEADER_SIZE..])
+                        Entry::from_blob_bytes(&blob_data)
                             .expect("Blobs in database must be deserializable")
                     }
                 };
@@ -626,6 +637,12 @@ impl DbLedger {
 
     /// Return an iterator for all the entries in the given file.
     pub fn read_ledger(&self) -> Result<impl Iterator<Item = Entry>> {
+        Ok(self.read_ledger_with_slots()?.map(|(_, entry)| entry))
+    }
+
+    /// Return an iterator for all the entries in the given file, each paired with the
+    /// slot it's stored under.
+    pub fn read_ledger_with_slots(&self) -> Result<impl Iterator<Item = (u64, Entry)>> {
         let mut db_iterator = self.db.raw_iterator_cf(self.data_cf.handle())?;
 
         db_iterator.seek_to_first();
@@ -804,12 +821,15 @@ struct EntryIterator {
 }
 
 impl Iterator for EntryIterator {
-    type Item = Entry;
+    type Item = (u64, Entry);
 
-    fn next(&mut self) -> Option<Entry> {
+    fn next(&mut self) -> Option<(u64, Entry)> {
         if self.db_iterator.valid() {
-            if let Some(value) = self.db_iterator.value() {
-                if let Ok(entry) = deserialize::<Entry>(&value[BLOB_HEADER_SIZE..]) {
+            if let (Some(key), Some(value)) = (self.db_iterator.key(), self.db_iterator.value()) {
+                if let (Ok(slot), Ok(entry)) = (
+                    DataCf::slot_height_from_key(&key),
+                    Entry::from_blob_bytes(&value),
+                ) {
                     if let Some(last_id) = self.last_id {
                         if !entry.verify(&last_id) {
                             return None;
@@ -817,7 +837,7 @@ impl Iterator for EntryIterator {
                     }
                     self.db_iterator.next();
                     self.last_id = Some(entry.id);
-                    return Some(entry);
+                    return Some((slot, entry));
                 }
             }
         }
@@ -829,9 +849,11 @@ pub fn create_new_ledger(ledger_path: &str, genesis_block: &GenesisBlock) -> Res
     DbLedger::destroy(ledger_path)?;
     genesis_block.write(&ledger_path)?;
 
-    // Add a single tick linked back to the genesis_block to bootstrap the ledger
+    // Add a single tick linked back to the genesis_block to bootstrap the ledger.  It rolls
+    // a full tick's worth of hashes so the ledger replays under the genesis tick rate
     let db_ledger = DbLedger::open(ledger_path)?;
-    let entries = crate::entry::create_ticks(1, genesis_block.last_id());
+    let num_hashes = genesis_block.hashes_per_tick.unwrap_or(1);
+    let entries = vec![Entry::new(&genesis_block.last_id(), 0, num_hashes, vec![])];
     db_ledger.write_entries(DEFAULT_SLOT_HEIGHT, 0, &entries)?;
 
     Ok((1, entries[0].id))
@@ -848,7 +870,7 @@ where
         .into_iter()
         .enumerate()
         .map(|(idx, entry)| {
-            let mut b = entry.borrow().to_blob();
+            let mut b = entry.borrow().to_compressed_blob();
             b.set_index(idx as u64);
             b.set_id(&keypair.pubkey());
             b.set_slot(DEFAULT_SLOT_HEIGHT);
@@ -1247,6 +1269,51 @@ mod tests {
         DbLedger::destroy(&db_ledger_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    pub fn test_insert_data_blobs_compressed() {
+        let db_ledger_path = get_tmp_ledger_path("test_insert_data_blobs_compressed");
+        {
+            let db_ledger = DbLedger::open(&db_ledger_path).unwrap();
+
+            // Mix compressed and uncompressed blobs
+            let num_entries = 10;
+            let original_entries = make_tiny_test_entries(num_entries);
+            let shared_blobs: Vec<_> = original_entries
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    if i % 2 == 0 {
+                        entry.to_compressed_shared_blob()
+                    } else {
+                        entry.to_shared_blob()
+                    }
+                })
+                .collect();
+            for (i, b) in shared_blobs.iter().enumerate() {
+                let mut w_b = b.write().unwrap();
+                w_b.set_index(i as u64);
+                w_b.set_slot(DEFAULT_SLOT_HEIGHT);
+            }
+
+            assert_eq!(
+                db_ledger
+                    .write_shared_blobs(shared_blobs.iter().skip(1))
+                    .unwrap(),
+                vec![]
+            );
+            assert_eq!(
+                db_ledger
+                    .write_shared_blobs(shared_blobs.iter().take(1))
+                    .unwrap(),
+                original_entries
+            );
+
+            let entries: Vec<_> = db_ledger.read_ledger().unwrap().collect();
+            assert_eq!(entries, original_entries);
+        }
+        DbLedger::destroy(&db_ledger_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     pub fn test_insert_data_blobs_duplicate() {
         // Create RocksDb ledger
@@ -1400,5 +1467,4 @@ mod tests {
 
         DbLedger::destroy(&ledger_path).expect("Expected successful database destruction");
     }
-
 }
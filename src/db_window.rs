@@ -3,7 +3,6 @@ use crate::cluster_info::ClusterInfo;
 use crate::counter::Counter;
 use crate::db_ledger::*;
 use crate::entry::Entry;
-#[cfg(feature = "erasure")]
 use crate::erasure;
 use crate::leader_scheduler::LeaderScheduler;
 use crate::packet::{SharedBlob, BLOB_HEADER_SIZE};
@@ -207,17 +206,14 @@ pub fn process_blob(
         db_ledger.insert_data_blobs(vec![(*blob.read().unwrap()).borrow()])?
     };
 
-    #[cfg(feature = "erasure")]
-    {
-        // If write_shared_blobs() of these recovered blobs fails fails, don't return
-        // because consumed_entries might be nonempty from earlier, and tick height needs to
-        // be updated. Hopefully we can recover these blobs next time successfully.
-        if let Err(e) = try_erasure(db_ledger, &mut consumed_entries) {
-            trace!(
-                "erasure::recover failed to write recovered coding blobs. Err: {:?}",
-                e
-            );
-        }
+    // If write_shared_blobs() of these recovered blobs fails fails, don't return
+    // because consumed_entries might be nonempty from earlier, and tick height needs to
+    // be updated. Hopefully we can recover these blobs next time successfully.
+    if let Err(e) = try_erasure(db_ledger, &mut consumed_entries) {
+        trace!(
+            "erasure::recover failed to write recovered coding blobs. Err: {:?}",
+            e
+        );
     }
 
     for entry in &consumed_entries {
@@ -268,7 +264,6 @@ pub fn calculate_max_repair_entry_height(
     }
 }
 
-#[cfg(feature = "erasure")]
 fn try_erasure(db_ledger: &Arc<DbLedger>, consume_queue: &mut Vec<Entry>) -> Result<()> {
     let meta = db_ledger.meta()?;
 
@@ -294,15 +289,15 @@ fn try_erasure(db_ledger: &Arc<DbLedger>, consume_queue: &mut Vec<Entry>) -> Res
 mod test {
     use super::*;
     use crate::db_ledger::get_tmp_ledger_path;
-    #[cfg(all(feature = "erasure", test))]
-    use crate::entry::reconstruct_entries_from_blobs;
-    use crate::entry::{make_tiny_test_entries, EntrySlice};
-    #[cfg(all(feature = "erasure", test))]
+    use crate::entry::{make_tiny_test_entries, reconstruct_entries_from_blobs, EntrySlice};
     use crate::erasure::test::{generate_db_ledger_from_window, setup_window_ledger};
-    #[cfg(all(feature = "erasure", test))]
-    use crate::erasure::{NUM_CODING, NUM_DATA};
-    use crate::packet::{index_blobs, Blob, Packet, Packets, SharedBlob, PACKET_DATA_SIZE};
-    use crate::streamer::{receiver, responder, PacketReceiver};
+    use crate::erasure::ErasureConfig;
+    use crate::packet::{
+        index_blobs, Blob, Packet, Packets, PacketsRecycler, SharedBlob, PACKET_DATA_SIZE,
+    };
+    use crate::streamer::{
+        packet_channel, receiver, responder, PacketReceiver, PACKET_CHANNEL_MAX_PACKETS,
+    };
     use solana_sdk::signature::{Keypair, KeypairUtil};
     use std::io;
     use std::io::Write;
@@ -339,11 +334,12 @@ mod test {
         let addr = read.local_addr().unwrap();
         let send = UdpSocket::bind("127.0.0.1:0").expect("bind");
         let exit = Arc::new(AtomicBool::new(false));
-        let (s_reader, r_reader) = channel();
+        let (s_reader, r_reader) = packet_channel(PACKET_CHANNEL_MAX_PACKETS);
         let t_receiver = receiver(
             Arc::new(read),
             exit.clone(),
             s_reader,
+            &PacketsRecycler::default(),
             "window-streamer-test",
         );
         let t_responder = {
@@ -600,18 +596,21 @@ mod test {
         DbLedger::destroy(&db_ledger_path).expect("Expected successful database destruction");
     }
 
-    #[cfg(all(feature = "erasure", test))]
     #[test]
     pub fn test_try_erasure() {
+        // The test window and ledger are coded with the default erasure sets
+        let erasure_config = ErasureConfig::default();
+        let (num_data, num_coding) = (erasure_config.num_data, erasure_config.num_coding);
+
         // Setup the window
         let offset = 0;
-        let num_blobs = NUM_DATA + 2;
+        let num_blobs = num_data + 2;
         let slot_height = DEFAULT_SLOT_HEIGHT;
         let mut window = setup_window_ledger(offset, num_blobs, false, slot_height);
         let end_index = (offset + num_blobs) % window.len();
 
         // Test erasing a data block and an erasure block
-        let coding_start = offset - (offset % NUM_DATA) + (NUM_DATA - NUM_CODING);
+        let coding_start = offset - (offset % num_data) + (num_data - num_coding);
 
         let erased_index = coding_start % window.len();
 
@@ -624,6 +623,7 @@ mod test {
         // Generate the db_ledger from the window
         let ledger_path = get_tmp_ledger_path("test_try_erasure");
         let db_ledger = Arc::new(generate_db_ledger_from_window(&ledger_path, &window, false));
+        assert_eq!(db_ledger.erasure_config(), erasure_config);
 
         let mut consume_queue = vec![];
         try_erasure(&db_ledger, &mut consume_queue).expect("Expected successful erasure attempt");
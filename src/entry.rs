@@ -2,11 +2,14 @@
 //! unique ID that is the hash of the Entry before it, plus the hash of the
 //! transactions within it. Entries cannot be reordered, and its field `num_hashes`
 //! represents an approximate amount of time since the last Entry was created.
-use crate::packet::{Blob, SharedBlob, BLOB_DATA_SIZE};
+use crate::packet::{
+    blob_flags, Blob, SharedBlob, BLOB_DATA_SIZE, BLOB_FLAG_IS_COMPRESSED, BLOB_HEADER_SIZE,
+};
 use crate::poh::Poh;
 use crate::result::Result;
-use bincode::{deserialize, serialize_into, serialized_size};
+use bincode::{config, serialize, serialize_into, serialized_size};
 use chrono::prelude::Utc;
+use libflate::deflate;
 use rayon::prelude::*;
 use solana_sdk::budget_transaction::BudgetTransaction;
 use solana_sdk::hash::{hash, Hash};
@@ -16,7 +19,7 @@ use solana_sdk::transaction::Transaction;
 use solana_sdk::vote_program::Vote;
 use solana_sdk::vote_transaction::VoteTransaction;
 use std::borrow::Borrow;
-use std::io::Cursor;
+use std::io::{Cursor, Read, Write};
 use std::mem::size_of;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, RwLock};
@@ -114,15 +117,80 @@ impl Entry {
 
     pub fn to_blob(&self) -> Blob {
         let mut blob = Blob::default();
+        self.write_blob(&mut blob);
+        blob
+    }
+
+    fn write_blob(&self, blob: &mut Blob) {
         let pos = {
             let mut out = Cursor::new(blob.data_mut());
             serialize_into(&mut out, &self).expect("failed to serialize output");
             out.position() as usize
         };
         blob.set_size(pos);
+    }
+
+    pub fn to_compressed_shared_blob(&self) -> SharedBlob {
+        let blob = self.to_compressed_blob();
+        Arc::new(RwLock::new(blob))
+    }
+
+    /// Like `to_blob`, but deflates the serialized entry and flags the blob as
+    /// compressed.  Entries that don't shrink are stored uncompressed.
+    pub fn to_compressed_blob(&self) -> Blob {
+        let mut blob = Blob::default();
+        self.write_compressed_blob(&mut blob);
         blob
     }
 
+    /// Like `to_compressed_blob`, but writes into `blob`, which must be zeroed, like
+    /// the blobs handed out by a `BlobRecycler`.
+    pub fn write_compressed_blob(&self, blob: &mut Blob) {
+        let serialized = serialize(&self).expect("failed to serialize output");
+        let compressed = {
+            let mut encoder = deflate::Encoder::new(Vec::with_capacity(serialized.len()));
+            encoder
+                .write_all(&serialized)
+                .expect("failed to compress output");
+            encoder
+                .finish()
+                .into_result()
+                .expect("failed to compress output")
+        };
+        if compressed.len() >= serialized.len() {
+            self.write_blob(blob);
+            return;
+        }
+
+        blob.data_mut()[..compressed.len()].copy_from_slice(&compressed);
+        blob.set_size(compressed.len());
+        blob.set_compressed();
+    }
+
+    /// Deserialize the entry carried in `blob`, inflating it if the blob is
+    /// flagged as compressed.
+    pub fn from_blob(blob: &Blob) -> Result<Entry> {
+        Self::from_payload(blob.flags(), &blob.data()[..blob.size()])
+    }
+
+    /// Deserialize the entry carried in the raw bytes of a blob, header
+    /// included, as they are stored in the ledger.
+    pub fn from_blob_bytes(data: &[u8]) -> Result<Entry> {
+        Self::from_payload(blob_flags(data), &data[BLOB_HEADER_SIZE..])
+    }
+
+    fn from_payload(flags: u32, payload: &[u8]) -> Result<Entry> {
+        // An entry never serializes to more than a blob's worth of data, so don't
+        // inflate or allocate past that, however the payload claims otherwise
+        let limit = BLOB_DATA_SIZE as u64;
+        if flags & BLOB_FLAG_IS_COMPRESSED != 0 {
+            let decoder = deflate::Decoder::new(payload).take(limit);
+            Ok(config().limit(limit).deserialize_from(decoder)?)
+        } else {
+            Ok(config().limit(limit).deserialize(payload)?)
+        }
+    }
+
     /// Estimate serialized_size of Entry without creating an Entry.
     pub fn serialized_size(transactions: &[Transaction]) -> u64 {
         let txs_size: u64 = transactions
@@ -246,10 +314,7 @@ where
     let mut num_ticks = 0;
 
     for blob in blobs.into_iter() {
-        let entry: Entry = {
-            let msg_size = blob.borrow().size();
-            deserialize(&blob.borrow().data()[..msg_size])?
-        };
+        let entry = Entry::from_blob(blob.borrow())?;
 
         if entry.is_tick() {
             num_ticks += 1
@@ -263,34 +328,65 @@ where
 pub trait EntrySlice {
     /// Verifies the hashes and counts of a slice of transactions are all consistent.
     fn verify(&self, start_hash: &Hash) -> bool;
+    /// Verifies the PoH of the slice in parallel, returning the index of the
+    /// first entry that doesn't follow from the one before it.
+    fn first_invalid_entry(&self, start_hash: &Hash) -> Option<usize>;
+    /// Checks that every tick in the slice ends a run of exactly `hashes_per_tick` hashes.
+    /// `tick_hash_count` carries the hashes seen since the last tick across calls.
+    fn verify_tick_hash_count(&self, tick_hash_count: &mut u64, hashes_per_tick: u64) -> bool;
     fn to_shared_blobs(&self) -> Vec<SharedBlob>;
+    fn to_compressed_shared_blobs(&self) -> Vec<SharedBlob>;
     fn to_blobs(&self) -> Vec<Blob>;
     fn votes(&self) -> Vec<(Pubkey, Vote, Hash)>;
 }
 
 impl EntrySlice for [Entry] {
     fn verify(&self, start_hash: &Hash) -> bool {
-        let genesis = [Entry {
-            tick_height: 0,
-            num_hashes: 0,
-            id: *start_hash,
-            transactions: vec![],
-        }];
-        let entry_pairs = genesis.par_iter().chain(self).zip(self);
-        entry_pairs.all(|(x0, x1)| {
-            let r = x1.verify(&x0.id);
-            if !r {
+        match self.first_invalid_entry(start_hash) {
+            None => true,
+            Some(i) => {
+                let x0 = if i == 0 { start_hash } else { &self[i - 1].id };
                 warn!(
-                    "entry invalid!: x0: {:?}, x1: {:?} num txs: {}",
-                    x0.id,
-                    x1.id,
-                    x1.transactions.len()
+                    "entry invalid!: index: {} x0: {:?}, x1: {:?} num txs: {}",
+                    i,
+                    x0,
+                    self[i].id,
+                    self[i].transactions.len()
                 );
+                false
             }
-            r
+        }
+    }
+
+    fn first_invalid_entry(&self, start_hash: &Hash) -> Option<usize> {
+        // Each entry's id ends its own stretch of the hash chain, so the
+        // stretches can be checked against their predecessors concurrently
+        (0..self.len()).into_par_iter().position_first(|i| {
+            let x0 = if i == 0 { start_hash } else { &self[i - 1].id };
+            !self[i].verify(x0)
         })
     }
 
+    fn verify_tick_hash_count(&self, tick_hash_count: &mut u64, hashes_per_tick: u64) -> bool {
+        for entry in self {
+            if entry.is_tick() {
+                *tick_hash_count += entry.num_hashes;
+                if *tick_hash_count != hashes_per_tick {
+                    warn!(
+                        "invalid tick hash count!: tick: {:?}, count: {}, expected: {}",
+                        entry.id, tick_hash_count, hashes_per_tick
+                    );
+                    return false;
+                }
+                *tick_hash_count = 0;
+            } else {
+                // The hash that mixes in the transactions isn't part of the tick's count
+                *tick_hash_count += entry.num_hashes.saturating_sub(1);
+            }
+        }
+        *tick_hash_count < hashes_per_tick
+    }
+
     fn to_blobs(&self) -> Vec<Blob> {
         self.iter().map(|entry| entry.to_blob()).collect()
     }
@@ -299,6 +395,12 @@ impl EntrySlice for [Entry] {
         self.iter().map(|entry| entry.to_shared_blob()).collect()
     }
 
+    fn to_compressed_shared_blobs(&self) -> Vec<SharedBlob> {
+        self.iter()
+            .map(|entry| entry.to_compressed_shared_blob())
+            .collect()
+    }
+
     fn votes(&self) -> Vec<(Pubkey, Vote, Hash)> {
         self.iter()
             .flat_map(|entry| {
@@ -600,6 +702,69 @@ mod tests {
         assert!(!bad_ticks.verify(&zero)); // inductive step, bad
     }
 
+    #[test]
+    fn test_first_invalid_entry() {
+        let zero = Hash::default();
+        let one = hash(&zero.as_ref());
+        assert_eq!(vec![][..].first_invalid_entry(&zero), None);
+        assert_eq!(
+            vec![Entry::new_tick(0, 0, &zero)][..].first_invalid_entry(&one),
+            Some(0)
+        );
+
+        let mut entries = vec![];
+        let mut id = zero;
+        for _ in 0..64 {
+            let entry = next_entry(&id, 3, vec![]);
+            id = entry.id;
+            entries.push(entry);
+        }
+        assert_eq!(entries.first_invalid_entry(&zero), None);
+        assert_eq!(entries.first_invalid_entry(&one), Some(0));
+
+        // The earliest of several bad entries is reported
+        let mut bad_entries = entries.clone();
+        bad_entries[40].num_hashes += 1;
+        bad_entries[17].id = one;
+        assert_eq!(bad_entries.first_invalid_entry(&zero), Some(17));
+        assert!(!bad_entries.verify(&zero));
+
+        let mut bad_entries = entries.clone();
+        bad_entries[63].num_hashes = 1;
+        assert_eq!(bad_entries.first_invalid_entry(&zero), Some(63));
+    }
+
+    #[test]
+    fn test_verify_tick_hash_count() {
+        let zero = Hash::default();
+        let keypair = Keypair::new();
+        let tx = SystemTransaction::new_account(&keypair, keypair.pubkey(), 0, zero, 0);
+        let tx_entry = next_entry(&zero, 3, vec![tx]);
+        let tick = next_entry(&zero, 5, vec![]);
+
+        let mut tick_hash_count = 0;
+        assert!(vec![tick.clone()].verify_tick_hash_count(&mut tick_hash_count, 5));
+        assert_eq!(tick_hash_count, 0);
+        assert!(!vec![tick.clone()].verify_tick_hash_count(&mut tick_hash_count, 4));
+
+        // The mixin hash of a transaction entry doesn't count towards the tick
+        let mut tick_hash_count = 0;
+        let entries = vec![tx_entry.clone(), tick.clone()];
+        assert!(entries.verify_tick_hash_count(&mut tick_hash_count, 7));
+        assert!(!entries.verify_tick_hash_count(&mut tick_hash_count, 8));
+
+        // The count carries over between slices
+        let mut tick_hash_count = 0;
+        assert!(vec![tx_entry.clone()].verify_tick_hash_count(&mut tick_hash_count, 7));
+        assert_eq!(tick_hash_count, 2);
+        assert!(vec![tick.clone()].verify_tick_hash_count(&mut tick_hash_count, 7));
+        assert_eq!(tick_hash_count, 0);
+
+        // Too many hashes before the next tick
+        let mut tick_hash_count = 0;
+        assert!(!vec![tx_entry; 3].verify_tick_hash_count(&mut tick_hash_count, 5));
+    }
+
     fn make_test_entries() -> Vec<Entry> {
         let zero = Hash::default();
         let one = hash(&zero.as_ref());
@@ -638,6 +803,64 @@ mod tests {
         assert_eq!(reconstruct_entries_from_blobs(blob_q).unwrap().0, entries);
     }
 
+    #[test]
+    fn test_entries_to_compressed_shared_blobs() {
+        solana_logger::setup();
+        let entries = make_test_entries();
+
+        let blob_q = entries.to_compressed_shared_blobs();
+        assert!(blob_q
+            .iter()
+            .all(|blob| blob.read().unwrap().is_compressed()));
+        for (blob, entry) in blob_q.iter().zip(&entries) {
+            assert!(blob.read().unwrap().size() < entry.to_blob().size());
+        }
+
+        let blob_q: Vec<_> = blob_q
+            .into_iter()
+            .map(|blob| blob.read().unwrap().clone())
+            .collect();
+        assert_eq!(reconstruct_entries_from_blobs(blob_q).unwrap().0, entries);
+    }
+
+    #[test]
+    fn test_compressed_blob_never_grows() {
+        // Small entries may not shrink, in which case they're left uncompressed
+        let tick = next_entry(&Hash::default(), 1, vec![]);
+        let blob = tick.to_compressed_blob();
+        assert!(blob.size() <= tick.to_blob().size());
+        assert_eq!(Entry::from_blob(&blob).unwrap(), tick);
+    }
+
+    #[test]
+    fn test_entry_from_blob_bytes() {
+        let entries = make_test_entries();
+        for entry in &entries {
+            for blob in &[entry.to_blob(), entry.to_compressed_blob()] {
+                let bytes = &blob.data[..BLOB_HEADER_SIZE + blob.size()];
+                assert_eq!(&Entry::from_blob_bytes(bytes).unwrap(), entry);
+            }
+        }
+    }
+
+    #[test]
+    fn test_entry_from_oversized_compressed_payload() {
+        // Repeated transactions deflate to well under a blob, but inflate past one
+        let keypair = Keypair::new();
+        let tx = SystemTransaction::new_account(&keypair, keypair.pubkey(), 1, Hash::default(), 0);
+        let entry = next_entry(&Hash::default(), 1, vec![tx; 1000]);
+        let serialized = serialize(&entry).unwrap();
+        assert!(serialized.len() > BLOB_DATA_SIZE);
+
+        let mut encoder = deflate::Encoder::new(vec![]);
+        encoder.write_all(&serialized).unwrap();
+        let compressed = encoder.finish().into_result().unwrap();
+        assert!(compressed.len() < BLOB_DATA_SIZE);
+
+        assert!(Entry::from_payload(BLOB_FLAG_IS_COMPRESSED, &compressed).is_err());
+        assert!(Entry::from_payload(0, &serialized).is_err());
+    }
+
     #[test]
     fn test_bad_blobs_attack() {
         solana_logger::setup();
@@ -2,19 +2,20 @@
 use crate::db_ledger::DbLedger;
 use crate::packet::{Blob, SharedBlob, BLOB_DATA_SIZE, BLOB_HEADER_SIZE, BLOB_SIZE};
 use crate::result::{Error, Result};
+use byteorder::{ByteOrder, LittleEndian};
 use std::cmp;
 use std::sync::{Arc, RwLock};
 
 //TODO(sakridge) pick these values
-pub const NUM_DATA: usize = 16; // number of data blobs
-pub const NUM_CODING: usize = 4; // number of coding blobs, also the maximum number that can go missing
-pub const ERASURE_SET_SIZE: usize = NUM_DATA + NUM_CODING; // total number of blobs in an erasure set, includes data and coding blobs
+pub const NUM_DATA: usize = 16; // default number of data blobs
+pub const NUM_CODING: usize = 4; // default number of coding blobs, also the maximum number that can go missing
+pub const ERASURE_SET_SIZE: usize = NUM_DATA + NUM_CODING; // default total number of blobs in an erasure set, includes data and coding blobs
 
-pub const JERASURE_ALIGN: usize = 4; // data size has to be a multiple of 4 bytes
+pub const ERASURE_ALIGN: usize = 4; // data size has to be a multiple of the 4 byte word size
 
 macro_rules! align {
     ($x:expr, $align:expr) => {
-        $x + ($align - 1) & !($align - 1)
+        ($x + ($align - 1)) & !($align - 1)
     };
 }
 
@@ -27,62 +28,353 @@ pub enum ErasureError {
     InvalidBlobData,
 }
 
+/// Shape of an erasure set, fixed for the whole cluster by the `GenesisBlock`.
+/// The coding blobs of a set carry the indexes of the set's last `num_coding`
+/// data blobs, so there can't be more coding blobs than data blobs.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ErasureConfig {
+    /// Number of data blobs in an erasure set
+    pub num_data: usize,
+    /// Number of coding blobs in an erasure set, also the maximum number that can go missing
+    pub num_coding: usize,
+}
+
+impl Default for ErasureConfig {
+    fn default() -> Self {
+        Self {
+            num_data: NUM_DATA,
+            num_coding: NUM_CODING,
+        }
+    }
+}
+
+impl ErasureConfig {
+    pub fn new(num_data: usize, num_coding: usize) -> Self {
+        assert!(
+            num_coding > 0,
+            "an erasure set needs at least one coding blob"
+        );
+        assert!(
+            num_coding <= num_data,
+            "an erasure set can't have more coding blobs than data blobs"
+        );
+        Self {
+            num_data,
+            num_coding,
+        }
+    }
+
+    /// Whether this config satisfies the constraints `new` enforces, for configs that were
+    /// deserialized rather than constructed
+    pub fn is_valid(&self) -> bool {
+        self.num_coding > 0 && self.num_coding <= self.num_data
+    }
+
+    /// Total number of blobs in an erasure set, includes data and coding blobs
+    pub fn set_size(&self) -> usize {
+        self.num_data + self.num_coding
+    }
+}
+
 // k = number of data devices
 // m = number of coding devices
 // w = word size
 
-extern "C" {
-    fn jerasure_matrix_encode(
-        k: i32,
-        m: i32,
-        w: i32,
-        matrix: *const i32,
-        data_ptrs: *const *const u8,
-        coding_ptrs: *const *mut u8,
-        size: i32,
-    );
-    fn jerasure_matrix_decode(
-        k: i32,
-        m: i32,
-        w: i32,
-        matrix: *const i32,
-        row_k_ones: i32,
-        erasures: *const i32,
-        data_ptrs: *const *mut u8,
-        coding_ptrs: *const *mut u8,
-        size: i32,
-    ) -> i32;
-    fn galois_single_divide(a: i32, b: i32, w: i32) -> i32;
+pub const ERASURE_W: i32 = 32;
+
+// The pure Rust backend works in the same field, GF(2^32) with gf-complete's default
+// primitive polynomial x^32 + x^22 + x^2 + x + 1, and with the same Cauchy matrix as
+// the Jerasure backend, so both generate identical coding blocks.
+const GF_PRIM_POLY: u32 = 0x0040_0007;
+
+fn gf_mul(mut a: u32, mut b: u32) -> u32 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        let carry = a & 0x8000_0000 != 0;
+        a <<= 1;
+        if carry {
+            a ^= GF_PRIM_POLY;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+// a^(2^32 - 2) is the multiplicative inverse of a
+fn gf_inv(a: u32) -> u32 {
+    assert!(a != 0);
+    let mut result = 1;
+    let mut base = a;
+    let mut exp = (1u64 << ERASURE_W) - 2;
+    while exp != 0 {
+        if exp & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+// Entry (i, j) of the m x k coding matrix, 1 / (i ^ (m + j)) like Jerasure's
+// galois_single_divide(1, i ^ (m + j), w)
+fn cauchy_coefficient(m: usize, i: usize, j: usize) -> u32 {
+    gf_inv((i ^ (m + j)) as u32)
 }
 
-fn get_matrix(m: i32, k: i32, w: i32) -> Vec<i32> {
-    let mut matrix = vec![0; (m * k) as usize];
-    for i in 0..m {
-        for j in 0..k {
-            unsafe {
-                matrix[(i * k + j) as usize] = galois_single_divide(1, i ^ (m + j), w);
+// Multiplication by a constant, one lookup per byte of the multiplicand
+struct GfMulTable([[u32; 256]; 4]);
+
+impl GfMulTable {
+    fn new(c: u32) -> Self {
+        let mut table = [[0; 256]; 4];
+        for (k, row) in table.iter_mut().enumerate() {
+            for (v, product) in row.iter_mut().enumerate() {
+                *product = gf_mul(c, (v as u32) << (8 * k));
             }
         }
+        GfMulTable(table)
+    }
+
+    fn mul(&self, x: u32) -> u32 {
+        self.0[0][(x & 0xff) as usize]
+            ^ self.0[1][((x >> 8) & 0xff) as usize]
+            ^ self.0[2][((x >> 16) & 0xff) as usize]
+            ^ self.0[3][(x >> 24) as usize]
     }
-    matrix
 }
 
-pub const ERASURE_W: i32 = 32;
+// dst ^= c * src, word by word
+fn region_multiply_xor(c: &GfMulTable, src: &[u8], dst: &mut [u8]) {
+    for (s, d) in src.chunks(4).zip(dst.chunks_mut(4)) {
+        let word = LittleEndian::read_u32(d) ^ c.mul(LittleEndian::read_u32(s));
+        LittleEndian::write_u32(d, word);
+    }
+}
+
+// Gauss-Jordan elimination, None if the matrix is singular
+fn gf_invert_matrix(mut matrix: Vec<Vec<u32>>) -> Option<Vec<Vec<u32>>> {
+    let n = matrix.len();
+    let mut inverse: Vec<Vec<u32>> = (0..n)
+        .map(|i| (0..n).map(|j| (i == j) as u32).collect())
+        .collect();
+
+    for col in 0..n {
+        let pivot = (col..n).find(|row| matrix[*row][col] != 0)?;
+        matrix.swap(col, pivot);
+        inverse.swap(col, pivot);
+
+        let scale = gf_inv(matrix[col][col]);
+        for x in 0..n {
+            matrix[col][x] = gf_mul(matrix[col][x], scale);
+            inverse[col][x] = gf_mul(inverse[col][x], scale);
+        }
+
+        for row in 0..n {
+            let factor = matrix[row][col];
+            if row == col || factor == 0 {
+                continue;
+            }
+            for x in 0..n {
+                let m = gf_mul(factor, matrix[col][x]);
+                let i = gf_mul(factor, inverse[col][x]);
+                matrix[row][x] ^= m;
+                inverse[row][x] ^= i;
+            }
+        }
+    }
+    Some(inverse)
+}
+
+#[cfg_attr(feature = "jerasure", allow(dead_code))]
+fn rs_generate_coding_blocks(coding: &mut [&mut [u8]], data: &[&[u8]]) {
+    let m = coding.len();
+    for (i, block) in coding.iter_mut().enumerate() {
+        for b in block.iter_mut() {
+            *b = 0;
+        }
+        for (j, data_block) in data.iter().enumerate() {
+            let c = GfMulTable::new(cauchy_coefficient(m, i, j));
+            region_multiply_xor(&c, data_block, block);
+        }
+    }
+}
+
+#[cfg_attr(feature = "jerasure", allow(dead_code))]
+fn rs_decode_blocks(
+    data: &mut [&mut [u8]],
+    coding: &mut [&mut [u8]],
+    erasures: &[i32],
+) -> Result<()> {
+    let k = data.len();
+    let m = coding.len();
+    let block_len = data[0].len();
+
+    let mut erased = vec![false; k + m];
+    for e in erasures.iter().take_while(|e| **e >= 0) {
+        let e = *e as usize;
+        if e >= k + m {
+            return Err(Error::ErasureError(ErasureError::DecodeError));
+        }
+        erased[e] = true;
+    }
+    let num_erased = erased.iter().filter(|e| **e).count();
+    if num_erased > m {
+        return Err(Error::ErasureError(ErasureError::DecodeError));
+    }
+
+    if erased[..k].iter().any(|e| *e) {
+        // Any k surviving blocks determine the data blocks: invert the rows of the
+        // generator matrix that produced them
+        let survivors: Vec<usize> = (0..k + m).filter(|i| !erased[*i]).take(k).collect();
+        let rows: Vec<Vec<u32>> = survivors
+            .iter()
+            .map(|s| {
+                (0..k)
+                    .map(|j| {
+                        if *s < k {
+                            (*s == j) as u32
+                        } else {
+                            cauchy_coefficient(m, *s - k, j)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let decode_matrix =
+            gf_invert_matrix(rows).ok_or_else(|| Error::ErasureError(ErasureError::DecodeError))?;
+
+        for j in (0..k).filter(|j| erased[*j]) {
+            let mut recovered = vec![0; block_len];
+            for (s, survivor) in survivors.iter().enumerate() {
+                let c = GfMulTable::new(decode_matrix[j][s]);
+                let block: &[u8] = if *survivor < k {
+                    &data[*survivor]
+                } else {
+                    &coding[*survivor - k]
+                };
+                region_multiply_xor(&c, block, &mut recovered);
+            }
+            data[j].copy_from_slice(&recovered);
+        }
+    }
+
+    // With all the data blocks present, erased coding blocks are simply regenerated
+    for i in (0..m).filter(|i| erased[k + *i]) {
+        for b in coding[i].iter_mut() {
+            *b = 0;
+        }
+        for (j, data_block) in data.iter().enumerate() {
+            let c = GfMulTable::new(cauchy_coefficient(m, i, j));
+            region_multiply_xor(&c, data_block, &mut coding[i]);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "jerasure")]
+mod jerasure {
+    use super::ERASURE_W;
+
+    extern "C" {
+        fn jerasure_matrix_encode(
+            k: i32,
+            m: i32,
+            w: i32,
+            matrix: *const i32,
+            data_ptrs: *const *const u8,
+            coding_ptrs: *const *mut u8,
+            size: i32,
+        );
+        fn jerasure_matrix_decode(
+            k: i32,
+            m: i32,
+            w: i32,
+            matrix: *const i32,
+            row_k_ones: i32,
+            erasures: *const i32,
+            data_ptrs: *const *mut u8,
+            coding_ptrs: *const *mut u8,
+            size: i32,
+        ) -> i32;
+        fn galois_single_divide(a: i32, b: i32, w: i32) -> i32;
+    }
+
+    fn get_matrix(m: i32, k: i32, w: i32) -> Vec<i32> {
+        let mut matrix = vec![0; (m * k) as usize];
+        for i in 0..m {
+            for j in 0..k {
+                unsafe {
+                    matrix[(i * k + j) as usize] = galois_single_divide(1, i ^ (m + j), w);
+                }
+            }
+        }
+        matrix
+    }
+
+    pub fn generate_coding_blocks(coding: &mut [&mut [u8]], data: &[&[u8]]) {
+        let k = data.len() as i32;
+        let m = coding.len() as i32;
+        let block_len = data[0].len() as i32;
+        let matrix: Vec<i32> = get_matrix(m, k, ERASURE_W);
+        let data_arg: Vec<_> = data.iter().map(|block| block.as_ptr()).collect();
+        let coding_arg: Vec<_> = coding.iter_mut().map(|block| block.as_mut_ptr()).collect();
+
+        unsafe {
+            jerasure_matrix_encode(
+                k,
+                m,
+                ERASURE_W,
+                matrix.as_ptr(),
+                data_arg.as_ptr(),
+                coding_arg.as_ptr(),
+                block_len,
+            );
+        }
+    }
+
+    // Returns jerasure_matrix_decode()'s return code, negative on failure
+    pub fn decode_blocks(
+        data: &mut [&mut [u8]],
+        coding: &mut [&mut [u8]],
+        erasures: &[i32],
+    ) -> i32 {
+        let matrix: Vec<i32> = get_matrix(coding.len() as i32, data.len() as i32, ERASURE_W);
+        let coding_arg: Vec<*mut u8> = coding.iter_mut().map(|x| x.as_mut_ptr()).collect();
+        let data_arg: Vec<*mut u8> = data.iter_mut().map(|x| x.as_mut_ptr()).collect();
+
+        unsafe {
+            jerasure_matrix_decode(
+                data.len() as i32,
+                coding.len() as i32,
+                ERASURE_W,
+                matrix.as_ptr(),
+                0,
+                erasures.as_ptr(),
+                data_arg.as_ptr(),
+                coding_arg.as_ptr(),
+                data[0].len() as i32,
+            )
+        }
+    }
+}
 
 // Generate coding blocks into coding
-//   There are some alignment restrictions, blocks should be aligned by 16 bytes
-//   which means their size should be >= 16 bytes
+//   Blocks must all have the same size, a multiple of ERASURE_ALIGN bytes
 pub fn generate_coding_blocks(coding: &mut [&mut [u8]], data: &[&[u8]]) -> Result<()> {
     if data.is_empty() {
         return Ok(());
     }
-    let k = data.len() as i32;
-    let m = coding.len() as i32;
-    let block_len = data[0].len() as i32;
-    let matrix: Vec<i32> = get_matrix(m, k, ERASURE_W);
-    let mut data_arg = Vec::with_capacity(data.len());
+    let block_len = data[0].len();
+    if block_len % ERASURE_ALIGN != 0 {
+        error!("block size {} is not word aligned", block_len);
+        return Err(Error::ErasureError(ErasureError::InvalidBlockSize));
+    }
     for block in data {
-        if block_len != block.len() as i32 {
+        if block_len != block.len() {
             error!(
                 "data block size incorrect {} expected {}",
                 block.len(),
@@ -90,11 +382,9 @@ pub fn generate_coding_blocks(coding: &mut [&mut [u8]], data: &[&[u8]]) -> Resul
             );
             return Err(Error::ErasureError(ErasureError::InvalidBlockSize));
         }
-        data_arg.push(block.as_ptr());
     }
-    let mut coding_arg = Vec::with_capacity(coding.len());
-    for block in coding {
-        if block_len != block.len() as i32 {
+    for block in coding.iter() {
+        if block_len != block.len() {
             error!(
                 "coding block size incorrect {} expected {}",
                 block.len(),
@@ -102,20 +392,13 @@ pub fn generate_coding_blocks(coding: &mut [&mut [u8]], data: &[&[u8]]) -> Resul
             );
             return Err(Error::ErasureError(ErasureError::InvalidBlockSize));
         }
-        coding_arg.push(block.as_mut_ptr());
     }
 
-    unsafe {
-        jerasure_matrix_encode(
-            k,
-            m,
-            ERASURE_W,
-            matrix.as_ptr(),
-            data_arg.as_ptr(),
-            coding_arg.as_ptr(),
-            block_len,
-        );
-    }
+    #[cfg(feature = "jerasure")]
+    jerasure::generate_coding_blocks(coding, data);
+    #[cfg(not(feature = "jerasure"))]
+    rs_generate_coding_blocks(coding, data);
+
     Ok(())
 }
 
@@ -132,67 +415,58 @@ pub fn decode_blocks(
         return Ok(());
     }
     let block_len = data[0].len();
-    let matrix: Vec<i32> = get_matrix(coding.len() as i32, data.len() as i32, ERASURE_W);
+    if block_len % ERASURE_ALIGN != 0 {
+        return Err(Error::ErasureError(ErasureError::InvalidBlockSize));
+    }
 
-    // generate coding pointers, blocks should be the same size
-    let mut coding_arg: Vec<*mut u8> = Vec::new();
-    for x in coding.iter_mut() {
-        if x.len() != block_len {
-            return Err(Error::ErasureError(ErasureError::InvalidBlockSize));
-        }
-        coding_arg.push(x.as_mut_ptr());
+    // blocks should be the same size
+    if coding
+        .iter()
+        .chain(data.iter())
+        .any(|x| x.len() != block_len)
+    {
+        return Err(Error::ErasureError(ErasureError::InvalidBlockSize));
     }
 
-    // generate data pointers, blocks should be the same size
-    let mut data_arg: Vec<*mut u8> = Vec::new();
-    for x in data.iter_mut() {
-        if x.len() != block_len {
-            return Err(Error::ErasureError(ErasureError::InvalidBlockSize));
+    #[cfg(feature = "jerasure")]
+    {
+        let ret = jerasure::decode_blocks(data, coding, erasures);
+        trace!("jerasure_matrix_decode ret: {}", ret);
+        if ret < 0 {
+            return Err(Error::ErasureError(ErasureError::DecodeError));
         }
-        data_arg.push(x.as_mut_ptr());
     }
-    let ret = unsafe {
-        jerasure_matrix_decode(
-            data.len() as i32,
-            coding.len() as i32,
-            ERASURE_W,
-            matrix.as_ptr(),
-            0,
-            erasures.as_ptr(),
-            data_arg.as_ptr(),
-            coding_arg.as_ptr(),
-            data[0].len() as i32,
-        )
-    };
-    trace!("jerasure_matrix_decode ret: {}", ret);
+    #[cfg(not(feature = "jerasure"))]
+    rs_decode_blocks(data, coding, erasures)?;
+
     for x in data[erasures[0] as usize][0..8].iter() {
         trace!("{} ", x)
     }
     trace!("");
-    if ret < 0 {
-        return Err(Error::ErasureError(ErasureError::DecodeError));
-    }
     Ok(())
 }
 
 fn decode_blobs(
+    erasure_config: &ErasureConfig,
     blobs: &[SharedBlob],
     erasures: &[i32],
     size: usize,
     block_start_idx: u64,
     slot: u64,
 ) -> Result<bool> {
-    let mut locks = Vec::with_capacity(NUM_DATA + NUM_CODING);
-    let mut coding_ptrs: Vec<&mut [u8]> = Vec::with_capacity(NUM_CODING);
-    let mut data_ptrs: Vec<&mut [u8]> = Vec::with_capacity(NUM_DATA);
+    let num_data = erasure_config.num_data;
+    let num_coding = erasure_config.num_coding;
+    let mut locks = Vec::with_capacity(erasure_config.set_size());
+    let mut coding_ptrs: Vec<&mut [u8]> = Vec::with_capacity(num_coding);
+    let mut data_ptrs: Vec<&mut [u8]> = Vec::with_capacity(num_data);
 
-    assert!(blobs.len() == NUM_DATA + NUM_CODING);
+    assert!(blobs.len() == erasure_config.set_size());
     for b in blobs {
         locks.push(b.write().unwrap());
     }
 
     for (i, l) in locks.iter_mut().enumerate() {
-        if i < NUM_DATA {
+        if i < num_data {
             data_ptrs.push(&mut l.data[..size]);
         } else {
             coding_ptrs.push(&mut l.data_mut()[..size]);
@@ -203,7 +477,7 @@ fn decode_blobs(
     decode_blocks(
         data_ptrs.as_mut_slice(),
         coding_ptrs.as_mut_slice(),
-        &erasures,
+        erasures,
     )?;
 
     // Create the missing blobs from the reconstructed data
@@ -214,7 +488,7 @@ fn decode_blobs(
         let mut idx = n as u64 + block_start_idx;
 
         let mut data_size;
-        if n < NUM_DATA {
+        if n < num_data {
             data_size = locks[n].data_size() as usize;
             data_size -= BLOB_HEADER_SIZE;
             if data_size > BLOB_DATA_SIZE {
@@ -224,7 +498,7 @@ fn decode_blobs(
             }
         } else {
             data_size = size;
-            idx -= NUM_CODING as u64;
+            idx -= num_coding as u64;
             locks[n].set_slot(slot);
             locks[n].set_index(idx);
 
@@ -253,8 +527,8 @@ fn decode_blobs(
 //   at the end of the block like so:
 //
 //  block-size part of a Window, with each element a WindowSlot..
-//  |<======================= NUM_DATA ==============================>|
-//                                              |<==== NUM_CODING ===>|
+//  |<======================= num_data ==============================>|
+//                                              |<==== num_coding ===>|
 //  +---+ +---+ +---+ +---+ +---+         +---+ +---+ +---+ +---+ +---+
 //  | D | | D | | D | | D | | D |         | D | | D | | D | | D | | D |
 //  +---+ +---+ +---+ +---+ +---+  . . .  +---+ +---+ +---+ +---+ +---+
@@ -287,24 +561,38 @@ fn decode_blobs(
 //
 pub struct CodingGenerator {
     leftover: Vec<SharedBlob>, // SharedBlobs that couldn't be used in last call to next()
+    erasure_config: ErasureConfig,
+}
+
+impl Default for CodingGenerator {
+    fn default() -> Self {
+        Self::new_with_config(ErasureConfig::default())
+    }
 }
 
 impl CodingGenerator {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_with_config(erasure_config: ErasureConfig) -> Self {
         Self {
-            leftover: Vec::with_capacity(NUM_DATA),
+            leftover: Vec::with_capacity(erasure_config.num_data),
+            erasure_config,
         }
     }
 
     // must be called with consecutive data blobs from previous invocation
     pub fn next(&mut self, next_data: &[SharedBlob]) -> Result<Vec<SharedBlob>> {
+        let num_data = self.erasure_config.num_data;
+        let num_coding = self.erasure_config.num_coding;
         let mut next_coding =
-            Vec::with_capacity((self.leftover.len() + next_data.len()) / NUM_DATA * NUM_CODING);
+            Vec::with_capacity((self.leftover.len() + next_data.len()) / num_data * num_coding);
 
         let next_data: Vec<_> = self.leftover.iter().chain(next_data).cloned().collect();
 
-        for data_blobs in next_data.chunks(NUM_DATA) {
-            if data_blobs.len() < NUM_DATA {
+        for data_blobs in next_data.chunks(num_data) {
+            if data_blobs.len() < num_data {
                 self.leftover = data_blobs.to_vec();
                 break;
             }
@@ -315,7 +603,7 @@ impl CodingGenerator {
                 data_blobs
                     .iter()
                     .fold(0, |max, blob| cmp::max(blob.read().unwrap().meta.size, max)),
-                JERASURE_ALIGN
+                ERASURE_ALIGN
             );
 
             let data_locks: Vec<_> = data_blobs.iter().map(|b| b.read().unwrap()).collect();
@@ -324,9 +612,9 @@ impl CodingGenerator {
                 .map(|l| &l.data[..max_data_size])
                 .collect();
 
-            let mut coding_blobs = Vec::with_capacity(NUM_CODING);
+            let mut coding_blobs = Vec::with_capacity(num_coding);
 
-            for data_blob in &data_locks[NUM_DATA - NUM_CODING..NUM_DATA] {
+            for data_blob in &data_locks[num_data - num_coding..num_data] {
                 let index = data_blob.index();
                 let slot = data_blob.slot();
                 let id = data_blob.id();
@@ -368,12 +656,15 @@ pub fn recover(
     slot: u64,
     start_idx: u64,
 ) -> Result<(Vec<SharedBlob>, Vec<SharedBlob>)> {
-    let block_start_idx = start_idx - (start_idx % NUM_DATA as u64);
+    let erasure_config = db_ledger.erasure_config();
+    let num_data = erasure_config.num_data;
+    let num_coding = erasure_config.num_coding;
+    let block_start_idx = start_idx - (start_idx % num_data as u64);
 
     debug!("block_start_idx: {}", block_start_idx);
 
-    let coding_start_idx = block_start_idx + NUM_DATA as u64 - NUM_CODING as u64;
-    let block_end_idx = block_start_idx + NUM_DATA as u64;
+    let coding_start_idx = block_start_idx + num_data as u64 - num_coding as u64;
+    let block_end_idx = block_start_idx + num_data as u64;
     trace!(
         "recover: coding_start_idx: {} block_end_idx: {}",
         coding_start_idx,
@@ -381,10 +672,10 @@ pub fn recover(
     );
 
     let data_missing = db_ledger
-        .find_missing_data_indexes(slot, block_start_idx, block_end_idx, NUM_DATA)
+        .find_missing_data_indexes(slot, block_start_idx, block_end_idx, num_data)
         .len();
     let coding_missing = db_ledger
-        .find_missing_coding_indexes(slot, coding_start_idx, block_end_idx, NUM_CODING)
+        .find_missing_coding_indexes(slot, coding_start_idx, block_end_idx, num_coding)
         .len();
 
     // if we're not missing data, or if we have too much missing but have enough coding
@@ -393,7 +684,7 @@ pub fn recover(
         return Ok((vec![], vec![]));
     }
 
-    if (data_missing + coding_missing) > NUM_CODING {
+    if (data_missing + coding_missing) > num_coding {
         trace!(
             "recover: start: {} skipping recovery data: {} coding: {}",
             block_start_idx,
@@ -410,8 +701,8 @@ pub fn recover(
         coding_missing
     );
 
-    let mut blobs: Vec<SharedBlob> = Vec::with_capacity(NUM_DATA + NUM_CODING);
-    let mut erasures: Vec<i32> = Vec::with_capacity(NUM_CODING);
+    let mut blobs: Vec<SharedBlob> = Vec::with_capacity(erasure_config.set_size());
+    let mut erasures: Vec<i32> = Vec::with_capacity(num_coding);
 
     let mut missing_data: Vec<SharedBlob> = vec![];
     let mut missing_coding: Vec<SharedBlob> = vec![];
@@ -439,7 +730,7 @@ pub fn recover(
             &mut blobs,
             &mut missing_coding,
             &mut erasures,
-            ((i - coding_start_idx) + NUM_DATA as u64) as i32,
+            ((i - coding_start_idx) + num_data as u64) as i32,
         )?;
 
         if let Some(b) = result {
@@ -448,7 +739,7 @@ pub fn recover(
             }
         }
     }
-    // Due to checks above verifying that (data_missing + coding_missing) <= NUM_CODING and
+    // Due to checks above verifying that (data_missing + coding_missing) <= num_coding and
     //  data_missing > 0, we know at least one coding block must exist, so "size" can
     //  not remain None after the above processing.
     let size = size.unwrap();
@@ -458,7 +749,14 @@ pub fn recover(
 
     trace!("erasures[]:{:?} data_size: {}", erasures, size,);
 
-    let corrupt = decode_blobs(&blobs, &erasures, size, block_start_idx, slot)?;
+    let corrupt = decode_blobs(
+        &erasure_config,
+        &blobs,
+        &erasures,
+        size,
+        block_start_idx,
+        slot,
+    )?;
 
     if corrupt {
         // Remove the corrupted coding blobs so there's no effort wasted in trying to
@@ -484,7 +782,7 @@ fn categorize_blob(
             if b.len() <= BLOB_HEADER_SIZE || b.len() > BLOB_SIZE {
                 return Err(Error::ErasureError(ErasureError::InvalidBlobData));
             }
-            blobs.push(Arc::new(RwLock::new(Blob::new(&b))));
+            blobs.push(Arc::new(RwLock::new(Blob::new(b))));
         }
         None => {
             // Mark the missing memory
@@ -561,6 +859,123 @@ pub mod test {
         assert_eq!(v_orig, vs[0]);
     }
 
+    #[test]
+    fn test_gf_inv() {
+        assert_eq!(gf_mul(1, 0x1234_5678), 0x1234_5678);
+        for a in &[1, 2, 3, 0x8000_0000, 0xdead_beef, std::u32::MAX] {
+            assert_eq!(gf_mul(*a, gf_inv(*a)), 1);
+        }
+    }
+
+    fn random_blocks(num: usize, block_len: usize) -> Vec<Vec<u8>> {
+        (0..num)
+            .map(|_| (0..block_len).map(|_| thread_rng().gen()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_blocks_max_erasures() {
+        let (k, m, block_len) = (8, 4, 64);
+        let data = random_blocks(k, block_len);
+        let mut coding = vec![vec![0u8; block_len]; m];
+        {
+            let data_slices: Vec<_> = data.iter().map(|x| x.as_slice()).collect();
+            let mut coding_slices: Vec<_> = coding.iter_mut().map(|x| x.as_mut_slice()).collect();
+            generate_coding_blocks(&mut coding_slices, &data_slices).unwrap();
+        }
+        let expected_coding = coding.clone();
+
+        // Lose two data blocks and two coding blocks
+        let erasures = vec![1, 6, k as i32, k as i32 + 3, -1];
+        let mut damaged = data.clone();
+        for e in &erasures[..erasures.len() - 1] {
+            let e = *e as usize;
+            if e < k {
+                damaged[e] = vec![0; block_len];
+            } else {
+                coding[e - k] = vec![0; block_len];
+            }
+        }
+        {
+            let mut data_slices: Vec<_> = damaged.iter_mut().map(|x| x.as_mut_slice()).collect();
+            let mut coding_slices: Vec<_> = coding.iter_mut().map(|x| x.as_mut_slice()).collect();
+            decode_blocks(&mut data_slices, &mut coding_slices, &erasures).unwrap();
+        }
+        assert_eq!(damaged, data);
+        assert_eq!(coding, expected_coding);
+
+        // One erasure too many
+        let erasures = vec![0, 1, 2, 3, 4, -1];
+        let mut data_slices: Vec<_> = damaged.iter_mut().map(|x| x.as_mut_slice()).collect();
+        let mut coding_slices: Vec<_> = coding.iter_mut().map(|x| x.as_mut_slice()).collect();
+        assert!(decode_blocks(&mut data_slices, &mut coding_slices, &erasures).is_err());
+    }
+
+    #[test]
+    fn test_decode_blocks_unaligned() {
+        let mut data = random_blocks(2, 6);
+        let mut coding = vec![vec![0u8; 6]];
+        let mut data_slices: Vec<_> = data.iter_mut().map(|x| x.as_mut_slice()).collect();
+        let mut coding_slices: Vec<_> = coding.iter_mut().map(|x| x.as_mut_slice()).collect();
+        assert_matches!(
+            decode_blocks(&mut data_slices, &mut coding_slices, &[0, -1]),
+            Err(Error::ErasureError(ErasureError::InvalidBlockSize))
+        );
+    }
+
+    #[cfg(feature = "jerasure")]
+    #[test]
+    fn test_rs_matches_jerasure() {
+        let (k, m, block_len) = (NUM_DATA, NUM_CODING, 256);
+        let data = random_blocks(k, block_len);
+        let data_slices: Vec<_> = data.iter().map(|x| x.as_slice()).collect();
+
+        let mut rs_coding = vec![vec![0u8; block_len]; m];
+        let mut jerasure_coding = vec![vec![0u8; block_len]; m];
+        {
+            let mut coding_slices: Vec<_> =
+                rs_coding.iter_mut().map(|x| x.as_mut_slice()).collect();
+            rs_generate_coding_blocks(&mut coding_slices, &data_slices);
+        }
+        {
+            let mut coding_slices: Vec<_> = jerasure_coding
+                .iter_mut()
+                .map(|x| x.as_mut_slice())
+                .collect();
+            jerasure::generate_coding_blocks(&mut coding_slices, &data_slices);
+        }
+        assert_eq!(rs_coding, jerasure_coding);
+    }
+
+    #[test]
+    fn test_erasure_generate_coding_with_config() {
+        let erasure_config = ErasureConfig::new(8, 2);
+        let mut coding_generator = CodingGenerator::new_with_config(erasure_config);
+        let data_blobs = generate_test_blobs(0, erasure_config.num_data);
+
+        let coding = coding_generator.next(&data_blobs).unwrap();
+        assert_eq!(coding.len(), erasure_config.num_coding);
+        let size = coding[0].read().unwrap().size();
+
+        // toss one data and one coding
+        let erasures: Vec<i32> = vec![3, erasure_config.num_data as i32, -1];
+        let mut blobs: Vec<SharedBlob> = data_blobs.iter().cloned().collect();
+        blobs[3] = SharedBlob::default();
+        blobs.push(SharedBlob::default());
+        blobs.push(coding[1].clone());
+
+        let corrupt = decode_blobs(&erasure_config, &blobs, &erasures, size, 0, 0).unwrap();
+        assert!(!corrupt);
+        assert_eq!(
+            blobs[3].read().unwrap().data(),
+            data_blobs[3].read().unwrap().data()
+        );
+        assert_eq!(
+            blobs[erasure_config.num_data].read().unwrap().data(),
+            coding[0].read().unwrap().data()
+        );
+    }
+
     #[test]
     fn test_erasure_generate_coding() {
         solana_logger::setup();
@@ -601,8 +1016,15 @@ pub mod test {
                     blobs.push(blob.clone());
                 }
 
-                let corrupt =
-                    decode_blobs(&blobs, &erasures, size, block_start_idx as u64, 0).unwrap();
+                let corrupt = decode_blobs(
+                    &ErasureConfig::default(),
+                    &blobs,
+                    &erasures,
+                    size,
+                    block_start_idx as u64,
+                    0,
+                )
+                .unwrap();
 
                 assert!(!corrupt);
 
@@ -720,8 +1142,8 @@ pub mod test {
                 }
             }
 
-            // round up to the nearest jerasure alignment
-            max_data_size = align!(max_data_size, JERASURE_ALIGN);
+            // round up to the nearest erasure alignment
+            max_data_size = align!(max_data_size, ERASURE_ALIGN);
 
             let mut data_blobs = Vec::with_capacity(NUM_DATA);
             for i in block_start..block_end {
@@ -1,10 +1,12 @@
-//! The `fetch_stage` batches input from a UDP socket and sends it to a channel.
+//! The `fetch_stage` batches input from UDP sockets and a TCP listener and sends it to a
+//! channel.
 
+use crate::packet::{PacketsRecycler, PACKETS_RECYCLER_LIMIT};
 use crate::service::Service;
-use crate::streamer::{self, PacketReceiver, PacketSender};
-use std::net::UdpSocket;
+use crate::streamer::{self, PacketReceiver, PacketSender, PACKET_CHANNEL_MAX_PACKETS};
+use crate::tcp_receiver::{tcp_receiver, FrameStatus};
+use std::net::{TcpListener, UdpSocket};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::channel;
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 
@@ -15,27 +17,52 @@ pub struct FetchStage {
 
 impl FetchStage {
     #[allow(clippy::new_ret_no_self)]
-    pub fn new(sockets: Vec<UdpSocket>, exit: Arc<AtomicBool>) -> (Self, PacketReceiver) {
-        let (sender, receiver) = channel();
-        (Self::new_with_sender(sockets, exit, &sender), receiver)
+    pub fn new(
+        sockets: Vec<UdpSocket>,
+        tcp_listener: TcpListener,
+        exit: Arc<AtomicBool>,
+    ) -> (Self, PacketReceiver) {
+        let (sender, receiver) = streamer::packet_channel(PACKET_CHANNEL_MAX_PACKETS);
+        (
+            Self::new_with_sender(sockets, tcp_listener, exit, &sender),
+            receiver,
+        )
     }
     pub fn new_with_sender(
         sockets: Vec<UdpSocket>,
+        tcp_listener: TcpListener,
         exit: Arc<AtomicBool>,
         sender: &PacketSender,
     ) -> Self {
         let tx_sockets = sockets.into_iter().map(Arc::new).collect();
-        Self::new_multi_socket(tx_sockets, exit, &sender)
+        Self::new_multi_socket(tx_sockets, tcp_listener, exit, &sender)
     }
     fn new_multi_socket(
         sockets: Vec<Arc<UdpSocket>>,
+        tcp_listener: TcpListener,
         exit: Arc<AtomicBool>,
         sender: &PacketSender,
     ) -> Self {
-        let thread_hdls: Vec<_> = sockets
+        let recycler = PacketsRecycler::new("fetch-stage", PACKETS_RECYCLER_LIMIT);
+        let mut thread_hdls: Vec<_> = sockets
             .into_iter()
-            .map(|socket| streamer::receiver(socket, exit.clone(), sender.clone(), "fetch-stage"))
+            .map(|socket| {
+                streamer::receiver(
+                    socket,
+                    exit.clone(),
+                    sender.clone(),
+                    &recycler,
+                    "fetch-stage",
+                )
+            })
             .collect();
+        thread_hdls.push(tcp_receiver(
+            tcp_listener,
+            exit.clone(),
+            sender.clone(),
+            FrameStatus::Accepted,
+            "fetch-stage",
+        ));
 
         Self { exit, thread_hdls }
     }
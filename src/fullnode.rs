@@ -4,12 +4,16 @@ use crate::bank::Bank;
 use crate::cluster_info::{ClusterInfo, Node, NodeInfo};
 use crate::counter::Counter;
 use crate::db_ledger::DbLedger;
+use crate::erasure::ErasureConfig;
 use crate::genesis_block::GenesisBlock;
+use crate::gossip_peer_score::PeerFilter;
 use crate::gossip_service::GossipService;
 use crate::leader_scheduler::LeaderScheduler;
+use crate::poh_service::Config as PohServiceConfig;
 use crate::rpc::JsonRpcService;
 use crate::rpc_pubsub::PubSubService;
 use crate::service::Service;
+use crate::sigverify::VerifyMode;
 use crate::storage_stage::StorageState;
 use crate::streamer::BlobSender;
 use crate::tpu::{Tpu, TpuReturnType};
@@ -20,13 +24,13 @@ use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, KeypairUtil};
 use solana_sdk::timing::{duration_as_ms, timestamp};
-use std::net::UdpSocket;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, UdpSocket};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::channel;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, RwLock};
 use std::thread::sleep;
+use std::thread::JoinHandle;
 use std::thread::Result;
 use std::time::Duration;
 use std::time::Instant;
@@ -70,10 +74,14 @@ pub enum FullnodeReturnType {
 }
 
 pub struct FullnodeConfig {
-    pub sigverify_disabled: bool,
+    pub sigverify_mode: VerifyMode,
     pub voting_disabled: bool,
+    /// Join gossip and replay the ledger to serve RPC, but never vote, advertise a TPU or
+    /// become leader
+    pub observer: bool,
     pub entry_stream: Option<String>,
     pub storage_rotate_count: u64,
+    pub gossip_peer_filter: PeerFilter,
 }
 impl Default for FullnodeConfig {
     fn default() -> Self {
@@ -82,10 +90,12 @@ impl Default for FullnodeConfig {
         // so tests don't take forever to run.
         const NUM_HASHES_FOR_STORAGE_ROTATE: u64 = 1024;
         Self {
-            sigverify_disabled: false,
+            sigverify_mode: VerifyMode::default(),
             voting_disabled: false,
+            observer: false,
             entry_stream: None,
             storage_rotate_count: NUM_HASHES_FOR_STORAGE_ROTATE,
+            gossip_peer_filter: PeerFilter::default(),
         }
     }
 }
@@ -96,14 +106,18 @@ pub struct Fullnode {
     rpc_service: Option<JsonRpcService>,
     rpc_pubsub_service: Option<PubSubService>,
     gossip_service: GossipService,
+    ip_echo_server: Option<JoinHandle<()>>,
     bank: Arc<Bank>,
     cluster_info: Arc<RwLock<ClusterInfo>>,
-    sigverify_disabled: bool,
+    sigverify_mode: VerifyMode,
     tpu_sockets: Vec<UdpSocket>,
+    tpu_tcp_listener: TcpListener,
     broadcast_socket: UdpSocket,
     pub node_services: NodeServices,
     pub role_notifiers: (TvuRotationReceiver, TpuRotationReceiver),
     blob_sender: BlobSender,
+    erasure_config: ErasureConfig,
+    poh_config: PohServiceConfig,
 }
 
 impl Fullnode {
@@ -118,7 +132,11 @@ impl Fullnode {
     ) -> Self {
         let id = keypair.pubkey();
         let (genesis_block, db_ledger) = Self::make_db_ledger(ledger_path);
-        let (bank, entry_height, last_entry_id) =
+        assert!(
+            !config.observer || genesis_block.bootstrap_leader_id != id,
+            "The bootstrap leader can't be an observer"
+        );
+        let (bank, entry_height, last_entry_id, tick_hash_count) =
             Self::new_bank_from_db_ledger(&genesis_block, &db_ledger, leader_scheduler);
 
         info!("node info: {:?}", node.info);
@@ -132,11 +150,21 @@ impl Fullnode {
         let bank = Arc::new(bank);
 
         node.info.wallclock = timestamp();
+        if config.observer {
+            // Observers never accept transactions, so keep them out of the tpu peers
+            node.info.tpu = socketaddr_any!();
+            node.info.tpu_tcp = socketaddr_any!();
+        }
         assert_eq!(id, node.info.id);
         let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_keypair(
             node.info.clone(),
             keypair.clone(),
         )));
+        cluster_info.write().unwrap().push_version();
+        cluster_info
+            .write()
+            .unwrap()
+            .set_peer_filter(config.gossip_peer_filter.clone());
 
         // TODO: The RPC service assumes that there is a drone running on the cluster
         //       entrypoint, which is a bad assumption.
@@ -176,6 +204,13 @@ impl Fullnode {
             exit.clone(),
         );
 
+        // Let joining nodes discover their public address and check their ports through us
+        let ip_echo_server = node
+            .sockets
+            .ip_echo
+            .take()
+            .map(|ip_echo| solana_netutil::ip_echo_server(ip_echo, exit.clone()));
+
         // Insert the entrypoint info, should only be None if this node
         // is the bootstrap leader
         if let Some(entrypoint_info) = entrypoint_info_option {
@@ -213,7 +248,7 @@ impl Fullnode {
                 .collect(),
         };
 
-        let voting_keypair_option = if config.voting_disabled {
+        let voting_keypair_option = if config.voting_disabled || config.observer {
             None
         } else {
             Some(Arc::new(voting_keypair))
@@ -228,6 +263,7 @@ impl Fullnode {
             &bank,
             entry_height,
             last_entry_id,
+            tick_hash_count,
             &cluster_info,
             sockets,
             db_ledger.clone(),
@@ -243,25 +279,30 @@ impl Fullnode {
 
         let tpu = Tpu::new(
             &Arc::new(bank.copy_for_tpu()),
-            Default::default(),
+            genesis_block.poh_config(),
             node.sockets
                 .tpu
                 .iter()
                 .map(|s| s.try_clone().expect("Failed to clone TPU sockets"))
                 .collect(),
+            node.sockets
+                .tpu_tcp
+                .try_clone()
+                .expect("Failed to clone TPU TCP listener"),
             node.sockets
                 .broadcast
                 .try_clone()
                 .expect("Failed to clone broadcast socket"),
             cluster_info.clone(),
             entry_height,
-            config.sigverify_disabled,
+            config.sigverify_mode,
             max_tick_height,
             &last_entry_id,
             id,
             scheduled_leader == id,
             &to_validator_sender,
             &blob_sender,
+            genesis_block.erasure_config,
         );
 
         inc_new_counter_info!("fullnode-new", 1);
@@ -270,16 +311,20 @@ impl Fullnode {
             id,
             cluster_info,
             bank,
-            sigverify_disabled: config.sigverify_disabled,
+            sigverify_mode: config.sigverify_mode,
             gossip_service,
+            ip_echo_server,
             rpc_service: Some(rpc_service),
             rpc_pubsub_service: Some(rpc_pubsub_service),
             node_services: NodeServices::new(tpu, tvu),
             exit,
             tpu_sockets: node.sockets.tpu,
+            tpu_tcp_listener: node.sockets.tpu_tcp,
             broadcast_socket: node.sockets.broadcast,
             role_notifiers: (to_leader_receiver, to_validator_receiver),
             blob_sender,
+            erasure_config: genesis_block.erasure_config,
+            poh_config: genesis_block.poh_config(),
         }
     }
 
@@ -316,6 +361,9 @@ impl Fullnode {
                     .iter()
                     .map(|s| s.try_clone().expect("Failed to clone TPU sockets"))
                     .collect(),
+                self.tpu_tcp_listener
+                    .try_clone()
+                    .expect("Failed to clone TPU TCP listener"),
                 self.cluster_info.clone(),
             );
             Ok(())
@@ -335,22 +383,26 @@ impl Fullnode {
         self.role_notifiers.1 = to_validator_receiver;
         self.node_services.tpu.switch_to_leader(
             &Arc::new(self.bank.copy_for_tpu()),
-            Default::default(),
+            self.poh_config,
             self.tpu_sockets
                 .iter()
                 .map(|s| s.try_clone().expect("Failed to clone TPU sockets"))
                 .collect(),
+            self.tpu_tcp_listener
+                .try_clone()
+                .expect("Failed to clone TPU TCP listener"),
             self.broadcast_socket
                 .try_clone()
                 .expect("Failed to clone broadcast socket"),
             self.cluster_info.clone(),
-            self.sigverify_disabled,
+            self.sigverify_mode,
             max_tick_height,
             entry_height,
             &last_id,
             self.id,
             &to_validator_sender,
             &self.blob_sender,
+            self.erasure_config,
         )
     }
 
@@ -400,7 +452,7 @@ impl Fullnode {
         genesis_block: &GenesisBlock,
         db_ledger: &DbLedger,
         leader_scheduler: Arc<RwLock<LeaderScheduler>>,
-    ) -> (Bank, u64, Hash) {
+    ) -> (Bank, u64, Hash, u64) {
         let mut bank = Bank::new(genesis_block);
         leader_scheduler.write().unwrap().bootstrap_leader = genesis_block.bootstrap_leader_id;
         bank.leader_scheduler = leader_scheduler;
@@ -409,7 +461,8 @@ impl Fullnode {
         let entries = db_ledger.read_ledger().expect("opening ledger");
         info!("processing ledger...");
 
-        let (entry_height, last_entry_id) = bank.process_ledger(entries).expect("process_ledger");
+        let (entry_height, last_entry_id, tick_hash_count) =
+            bank.process_ledger(entries).expect("process_ledger");
         // entry_height is the network-wide agreed height of the ledger.
         //  initialize it from the input ledger
         info!(
@@ -417,13 +470,13 @@ impl Fullnode {
             entry_height,
             duration_as_ms(&now.elapsed())
         );
-        (bank, entry_height, last_entry_id)
+        (bank, entry_height, last_entry_id, tick_hash_count)
     }
 
     pub fn new_bank_from_ledger(
         ledger_path: &str,
         leader_scheduler: Arc<RwLock<LeaderScheduler>>,
-    ) -> (Bank, u64, Hash) {
+    ) -> (Bank, u64, Hash, u64) {
         let (genesis_block, db_ledger) = Self::make_db_ledger(ledger_path);
         Self::new_bank_from_db_ledger(&genesis_block, &db_ledger, leader_scheduler)
     }
@@ -433,12 +486,13 @@ impl Fullnode {
     }
 
     fn make_db_ledger(ledger_path: &str) -> (GenesisBlock, Arc<DbLedger>) {
-        let db_ledger = Arc::new(
-            DbLedger::open(ledger_path).expect("Expected to successfully open database ledger"),
-        );
-
         let genesis_block =
             GenesisBlock::load(ledger_path).expect("Expected to successfully open genesis block");
+
+        let db_ledger = Arc::new(
+            DbLedger::open_with_config(ledger_path, genesis_block.erasure_config)
+                .expect("Expected to successfully open database ledger"),
+        );
         (genesis_block, db_ledger)
     }
 }
@@ -455,6 +509,9 @@ impl Service for Fullnode {
         }
 
         self.gossip_service.join()?;
+        if let Some(ip_echo_server) = self.ip_echo_server {
+            ip_echo_server.join()?;
+        }
         self.node_services.join()?;
         Ok(())
     }
@@ -464,6 +521,7 @@ impl Service for Fullnode {
 mod tests {
     use super::*;
     use crate::cluster_info::Node;
+    use crate::contact_info::ContactInfo;
     use crate::db_ledger::*;
     use crate::entry::make_consecutive_blobs;
     use crate::leader_scheduler::{
@@ -506,6 +564,38 @@ mod tests {
         remove_dir_all(validator_ledger_path).unwrap();
     }
 
+    #[test]
+    fn observer_exit() {
+        let leader_keypair = Keypair::new();
+        let leader_node = Node::new_localhost_with_pubkey(leader_keypair.pubkey());
+
+        let observer_keypair = Keypair::new();
+        let observer_node = Node::new_localhost_with_pubkey(observer_keypair.pubkey());
+        let (_, observer_ledger_path, _, _) =
+            create_tmp_sample_ledger("observer_exit", 10_000, 0, leader_keypair.pubkey(), 1000);
+
+        let mut fullnode_config = FullnodeConfig::default();
+        fullnode_config.observer = true;
+        let observer = Fullnode::new(
+            observer_node,
+            &Arc::new(observer_keypair),
+            &observer_ledger_path,
+            Arc::new(RwLock::new(LeaderScheduler::new(&Default::default()))),
+            VotingKeypair::new(),
+            Some(&leader_node.info),
+            &fullnode_config,
+        );
+        {
+            let cluster_info = observer.cluster_info.read().unwrap();
+            let my_data = cluster_info.my_data();
+            assert!(!ContactInfo::is_valid_address(&my_data.tpu));
+            assert!(ContactInfo::is_valid_address(&my_data.tvu));
+        }
+        assert!(observer.rpc_service.is_some());
+        observer.close().unwrap();
+        remove_dir_all(observer_ledger_path).unwrap();
+    }
+
     #[test]
     fn validator_parallel_exit() {
         let leader_keypair = Keypair::new();
@@ -781,7 +871,7 @@ mod tests {
 
         //close the validator so that rocksdb has locks available
         validator.close().unwrap();
-        let (bank, entry_height, _) = Fullnode::new_bank_from_ledger(
+        let (bank, entry_height, _, _) = Fullnode::new_bank_from_ledger(
             &validator_ledger_path,
             Arc::new(RwLock::new(LeaderScheduler::new(&leader_scheduler_config))),
         );
@@ -1,10 +1,13 @@
 //! The `genesis_block` module is a library for generating the chain's genesis block.
 
+use crate::erasure::ErasureConfig;
+use crate::leader_scheduler::DEFAULT_TICKS_PER_SLOT;
+use crate::poh_service::Config;
 use solana_sdk::hash::{hash, Hash};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, KeypairUtil};
 use std::fs::File;
-use std::io::Write;
+use std::io::{ErrorKind, Write};
 use std::path::Path;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -13,6 +16,18 @@ pub struct GenesisBlock {
     pub bootstrap_leader_tokens: u64,
     pub mint_id: Pubkey,
     pub tokens: u64,
+    #[serde(default)]
+    pub erasure_config: ErasureConfig,
+    /// Number of hashes between ticks.  `None` leaves the leader in the
+    /// low-power sleep mode and the tick hash count unenforced
+    #[serde(default)]
+    pub hashes_per_tick: Option<u64>,
+    #[serde(default = "default_ticks_per_slot")]
+    pub ticks_per_slot: u64,
+}
+
+fn default_ticks_per_slot() -> u64 {
+    DEFAULT_TICKS_PER_SLOT
 }
 
 impl GenesisBlock {
@@ -25,6 +40,9 @@ impl GenesisBlock {
                 bootstrap_leader_tokens: 0,
                 mint_id: mint_keypair.pubkey(),
                 tokens,
+                erasure_config: ErasureConfig::default(),
+                hashes_per_tick: None,
+                ticks_per_slot: DEFAULT_TICKS_PER_SLOT,
             },
             mint_keypair,
         )
@@ -42,11 +60,23 @@ impl GenesisBlock {
                 bootstrap_leader_tokens,
                 mint_id: mint_keypair.pubkey(),
                 tokens,
+                erasure_config: ErasureConfig::default(),
+                hashes_per_tick: None,
+                ticks_per_slot: DEFAULT_TICKS_PER_SLOT,
             },
             mint_keypair,
         )
     }
 
+    /// The PoH configuration a leader should run to produce ticks this
+    /// genesis block's validators will accept
+    pub fn poh_config(&self) -> Config {
+        match self.hashes_per_tick {
+            Some(hashes_per_tick) => Config::Tick(hashes_per_tick as usize),
+            None => Config::default(),
+        }
+    }
+
     pub fn last_id(&self) -> Hash {
         let serialized = serde_json::to_string(self).unwrap();
         hash(&serialized.into_bytes())
@@ -54,7 +84,13 @@ impl GenesisBlock {
 
     pub fn load(ledger_path: &str) -> Result<Self, std::io::Error> {
         let file = File::open(&Path::new(ledger_path).join("genesis.json"))?;
-        let genesis_block = serde_json::from_reader(file)?;
+        let genesis_block: Self = serde_json::from_reader(file)?;
+        if !genesis_block.erasure_config.is_valid() {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid erasure config: {:?}", genesis_block.erasure_config),
+            ));
+        }
         Ok(genesis_block)
     }
 
@@ -68,6 +104,8 @@ impl GenesisBlock {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db_ledger::get_tmp_ledger_path;
+    use std::fs;
 
     #[test]
     fn test_genesis_block_new() {
@@ -76,6 +114,9 @@ mod tests {
         assert_eq!(genesis_block.mint_id, mint.pubkey());
         assert_eq!(genesis_block.bootstrap_leader_id, Pubkey::default());
         assert_eq!(genesis_block.bootstrap_leader_tokens, 0);
+        assert_eq!(genesis_block.erasure_config, ErasureConfig::default());
+        assert_eq!(genesis_block.hashes_per_tick, None);
+        assert_eq!(genesis_block.ticks_per_slot, DEFAULT_TICKS_PER_SLOT);
     }
 
     #[test]
@@ -89,4 +130,55 @@ mod tests {
         assert_eq!(genesis_block.bootstrap_leader_id, leader_keypair.pubkey());
         assert_eq!(genesis_block.bootstrap_leader_tokens, 123);
     }
+
+    #[test]
+    fn test_genesis_block_tick_config_defaults() {
+        let (genesis_block, _mint) = GenesisBlock::new(10_000);
+        let mut value = serde_json::to_value(&genesis_block).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.remove("hashes_per_tick");
+        object.remove("ticks_per_slot");
+
+        // Genesis blocks written before the tick config existed still load
+        let genesis_block: GenesisBlock = serde_json::from_value(value).unwrap();
+        assert_eq!(genesis_block.hashes_per_tick, None);
+        assert_eq!(genesis_block.ticks_per_slot, DEFAULT_TICKS_PER_SLOT);
+        match genesis_block.poh_config() {
+            Config::Sleep(_) => (),
+            Config::Tick(_) => panic!("expected the sleep config"),
+        }
+    }
+
+    #[test]
+    fn test_genesis_block_poh_config() {
+        let (mut genesis_block, _mint) = GenesisBlock::new(10_000);
+        genesis_block.hashes_per_tick = Some(42);
+        match genesis_block.poh_config() {
+            Config::Tick(hashes_per_tick) => assert_eq!(hashes_per_tick, 42),
+            Config::Sleep(_) => panic!("expected the tick config"),
+        }
+    }
+
+    #[test]
+    fn test_genesis_block_load_invalid_erasure_config() {
+        let ledger_path = get_tmp_ledger_path("test_genesis_block_load_invalid_erasure_config");
+        fs::create_dir_all(&ledger_path).unwrap();
+        let (mut genesis_block, _mint) = GenesisBlock::new(10_000);
+
+        genesis_block.write(&ledger_path).unwrap();
+        assert!(GenesisBlock::load(&ledger_path).is_ok());
+
+        // Configs that would make recovery divide by zero or underflow are rejected
+        for &(num_data, num_coding) in &[(0, 0), (4, 0), (2, 4)] {
+            genesis_block.erasure_config = ErasureConfig {
+                num_data,
+                num_coding,
+            };
+            genesis_block.write(&ledger_path).unwrap();
+            let err = GenesisBlock::load(&ledger_path).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+
+        fs::remove_dir_all(ledger_path).unwrap();
+    }
 }
@@ -0,0 +1,288 @@
+//! The `gossip_peer_score` module keeps per-peer accounting for the gossip listen thread.
+//!
+//! Every gossip packet is charged to the IP address it came from, whatever its port.  A peer
+//! that exceeds the per-second message or byte budget has the excess dropped.
+//!
+//! Nothing a peer sends lowers its standing beyond that window.  The source address of a UDP
+//! packet can be forged, and anyone can replay validly signed values, so invalid values,
+//! duplicates and rate limit hits are only counted: acting on them would let an attacker
+//! get any peer banned.  Once `PEER_MAX_TRACKED_ADDRS` addresses are tracked, packets from
+//! new addresses share a single budget, so a flood of forged addresses can't grow the
+//! accounting without bound.
+//!
+//! Operators can additionally allow addresses, which are never limited, and deny addresses
+//! or node ids outright.  Node ids are matched against the signed origin of each value,
+//! never against the unauthenticated sender of a message.
+
+use hashbrown::{HashMap, HashSet};
+use solana_sdk::pubkey::Pubkey;
+use std::net::{IpAddr, SocketAddr};
+
+/// Length of the accounting window
+pub const PEER_WINDOW_MS: u64 = 1000;
+/// Maximum number of packets accepted from a single peer per window
+pub const PEER_MAX_MESSAGES_PER_WINDOW: u64 = 1000;
+/// Maximum number of bytes accepted from a single peer per window
+pub const PEER_MAX_BYTES_PER_WINDOW: u64 = 4 * 1024 * 1024;
+/// Maximum number of addresses with their own accounting
+pub const PEER_MAX_TRACKED_ADDRS: usize = 16 * 1024;
+/// Accounting for peers that have been quiet for this long is dropped
+pub const PEER_STATS_TIMEOUT_MS: u64 = 60 * 1000;
+
+/// Operator configured lists of gossip peers
+#[derive(Debug, Default, Clone)]
+pub struct PeerFilter {
+    /// Addresses that are never rate limited
+    pub allowed: HashSet<IpAddr>,
+    /// Addresses whose packets are always dropped
+    pub denied: HashSet<IpAddr>,
+    /// Node ids whose signed values and messages are always dropped
+    pub denied_ids: HashSet<Pubkey>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PeerStats {
+    /// Total packets received
+    pub messages: u64,
+    /// Total bytes received
+    pub bytes: u64,
+    /// Packets dropped by the rate limit
+    pub dropped: u64,
+    /// Values that failed to deserialize or verify
+    pub invalid: u64,
+    /// Values that were already known
+    pub duplicates: u64,
+    /// Gossip protocol version of the last packet that didn't match ours
+    pub incompatible_version: Option<u32>,
+    window_start: u64,
+    window_messages: u64,
+    window_bytes: u64,
+    last_seen: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct PeerScores {
+    filter: PeerFilter,
+    peers: HashMap<IpAddr, PeerStats>,
+    /// Accounting shared by the addresses seen once `peers` is full
+    overflow: PeerStats,
+}
+
+impl PeerScores {
+    pub fn new(filter: PeerFilter) -> Self {
+        PeerScores {
+            filter,
+            ..PeerScores::default()
+        }
+    }
+
+    pub fn set_filter(&mut self, filter: PeerFilter) {
+        self.filter = filter;
+    }
+
+    pub fn is_allowed(&self, addr: &SocketAddr) -> bool {
+        self.filter.allowed.contains(&addr.ip())
+    }
+
+    pub fn is_denied_id(&self, id: &Pubkey) -> bool {
+        self.filter.denied_ids.contains(id)
+    }
+
+    pub fn stats(&self, ip: &IpAddr) -> Option<&PeerStats> {
+        self.peers.get(ip)
+    }
+
+    /// Accounting for `addr`, or the shared accounting if it isn't tracked and there's no
+    /// room left
+    fn stats_mut(&mut self, addr: &SocketAddr) -> &mut PeerStats {
+        let ip = addr.ip();
+        if self.peers.len() >= PEER_MAX_TRACKED_ADDRS && !self.peers.contains_key(&ip) {
+            return &mut self.overflow;
+        }
+        self.peers.entry(ip).or_insert_with(PeerStats::default)
+    }
+
+    /// Charge a packet of `bytes` to `addr`, returns false if it should be dropped
+    pub fn admit(&mut self, addr: &SocketAddr, bytes: usize, now: u64) -> bool {
+        if self.filter.denied.contains(&addr.ip()) {
+            return false;
+        }
+        let allowed = self.is_allowed(addr);
+        let stats = self.stats_mut(addr);
+        if now >= stats.window_start + PEER_WINDOW_MS {
+            stats.window_start = now;
+            stats.window_messages = 0;
+            stats.window_bytes = 0;
+        }
+        stats.messages += 1;
+        stats.bytes += bytes as u64;
+        stats.window_messages += 1;
+        stats.window_bytes += bytes as u64;
+        stats.last_seen = now;
+        if allowed {
+            return true;
+        }
+        if stats.window_messages > PEER_MAX_MESSAGES_PER_WINDOW
+            || stats.window_bytes > PEER_MAX_BYTES_PER_WINDOW
+        {
+            stats.dropped += 1;
+            return false;
+        }
+        true
+    }
+
+    /// Record a packet from `addr` using gossip protocol `version`, which isn't ours.
+    /// Returns true the first time `addr` is seen using `version`.
+    pub fn record_incompatible_version(&mut self, addr: &SocketAddr, version: u32) -> bool {
+        let stats = self.stats_mut(addr);
+        let first = stats.incompatible_version != Some(version);
+        stats.incompatible_version = Some(version);
+        first
+    }
+
+    /// Peers whose last incompatible packet is still remembered, and the version it used
+    pub fn incompatible_peers(&self) -> Vec<(IpAddr, u32)> {
+        self.peers
+            .iter()
+            .filter_map(|(ip, stats)| stats.incompatible_version.map(|version| (*ip, version)))
+            .collect()
+    }
+
+    /// Record `count` values from `addr` that failed to deserialize or verify
+    pub fn record_invalid(&mut self, addr: &SocketAddr, count: usize) {
+        self.stats_mut(addr).invalid += count as u64;
+    }
+
+    /// Record `count` values from `addr` that were already known
+    pub fn record_duplicate(&mut self, addr: &SocketAddr, count: usize) {
+        self.stats_mut(addr).duplicates += count as u64;
+    }
+
+    /// Drop the accounting of peers that have gone quiet
+    pub fn purge(&mut self, now: u64) {
+        self.peers
+            .retain(|_, stats| stats.last_seen + PEER_STATS_TIMEOUT_MS > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::{Keypair, KeypairUtil};
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_rate_limit() {
+        let addr = socketaddr!("127.0.0.1:1234");
+        let mut scores = PeerScores::default();
+        for _ in 0..PEER_MAX_MESSAGES_PER_WINDOW {
+            assert!(scores.admit(&addr, 1, 0));
+        }
+        assert!(!scores.admit(&addr, 1, 0));
+        assert_eq!(scores.stats(&addr.ip()).unwrap().dropped, 1);
+
+        // changing ports doesn't escape the limit
+        assert!(!scores.admit(&socketaddr!("127.0.0.1:1235"), 1, 0));
+
+        // a new window resets the budget
+        assert!(scores.admit(&addr, 1, PEER_WINDOW_MS));
+        assert!(!scores.admit(&addr, PEER_MAX_BYTES_PER_WINDOW as usize, PEER_WINDOW_MS));
+
+        // other peers have their own budget
+        assert!(scores.admit(&socketaddr!("127.0.0.2:1234"), 1, PEER_WINDOW_MS));
+    }
+
+    #[test]
+    fn test_duplicates_never_ban() {
+        let addr = socketaddr!("127.0.0.1:1234");
+        let mut scores = PeerScores::default();
+        assert!(scores.admit(&addr, 1, 0));
+        scores.record_duplicate(&addr, 1_000_000);
+        assert_eq!(scores.stats(&addr.ip()).unwrap().duplicates, 1_000_000);
+        assert!(scores.admit(&addr, 1, 0));
+
+        // nor does hitting the rate limit, past the current window
+        for _ in 0..2 * PEER_MAX_MESSAGES_PER_WINDOW {
+            scores.admit(&addr, 1, 0);
+        }
+        assert!(!scores.admit(&addr, 1, 0));
+        assert!(scores.admit(&addr, 1, PEER_WINDOW_MS));
+    }
+
+    #[test]
+    fn test_filter() {
+        let allowed = socketaddr!("127.0.0.2:1234");
+        let denied = socketaddr!("127.0.0.3:1234");
+        let denied_id = Keypair::new().pubkey();
+        let mut filter = PeerFilter::default();
+        filter.allowed.insert(allowed.ip());
+        filter.denied.insert(denied.ip());
+        filter.denied_ids.insert(denied_id);
+        let mut scores = PeerScores::new(filter);
+
+        assert!(!scores.admit(&denied, 1, 0));
+        assert!(!scores.admit(&socketaddr!("127.0.0.3:1235"), 1, 0));
+        assert!(scores.is_denied_id(&denied_id));
+        assert!(!scores.is_denied_id(&Keypair::new().pubkey()));
+
+        // allowed peers are never limited
+        for _ in 0..=PEER_MAX_MESSAGES_PER_WINDOW {
+            assert!(scores.admit(&allowed, 1, 0));
+        }
+    }
+
+    #[test]
+    fn test_invalid_counted() {
+        let addr = socketaddr!("127.0.0.1:1234");
+        let mut scores = PeerScores::default();
+        assert!(scores.admit(&addr, 1, 0));
+        scores.record_invalid(&addr, 1000);
+        assert_eq!(scores.stats(&addr.ip()).unwrap().invalid, 1000);
+        assert!(scores.admit(&addr, 1, 0));
+    }
+
+    #[test]
+    fn test_incompatible_version() {
+        let addr = socketaddr!("127.0.0.1:1234");
+        let mut scores = PeerScores::default();
+        assert!(scores.incompatible_peers().is_empty());
+        assert!(scores.record_incompatible_version(&addr, 2));
+        assert!(!scores.record_incompatible_version(&addr, 2));
+        assert!(scores.record_incompatible_version(&addr, 3));
+        assert_eq!(scores.incompatible_peers(), vec![(addr.ip(), 3)]);
+    }
+
+    #[test]
+    fn test_max_tracked_addrs() {
+        let mut scores = PeerScores::default();
+        for i in 0..PEER_MAX_TRACKED_ADDRS as u32 {
+            let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::from(0x0a00_0000 + i)), 1234);
+            assert!(scores.admit(&addr, 1, 0));
+        }
+        assert_eq!(scores.peers.len(), PEER_MAX_TRACKED_ADDRS);
+
+        // new addresses share one budget instead of growing the accounting
+        let new_addr =
+            |i: u64| SocketAddr::new(IpAddr::V4(Ipv4Addr::from(0x0b00_0000 + i as u32)), 1234);
+        for i in 0..PEER_MAX_MESSAGES_PER_WINDOW {
+            assert!(scores.admit(&new_addr(i), 1, 0));
+        }
+        assert!(!scores.admit(&new_addr(PEER_MAX_MESSAGES_PER_WINDOW), 1, 0));
+        assert_eq!(scores.peers.len(), PEER_MAX_TRACKED_ADDRS);
+        assert!(scores.stats(&new_addr(0).ip()).is_none());
+
+        // while tracked addresses keep theirs
+        assert!(scores.admit(&socketaddr!("10.0.0.0:1234"), 1, 0));
+    }
+
+    #[test]
+    fn test_purge() {
+        let addr = socketaddr!("127.0.0.1:1234");
+        let mut scores = PeerScores::default();
+        assert!(scores.admit(&addr, 1, 0));
+        scores.purge(PEER_STATS_TIMEOUT_MS - 1);
+        assert!(scores.stats(&addr.ip()).is_some());
+        scores.purge(PEER_STATS_TIMEOUT_MS);
+        assert!(scores.stats(&addr.ip()).is_none());
+    }
+}
@@ -52,6 +52,12 @@ impl LastIdQueue {
     pub fn check_entry(&self, entry_id: Hash) -> bool {
         self.entries.get(&entry_id).is_some()
     }
+    /// Number of ticks registered since entry_id, or None if it isn't tracked
+    pub fn entry_id_age(&self, entry_id: &Hash) -> Option<u64> {
+        self.entries
+            .get(entry_id)
+            .map(|entry| self.tick_height - entry.tick_height)
+    }
 
     pub fn genesis_last_id(&mut self, last_id: &Hash) {
         self.entries.insert(
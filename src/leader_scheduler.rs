@@ -14,6 +14,7 @@ use solana_sdk::signature::{Keypair, KeypairUtil};
 use solana_sdk::system_transaction::SystemTransaction;
 use solana_sdk::vote_program::{self, VoteState};
 use solana_sdk::vote_transaction::VoteTransaction;
+use std::collections::BTreeMap;
 use std::io::Cursor;
 use std::sync::Arc;
 
@@ -71,6 +72,18 @@ impl Default for LeaderSchedulerConfig {
     }
 }
 
+// The stakes of the active validators at the start of the epoch before `epoch`, ordered by
+// ascending stake with ties broken by id, along with the leader of the last slot of the
+// epoch before `epoch`.  The leader schedule of `epoch` is computed from these alone, so a
+// node that restores them from a snapshot schedules the same leaders as one that replayed
+// every earlier epoch.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct EpochStakes {
+    pub epoch: u64,
+    pub stakes: Vec<(Pubkey, u64)>,
+    pub previous_leader: Pubkey,
+}
+
 #[derive(Clone, Debug)]
 pub struct LeaderScheduler {
     // Set to true if we want the default implementation of the LeaderScheduler,
@@ -91,18 +104,18 @@ pub struct LeaderScheduler {
     // the leader rotation process begins to pick future leaders
     pub bootstrap_height: u64,
 
-    // The last height at which the seed + schedule was generated
+    // The last height at which epoch stakes were frozen and a schedule generated
     pub last_seed_height: Option<u64>,
 
     // The length of time in ticks for which a vote qualifies a candidate for leader
     // selection
     pub active_window_length: u64,
 
-    // Round-robin ordering for the validators
-    leader_schedule: Vec<Pubkey>,
+    // The frozen stakes of the previous, current and next epochs
+    epoch_stakes: BTreeMap<u64, EpochStakes>,
 
-    // The seed used to determine the round robin order of leaders
-    seed: u64,
+    // Round-robin ordering of the validators for each epoch in epoch_stakes
+    leader_schedules: BTreeMap<u64, Vec<Pubkey>>,
 }
 
 // The LeaderScheduler implements a schedule for leaders as follows:
@@ -110,18 +123,19 @@ pub struct LeaderScheduler {
 // 1) During the bootstrapping period of bootstrap_height PoH counts, the
 // leader is hard-coded to the bootstrap_leader that is read from the genesis block.
 //
-// 2) After the first seed is generated, this signals the beginning of actual leader rotation.
-// From this point on, every seed_rotation_interval PoH counts we generate the seed based
-// on the PoH height, and use it to do a weighted sample from the set
-// of validators based on current stake weight. This gets you the bootstrap leader A for
-// the next leader_rotation_interval PoH counts. On the same PoH count we generate the seed,
-// we also order the validators based on their current stake weight, and starting
-// from leader A, we then pick the next leader sequentially every leader_rotation_interval
-// PoH counts based on this fixed ordering, so the next
-// seed_rotation_interval / leader_rotation_interval leaders are determined.
+// 2) At the end of the bootstrapping period the stakes of the active validators are frozen,
+// and the schedules of both epoch 1 and epoch 2 are generated from them. To generate the
+// schedule of an epoch, the seed derived from the PoH height the epoch's predecessor ends on
+// is used to do a weighted sample from the frozen stakes. This gets you the leader A for the
+// first leader_rotation_interval PoH counts of the epoch. The validators are ordered by
+// their frozen stake, and starting from leader A, we then pick the next leader sequentially
+// every leader_rotation_interval PoH counts based on this fixed ordering, so all
+// seed_rotation_interval / leader_rotation_interval leaders of the epoch are determined.
 //
-// 3) When we we hit the next seed rotation PoH height, step 2) is executed again to
-// calculate the leader schedule for the upcoming seed_rotation_interval PoH counts.
+// 3) At the start of every later epoch N, the stakes are frozen again and step 2) is
+// executed to calculate the leader schedule of epoch N + 1. Every node thus knows the
+// schedule of the next epoch a full epoch ahead of time, and because a schedule depends on
+// nothing but its EpochStakes, nodes that start from a snapshot agree with the rest.
 impl LeaderScheduler {
     pub fn from_bootstrap_leader(bootstrap_leader: Pubkey) -> Self {
         let config = LeaderSchedulerConfig::default();
@@ -146,12 +160,12 @@ impl LeaderScheduler {
             use_only_bootstrap_leader: false,
             leader_rotation_interval,
             seed_rotation_interval,
-            leader_schedule: Vec::new(),
             last_seed_height: None,
             bootstrap_leader: Pubkey::default(),
             bootstrap_height,
             active_window_length,
-            seed: 0,
+            epoch_stakes: BTreeMap::new(),
+            leader_schedules: BTreeMap::new(),
         }
     }
 
@@ -202,17 +216,17 @@ impl LeaderScheduler {
             return None;
         }
 
+        let epoch = self.tick_height_to_epoch(height);
         let result = {
-            if height <= self.bootstrap_height || self.leader_schedule.len() > 1 {
+            if epoch == 0 || self.leader_schedules[&epoch].len() > 1 {
                 // Two cases to consider:
                 //
-                // 1) If height is less than the bootstrap height, then the current leader's
+                // 1) If height is in the bootstrap period, then the current leader's
                 // slot ends when PoH height = bootstrap_height
                 //
-                // 2) Otherwise, if height >= bootstrap height, then we have generated a schedule.
-                // If this leader is not the only one in the schedule, then they will
-                // only be leader until the end of this slot (someone else is then guaranteed
-                // to take over)
+                // 2) Otherwise, if this leader is not the only one in the epoch's schedule,
+                // then they will only be leader until the end of this slot (someone else
+                // is then guaranteed to take over)
                 //
                 // Both above cases are calculated by the function:
                 // num_ticks_left_in_slot() + height
@@ -221,14 +235,9 @@ impl LeaderScheduler {
                      of LeaderScheduler",
                 ) + height
             } else {
-                // If the height is greater than bootstrap_height and this leader is
-                // the only leader in the schedule, then that leader will be in power
-                // for every slot until the next epoch, which is seed_rotation_interval
-                // PoH counts from the beginning of the last epoch.
-                self.last_seed_height.expect(
-                    "If height >= bootstrap height, then we expect
-                    a seed has been generated",
-                ) + self.seed_rotation_interval
+                // If this leader is the only leader in the schedule, then that leader will
+                // be in power for every slot until the end of the epoch
+                self.epoch_seed_height(epoch) + self.seed_rotation_interval
             }
         };
 
@@ -237,6 +246,8 @@ impl LeaderScheduler {
 
     pub fn reset(&mut self) {
         self.last_seed_height = None;
+        self.epoch_stakes.clear();
+        self.leader_schedules.clear();
     }
 
     pub fn update_height(&mut self, height: u64, bank: &Bank) {
@@ -259,54 +270,134 @@ impl LeaderScheduler {
         }
     }
 
-    // Uses the schedule generated by the last call to generate_schedule() to return the
-    // leader for a given PoH height in round-robin fashion
+    // Returns the leader for a given PoH height from the schedule of the epoch the height
+    // falls in, or None if that epoch's stakes haven't been frozen yet
     pub fn get_scheduled_leader(&self, height: u64) -> Option<(Pubkey, u64)> {
         if self.use_only_bootstrap_leader {
             return Some((self.bootstrap_leader, 0));
         }
 
-        // This covers cases where the schedule isn't yet generated.
-        if self.last_seed_height == None {
-            if height <= self.bootstrap_height {
-                return Some((self.bootstrap_leader, 0));
-            } else {
-                // If there's been no schedule generated yet before we reach the end of the
-                // bootstrapping period, then the leader is unknown
-                return None;
-            }
+        let epoch = self.tick_height_to_epoch(height);
+        if epoch == 0 {
+            return Some((self.bootstrap_leader, 0));
         }
 
-        // If we have a schedule, then just check that we are within the bounds of that
-        // schedule (last_seed_height, last_seed_height + seed_rotation_interval].
-        // Leaders outside of this bound are undefined.
-        let last_seed_height = self.last_seed_height.unwrap();
+        let leader_schedule = self.leader_schedules.get(&epoch)?;
+        let leader_slot = self.tick_height_to_slot(height);
+        let index = leader_slot - self.first_slot_in_epoch(epoch);
+        let validator_index = index as usize % leader_schedule.len();
+        Some((leader_schedule[validator_index], leader_slot))
+    }
 
-        if height > last_seed_height + self.seed_rotation_interval || height <= last_seed_height {
+    pub fn get_leader_for_slot(&self, slot_height: u64) -> Option<Pubkey> {
+        let tick_height = self.slot_height_to_first_tick_height(slot_height);
+        self.get_scheduled_leader(tick_height).map(|(id, _)| id)
+    }
+
+    // Returns the slot the given tick height falls in.  The bootstrap period is slot 0
+    pub fn tick_height_to_slot(&self, height: u64) -> u64 {
+        if self.use_only_bootstrap_leader || height <= self.bootstrap_height {
+            0
+        } else {
+            (height - self.bootstrap_height - 1) / self.leader_rotation_interval + 1
+        }
+    }
+
+    // Returns the epoch, the span of one seed_rotation_interval, that the given tick
+    // height falls in.  The bootstrap period is epoch 0
+    pub fn tick_height_to_epoch(&self, height: u64) -> u64 {
+        if self.use_only_bootstrap_leader || height <= self.bootstrap_height {
+            0
+        } else {
+            (height - self.bootstrap_height - 1) / self.seed_rotation_interval + 1
+        }
+    }
+
+    // Returns the epoch the given slot falls in
+    pub fn slot_to_epoch(&self, slot: u64) -> u64 {
+        if self.use_only_bootstrap_leader || slot == 0 {
+            0
+        } else {
+            (slot - 1) / self.num_slots_in_epoch(1) + 1
+        }
+    }
+
+    // Returns the number of slots in the given epoch.  The bootstrap period is a single slot
+    pub fn num_slots_in_epoch(&self, epoch: u64) -> u64 {
+        if epoch == 0 {
+            1
+        } else {
+            self.seed_rotation_interval / self.leader_rotation_interval
+        }
+    }
+
+    // Returns the first slot of the given epoch
+    pub fn first_slot_in_epoch(&self, epoch: u64) -> u64 {
+        if epoch == 0 {
+            0
+        } else {
+            (epoch - 1) * self.num_slots_in_epoch(epoch) + 1
+        }
+    }
+
+    // Returns the leader of every slot in the given epoch, starting from
+    // first_slot_in_epoch(), or None if the schedule for that epoch isn't known
+    pub fn get_leader_schedule(&self, epoch: u64) -> Option<Vec<Pubkey>> {
+        if epoch == 0 {
+            return Some(vec![self.bootstrap_leader]);
+        }
+        if self.use_only_bootstrap_leader {
             return None;
         }
 
-        // Find index into the leader_schedule that this PoH height maps to. Note by the time
-        // we reach here, last_seed_height is not None, which implies:
-        //
-        // last_seed_height >= self.bootstrap_height
-        //
-        // Also we know from the recent range check that height > last_seed_height, so
-        // height - self.bootstrap_height >= 1, so the below logic is safe.
-        let leader_slot = (height - self.bootstrap_height - 1) / self.leader_rotation_interval + 1;
-        let index = (height - last_seed_height - 1) / self.leader_rotation_interval;
-        let validator_index = index as usize % self.leader_schedule.len();
-        Some((self.leader_schedule[validator_index], leader_slot))
+        let first_slot = self.first_slot_in_epoch(epoch);
+        (first_slot..first_slot + self.num_slots_in_epoch(epoch))
+            .map(|slot| self.get_leader_for_slot(slot))
+            .collect()
     }
 
-    pub fn get_leader_for_slot(&self, slot_height: u64) -> Option<Pubkey> {
-        let tick_height = self.slot_height_to_first_tick_height(slot_height);
-        self.get_scheduled_leader(tick_height).map(|(id, _)| id)
+    // Returns the frozen stakes the given epoch is scheduled from
+    pub fn get_epoch_stakes(&self, epoch: u64) -> Option<&EpochStakes> {
+        self.epoch_stakes.get(&epoch)
+    }
+
+    // Installs the frozen stakes of an epoch, such as ones restored from a snapshot, and
+    // generates that epoch's schedule from them
+    pub fn set_epoch_stakes(&mut self, epoch_stakes: EpochStakes) {
+        let epoch = epoch_stakes.epoch;
+        assert!(epoch > 0);
+
+        // The stakes of epoch 1 and 2 are both frozen at the end of the bootstrap period,
+        // the stakes of every later epoch at the start of the epoch before it
+        let seed_height = self.epoch_seed_height(epoch.max(2) - 1);
+        if self
+            .last_seed_height
+            .map_or(true, |height| height < seed_height)
+        {
+            self.last_seed_height = Some(seed_height);
+        }
+
+        let leader_schedule = self.leader_schedule_from_stakes(&epoch_stakes);
+        self.leader_schedules.insert(epoch, leader_schedule);
+        self.epoch_stakes.insert(epoch, epoch_stakes);
+
+        // Only the previous, current and next epochs are ever looked up
+        let newest_epoch = *self.epoch_stakes.keys().next_back().unwrap();
+        let oldest_epoch = newest_epoch.saturating_sub(2);
+        self.epoch_stakes = self.epoch_stakes.split_off(&oldest_epoch);
+        self.leader_schedules = self.leader_schedules.split_off(&oldest_epoch);
     }
 
     #[cfg(test)]
-    pub fn set_leader_schedule(&mut self, schedule: Vec<Pubkey>) {
-        self.leader_schedule = schedule;
+    pub fn set_leader_schedule(&mut self, epoch: u64, schedule: Vec<Pubkey>) {
+        self.leader_schedules.insert(epoch, schedule);
+    }
+
+    // Returns the PoH height the seed of the given epoch is derived from, the last tick
+    // height of the epoch before it
+    fn epoch_seed_height(&self, epoch: u64) -> u64 {
+        assert!(epoch > 0);
+        self.bootstrap_height + (epoch - 1) * self.seed_rotation_interval
     }
 
     // Maps the nth slot (where n == slot_height) to the tick height of
@@ -353,70 +444,83 @@ impl LeaderScheduler {
         }
     }
 
-    // Called every seed_rotation_interval entries, generates the leader schedule
-    // for the range of entries: (height, height + seed_rotation_interval]
+    // Called every seed_rotation_interval entries, freezes the stakes of the active set and
+    // generates the leader schedule of the epoch after the one starting at height + 1
     fn generate_schedule(&mut self, height: u64, bank: &Bank) {
         assert!(height >= self.bootstrap_height);
         assert!((height - self.bootstrap_height) % self.seed_rotation_interval == 0);
-        let seed = Self::calculate_seed(height);
-        self.seed = seed;
         let active_set = self.get_active_set(height, &bank);
-        let ranked_active_set = Self::rank_active_set(bank, active_set.iter());
+        let stakes: Vec<_> = Self::rank_active_set(bank, active_set.iter())
+            .into_iter()
+            .map(|(id, stake)| (*id, stake))
+            .collect();
+
+        // The first epoch after the bootstrap period has no epoch before it to be scheduled
+        // in, so it's scheduled from the same stakes as the epoch after it
+        let next_epoch = self.tick_height_to_epoch(height + 1) + 1;
+        if next_epoch == 2 {
+            self.set_epoch_stakes(EpochStakes {
+                epoch: 1,
+                stakes: stakes.clone(),
+                previous_leader: self.bootstrap_leader,
+            });
+        }
 
+        // The schedule of the epoch before next_epoch was generated a full epoch ago, so
+        // the leader of its last slot is already known
+        let previous_leader = self
+            .get_leader_for_slot(self.first_slot_in_epoch(next_epoch) - 1)
+            .expect("Previous leader schedule should still exist");
+        self.set_epoch_stakes(EpochStakes {
+            epoch: next_epoch,
+            stakes,
+            previous_leader,
+        });
+    }
+
+    // Generates the leader schedule of an epoch from its frozen stakes
+    fn leader_schedule_from_stakes(&self, epoch_stakes: &EpochStakes) -> Vec<Pubkey> {
         // Handle case where there are no active validators with
         // non-zero stake. In this case, use the bootstrap leader for
-        // the upcoming rounds
-        if ranked_active_set.is_empty() {
-            self.last_seed_height = Some(height);
-            self.leader_schedule = vec![self.bootstrap_leader];
-            self.last_seed_height = Some(height);
-            return;
+        // the whole epoch
+        if epoch_stakes.stakes.is_empty() {
+            return vec![self.bootstrap_leader];
         }
 
-        let (mut validator_rankings, total_stake) = ranked_active_set.iter().fold(
-            (Vec::with_capacity(ranked_active_set.len()), 0),
-            |(mut ids, total_stake), (pk, stake)| {
-                ids.push(**pk);
-                (ids, total_stake + stake)
-            },
-        );
+        let total_stake: u64 = epoch_stakes.stakes.iter().map(|(_, stake)| stake).sum();
+        let seed = Self::calculate_seed(self.epoch_seed_height(epoch_stakes.epoch));
 
-        // Choose the validator that will be the first to be the leader in this new
+        // Choose the validator that will be the first to be the leader in this
         // schedule
-        let ordered_account_stake = ranked_active_set.into_iter().map(|(_, stake)| stake);
-        let start_index = Self::choose_account(ordered_account_stake, self.seed, total_stake);
+        let ordered_account_stake = epoch_stakes.stakes.iter().map(|(_, stake)| *stake);
+        let start_index = Self::choose_account(ordered_account_stake, seed, total_stake);
+
+        let mut validator_rankings: Vec<_> =
+            epoch_stakes.stakes.iter().map(|(id, _)| *id).collect();
         validator_rankings.rotate_left(start_index);
 
         // There are only seed_rotation_interval / self.leader_rotation_interval slots, so
         // we only need to keep at most that many validators in the schedule
-        let slots_per_epoch = self.seed_rotation_interval / self.leader_rotation_interval;
+        let slots_per_epoch = self.num_slots_in_epoch(epoch_stakes.epoch);
 
         // If possible, try to avoid having the same leader twice in a row, but
         // if there's only one leader to choose from, then we have no other choice
-        if validator_rankings.len() > 1 {
-            let (old_epoch_last_leader, _) = self
-                .get_scheduled_leader(height - 1)
-                .expect("Previous leader schedule should still exist");
-            let new_epoch_start_leader = validator_rankings[0];
-
-            if old_epoch_last_leader == new_epoch_start_leader {
-                if slots_per_epoch == 1 {
-                    // If there is only one slot per epoch, and the same leader as the last slot
-                    // of the previous epoch was chosen, then pick the next leader in the
-                    // rankings instead
-                    validator_rankings[0] = validator_rankings[1];
-                } else {
-                    // If there is more than one leader in the schedule, truncate and set the most
-                    // recent leader to the back of the line. This way that node will still remain
-                    // in the rotation, just at a later slot.
-                    validator_rankings.truncate(slots_per_epoch as usize);
-                    validator_rankings.rotate_left(1);
-                }
+        if validator_rankings.len() > 1 && validator_rankings[0] == epoch_stakes.previous_leader {
+            if slots_per_epoch == 1 {
+                // If there is only one slot per epoch, and the same leader as the last slot
+                // of the previous epoch was chosen, then pick the next leader in the
+                // rankings instead
+                validator_rankings[0] = validator_rankings[1];
+            } else {
+                // If there is more than one leader in the schedule, truncate and set the most
+                // recent leader to the back of the line. This way that node will still remain
+                // in the rotation, just at a later slot.
+                validator_rankings.truncate(slots_per_epoch as usize);
+                validator_rankings.rotate_left(1);
             }
         }
 
-        self.leader_schedule = validator_rankings;
-        self.last_seed_height = Some(height);
+        validator_rankings
     }
 
     fn rank_active_set<'a, I>(bank: &Bank, active: I) -> Vec<(&'a Pubkey, u64)>
@@ -650,16 +754,24 @@ pub mod tests {
         // same leader for the next leader_rotation_interval entries
         leader_scheduler.generate_schedule(bootstrap_height, &bank);
 
-        // The leader outside of the newly generated schedule window:
-        // (bootstrap_height, bootstrap_height + seed_rotation_interval]
-        // should be undefined
+        // The bootstrap period keeps its leader, and the stakes frozen at the end of it
+        // schedule the first two epochs
         assert_eq!(
             leader_scheduler.get_scheduled_leader(bootstrap_height),
-            None,
+            Some((bootstrap_leader_id, 0))
+        );
+        assert_eq!(
+            leader_scheduler.get_epoch_stakes(1).unwrap().stakes,
+            leader_scheduler.get_epoch_stakes(2).unwrap().stakes
         );
+        assert!(leader_scheduler
+            .get_scheduled_leader(bootstrap_height + seed_rotation_interval + 1)
+            .is_some());
 
+        // The leader past the epochs scheduled so far should be undefined
         assert_eq!(
-            leader_scheduler.get_scheduled_leader(bootstrap_height + seed_rotation_interval + 1),
+            leader_scheduler
+                .get_scheduled_leader(bootstrap_height + 2 * seed_rotation_interval + 1),
             None,
         );
 
@@ -1055,18 +1167,18 @@ pub mod tests {
         }
 
         // Generate schedule every active_window_length entries and check that
-        // validators are falling out of the rotation as they fall out of the
-        // active set
+        // validators are falling out of the rotation of the epoch after next as they
+        // fall out of the active set
         for i in 0..=num_validators {
             leader_scheduler.generate_schedule(i * active_window_length + bootstrap_height, &bank);
-            let result = &leader_scheduler.leader_schedule;
+            let result = leader_scheduler.get_leader_schedule(i + 2);
             let expected = if i == num_validators {
                 bootstrap_leader_id
             } else {
                 validators[i as usize]
             };
 
-            assert_eq!(vec![expected], *result);
+            assert_eq!(Some(vec![expected; num_validators as usize]), result);
         }
     }
 
@@ -1120,6 +1232,60 @@ pub mod tests {
         assert!(result.is_empty());
     }
 
+    #[test]
+    fn test_leader_schedule() {
+        let bootstrap_leader_id = Keypair::new().pubkey();
+        let leader_scheduler_config = LeaderSchedulerConfig::new(10, 2, 6, 6);
+        let mut leader_scheduler = LeaderScheduler::new(&leader_scheduler_config);
+        leader_scheduler.bootstrap_leader = bootstrap_leader_id;
+
+        // The bootstrap period is slot 0 of epoch 0
+        assert_eq!(leader_scheduler.tick_height_to_slot(0), 0);
+        assert_eq!(leader_scheduler.tick_height_to_slot(10), 0);
+        assert_eq!(leader_scheduler.tick_height_to_epoch(10), 0);
+        assert_eq!(leader_scheduler.num_slots_in_epoch(0), 1);
+        assert_eq!(leader_scheduler.first_slot_in_epoch(0), 0);
+        assert_eq!(
+            leader_scheduler.get_leader_schedule(0),
+            Some(vec![bootstrap_leader_id])
+        );
+
+        // Ticks 11 and 12 are slot 1, ticks 11 through 16 are epoch 1
+        assert_eq!(leader_scheduler.tick_height_to_slot(11), 1);
+        assert_eq!(leader_scheduler.tick_height_to_slot(12), 1);
+        assert_eq!(leader_scheduler.tick_height_to_slot(13), 2);
+        assert_eq!(leader_scheduler.tick_height_to_epoch(16), 1);
+        assert_eq!(leader_scheduler.tick_height_to_epoch(17), 2);
+        assert_eq!(leader_scheduler.num_slots_in_epoch(1), 3);
+        assert_eq!(leader_scheduler.first_slot_in_epoch(1), 1);
+        assert_eq!(leader_scheduler.first_slot_in_epoch(2), 4);
+        assert_eq!(leader_scheduler.slot_to_epoch(0), 0);
+        assert_eq!(leader_scheduler.slot_to_epoch(3), 1);
+        assert_eq!(leader_scheduler.slot_to_epoch(4), 2);
+
+        // No schedule has been generated past the bootstrap period yet
+        assert_eq!(leader_scheduler.get_leader_schedule(1), None);
+
+        let validator0 = Keypair::new().pubkey();
+        let validator1 = Keypair::new().pubkey();
+        leader_scheduler.set_leader_schedule(1, vec![validator0, validator1]);
+        assert_eq!(
+            leader_scheduler.get_leader_schedule(1),
+            Some(vec![validator0, validator1, validator0])
+        );
+        assert_eq!(leader_scheduler.get_leader_schedule(2), None);
+
+        // Only the bootstrap leader is ever scheduled without leader rotation
+        let leader_scheduler = LeaderScheduler::from_bootstrap_leader(bootstrap_leader_id);
+        assert_eq!(leader_scheduler.tick_height_to_slot(5000), 0);
+        assert_eq!(leader_scheduler.tick_height_to_epoch(5000), 0);
+        assert_eq!(
+            leader_scheduler.get_leader_schedule(0),
+            Some(vec![bootstrap_leader_id])
+        );
+        assert_eq!(leader_scheduler.get_leader_schedule(1), None);
+    }
+
     #[test]
     fn test_update_height() {
         let bootstrap_leader_id = Keypair::new().pubkey();
@@ -1207,6 +1373,84 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_epoch_stakes() {
+        let bootstrap_leader_id = Keypair::new().pubkey();
+        let bootstrap_height = 500;
+        let leader_rotation_interval = 100;
+        let seed_rotation_interval = 10 * leader_rotation_interval;
+        let active_window_length = bootstrap_height + 3 * seed_rotation_interval;
+
+        let leader_scheduler_config = LeaderSchedulerConfig::new(
+            bootstrap_height,
+            leader_rotation_interval,
+            seed_rotation_interval,
+            active_window_length,
+        );
+
+        let mut leader_scheduler = LeaderScheduler::new(&leader_scheduler_config);
+        leader_scheduler.bootstrap_leader = bootstrap_leader_id;
+
+        // Create the bank and a staked, voting validator
+        let (genesis_block, mint_keypair) = GenesisBlock::new(10000);
+        let bank = Bank::new(&genesis_block);
+        let last_id = genesis_block.last_id();
+        let add_validator = |stake| {
+            let validator_keypair = Arc::new(Keypair::new());
+            let validator_id = validator_keypair.pubkey();
+            bank.transfer(stake + 1, &mint_keypair, validator_id, last_id)
+                .unwrap();
+            let voting_keypair = VotingKeypair::new_local(&validator_keypair);
+            new_vote_account(&validator_keypair, &voting_keypair, &bank, 1, last_id);
+            push_vote(&voting_keypair, &bank, 1, last_id);
+            validator_id
+        };
+        let validator0 = add_validator(1);
+
+        leader_scheduler.update_height(bootstrap_height, &bank);
+        assert_eq!(leader_scheduler.last_seed_height, Some(bootstrap_height));
+        let epoch1_stakes = leader_scheduler.get_epoch_stakes(1).unwrap().clone();
+        assert_eq!(epoch1_stakes.stakes, vec![(validator0, 1)]);
+        assert_eq!(
+            leader_scheduler.get_leader_schedule(2),
+            Some(vec![validator0; 10])
+        );
+
+        // Stake that shows up during epoch 1 only affects the schedule of epoch 3, which
+        // is fixed at the start of epoch 2
+        let validator1 = add_validator(5);
+        let epoch2_schedule = leader_scheduler.get_leader_schedule(2);
+        leader_scheduler.update_height(bootstrap_height + seed_rotation_interval, &bank);
+        assert_eq!(leader_scheduler.get_leader_schedule(2), epoch2_schedule);
+        let epoch3_stakes = leader_scheduler.get_epoch_stakes(3).unwrap().clone();
+        assert_eq!(epoch3_stakes.stakes, vec![(validator0, 1), (validator1, 5)]);
+        let epoch3_schedule = leader_scheduler.get_leader_schedule(3).unwrap();
+        assert_eq!(epoch3_schedule.len(), 10);
+        assert!(epoch3_schedule.contains(&validator0));
+        assert!(epoch3_schedule.contains(&validator1));
+
+        // A scheduler that only knows the frozen stakes, like one restored from a
+        // snapshot, computes the same schedules without replaying earlier epochs
+        let mut restored_scheduler = LeaderScheduler::new(&leader_scheduler_config);
+        restored_scheduler.bootstrap_leader = bootstrap_leader_id;
+        restored_scheduler.set_epoch_stakes(epoch3_stakes);
+        assert_eq!(
+            restored_scheduler.last_seed_height,
+            Some(bootstrap_height + seed_rotation_interval)
+        );
+        assert_eq!(
+            restored_scheduler.get_leader_schedule(3),
+            Some(epoch3_schedule)
+        );
+        assert_eq!(restored_scheduler.get_leader_schedule(2), None);
+
+        // Only the previous, current and next epochs are kept
+        leader_scheduler.update_height(bootstrap_height + 2 * seed_rotation_interval, &bank);
+        assert!(leader_scheduler.get_epoch_stakes(1).is_none());
+        assert!(leader_scheduler.get_epoch_stakes(2).is_some());
+        assert!(leader_scheduler.get_epoch_stakes(4).is_some());
+    }
+
     fn run_consecutive_leader_test(num_slots_per_epoch: u64, add_validator: bool) {
         let bootstrap_leader_keypair = Arc::new(Keypair::new());
         let bootstrap_leader_id = bootstrap_leader_keypair.pubkey();
@@ -1295,11 +1539,30 @@ pub mod tests {
 
         // Make sure the validator, not the leader is selected on the first slot of the
         // next epoch
+        let epoch1_schedule = leader_scheduler.get_leader_schedule(1).unwrap();
         if add_validator {
-            assert!(leader_scheduler.leader_schedule[0] == validator_id);
+            assert_eq!(epoch1_schedule[0], validator_id);
         } else {
-            assert!(leader_scheduler.leader_schedule[0] == bootstrap_leader_id);
+            assert_eq!(epoch1_schedule[0], bootstrap_leader_id);
         }
+
+        // The epoch after it doesn't start with the leader epoch 1 ends with either,
+        // and a scheduler restored from its frozen stakes agrees
+        let epoch2_schedule = leader_scheduler.get_leader_schedule(2).unwrap();
+        let epoch1_last_leader = *epoch1_schedule.last().unwrap();
+        let epoch2_stakes = leader_scheduler.get_epoch_stakes(2).unwrap().clone();
+        assert_eq!(epoch2_stakes.previous_leader, epoch1_last_leader);
+        if add_validator {
+            assert_ne!(epoch2_schedule[0], epoch1_last_leader);
+        }
+
+        let mut restored_scheduler = LeaderScheduler::new(&leader_scheduler_config);
+        restored_scheduler.bootstrap_leader = bootstrap_leader_id;
+        restored_scheduler.set_epoch_stakes(epoch2_stakes);
+        assert_eq!(
+            restored_scheduler.get_leader_schedule(2),
+            Some(epoch2_schedule)
+        );
     }
 
     #[test]
@@ -1363,32 +1626,42 @@ pub mod tests {
         // Test when the active set == 1 node
 
         // Generate schedule where the bootstrap leader will be the only
-        // choice because the active set is empty. Thus if the schedule
-        // was generated on PoH height bootstrap_height + n * seed_rotation_interval,
-        // then the same leader will be in power until PoH height
+        // choice because the active set is empty. Thus in the epoch starting after
+        // PoH height bootstrap_height + n * seed_rotation_interval, the same leader
+        // will be in power until PoH height
         // bootstrap_height + (n + 1) * seed_rotation_interval
         leader_scheduler.generate_schedule(bootstrap_height, &bank);
         assert_eq!(
             leader_scheduler.max_height_for_leader(bootstrap_height),
-            None
+            Some(bootstrap_height)
         );
         assert_eq!(
             leader_scheduler.max_height_for_leader(bootstrap_height - 1),
-            None
+            Some(bootstrap_height)
         );
         assert_eq!(
             leader_scheduler.max_height_for_leader(bootstrap_height + 1),
             Some(bootstrap_height + seed_rotation_interval)
         );
-        leader_scheduler.generate_schedule(bootstrap_height + seed_rotation_interval, &bank);
         assert_eq!(
             leader_scheduler.max_height_for_leader(bootstrap_height + seed_rotation_interval + 1),
             Some(bootstrap_height + 2 * seed_rotation_interval)
         );
         assert_eq!(
-            leader_scheduler.max_height_for_leader(bootstrap_height + seed_rotation_interval),
+            leader_scheduler
+                .max_height_for_leader(bootstrap_height + 2 * seed_rotation_interval + 1),
             None
         );
+        leader_scheduler.generate_schedule(bootstrap_height + seed_rotation_interval, &bank);
+        assert_eq!(
+            leader_scheduler
+                .max_height_for_leader(bootstrap_height + 2 * seed_rotation_interval + 1),
+            Some(bootstrap_height + 3 * seed_rotation_interval)
+        );
+        assert_eq!(
+            leader_scheduler.max_height_for_leader(bootstrap_height + seed_rotation_interval),
+            Some(bootstrap_height + seed_rotation_interval)
+        );
 
         leader_scheduler.reset();
 
@@ -1442,7 +1715,7 @@ pub mod tests {
 
         assert_eq!(
             leader_scheduler.max_height_for_leader(bootstrap_height),
-            None
+            Some(bootstrap_height)
         );
         assert_eq!(
             leader_scheduler.max_height_for_leader(bootstrap_height + 1),
@@ -1470,6 +1743,11 @@ pub mod tests {
         );
         assert_eq!(
             leader_scheduler.max_height_for_leader(bootstrap_height + seed_rotation_interval + 1),
+            Some(bootstrap_height + seed_rotation_interval + leader_rotation_interval),
+        );
+        assert_eq!(
+            leader_scheduler
+                .max_height_for_leader(bootstrap_height + 2 * seed_rotation_interval + 1),
             None,
         );
 
@@ -1478,7 +1756,7 @@ pub mod tests {
 
         assert_eq!(
             leader_scheduler.max_height_for_leader(bootstrap_height + seed_rotation_interval),
-            None
+            Some(bootstrap_height + seed_rotation_interval)
         );
         assert_eq!(
             leader_scheduler.max_height_for_leader(bootstrap_height + seed_rotation_interval + 1),
@@ -1491,7 +1769,7 @@ pub mod tests {
         assert_eq!(
             leader_scheduler
                 .max_height_for_leader(bootstrap_height + 2 * seed_rotation_interval + 1),
-            None
+            Some(bootstrap_height + 2 * seed_rotation_interval + leader_rotation_interval)
         );
     }
 }
@@ -0,0 +1,250 @@
+//! The `ledger_archive` module exports a range of ledger entries, together with
+//! the ledger's genesis block, into a self-describing compressed archive, and
+//! rebuilds a `DbLedger` from such an archive.  Unlike a copy of the RocksDB
+//! directory, an archive is independent of the storage engine's on-disk format.
+
+use crate::db_ledger::{DbLedger, DEFAULT_SLOT_HEIGHT};
+use crate::entry::Entry;
+use crate::genesis_block::GenesisBlock;
+use crate::result::Result;
+use bincode::{deserialize_from, serialize_into};
+use libflate::gzip;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Leading bytes of every ledger archive
+pub const LEDGER_ARCHIVE_MAGIC: &[u8; 8] = b"SOLLEDGR";
+
+/// Version of the archive layout that follows the magic bytes
+pub const LEDGER_ARCHIVE_VERSION: u32 = 2;
+
+/// Number of entries `import_ledger` writes to the ledger at a time
+const IMPORT_BATCH_SIZE: usize = 1024;
+
+/// Describes the contents of an archive.  It is the first value of the
+/// compressed stream and is followed by `num_entries` serialized entries, each
+/// preceded by the slot it was stored under.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LedgerArchiveHeader {
+    pub version: u32,
+    pub genesis_block: GenesisBlock,
+    /// Index of the first archived entry in the source ledger
+    pub start_index: u64,
+    pub num_entries: u64,
+}
+
+fn invalid_archive(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Write the entries in the range [start_index, end_index) of the ledger at
+/// `ledger_path` into `writer`.  An `end_index` of None archives every entry
+/// from `start_index` to the end of the ledger.  Returns the number of entries
+/// written.
+pub fn export_ledger<W: Write>(
+    ledger_path: &str,
+    start_index: u64,
+    end_index: Option<u64>,
+    mut writer: W,
+) -> Result<u64> {
+    let genesis_block = GenesisBlock::load(ledger_path)?;
+    let db_ledger = DbLedger::open(ledger_path)?;
+
+    let end_index = end_index.unwrap_or_else(u64::max_value);
+    let read_range = || -> Result<_> {
+        Ok(db_ledger
+            .read_ledger_with_slots()?
+            .skip(start_index as usize)
+            .take(end_index.saturating_sub(start_index) as usize))
+    };
+
+    // Count the entries up front so they can be streamed into the archive after
+    // the header rather than held in memory
+    let header = LedgerArchiveHeader {
+        version: LEDGER_ARCHIVE_VERSION,
+        genesis_block,
+        start_index,
+        num_entries: read_range()?.count() as u64,
+    };
+
+    writer.write_all(LEDGER_ARCHIVE_MAGIC)?;
+    let mut encoder = gzip::Encoder::new(writer)?;
+    serialize_into(&mut encoder, &header)?;
+    let mut num_written = 0;
+    for (slot, entry) in read_range()?.take(header.num_entries as usize) {
+        serialize_into(&mut encoder, &slot)?;
+        serialize_into(&mut encoder, &entry)?;
+        num_written += 1;
+    }
+    if num_written != header.num_entries {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "ledger entries went missing during the export",
+        ))?;
+    }
+    encoder.finish().into_result()?.flush()?;
+
+    Ok(header.num_entries)
+}
+
+/// Create a new ledger at `ledger_path` from an archive produced by
+/// `export_ledger`.  Entries keep the slot and index they had in the source
+/// ledger.
+/// Fails if a ledger already exists at `ledger_path`.
+pub fn import_ledger<R: Read>(mut reader: R, ledger_path: &str) -> Result<LedgerArchiveHeader> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != LEDGER_ARCHIVE_MAGIC {
+        Err(invalid_archive("not a ledger archive"))?;
+    }
+
+    let mut decoder = gzip::Decoder::new(reader)?;
+    let header: LedgerArchiveHeader = deserialize_from(&mut decoder)?;
+    if header.version != LEDGER_ARCHIVE_VERSION {
+        Err(invalid_archive(&format!(
+            "unsupported ledger archive version {}",
+            header.version
+        )))?;
+    }
+
+    if Path::new(ledger_path).join("genesis.json").exists() {
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("a ledger already exists at {}", ledger_path),
+        ))?;
+    }
+
+    // Write the entries as they're decoded, a run of entries from the same slot
+    // at a time
+    let db_ledger = DbLedger::open(ledger_path)?;
+    let mut batch_slot = DEFAULT_SLOT_HEIGHT;
+    let mut batch_index = header.start_index;
+    let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    for _ in 0..header.num_entries {
+        let slot: u64 = deserialize_from(&mut decoder)?;
+        let entry: Entry = deserialize_from(&mut decoder)?;
+        if slot != batch_slot || batch.len() == IMPORT_BATCH_SIZE {
+            db_ledger.write_entries(batch_slot, batch_index, &batch)?;
+            batch_index += batch.len() as u64;
+            batch.clear();
+            batch_slot = slot;
+        }
+        batch.push(entry);
+    }
+    db_ledger.write_entries(batch_slot, batch_index, &batch)?;
+    header.genesis_block.write(ledger_path)?;
+
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_ledger::{create_tmp_sample_ledger, get_tmp_ledger_path};
+    use crate::entry::create_ticks;
+    use solana_sdk::signature::{Keypair, KeypairUtil};
+
+    #[test]
+    fn test_export_import_ledger() {
+        let leader_keypair = Keypair::new();
+        let (_, ledger_path, entry_height, last_id) = create_tmp_sample_ledger(
+            "test_export_import_ledger",
+            100,
+            9,
+            leader_keypair.pubkey(),
+            50,
+        );
+
+        // Store a few more entries under the next slot
+        let db_ledger = DbLedger::open(&ledger_path).unwrap();
+        db_ledger
+            .write_entries(1, entry_height, &create_ticks(5, last_id))
+            .unwrap();
+        drop(db_ledger);
+        let entry_height = entry_height + 5;
+
+        let expected: Vec<_> = DbLedger::open(&ledger_path)
+            .unwrap()
+            .read_ledger_with_slots()
+            .unwrap()
+            .collect();
+        assert_eq!(expected.len() as u64, entry_height);
+        assert_eq!(expected.last().unwrap().0, 1);
+
+        let mut archive = vec![];
+        assert_eq!(
+            export_ledger(&ledger_path, 0, None, &mut archive).unwrap(),
+            entry_height
+        );
+
+        let import_path = get_tmp_ledger_path("test_export_import_ledger_import");
+        let header = import_ledger(&archive[..], &import_path).unwrap();
+        assert_eq!(header.start_index, 0);
+        assert_eq!(header.num_entries, entry_height);
+
+        let db_ledger = DbLedger::open(&import_path).unwrap();
+        assert!(db_ledger
+            .get_data_blob_bytes(1, entry_height - 1)
+            .unwrap()
+            .is_some());
+        let imported: Vec<_> = db_ledger.read_ledger_with_slots().unwrap().collect();
+        assert_eq!(imported, expected);
+        drop(db_ledger);
+        assert_eq!(
+            GenesisBlock::load(&import_path).unwrap().last_id(),
+            GenesisBlock::load(&ledger_path).unwrap().last_id()
+        );
+
+        // Importing over an existing ledger is refused
+        assert!(import_ledger(&archive[..], &import_path).is_err());
+
+        DbLedger::destroy(&ledger_path).unwrap();
+        DbLedger::destroy(&import_path).unwrap();
+    }
+
+    #[test]
+    fn test_export_ledger_range() {
+        let leader_keypair = Keypair::new();
+        let (_, ledger_path, _, _) = create_tmp_sample_ledger(
+            "test_export_ledger_range",
+            100,
+            9,
+            leader_keypair.pubkey(),
+            50,
+        );
+        let expected: Vec<_> = DbLedger::open(&ledger_path)
+            .unwrap()
+            .read_ledger()
+            .unwrap()
+            .skip(3)
+            .take(4)
+            .collect();
+
+        let mut archive = vec![];
+        assert_eq!(
+            export_ledger(&ledger_path, 3, Some(7), &mut archive).unwrap(),
+            4
+        );
+
+        let import_path = get_tmp_ledger_path("test_export_ledger_range_import");
+        let header = import_ledger(&archive[..], &import_path).unwrap();
+        assert_eq!(header.start_index, 3);
+
+        let db_ledger = DbLedger::open(&import_path).unwrap();
+        assert!(db_ledger
+            .get_data_blob_bytes(DEFAULT_SLOT_HEIGHT, 2)
+            .unwrap()
+            .is_none());
+        let imported: Vec<_> = db_ledger.read_ledger().unwrap().collect();
+        assert_eq!(imported, expected);
+
+        DbLedger::destroy(&ledger_path).unwrap();
+        DbLedger::destroy(&import_path).unwrap();
+    }
+
+    #[test]
+    fn test_import_ledger_bad_magic() {
+        let import_path = get_tmp_ledger_path("test_import_ledger_bad_magic");
+        assert!(import_ledger(&b"NOTANARCHIVE"[..], &import_path).is_err());
+    }
+}
@@ -35,21 +35,23 @@ pub mod db_ledger;
 pub mod db_window;
 pub mod entry;
 pub mod entry_stream;
-#[cfg(feature = "erasure")]
 pub mod erasure;
 pub mod fetch_stage;
 pub mod fullnode;
 pub mod gen_keys;
 pub mod genesis_block;
+pub mod gossip_peer_score;
 pub mod gossip_service;
 pub mod last_id_queue;
 pub mod leader_scheduler;
+pub mod ledger_archive;
 pub mod local_vote_signer_service;
 pub mod packet;
 pub mod poh;
 pub mod poh_recorder;
 pub mod poh_service;
 pub mod recvmmsg;
+pub mod recycler;
 pub mod replay_stage;
 pub mod replicator;
 pub mod result;
@@ -65,9 +67,11 @@ pub mod sigverify_stage;
 pub mod status_cache;
 pub mod storage_stage;
 pub mod streamer;
+pub mod tcp_receiver;
 pub mod test_tx;
 pub mod thin_client;
 pub mod tpu;
+pub mod tpu_client;
 pub mod tpu_forwarder;
 pub mod tvu;
 pub mod voting_keypair;
@@ -1,6 +1,7 @@
 //! The `packet` module defines data structures and methods to pull data from the network.
 use crate::counter::Counter;
 use crate::recvmmsg::{recv_mmsg, NUM_RCVMMSGS};
+use crate::recycler::{Recycler, Reset};
 use crate::result::{Error, Result};
 use bincode::{serialize, serialize_into};
 use byteorder::{ByteOrder, LittleEndian};
@@ -11,7 +12,7 @@ use solana_sdk::pubkey::Pubkey;
 use std::cmp;
 use std::fmt;
 use std::io;
-use std::mem::size_of;
+use std::mem::{self, size_of};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
 use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, RwLock};
@@ -33,6 +34,8 @@ pub struct Meta {
     pub addr: [u16; 8],
     pub port: u16,
     pub v6: bool,
+    /// Set by the SigVerify stage to drop the packet without verifying it
+    pub discard: bool,
 }
 
 #[derive(Clone)]
@@ -105,18 +108,96 @@ impl Meta {
 #[derive(Debug)]
 pub struct Packets {
     pub packets: Vec<Packet>,
+    recycler: Option<PacketsRecycler>,
 }
 
 //auto derive doesn't support large arrays
 impl Default for Packets {
     fn default() -> Packets {
-        Packets {
-            packets: vec![Packet::default(); NUM_PACKETS],
+        Packets::new(vec![Packet::default(); NUM_PACKETS])
+    }
+}
+
+impl Drop for Packets {
+    fn drop(&mut self) {
+        if let Some(recycler) = self.recycler.take() {
+            recycler.recycle(PacketBuffer(mem::replace(&mut self.packets, vec![])));
+        }
+    }
+}
+
+#[cfg(feature = "cuda")]
+extern "C" {
+    fn cudaHostRegister(ptr: *mut u8, size: usize, flags: u32) -> i32;
+    fn cudaHostUnregister(ptr: *mut u8) -> i32;
+}
+
+/// The buffer behind the `Packets` handed out by a `PacketsRecycler`. With the `cuda`
+/// feature it's page-locked once, when it's first allocated, so the GPU can copy it
+/// without staging, and unlocked when it's finally freed. It must not grow past
+/// `NUM_PACKETS`, which would move it out of the locked pages.
+pub struct PacketBuffer(Vec<Packet>);
+
+pub type PacketsRecycler = Recycler<PacketBuffer>;
+
+/// Most idle buffers a `PacketsRecycler` keeps, each `NUM_PACKETS` packets large
+pub const PACKETS_RECYCLER_LIMIT: usize = 32;
+
+impl Default for PacketBuffer {
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut packets = vec![Packet::default(); NUM_PACKETS];
+        #[cfg(feature = "cuda")]
+        unsafe {
+            let err = cudaHostRegister(
+                packets.as_mut_ptr() as *mut u8,
+                packets.capacity() * size_of::<Packet>(),
+                0,
+            );
+            if err != 0 {
+                warn!("cudaHostRegister error: {}", err);
+            }
+        }
+        PacketBuffer(packets)
+    }
+}
+
+impl Reset for PacketBuffer {
+    fn reset(&mut self) {
+        for packet in self.0.iter_mut() {
+            packet.meta = Meta::default();
+        }
+    }
+}
+
+#[cfg(feature = "cuda")]
+impl Drop for PacketBuffer {
+    fn drop(&mut self) {
+        if self.0.capacity() > 0 {
+            unsafe {
+                cudaHostUnregister(self.0.as_mut_ptr() as *mut u8);
+            }
         }
     }
 }
 
 impl Packets {
+    pub fn new(packets: Vec<Packet>) -> Self {
+        Packets {
+            packets,
+            recycler: None,
+        }
+    }
+
+    /// Packets backed by a buffer from `recycler`, which gets it back once they're dropped
+    pub fn new_with_recycler(recycler: &PacketsRecycler) -> Self {
+        let mut buffer = recycler.allocate();
+        Packets {
+            packets: mem::replace(&mut buffer.0, vec![]),
+            recycler: Some(recycler.clone()),
+        }
+    }
+
     pub fn set_addr(&mut self, addr: &SocketAddr) {
         for m in self.packets.iter_mut() {
             m.meta.set_addr(&addr);
@@ -124,6 +205,27 @@ impl Packets {
     }
 }
 
+pub type BlobRecycler = Recycler<SharedBlob>;
+
+impl Reset for SharedBlob {
+    fn reset(&mut self) {
+        let mut blob = self.write().unwrap();
+        for byte in blob.data.iter_mut() {
+            *byte = 0;
+        }
+        blob.meta = Meta::default();
+    }
+}
+
+/// Hands the blobs nothing else holds anymore back to `recycler`, and drops the rest
+pub fn recycle_blobs(recycler: &BlobRecycler, blobs: Vec<SharedBlob>) {
+    for blob in blobs {
+        if Arc::strong_count(&blob) == 1 {
+            recycler.recycle(blob);
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Blob {
     pub data: [u8; BLOB_SIZE],
@@ -294,6 +396,13 @@ macro_rules! align {
 pub const BLOB_HEADER_SIZE: usize = align!(SIZE_RANGE.end, 8);
 
 pub const BLOB_FLAG_IS_CODING: u32 = 0x1;
+/// The payload of the blob is deflate-compressed
+pub const BLOB_FLAG_IS_COMPRESSED: u32 = 0x2;
+
+/// Read the flags of a blob from its raw bytes, as stored in the ledger
+pub fn blob_flags(data: &[u8]) -> u32 {
+    LittleEndian::read_u32(&data[FLAGS_RANGE])
+}
 
 impl Blob {
     pub fn new(data: &[u8]) -> Self {
@@ -335,7 +444,7 @@ impl Blob {
     }
 
     pub fn flags(&self) -> u32 {
-        LittleEndian::read_u32(&self.data[FLAGS_RANGE])
+        blob_flags(&self.data)
     }
     pub fn set_flags(&mut self, ix: u32) {
         LittleEndian::write_u32(&mut self.data[FLAGS_RANGE], ix);
@@ -350,6 +459,15 @@ impl Blob {
         self.set_flags(flags | BLOB_FLAG_IS_CODING);
     }
 
+    pub fn is_compressed(&self) -> bool {
+        (self.flags() & BLOB_FLAG_IS_COMPRESSED) != 0
+    }
+
+    pub fn set_compressed(&mut self) {
+        let flags = self.flags();
+        self.set_flags(flags | BLOB_FLAG_IS_COMPRESSED);
+    }
+
     pub fn data_size(&self) -> u64 {
         LittleEndian::read_u64(&self.data[SIZE_RANGE])
     }
@@ -458,8 +576,8 @@ pub fn index_blobs(blobs: &[SharedBlob], id: &Pubkey, mut index: u64, slots: &[u
 #[cfg(test)]
 mod tests {
     use crate::packet::{
-        to_packets, Blob, Meta, Packet, Packets, SharedBlob, SharedPackets, NUM_PACKETS,
-        PACKET_DATA_SIZE,
+        blob_flags, recycle_blobs, to_packets, Blob, BlobRecycler, Meta, Packet, Packets,
+        PacketsRecycler, SharedBlob, SharedPackets, NUM_PACKETS, PACKET_DATA_SIZE,
     };
     use solana_sdk::hash::Hash;
     use solana_sdk::signature::{Keypair, KeypairUtil};
@@ -473,11 +591,51 @@ mod tests {
         // test that the address is actually being updated
         let send_addr = socketaddr!([127, 0, 0, 1], 123);
         let packets = vec![Packet::default()];
-        let mut msgs = Packets { packets };
+        let mut msgs = Packets::new(packets);
         msgs.set_addr(&send_addr);
         assert_eq!(SocketAddr::from(msgs.packets[0].meta.addr()), send_addr);
     }
 
+    #[test]
+    fn test_packets_recycler() {
+        let recycler = PacketsRecycler::default();
+        let mut msgs = Packets::new_with_recycler(&recycler);
+        assert_eq!(msgs.packets.len(), NUM_PACKETS);
+        msgs.packets.truncate(3);
+        msgs.packets[0].meta.size = 42;
+        msgs.packets[1].meta.discard = true;
+        drop(msgs);
+        assert_eq!(recycler.num_pooled(), 1);
+
+        // the buffer comes back with its packets cleared
+        let msgs = Packets::new_with_recycler(&recycler);
+        assert_eq!(recycler.num_allocated(), 1);
+        assert_eq!(recycler.num_reused(), 1);
+        assert!(msgs.packets.iter().all(|p| p.meta == Meta::default()));
+        drop(msgs);
+
+        // packets allocated without a recycler aren't pooled
+        drop(Packets::default());
+        assert_eq!(recycler.num_pooled(), 1);
+    }
+
+    #[test]
+    fn test_recycle_blobs() {
+        let recycler = BlobRecycler::default();
+        let blob = recycler.allocate();
+        blob.write().unwrap().set_size(10);
+        blob.write().unwrap().set_compressed();
+        let shared = recycler.allocate();
+        let _window = shared.clone();
+
+        recycle_blobs(&recycler, vec![blob, shared]);
+        assert_eq!(recycler.num_pooled(), 1);
+
+        // blobs come back zeroed
+        let blob = recycler.allocate();
+        assert_eq!(*blob.read().unwrap(), Blob::default());
+    }
+
     #[test]
     pub fn packet_send_recv() {
         let reader = UdpSocket::bind("127.0.0.1:0").expect("bind");
@@ -569,4 +727,17 @@ mod tests {
         assert_eq!(b.meta, Meta::default());
     }
 
+    #[test]
+    pub fn blob_flags_test() {
+        let mut b = Blob::default();
+        assert!(!b.is_coding());
+        assert!(!b.is_compressed());
+        b.set_compressed();
+        assert!(b.is_compressed());
+        assert!(!b.is_coding());
+        b.set_coding();
+        assert!(b.is_coding());
+        assert_eq!(blob_flags(&b.data), b.flags());
+    }
+
 }
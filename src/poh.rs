@@ -1,6 +1,8 @@
 //! The `Poh` module provides an object for generating a Proof of History.
 //! It records Hashes items on behalf of its users.
 use solana_sdk::hash::{hash, hashv, Hash};
+use solana_sdk::timing::duration_as_us;
+use std::time::{Duration, Instant};
 
 pub struct Poh {
     id: Hash,
@@ -62,6 +64,34 @@ impl Poh {
     }
 }
 
+/// Measures how many PoH hashes this machine can roll per second by hashing
+/// for at least `duration`.
+pub fn compute_hashes_per_second(duration: Duration) -> u64 {
+    // Check the clock only every so often so it doesn't dominate the measurement
+    const HASHES_PER_BATCH: u64 = 1024;
+
+    let mut poh = Poh::new(Hash::default(), 0);
+    let mut num_hashes = 0;
+    let now = Instant::now();
+    loop {
+        for _ in 0..HASHES_PER_BATCH {
+            poh.hash();
+        }
+        num_hashes += HASHES_PER_BATCH;
+
+        let elapsed = now.elapsed();
+        if elapsed >= duration {
+            return num_hashes * 1_000_000 / duration_as_us(&elapsed).max(1);
+        }
+    }
+}
+
+/// The number of hashes between ticks that produces `ticks_per_second` on a
+/// machine rolling `hashes_per_second`.
+pub fn compute_hashes_per_tick(hashes_per_second: u64, ticks_per_second: u64) -> u64 {
+    (hashes_per_second / ticks_per_second).max(1)
+}
+
 #[cfg(test)]
 pub fn verify(initial: Hash, entries: &[PohEntry]) -> bool {
     let mut id = initial;
@@ -86,8 +116,9 @@ pub fn verify(initial: Hash, entries: &[PohEntry]) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use crate::poh::{verify, Poh, PohEntry};
+    use crate::poh::{compute_hashes_per_second, compute_hashes_per_tick, verify, Poh, PohEntry};
     use solana_sdk::hash::{hash, hashv, Hash};
+    use std::time::Duration;
 
     #[test]
     fn test_poh_verify() {
@@ -191,4 +222,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compute_hashes_per_second() {
+        assert!(compute_hashes_per_second(Duration::from_millis(10)) > 0);
+        assert!(compute_hashes_per_second(Duration::from_millis(0)) > 0);
+    }
+
+    #[test]
+    fn test_compute_hashes_per_tick() {
+        assert_eq!(compute_hashes_per_tick(1_000_000, 10), 100_000);
+        assert_eq!(compute_hashes_per_tick(1_005, 10), 100);
+        assert_eq!(compute_hashes_per_tick(5, 10), 1);
+        assert_eq!(compute_hashes_per_tick(0, 10), 1);
+    }
 }
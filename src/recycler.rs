@@ -0,0 +1,148 @@
+//! The `recycler` module keeps pools of buffers that are expensive to allocate, like
+//! packet and blob buffers, so the stages on the hot path reuse them instead of going
+//! through the allocator for every batch.
+
+use crate::counter::Counter;
+use log::Level;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Most buffers a recycler keeps around by default
+pub const DEFAULT_RECYCLER_LIMIT: usize = 1024;
+
+/// A buffer that can be pooled by a `Recycler`
+pub trait Reset {
+    /// Clear `self` for its next user, keeping its allocation
+    fn reset(&mut self);
+}
+
+#[derive(Debug, Default)]
+struct RecyclerStats {
+    allocated: AtomicUsize,
+    reused: AtomicUsize,
+    freed: AtomicUsize,
+}
+
+/// A pool of `T`s, shared by all its clones. `allocate` hands out a pooled `T` if
+/// there is one and a new one otherwise, and `recycle` takes it back, up to `limit`
+/// pooled `T`s.
+pub struct Recycler<T> {
+    pool: Arc<Mutex<Vec<T>>>,
+    stats: Arc<RecyclerStats>,
+    limit: usize,
+    name: &'static str,
+}
+
+impl<T> Clone for Recycler<T> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            stats: self.stats.clone(),
+            limit: self.limit,
+            name: self.name,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Recycler<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Recycler {{ name: {}, limit: {}, stats: {:?} }}",
+            self.name, self.limit, self.stats
+        )
+    }
+}
+
+impl<T: Default + Reset> Default for Recycler<T> {
+    fn default() -> Self {
+        Self::new("default", DEFAULT_RECYCLER_LIMIT)
+    }
+}
+
+impl<T: Default + Reset> Recycler<T> {
+    pub fn new(name: &'static str, limit: usize) -> Self {
+        Self {
+            pool: Arc::new(Mutex::new(vec![])),
+            stats: Arc::new(RecyclerStats::default()),
+            limit,
+            name,
+        }
+    }
+
+    pub fn allocate(&self) -> T {
+        if let Some(x) = self.pool.lock().unwrap().pop() {
+            self.stats.reused.fetch_add(1, Ordering::Relaxed);
+            inc_new_counter_info!("recycler-reused", 1);
+            return x;
+        }
+        let allocated = self.stats.allocated.fetch_add(1, Ordering::Relaxed) + 1;
+        inc_new_counter_info!("recycler-allocated", 1);
+        trace!("{} recycler allocated {} buffers", self.name, allocated);
+        T::default()
+    }
+
+    pub fn recycle(&self, mut x: T) {
+        x.reset();
+        let mut pool = self.pool.lock().unwrap();
+        if pool.len() < self.limit {
+            pool.push(x);
+            return;
+        }
+        drop(pool);
+        self.stats.freed.fetch_add(1, Ordering::Relaxed);
+        inc_new_counter_info!("recycler-freed", 1);
+    }
+
+    /// Number of `T`s this recycler had to allocate
+    pub fn num_allocated(&self) -> usize {
+        self.stats.allocated.load(Ordering::Relaxed)
+    }
+
+    /// Number of `T`s handed out again after being recycled
+    pub fn num_reused(&self) -> usize {
+        self.stats.reused.load(Ordering::Relaxed)
+    }
+
+    /// Number of `T`s waiting in the pool
+    pub fn num_pooled(&self) -> usize {
+        self.pool.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Buffer(Vec<u8>);
+
+    impl Reset for Buffer {
+        fn reset(&mut self) {
+            self.0.clear();
+        }
+    }
+
+    #[test]
+    fn test_recycler() {
+        let recycler: Recycler<Buffer> = Recycler::new("test", 1);
+        let mut buffer = recycler.allocate();
+        buffer.0.extend_from_slice(&[1, 2, 3]);
+        let capacity = buffer.0.capacity();
+        recycler.recycle(buffer);
+        assert_eq!(recycler.num_pooled(), 1);
+
+        // comes back cleared, with its allocation
+        let buffer = recycler.clone().allocate();
+        assert!(buffer.0.is_empty());
+        assert_eq!(buffer.0.capacity(), capacity);
+        assert_eq!(recycler.num_allocated(), 1);
+        assert_eq!(recycler.num_reused(), 1);
+
+        // the pool doesn't grow past its limit
+        recycler.recycle(buffer);
+        recycler.recycle(Buffer::default());
+        assert_eq!(recycler.num_pooled(), 1);
+    }
+}
@@ -10,7 +10,6 @@ use crate::entry_stream::EntryStreamHandler;
 #[cfg(test)]
 use crate::entry_stream::MockEntryStream as EntryStream;
 use crate::fullnode::TvuRotationSender;
-use crate::leader_scheduler::DEFAULT_TICKS_PER_SLOT;
 use crate::packet::BlobError;
 use crate::result::{Error, Result};
 use crate::service::Service;
@@ -66,6 +65,7 @@ impl ReplayStage {
         ledger_entry_sender: &EntrySender,
         entry_height: &Arc<RwLock<u64>>,
         last_entry_id: &Arc<RwLock<Hash>>,
+        tick_hash_count: &mut u64,
         entry_stream: Option<&mut EntryStream>,
     ) -> Result<()> {
         let timer = Duration::new(1, 0);
@@ -97,6 +97,18 @@ impl ReplayStage {
             inc_new_counter_info!("replicate_stage-verify-fail", entries.len());
             return Err(Error::BlobError(BlobError::VerificationFailed));
         }
+        if let Some(hashes_per_tick) = bank.hashes_per_tick() {
+            // Rejected entries don't count towards the next tick
+            let mut new_tick_hash_count = *tick_hash_count;
+            if !entries
+                .as_slice()
+                .verify_tick_hash_count(&mut new_tick_hash_count, hashes_per_tick)
+            {
+                inc_new_counter_info!("replicate_stage-tick-hash-count-fail", entries.len());
+                return Err(Error::BlobError(BlobError::VerificationFailed));
+            }
+            *tick_hash_count = new_tick_hash_count;
+        }
         inc_new_counter_info!(
             "replicate_stage-verify-duration",
             duration_as_ms(&now.elapsed()) as usize
@@ -109,8 +121,8 @@ impl ReplayStage {
         let mut did_rotate = false;
 
         // Next vote tick is ceiling of (current tick/ticks per block)
-        let mut num_ticks_to_next_vote =
-            DEFAULT_TICKS_PER_SLOT - (bank.tick_height() % DEFAULT_TICKS_PER_SLOT);
+        let ticks_per_slot = bank.ticks_per_slot();
+        let mut num_ticks_to_next_vote = ticks_per_slot - (bank.tick_height() % ticks_per_slot);
         let mut start_entry_index = 0;
         for (i, entry) in entries.iter().enumerate() {
             inc_new_counter_info!("replicate-stage_bank-tick", bank.tick_height() as usize);
@@ -143,6 +155,17 @@ impl ReplayStage {
                 }
 
                 if 0 == num_ticks_to_next_vote {
+                    let stakes = {
+                        let leader_scheduler = bank.leader_scheduler.read().unwrap();
+                        let epoch = leader_scheduler.tick_height_to_epoch(bank.tick_height());
+                        leader_scheduler
+                            .get_epoch_stakes(epoch)
+                            .map(|epoch_stakes| epoch_stakes.stakes.iter().cloned().collect())
+                    };
+                    if let Some(stakes) = stakes {
+                        cluster_info.write().unwrap().set_stakes(stakes);
+                    }
+                    cluster_info.write().unwrap().push_root(bank.tick_height());
                     if let Some(voting_keypair) = voting_keypair {
                         let keypair = voting_keypair.as_ref();
                         let vote = VoteTransaction::new_vote(
@@ -169,7 +192,7 @@ impl ReplayStage {
                     break;
                 }
                 start_entry_index = i + 1;
-                num_ticks_to_next_vote = DEFAULT_TICKS_PER_SLOT;
+                num_ticks_to_next_vote = ticks_per_slot;
             }
         }
 
@@ -213,6 +236,7 @@ impl ReplayStage {
         exit: Arc<AtomicBool>,
         entry_height: Arc<RwLock<u64>>,
         last_entry_id: Arc<RwLock<Hash>>,
+        mut tick_hash_count: u64,
         to_leader_sender: TvuRotationSender,
         entry_stream: Option<&String>,
     ) -> (Self, EntryReceiver) {
@@ -252,6 +276,7 @@ impl ReplayStage {
                         &ledger_entry_sender,
                         &entry_height_.clone(),
                         &last_entry_id.clone(),
+                        &mut tick_hash_count,
                         entry_stream.as_mut(),
                     ) {
                         Err(Error::RecvTimeoutError(RecvTimeoutError::Disconnected)) => break,
@@ -285,6 +310,7 @@ mod test {
     use crate::entry::create_ticks;
     use crate::entry::Entry;
     use crate::fullnode::Fullnode;
+    use crate::genesis_block::GenesisBlock;
     use crate::leader_scheduler::{
         make_active_set_entries, LeaderScheduler, LeaderSchedulerConfig,
     };
@@ -298,6 +324,7 @@ mod test {
     use serde_json::Value;
     use solana_sdk::hash::Hash;
     use solana_sdk::signature::{Keypair, KeypairUtil};
+    use solana_sdk::system_transaction::SystemTransaction;
     use std::fs::remove_dir_all;
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::mpsc::channel;
@@ -366,7 +393,7 @@ mod test {
             Arc::new(RwLock::new(LeaderScheduler::new(&leader_scheduler_config)));
 
         // Set up the bank
-        let (bank, entry_height, last_entry_id) =
+        let (bank, entry_height, last_entry_id, tick_hash_count) =
             Fullnode::new_bank_from_ledger(&my_ledger_path, leader_scheduler);
 
         // Set up the replay stage
@@ -382,6 +409,7 @@ mod test {
             exit.clone(),
             Arc::new(RwLock::new(entry_height)),
             Arc::new(RwLock::new(last_entry_id)),
+            tick_hash_count,
             rotation_sender,
             None,
         );
@@ -456,7 +484,7 @@ mod test {
         );
 
         // Set up the bank
-        let (bank, entry_height, last_entry_id) =
+        let (bank, entry_height, last_entry_id, tick_hash_count) =
             Fullnode::new_bank_from_ledger(&my_ledger_path, leader_scheduler);
 
         // Set up the cluster info
@@ -478,6 +506,7 @@ mod test {
             exit.clone(),
             Arc::new(RwLock::new(entry_height)),
             Arc::new(RwLock::new(last_entry_id)),
+            tick_hash_count,
             to_leader_sender,
             None,
         );
@@ -564,7 +593,7 @@ mod test {
             Arc::new(RwLock::new(LeaderScheduler::new(&leader_scheduler_config)));
 
         // Set up the bank
-        let (bank, entry_height, last_entry_id) =
+        let (bank, entry_height, last_entry_id, tick_hash_count) =
             Fullnode::new_bank_from_ledger(&my_ledger_path, leader_scheduler);
 
         // Set up the cluster info
@@ -585,6 +614,7 @@ mod test {
             exit.clone(),
             Arc::new(RwLock::new(entry_height)),
             Arc::new(RwLock::new(last_entry_id)),
+            tick_hash_count,
             rotation_tx,
             None,
         );
@@ -680,6 +710,7 @@ mod test {
             &ledger_entry_sender,
             &Arc::new(RwLock::new(entry_height)),
             &Arc::new(RwLock::new(last_entry_id)),
+            &mut 0,
             None,
         );
 
@@ -706,6 +737,7 @@ mod test {
             &ledger_entry_sender,
             &Arc::new(RwLock::new(entry_height)),
             &Arc::new(RwLock::new(last_entry_id)),
+            &mut 0,
             None,
         );
 
@@ -720,6 +752,100 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_replay_stage_tick_hash_count_error() {
+        let my_keypair = Keypair::new();
+        let my_id = my_keypair.pubkey();
+        let my_node = Node::new_localhost_with_pubkey(my_id);
+        let cluster_info_me = Arc::new(RwLock::new(ClusterInfo::new(my_node.info.clone())));
+        let (entry_sender, entry_receiver) = channel();
+        let (ledger_entry_sender, _ledger_entry_receiver) = channel();
+        let last_entry_id = Hash::default();
+
+        // Ticks that follow the PoH but roll fewer hashes than the genesis block requires
+        let entries = create_ticks(5, last_entry_id);
+        entry_sender.send(entries).unwrap();
+
+        let (mut genesis_block, _mint_keypair) = GenesisBlock::new(10_000);
+        genesis_block.hashes_per_tick = Some(2);
+        let res = ReplayStage::process_entries(
+            &Arc::new(Bank::new(&genesis_block)),
+            &cluster_info_me,
+            &entry_receiver,
+            my_id,
+            None,
+            &ledger_entry_sender,
+            &Arc::new(RwLock::new(0)),
+            &Arc::new(RwLock::new(last_entry_id)),
+            &mut 0,
+            None,
+        );
+
+        match res {
+            Err(Error::BlobError(BlobError::VerificationFailed)) => (),
+            _ => assert!(false, "Should have failed the tick hash count"),
+        }
+    }
+
+    #[test]
+    fn test_replay_stage_tick_hash_count_restart() {
+        let my_keypair = Keypair::new();
+        let my_id = my_keypair.pubkey();
+        let my_node = Node::new_localhost_with_pubkey(my_id);
+        let cluster_info_me = Arc::new(RwLock::new(ClusterInfo::new(my_node.info.clone())));
+        let (entry_sender, entry_receiver) = channel();
+        let (ledger_entry_sender, _ledger_entry_receiver) = channel();
+
+        // The ledger stops halfway through a tick, its last entry having rolled one
+        // of the tick's two hashes
+        let (mut genesis_block, mint_keypair) = GenesisBlock::new(10_000);
+        genesis_block.hashes_per_tick = Some(2);
+        let mut bank = Bank::new(&genesis_block);
+        let tx = SystemTransaction::new_account(
+            &mint_keypair,
+            Keypair::new().pubkey(),
+            1,
+            bank.last_id(),
+            0,
+        );
+        let entry = Entry::new(&bank.last_id(), 0, 2, vec![tx]);
+        let (entry_height, last_entry_id, mut tick_hash_count) =
+            bank.process_ledger(vec![entry]).unwrap();
+        assert_eq!(tick_hash_count, 1);
+
+        let bank = Arc::new(bank);
+        let entry_height = Arc::new(RwLock::new(entry_height));
+        let last_entry_id_lock = Arc::new(RwLock::new(last_entry_id));
+        let replay = |entries, tick_hash_count: &mut u64| {
+            entry_sender.send(entries).unwrap();
+            ReplayStage::process_entries(
+                &bank,
+                &cluster_info_me,
+                &entry_receiver,
+                my_id,
+                None,
+                &ledger_entry_sender,
+                &entry_height,
+                &last_entry_id_lock,
+                tick_hash_count,
+                None,
+            )
+        };
+
+        // Counting from scratch after a restart would reject the tick that
+        // completes the ledger's
+        let tick = Entry::new(&last_entry_id, 1, 1, vec![]);
+        assert!(replay(vec![tick.clone()], &mut 0).is_err());
+
+        // A rejected tick leaves the count alone
+        let bad_tick = Entry::new(&last_entry_id, 1, 2, vec![]);
+        assert!(replay(vec![bad_tick], &mut tick_hash_count).is_err());
+        assert_eq!(tick_hash_count, 1);
+
+        replay(vec![tick], &mut tick_hash_count).unwrap();
+        assert_eq!(tick_hash_count, 0);
+    }
+
     #[test]
     fn test_replay_stage_stream_entries() {
         // Set up entry stream
@@ -760,6 +886,7 @@ mod test {
             &ledger_entry_sender,
             &Arc::new(RwLock::new(entry_height)),
             &Arc::new(RwLock::new(last_entry_id)),
+            &mut 0,
             Some(&mut entry_stream),
         )
         .unwrap();
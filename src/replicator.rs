@@ -4,6 +4,8 @@ use crate::chacha::{chacha_cbc_encrypt_ledger, CHACHA_BLOCK_SIZE};
 use crate::client::mk_client;
 use crate::cluster_info::{ClusterInfo, Node, NodeInfo};
 use crate::db_ledger::DbLedger;
+use crate::erasure::ErasureConfig;
+use crate::genesis_block::GenesisBlock;
 use crate::gossip_service::GossipService;
 use crate::leader_scheduler::LeaderScheduler;
 use crate::result::{self, Result};
@@ -17,55 +19,180 @@ use rand::thread_rng;
 use rand::Rng;
 use solana_drone::drone::{request_airdrop_transaction, DRONE_PORT};
 use solana_sdk::hash::{Hash, Hasher};
+use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, KeypairUtil, Signature};
-use solana_sdk::storage_program::StorageTransaction;
-use std::fs::File;
+use solana_sdk::storage_program::{segment_message, StorageTransaction};
+use std::cmp;
+use std::fs::{self, File};
 use std::io;
 use std::io::BufReader;
 use std::io::Read;
 use std::io::Seek;
 use std::io::SeekFrom;
+use std::io::Write;
 use std::io::{Error, ErrorKind};
 use std::mem::size_of;
 use std::net::UdpSocket;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::channel;
 use std::sync::{Arc, RwLock};
 use std::thread::sleep;
-use std::thread::JoinHandle;
+use std::thread::{Builder, JoinHandle};
 use std::time::{Duration, Instant};
 
+/// Name of the file, inside the ledger directory, that holds the `ReplicatorState`
+pub const REPLICATOR_STATE_FILE: &str = "replicator-state.json";
+
+pub struct ReplicatorConfig {
+    /// Number of ledger segments the replicator stores
+    pub num_segments: usize,
+    /// How often to poll the cluster for a new storage challenge
+    pub storage_poll_interval: Duration,
+    /// Erasure sets of the cluster, used when the ledger directory has no genesis block
+    /// to read them from
+    pub erasure_config: ErasureConfig,
+}
+
+impl Default for ReplicatorConfig {
+    fn default() -> Self {
+        Self {
+            num_segments: 4,
+            storage_poll_interval: Duration::from_secs(10),
+            erasure_config: ErasureConfig::default(),
+        }
+    }
+}
+
+/// A ledger segment stored by the replicator
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StoredSegment {
+    /// Entry height of the first entry in the segment
+    pub entry_height: u64,
+    /// Storage last_id of the last challenge a proof was submitted for
+    pub storage_last_id: Option<Hash>,
+    /// Key the segment was encrypted with for that proof
+    pub key: Option<Signature>,
+}
+
+/// What a replicator stores, persisted in its ledger directory so that it
+/// can resume after a restart
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ReplicatorState {
+    pub id: Pubkey,
+    pub segments: Vec<StoredSegment>,
+}
+
+impl ReplicatorState {
+    pub fn new(id: Pubkey) -> Self {
+        Self {
+            id,
+            segments: vec![],
+        }
+    }
+
+    pub fn load(ledger_path: &str) -> io::Result<Self> {
+        let file = File::open(&Path::new(ledger_path).join(REPLICATOR_STATE_FILE))?;
+        let state = serde_json::from_reader(file)?;
+        Ok(state)
+    }
+
+    /// Write the state through a temporary file, so a crash never leaves a
+    /// truncated state behind
+    pub fn write(&self, ledger_path: &str) -> io::Result<()> {
+        let path = Path::new(ledger_path).join(REPLICATOR_STATE_FILE);
+        let tmp_path = path.with_extension("tmp");
+        let serialized = serde_json::to_string(self)?;
+        {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(&serialized.into_bytes())?;
+            file.sync_all()?;
+        }
+        fs::rename(tmp_path, path)
+    }
+
+    /// Pick new segments, until `num_segments` are held or every segment of
+    /// a ledger `storage_entry_height` entries long is taken.  Segments are
+    /// picked by signing the current storage last_id.
+    pub fn add_segments(
+        &mut self,
+        keypair: &Keypair,
+        storage_last_id: &Hash,
+        storage_entry_height: u64,
+        num_segments: usize,
+    ) {
+        let max_segments = get_segment_from_entry(storage_entry_height) as usize;
+        let num_segments = cmp::min(num_segments, max_segments);
+        let max_attempts = 4 * num_segments as u64;
+
+        let mut attempt = 0;
+        while self.segments.len() < num_segments && attempt < max_attempts {
+            let signature = sign_segment_message(keypair, storage_last_id, attempt);
+            let (entry_height, _) =
+                get_entry_heights_from_last_id(&signature, storage_entry_height);
+            if self
+                .segments
+                .iter()
+                .all(|segment| segment.entry_height != entry_height)
+            {
+                self.segments.push(StoredSegment {
+                    entry_height,
+                    storage_last_id: None,
+                    key: None,
+                });
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Entry height the ledger must be downloaded to, to hold every segment
+    pub fn max_entry_height(&self) -> u64 {
+        self.segments
+            .iter()
+            .map(|segment| segment.entry_height + ENTRIES_PER_SEGMENT)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
 pub struct Replicator {
     gossip_service: GossipService,
     fetch_stage: BlobFetchStage,
     t_window: JoinHandle<()>,
+    t_storage: JoinHandle<()>,
     pub retransmit_receiver: BlobReceiver,
     exit: Arc<AtomicBool>,
     entry_height: u64,
+    segments: Vec<u64>,
 }
 
 pub fn sample_file(in_path: &Path, sample_offsets: &[u64]) -> io::Result<Hash> {
     let in_file = File::open(in_path)?;
     let metadata = in_file.metadata()?;
-    let mut buffer_file = BufReader::new(in_file);
+    sample_data(BufReader::new(in_file), metadata.len(), sample_offsets)
+}
 
+/// Hash the `Hash`-sized samples at `sample_offsets` of `data`, which is `data_len` bytes long
+pub fn sample_data<R: Read + Seek>(
+    mut data: R,
+    data_len: u64,
+    sample_offsets: &[u64],
+) -> io::Result<Hash> {
     let mut hasher = Hasher::default();
     let sample_size = size_of::<Hash>();
     let sample_size64 = sample_size as u64;
     let mut buf = vec![0; sample_size];
 
-    let file_len = metadata.len();
-    if file_len < sample_size64 {
+    if data_len < sample_size64 {
         return Err(Error::new(ErrorKind::Other, "file too short!"));
     }
     for offset in sample_offsets {
-        if *offset > (file_len - sample_size64) / sample_size64 {
+        if *offset > (data_len - sample_size64) / sample_size64 {
             return Err(Error::new(ErrorKind::Other, "offset too large"));
         }
-        buffer_file.seek(SeekFrom::Start(*offset * sample_size64))?;
+        data.seek(SeekFrom::Start(*offset * sample_size64))?;
         trace!("sampling @ {} ", *offset);
-        match buffer_file.read(&mut buf) {
+        match data.read(&mut buf) {
             Ok(size) => {
                 assert_eq!(size, buf.len());
                 hasher.hash(&buf);
@@ -80,10 +207,21 @@ pub fn sample_file(in_path: &Path, sample_offsets: &[u64]) -> io::Result<Hash> {
     Ok(hasher.result())
 }
 
-fn get_entry_heights_from_last_id(
-    signature: &ring::signature::Signature,
-    storage_entry_height: u64,
-) -> (u64, u64) {
+// Sign `storage_last_id` followed by `value`
+fn sign_segment_message(keypair: &Keypair, storage_last_id: &Hash, value: u64) -> Signature {
+    keypair.sign_message(&segment_message(storage_last_id, value))
+}
+
+/// The `Hash` encoded by a base-58 JSON string
+fn parse_hash(value: &serde_json::Value) -> Option<Hash> {
+    let bytes = bs58::decode(value.as_str()?).into_vec().ok()?;
+    if bytes.len() != size_of::<Hash>() {
+        return None;
+    }
+    Some(Hash::new(&bytes))
+}
+
+fn get_entry_heights_from_last_id(signature: &Signature, storage_entry_height: u64) -> (u64, u64) {
     let signature_vec = signature.as_ref();
     let mut segment_index = u64::from(signature_vec[0])
         | (u64::from(signature_vec[1]) << 8)
@@ -97,24 +235,30 @@ fn get_entry_heights_from_last_id(
     (entry_height, max_entry_height)
 }
 
+fn encrypted_segment_path(ledger_path: &str, entry_height: u64) -> PathBuf {
+    Path::new(ledger_path).join(format!("ledger-{}.enc", entry_height))
+}
+
 impl Replicator {
     /// Returns a Result that contains a replicator on success
     ///
     /// # Arguments
-    /// * `ledger_path` - path to where the ledger will be stored.
+    /// * `ledger_path` - path to where the ledger and the replicator state will be stored.
     /// Causes panic if none
     /// * `node` - The replicator node
     /// * `leader_info` - NodeInfo representing the leader
     /// * `keypair` - Keypair for this replicator
     /// * `timeout` - (optional) timeout for polling for leader/downloading the ledger. Defaults to
     /// 30 seconds
+    /// * `config` - number of segments to store and how often to look for new challenges
     #[allow(clippy::new_ret_no_self)]
     pub fn new(
         ledger_path: &str,
         node: Node,
         leader_info: &NodeInfo,
-        keypair: &Keypair,
+        keypair: &Arc<Keypair>,
         timeout: Option<Duration>,
+        config: &ReplicatorConfig,
     ) -> Result<Self> {
         let exit = Arc::new(AtomicBool::new(false));
         let done = Arc::new(AtomicBool::new(false));
@@ -122,7 +266,10 @@ impl Replicator {
 
         info!("Replicator: id: {}", keypair.pubkey());
         info!("Creating cluster info....");
-        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new(node.info.clone())));
+        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_keypair(
+            node.info.clone(),
+            keypair.clone(),
+        )));
 
         let leader_pubkey = leader_info.id;
         {
@@ -136,10 +283,38 @@ impl Replicator {
         // DbLedger. Note for now, this ledger will not contain any of the existing entries
         // in the ledger located at ledger_path, and will only append on newly received
         // entries after being passed to window_service
+        // Erasure sets must match the cluster's, or recovered blobs come out wrong
+        let erasure_config = match GenesisBlock::load(ledger_path) {
+            Ok(genesis_block) => genesis_block.erasure_config,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {
+                warn!(
+                    "no genesis block in {}, assuming erasure sets of {:?}",
+                    ledger_path, config.erasure_config
+                );
+                config.erasure_config
+            }
+            Err(e) => Err(e)?,
+        };
         let db_ledger = Arc::new(
-            DbLedger::open(ledger_path).expect("Expected to be able to open database ledger"),
+            DbLedger::open_with_config(ledger_path, erasure_config)
+                .expect("Expected to be able to open database ledger"),
         );
 
+        let mut state = match ReplicatorState::load(ledger_path) {
+            Ok(state) => {
+                if state.id != keypair.pubkey() {
+                    Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("{} belongs to replicator {}", ledger_path, state.id),
+                    ))?;
+                }
+                info!("Resuming with {} stored segments", state.segments.len());
+                state
+            }
+            Err(ref e) if e.kind() == ErrorKind::NotFound => ReplicatorState::new(keypair.pubkey()),
+            Err(e) => Err(e)?,
+        };
+
         let gossip_service = GossipService::new(
             &cluster_info,
             Some(db_ledger.clone()),
@@ -153,13 +328,34 @@ impl Replicator {
         info!("Got leader: {:?}", leader);
 
         let (storage_last_id, storage_entry_height) =
-            Self::poll_for_last_id_and_entry_height(&cluster_info)?;
+            Self::poll_for_last_id_and_entry_height(&cluster_info, &exit)?;
 
-        let signature = keypair.sign(storage_last_id.as_ref());
-        let (entry_height, max_entry_height) =
-            get_entry_heights_from_last_id(&signature, storage_entry_height);
-
-        info!("replicating entry_height: {}", entry_height);
+        state.add_segments(
+            keypair,
+            &storage_last_id,
+            storage_entry_height,
+            config.num_segments,
+        );
+        if state.segments.is_empty() {
+            Err(Error::new(
+                ErrorKind::Other,
+                "No ledger segments to replicate",
+            ))?;
+        }
+        state.write(ledger_path)?;
+
+        let segments: Vec<_> = state
+            .segments
+            .iter()
+            .map(|segment| segment.entry_height)
+            .collect();
+        let entry_height = segments[0];
+        let max_entry_height = state.max_entry_height();
+
+        info!(
+            "replicating segments: {:?} max_entry_height: {}",
+            segments, max_entry_height
+        );
 
         let repair_socket = Arc::new(node.sockets.repair);
         let mut blob_sockets: Vec<Arc<UdpSocket>> =
@@ -191,6 +387,12 @@ impl Replicator {
             exit.clone(),
         );
 
+        // A restarted replicator may already hold everything it needs
+        let consumed = db_ledger.meta()?.map_or(0, |meta| meta.consumed);
+        if consumed > max_entry_height {
+            done.store(true, Ordering::Relaxed);
+        }
+
         info!("window created, waiting for ledger download done");
         let start = Instant::now();
         let mut received_so_far = 0;
@@ -223,66 +425,140 @@ impl Replicator {
         Self::get_airdrop_tokens(&mut client, keypair, &leader_info);
         info!("Done downloading ledger at {}", ledger_path);
 
-        let ledger_path = Path::new(ledger_path);
-        let ledger_data_file_encrypted = ledger_path.join("ledger.enc");
-        let mut sampling_offsets = Vec::new();
+        Self::answer_challenges(
+            &db_ledger,
+            ledger_path,
+            keypair,
+            &mut client,
+            &mut state,
+            &storage_last_id,
+        );
+
+        let t_storage = {
+            let exit = exit.clone();
+            let keypair = keypair.clone();
+            let ledger_path = ledger_path.to_string();
+            let storage_poll_interval = config.storage_poll_interval;
+            Builder::new()
+                .name("solana-replicator-storage".to_string())
+                .spawn(move || loop {
+                    let start = Instant::now();
+                    while start.elapsed() < storage_poll_interval {
+                        if exit.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        sleep(Duration::from_millis(100));
+                    }
+
+                    match Self::poll_for_last_id_and_entry_height(&cluster_info, &exit) {
+                        Ok((storage_last_id, _)) => Self::answer_challenges(
+                            &db_ledger,
+                            &ledger_path,
+                            &keypair,
+                            &mut client,
+                            &mut state,
+                            &storage_last_id,
+                        ),
+                        Err(e) => info!("Error polling for the storage challenge: {:?}", e),
+                    }
+                })
+                .unwrap()
+        };
+
+        Ok(Self {
+            gossip_service,
+            fetch_stage,
+            t_window,
+            t_storage,
+            retransmit_receiver,
+            exit,
+            entry_height,
+            segments,
+        })
+    }
+
+    /// Submit a proof for every stored segment that hasn't answered the
+    /// challenge identified by `storage_last_id` yet.  The state is written
+    /// after each proof so that a restart doesn't resubmit it.
+    fn answer_challenges(
+        db_ledger: &Arc<DbLedger>,
+        ledger_path: &str,
+        keypair: &Keypair,
+        client: &mut ThinClient,
+        state: &mut ReplicatorState,
+        storage_last_id: &Hash,
+    ) {
+        for i in 0..state.segments.len() {
+            let entry_height = state.segments[i].entry_height;
+            if state.segments[i].storage_last_id == Some(*storage_last_id) {
+                continue;
+            }
+
+            match Self::submit_proof(
+                db_ledger,
+                ledger_path,
+                keypair,
+                client,
+                storage_last_id,
+                entry_height,
+            ) {
+                Ok(key) => {
+                    state.segments[i].storage_last_id = Some(*storage_last_id);
+                    state.segments[i].key = Some(key);
+                    if let Err(e) = state.write(ledger_path) {
+                        warn!("Error writing replicator state: {:?}", e);
+                    }
+                }
+                Err(e) => info!(
+                    "Error occurred while proving segment {}: {:?}",
+                    entry_height, e
+                ),
+            }
+        }
+    }
 
+    /// Encrypt the segment starting at `entry_height` with a key derived from
+    /// `storage_last_id`, sample it and submit the mining proof.  Returns the key.
+    #[cfg_attr(not(feature = "chacha"), allow(unused_variables))]
+    fn submit_proof(
+        db_ledger: &Arc<DbLedger>,
+        ledger_path: &str,
+        keypair: &Keypair,
+        client: &mut ThinClient,
+        storage_last_id: &Hash,
+        entry_height: u64,
+    ) -> Result<Signature> {
+        let key = sign_segment_message(keypair, storage_last_id, entry_height);
+        let ledger_data_file_encrypted = encrypted_segment_path(ledger_path, entry_height);
         #[cfg(not(feature = "chacha"))]
-        sampling_offsets.push(0);
+        let sampling_offsets = vec![0];
 
         #[cfg(feature = "chacha")]
-        {
-            use crate::storage_stage::NUM_STORAGE_SAMPLES;
-            use rand::{Rng, SeedableRng};
-            use rand_chacha::ChaChaRng;
+        let sampling_offsets = {
+            use crate::storage_stage::get_sample_offsets;
 
             let mut ivec = [0u8; 64];
-            ivec.copy_from_slice(signature.as_ref());
+            ivec.copy_from_slice(key.as_ref());
 
             let num_encrypted_bytes = chacha_cbc_encrypt_ledger(
-                &db_ledger,
+                db_ledger,
                 entry_height,
                 &ledger_data_file_encrypted,
                 &mut ivec,
             )?;
 
-            let num_chacha_blocks = num_encrypted_bytes / CHACHA_BLOCK_SIZE;
-            let mut rng_seed = [0u8; 32];
-            rng_seed.copy_from_slice(&signature.as_ref()[0..32]);
-            let mut rng = ChaChaRng::from_seed(rng_seed);
-            for _ in 0..NUM_STORAGE_SAMPLES {
-                sampling_offsets.push(rng.gen_range(0, num_chacha_blocks) as u64);
-            }
-        }
-
-        info!("Done encrypting the ledger");
+            get_sample_offsets(&key, num_encrypted_bytes / CHACHA_BLOCK_SIZE)
+        };
 
-        match sample_file(&ledger_data_file_encrypted, &sampling_offsets) {
-            Ok(hash) => {
-                let last_id = client.get_last_id();
-                info!("sampled hash: {}", hash);
-                let mut tx = StorageTransaction::new_mining_proof(
-                    &keypair,
-                    hash,
-                    last_id,
-                    entry_height,
-                    Signature::new(signature.as_ref()),
-                );
-                client
-                    .retry_transfer(&keypair, &mut tx, 10)
-                    .expect("transfer didn't work!");
-            }
-            Err(e) => info!("Error occurred while sampling: {:?}", e),
-        }
+        info!("Done encrypting segment {}", entry_height);
 
-        Ok(Self {
-            gossip_service,
-            fetch_stage,
-            t_window,
-            retransmit_receiver,
-            exit,
-            entry_height,
-        })
+        let hash = sample_file(&ledger_data_file_encrypted, &sampling_offsets)?;
+        let last_id = client.get_last_id();
+        info!("sampled hash: {}", hash);
+        let mut tx =
+            StorageTransaction::new_mining_proof(keypair, hash, last_id, entry_height, key);
+        client.retry_transfer(keypair, &mut tx, 10)?;
+        Ok(key)
     }
 
     pub fn close(self) {
@@ -294,6 +570,7 @@ impl Replicator {
         self.gossip_service.join().unwrap();
         self.fetch_stage.join().unwrap();
         self.t_window.join().unwrap();
+        self.t_storage.join().unwrap();
 
         // Drain the queue here to prevent self.retransmit_receiver from being dropped
         // before the window_service thread is joined
@@ -308,6 +585,11 @@ impl Replicator {
         self.entry_height
     }
 
+    /// Entry heights of the segments this replicator stores
+    pub fn segments(&self) -> &[u64] {
+        &self.segments
+    }
+
     fn poll_for_leader(
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         timeout: Duration,
@@ -333,30 +615,55 @@ impl Replicator {
 
     fn poll_for_last_id_and_entry_height(
         cluster_info: &Arc<RwLock<ClusterInfo>>,
-    ) -> Result<(String, u64)> {
+        exit: &Arc<AtomicBool>,
+    ) -> Result<(Hash, u64)> {
         for _ in 0..10 {
+            if exit.load(Ordering::Relaxed) {
+                Err(Error::new(
+                    ErrorKind::Interrupted,
+                    "Exited while polling for last_id and entry_height",
+                ))?;
+            }
+
             let rpc_client = {
                 let cluster_info = cluster_info.read().unwrap();
                 let rpc_peers = cluster_info.rpc_peers();
                 debug!("rpc peers: {:?}", rpc_peers);
-                let node_idx = thread_rng().gen_range(0, rpc_peers.len());
-                RpcClient::new_from_socket(rpc_peers[node_idx].rpc)
+                if rpc_peers.is_empty() {
+                    None
+                } else {
+                    let node_idx = thread_rng().gen_range(0, rpc_peers.len());
+                    Some(RpcClient::new_from_socket(rpc_peers[node_idx].rpc))
+                }
             };
 
-            let storage_last_id = rpc_client
-                .make_rpc_request(2, RpcRequest::GetStorageMiningLastId, None)
-                .expect("rpc request")
-                .to_string();
-            let storage_entry_height = rpc_client
-                .make_rpc_request(2, RpcRequest::GetStorageMiningEntryHeight, None)
-                .expect("rpc request")
-                .as_u64()
-                .unwrap();
-            if get_segment_from_entry(storage_entry_height) != 0 {
-                return Ok((storage_last_id, storage_entry_height));
+            // The replicator keeps polling for as long as it runs, so a
+            // failed request is retried rather than fatal
+            if let Some(rpc_client) = rpc_client {
+                match (
+                    rpc_client.make_rpc_request(2, RpcRequest::GetStorageMiningLastId, None),
+                    rpc_client.make_rpc_request(2, RpcRequest::GetStorageMiningEntryHeight, None),
+                ) {
+                    (Ok(storage_last_id), Ok(storage_entry_height)) => {
+                        let storage_entry_height = storage_entry_height.as_u64().unwrap_or(0);
+                        match parse_hash(&storage_last_id) {
+                            Some(storage_last_id)
+                                if get_segment_from_entry(storage_entry_height) != 0 =>
+                            {
+                                return Ok((storage_last_id, storage_entry_height));
+                            }
+                            Some(_) => info!("max entry_height: {}", storage_entry_height),
+                            None => info!("invalid storage last_id: {}", storage_last_id),
+                        }
+                    }
+                    (Err(e), _) | (_, Err(e)) => info!("rpc request failed: {:?}", e),
+                }
+            }
+
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_secs(3) && !exit.load(Ordering::Relaxed) {
+                sleep(Duration::from_millis(100));
             }
-            info!("max entry_height: {}", storage_entry_height);
-            sleep(Duration::from_secs(3));
         }
         Err(Error::new(
             ErrorKind::Other,
@@ -395,14 +702,16 @@ impl Replicator {
 
 #[cfg(test)]
 mod tests {
-    use crate::replicator::sample_file;
-    use solana_sdk::hash::Hash;
+    use crate::replicator::{sample_file, ReplicatorState, REPLICATOR_STATE_FILE};
+    use crate::storage_stage::ENTRIES_PER_SEGMENT;
+    use solana_sdk::hash::{hash, Hash};
     use solana_sdk::signature::{Keypair, KeypairUtil};
     use std::fs::File;
-    use std::fs::{create_dir_all, remove_file};
+    use std::fs::{create_dir_all, remove_dir_all, remove_file};
+    use std::io::ErrorKind;
     use std::io::Write;
     use std::mem::size_of;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
     fn tmp_file_path(name: &str) -> PathBuf {
         use std::env;
@@ -467,4 +776,57 @@ mod tests {
         let res = sample_file(&in_path, &samples);
         assert!(res.is_err());
     }
+
+    #[test]
+    fn test_replicator_state_add_segments() {
+        let keypair = Keypair::new();
+        let storage_entry_height = 10 * ENTRIES_PER_SEGMENT;
+        let mut state = ReplicatorState::new(keypair.pubkey());
+        state.add_segments(&keypair, &hash(b"last_id"), storage_entry_height, 4);
+        assert!(!state.segments.is_empty());
+        assert!(state.segments.len() <= 4);
+        for (i, segment) in state.segments.iter().enumerate() {
+            assert_eq!(segment.entry_height % ENTRIES_PER_SEGMENT, 0);
+            assert!(segment.entry_height < storage_entry_height);
+            assert!(state.segments[..i]
+                .iter()
+                .all(|other| other.entry_height != segment.entry_height));
+        }
+        assert_eq!(
+            state.max_entry_height(),
+            state
+                .segments
+                .iter()
+                .map(|segment| segment.entry_height)
+                .max()
+                .unwrap()
+                + ENTRIES_PER_SEGMENT
+        );
+
+        // Held segments are kept, and there can't be more than the ledger has
+        let held = state.segments.clone();
+        state.add_segments(&keypair, &hash(b"another_last_id"), ENTRIES_PER_SEGMENT, 4);
+        assert_eq!(state.segments, held);
+    }
+
+    #[test]
+    fn test_replicator_state_write_load() {
+        let ledger_path = tmp_file_path("test_replicator_state_write_load");
+        create_dir_all(&ledger_path).unwrap();
+        let ledger_path = ledger_path.to_str().unwrap();
+
+        let err = ReplicatorState::load(ledger_path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        let keypair = Keypair::new();
+        let mut state = ReplicatorState::new(keypair.pubkey());
+        state.add_segments(&keypair, &hash(b"last_id"), 4 * ENTRIES_PER_SEGMENT, 2);
+        state.segments[0].storage_last_id = Some(hash(b"last_id"));
+        state.segments[0].key = Some(keypair.sign_message(b"key"));
+        state.write(ledger_path).unwrap();
+
+        assert_eq!(ReplicatorState::load(ledger_path).unwrap(), state);
+        assert!(Path::new(ledger_path).join(REPLICATOR_STATE_FILE).exists());
+        remove_dir_all(ledger_path).unwrap();
+    }
 }
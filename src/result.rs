@@ -3,7 +3,6 @@
 use crate::bank;
 use crate::cluster_info;
 use crate::db_ledger;
-#[cfg(feature = "erasure")]
 use crate::erasure;
 use crate::packet;
 use crate::poh_recorder;
@@ -24,7 +23,6 @@ pub enum Error {
     BankError(bank::BankError),
     ClusterInfoError(cluster_info::ClusterInfoError),
     BlobError(packet::BlobError),
-    #[cfg(feature = "erasure")]
     ErasureError(erasure::ErasureError),
     SendError,
     PohRecorderError(poh_recorder::PohRecorderError),
@@ -61,7 +59,6 @@ impl std::convert::From<cluster_info::ClusterInfoError> for Error {
         Error::ClusterInfoError(e)
     }
 }
-#[cfg(feature = "erasure")]
 impl std::convert::From<erasure::ErasureError> for Error {
     fn from(e: erasure::ErasureError) -> Error {
         Error::ErasureError(e)
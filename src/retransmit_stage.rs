@@ -1,7 +1,7 @@
 //! The `retransmit_stage` retransmits blobs between validators
 
 use crate::bank::Bank;
-use crate::cluster_info::{ClusterInfo, DATA_PLANE_FANOUT, GROW_LAYER_CAPACITY, NEIGHBORHOOD_SIZE};
+use crate::cluster_info::{ClusterInfo, NodeInfo, RetransmitTree};
 use crate::counter::Counter;
 use crate::db_ledger::DbLedger;
 use crate::entry::Entry;
@@ -10,8 +10,10 @@ use crate::result::{Error, Result};
 use crate::service::Service;
 use crate::streamer::BlobReceiver;
 use crate::window_service::window_service;
+use hashbrown::HashMap;
 use log::Level;
 use solana_metrics::{influxdb, submit};
+use solana_sdk::pubkey::Pubkey;
 use std::net::UdpSocket;
 use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::sync::mpsc::RecvTimeoutError;
@@ -38,48 +40,57 @@ fn retransmit(
             .to_owned(),
     );
 
-    // TODO layer 2 logic here
-    // 1 - find out if I am in layer 1 first
-    // 1.1 - If yes, then broadcast to all layer 1 nodes
-    //      1 - using my layer 1 index, broadcast to all layer 2 nodes assuming you know neighborhood size
-    // 1.2 - If no, then figure out what layer I am in and who my neighbors are and only broadcast to them
-    //      1 - also check if there are nodes in lower layers and repeat the layer 1 to layer 2 logic
-    let peers = cluster_info.read().unwrap().sorted_retransmit_peers(bank);
-    let my_id = cluster_info.read().unwrap().id();
-    //calc num_layers and num_neighborhoods using the total number of nodes
-    let (num_layers, layer_indices) = ClusterInfo::describe_data_plane(
-        peers.len(),
-        DATA_PLANE_FANOUT,
-        NEIGHBORHOOD_SIZE,
-        GROW_LAYER_CAPACITY,
-    );
-    if num_layers <= 1 {
-        /* single layer data plane */
-        for b in &mut dq {
-            ClusterInfo::retransmit(&cluster_info, b, sock)?;
-        }
-    } else {
-        //find my index (my ix is the same as the first node with smaller stake)
-        let my_index = peers
-            .iter()
-            .position(|ci| bank.get_balance(&ci.id) <= bank.get_balance(&my_id));
-        //find my layer
-        let locality = ClusterInfo::localize(
-            &layer_indices,
-            NEIGHBORHOOD_SIZE,
-            my_index.unwrap_or(peers.len() - 1),
-        );
-        let mut retransmit_peers =
-            peers[locality.neighbor_bounds.0..locality.neighbor_bounds.1].to_vec();
-        locality.child_layer_peers.iter().for_each(|&ix| {
-            if let Some(peer) = peers.get(ix) {
-                retransmit_peers.push(peer.clone());
+    let (me, peers) = {
+        let cluster_info = cluster_info.read().unwrap();
+        (
+            cluster_info.my_data().clone(),
+            cluster_info.retransmit_peers(),
+        )
+    };
+    let mut stakes: Option<(u64, HashMap<Pubkey, u64>)> = None;
+    // The nodes of a slot's trees are only sorted by id and staked once, each blob then
+    // orders them by its own keys
+    let mut tree: Option<(u64, Vec<NodeInfo>, RetransmitTree)> = None;
+    for b in &mut dq {
+        let (slot, index) = {
+            let blob = b.read().unwrap();
+            (blob.slot(), blob.index())
+        };
+        if tree
+            .as_ref()
+            .map_or(true, |(tree_slot, _, _)| *tree_slot != slot)
+        {
+            let leader_scheduler = bank.leader_scheduler.read().unwrap();
+            let epoch = leader_scheduler.slot_to_epoch(slot);
+            if stakes
+                .as_ref()
+                .map_or(true, |(stakes_epoch, _)| *stakes_epoch != epoch)
+            {
+                stakes = Some((epoch, ClusterInfo::epoch_stakes(&leader_scheduler, epoch)));
             }
-        });
+            let (_, stakes) = stakes.as_ref().unwrap();
 
-        for b in &mut dq {
-            ClusterInfo::retransmit_to(&cluster_info, &retransmit_peers, b, sock)?;
+            // The slot's leader sends each blob to the root of the tree formed by all the
+            // other nodes
+            let leader_id = leader_scheduler.get_leader_for_slot(slot);
+            let mut nodes: Vec<_> = peers
+                .iter()
+                .filter(|peer| Some(peer.id) != leader_id)
+                .cloned()
+                .collect();
+            nodes.push(me.clone());
+            let slot_tree = RetransmitTree::new(&nodes, stakes);
+            tree = Some((slot, nodes, slot_tree));
         }
+        let (_, nodes, slot_tree) = tree.as_ref().unwrap();
+        let order = slot_tree.order(slot, index);
+        let my_index = order.iter().position(|i| nodes[*i].id == me.id).unwrap();
+        let retransmit_peers: Vec<_> =
+            ClusterInfo::retransmit_tree_peer_indices(order.len(), my_index)
+                .into_iter()
+                .map(|i| nodes[order[i]].clone())
+                .collect();
+        ClusterInfo::retransmit_to(&cluster_info, &retransmit_peers, b, sock)?;
     }
     Ok(())
 }
@@ -11,8 +11,12 @@ use bincode::{deserialize, serialize};
 use bs58;
 use solana_drone::drone::request_airdrop_transaction;
 use solana_sdk::account::Account;
+use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
+use solana_sdk::storage_program::{
+    is_proof_valid, ProofInfo, StorageProgramState, ENTRIES_PER_SEGMENT,
+};
 use solana_sdk::transaction::Transaction;
 use std::mem;
 use std::net::{SocketAddr, UdpSocket};
@@ -139,6 +143,88 @@ impl FromStr for RpcSignatureStatus {
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum RpcStorageProofStatus {
+    /// Submitted this storage epoch, validators check it during the next one
+    Submitted,
+    /// Being checked by validators this storage epoch
+    Validating,
+    Valid,
+    NotValid,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RpcStorageProof {
+    pub entry_height: u64,
+    pub signature: String,
+    pub sha_state: String,
+    pub status: RpcStorageProofStatus,
+}
+
+impl RpcStorageProof {
+    fn new(segment: usize, proof: &ProofInfo, status: RpcStorageProofStatus) -> Self {
+        RpcStorageProof {
+            entry_height: segment as u64 * ENTRIES_PER_SEGMENT,
+            signature: bs58::encode(proof.signature).into_string(),
+            sha_state: bs58::encode(proof.sha_state).into_string(),
+            status,
+        }
+    }
+}
+
+/// Proofs submitted by `pubkey`, oldest first, along with where each one is
+/// in validation
+pub fn get_storage_proofs(state: &StorageProgramState, pubkey: &Pubkey) -> Vec<RpcStorageProof> {
+    let mut storage_proofs = vec![];
+    for (segment, proofs) in state.reward_proofs.iter().enumerate() {
+        let validations = state
+            .reward_validations
+            .get(segment)
+            .map_or(&[][..], |validations| &validations[..]);
+        for (i, proof) in proofs.iter().enumerate() {
+            if proof.id == *pubkey {
+                let status = if is_proof_valid(validations, i) {
+                    RpcStorageProofStatus::Valid
+                } else {
+                    RpcStorageProofStatus::NotValid
+                };
+                storage_proofs.push(RpcStorageProof::new(segment, proof, status));
+            }
+        }
+    }
+    for (proofs, status) in &[
+        (&state.previous_proofs, RpcStorageProofStatus::Validating),
+        (&state.proofs, RpcStorageProofStatus::Submitted),
+    ] {
+        for (segment, proofs) in proofs.iter().enumerate() {
+            for proof in proofs.iter().filter(|proof| proof.id == *pubkey) {
+                storage_proofs.push(RpcStorageProof::new(segment, proof, *status));
+            }
+        }
+    }
+    storage_proofs
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RpcEpochInfo {
+    /// The current epoch
+    pub epoch: u64,
+    /// The current slot, relative to the start of the current epoch
+    pub slot_index: u64,
+    pub slots_in_epoch: u64,
+    pub absolute_slot: u64,
+    pub tick_height: u64,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RpcLeaderSchedule {
+    pub epoch: u64,
+    /// The slot led by the first leader in `leaders`
+    pub first_slot: u64,
+    /// The leader of each slot in the epoch, in order
+    pub leaders: Vec<String>,
+}
+
 build_rpc_trait! {
     pub trait RpcSol {
         type Metadata;
@@ -155,12 +241,24 @@ build_rpc_trait! {
         #[rpc(meta, name = "getConfirmationTime")]
         fn get_confirmation_time(&self, Self::Metadata) -> Result<usize>;
 
+        #[rpc(meta, name = "getEpochInfo")]
+        fn get_epoch_info(&self, Self::Metadata) -> Result<RpcEpochInfo>;
+
         #[rpc(meta, name = "getLastId")]
         fn get_last_id(&self, Self::Metadata) -> Result<String>;
 
+        #[rpc(meta, name = "getLastIdAge")]
+        fn get_last_id_age(&self, Self::Metadata, String) -> Result<Option<u64>>;
+
+        #[rpc(meta, name = "getLeaderSchedule")]
+        fn get_leader_schedule(&self, Self::Metadata, u64) -> Result<Option<RpcLeaderSchedule>>;
+
         #[rpc(meta, name = "getSignatureStatus")]
         fn get_signature_status(&self, Self::Metadata, String) -> Result<RpcSignatureStatus>;
 
+        #[rpc(meta, name = "getSlotLeader")]
+        fn get_slot_leader(&self, Self::Metadata) -> Result<String>;
+
         #[rpc(meta, name = "getTransactionCount")]
         fn get_transaction_count(&self, Self::Metadata) -> Result<u64>;
 
@@ -178,6 +276,12 @@ build_rpc_trait! {
 
         #[rpc(meta, name = "getStoragePubkeysForEntryHeight")]
         fn get_storage_pubkeys_for_entry_height(&self, Self::Metadata, u64) -> Result<Vec<Pubkey>>;
+
+        #[rpc(meta, name = "getStorageProofs")]
+        fn get_storage_proofs(&self, Self::Metadata, String) -> Result<Vec<RpcStorageProof>>;
+
+        #[rpc(meta, name = "getStoragePendingRewards")]
+        fn get_storage_pending_rewards(&self, Self::Metadata, String) -> Result<u64>;
     }
 }
 
@@ -211,10 +315,33 @@ impl RpcSol for RpcSolImpl {
             .unwrap()
             .get_confirmation_time()
     }
+    fn get_epoch_info(&self, meta: Self::Metadata) -> Result<RpcEpochInfo> {
+        info!("get_epoch_info rpc request received");
+        meta.request_processor.read().unwrap().get_epoch_info()
+    }
     fn get_last_id(&self, meta: Self::Metadata) -> Result<String> {
         info!("get_last_id rpc request received");
         meta.request_processor.read().unwrap().get_last_id()
     }
+    fn get_last_id_age(&self, meta: Self::Metadata, id: String) -> Result<Option<u64>> {
+        info!("get_last_id_age rpc request received: {:?}", id);
+        let last_id = verify_hash(&id)?;
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_last_id_age(last_id)
+    }
+    fn get_leader_schedule(
+        &self,
+        meta: Self::Metadata,
+        epoch: u64,
+    ) -> Result<Option<RpcLeaderSchedule>> {
+        info!("get_leader_schedule rpc request received: {:?}", epoch);
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_leader_schedule(epoch)
+    }
     fn get_signature_status(&self, meta: Self::Metadata, id: String) -> Result<RpcSignatureStatus> {
         info!("get_signature_status rpc request received: {:?}", id);
         let signature = verify_signature(&id)?;
@@ -242,6 +369,10 @@ impl RpcSol for RpcSolImpl {
         info!("get_signature_status rpc request status: {:?}", status);
         Ok(status)
     }
+    fn get_slot_leader(&self, meta: Self::Metadata) -> Result<String> {
+        info!("get_slot_leader rpc request received");
+        meta.request_processor.read().unwrap().get_slot_leader()
+    }
     fn get_transaction_count(&self, meta: Self::Metadata) -> Result<u64> {
         info!("get_transaction_count rpc request received");
         meta.request_processor
@@ -346,6 +477,20 @@ impl RpcSol for RpcSolImpl {
             .unwrap()
             .get_storage_pubkeys_for_entry_height(entry_height)
     }
+    fn get_storage_proofs(&self, meta: Self::Metadata, id: String) -> Result<Vec<RpcStorageProof>> {
+        let pubkey = verify_pubkey(id)?;
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_storage_proofs(&pubkey)
+    }
+    fn get_storage_pending_rewards(&self, meta: Self::Metadata, id: String) -> Result<u64> {
+        let pubkey = verify_pubkey(id)?;
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_storage_pending_rewards(&pubkey)
+    }
 }
 #[derive(Clone)]
 pub struct JsonRpcRequestProcessor {
@@ -374,10 +519,47 @@ impl JsonRpcRequestProcessor {
     fn get_confirmation_time(&self) -> Result<usize> {
         Ok(self.bank.confirmation_time())
     }
+    fn get_epoch_info(&self) -> Result<RpcEpochInfo> {
+        // The tick being produced now, which get_current_leader() schedules for
+        let tick_height = self.bank.tick_height() + 1;
+        let leader_scheduler = self.bank.leader_scheduler.read().unwrap();
+        let epoch = leader_scheduler.tick_height_to_epoch(tick_height);
+        let absolute_slot = leader_scheduler.tick_height_to_slot(tick_height);
+        Ok(RpcEpochInfo {
+            epoch,
+            slot_index: absolute_slot - leader_scheduler.first_slot_in_epoch(epoch),
+            slots_in_epoch: leader_scheduler.num_slots_in_epoch(epoch),
+            absolute_slot,
+            tick_height,
+        })
+    }
     fn get_last_id(&self) -> Result<String> {
         let id = self.bank.last_id();
         Ok(bs58::encode(id).into_string())
     }
+    fn get_last_id_age(&self, last_id: Hash) -> Result<Option<u64>> {
+        Ok(self.bank.last_id_age(&last_id))
+    }
+    fn get_leader_schedule(&self, epoch: u64) -> Result<Option<RpcLeaderSchedule>> {
+        let leader_scheduler = self.bank.leader_scheduler.read().unwrap();
+        Ok(leader_scheduler
+            .get_leader_schedule(epoch)
+            .map(|leaders| RpcLeaderSchedule {
+                epoch,
+                first_slot: leader_scheduler.first_slot_in_epoch(epoch),
+                leaders: leaders.iter().map(|id| id.to_string()).collect(),
+            }))
+    }
+    fn get_slot_leader(&self) -> Result<String> {
+        match self.bank.get_current_leader() {
+            Some((id, _)) => Ok(id.to_string()),
+            None => Err(Error {
+                code: ErrorCode::InternalError,
+                message: "No leader scheduled".into(),
+                data: None,
+            }),
+        }
+    }
     pub fn get_signature_status(&self, signature: Signature) -> Option<bank::Result<()>> {
         self.bank.get_signature_status(&signature)
     }
@@ -397,6 +579,13 @@ impl JsonRpcRequestProcessor {
             .storage_state
             .get_pubkeys_for_entry_height(entry_height))
     }
+    fn get_storage_proofs(&self, pubkey: &Pubkey) -> Result<Vec<RpcStorageProof>> {
+        let state = self.bank.get_storage_program_state();
+        Ok(get_storage_proofs(&state, pubkey))
+    }
+    fn get_storage_pending_rewards(&self, pubkey: &Pubkey) -> Result<u64> {
+        Ok(self.bank.get_storage_program_state().pending_reward(pubkey))
+    }
 }
 
 fn get_leader_addr(cluster_info: &Arc<RwLock<ClusterInfo>>) -> Result<SocketAddr> {
@@ -427,6 +616,19 @@ fn verify_pubkey(input: String) -> Result<Pubkey> {
     }
 }
 
+fn verify_hash(input: &str) -> Result<Hash> {
+    let hash_vec = bs58::decode(input).into_vec().map_err(|err| {
+        info!("verify_hash: invalid input: {}: {:?}", input, err);
+        Error::invalid_request()
+    })?;
+    if hash_vec.len() != mem::size_of::<Hash>() {
+        info!("verify_hash: invalid hash_vec length: {}", hash_vec.len());
+        Err(Error::invalid_request())
+    } else {
+        Ok(Hash::new(&hash_vec))
+    }
+}
+
 fn verify_signature(input: &str) -> Result<Signature> {
     let signature_vec = bs58::decode(input).into_vec().map_err(|err| {
         info!("verify_signature: invalid input: {}: {:?}", input, err);
@@ -686,6 +888,37 @@ mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_get_last_id_age() {
+        let bob_pubkey = Keypair::new().pubkey();
+        let (io, meta, last_id, _alice) = start_rpc_handler_with_tx(bob_pubkey);
+
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getLastIdAge","params":["{}"]}}"#,
+            last_id
+        );
+        let res = io.handle_request_sync(&req, meta.clone());
+        let expected = format!(r#"{{"jsonrpc":"2.0","result":0,"id":1}}"#);
+        let expected: Response =
+            serde_json::from_str(&expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+
+        // An id the bank doesn't track has no age
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getLastIdAge","params":["{}"]}}"#,
+            hash(&[1])
+        );
+        let res = io.handle_request_sync(&req, meta);
+        let expected = format!(r#"{{"jsonrpc":"2.0","result":null,"id":1}}"#);
+        let expected: Response =
+            serde_json::from_str(&expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
     #[test]
     fn test_rpc_fail_request_airdrop() {
         let bob_pubkey = Keypair::new().pubkey();
@@ -786,4 +1019,152 @@ mod tests {
             Err(Error::invalid_request())
         );
     }
+
+    #[test]
+    fn test_rpc_get_leader_schedule() {
+        let bob_pubkey = Keypair::new().pubkey();
+        let (io, meta, _last_id, _alice) = start_rpc_handler_with_tx(bob_pubkey);
+
+        let req =
+            format!(r#"{{"jsonrpc":"2.0","id":1,"method":"getLeaderSchedule","params":[0]}}"#);
+        let res = io.handle_request_sync(&req, meta.clone());
+        let expected = format!(
+            r#"{{"jsonrpc":"2.0","result":{{"epoch":0,"first_slot":0,"leaders":["{}"]}},"id":1}}"#,
+            Pubkey::default()
+        );
+        let expected: Response =
+            serde_json::from_str(&expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+
+        let req =
+            format!(r#"{{"jsonrpc":"2.0","id":1,"method":"getLeaderSchedule","params":[1]}}"#);
+        let res = io.handle_request_sync(&req, meta.clone());
+        let expected = format!(r#"{{"jsonrpc":"2.0","result":null,"id":1}}"#);
+        let expected: Response =
+            serde_json::from_str(&expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+
+        let req = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"getSlotLeader"}}"#);
+        let res = io.handle_request_sync(&req, meta);
+        let expected = format!(
+            r#"{{"jsonrpc":"2.0","result":"{}","id":1}}"#,
+            Pubkey::default()
+        );
+        let expected: Response =
+            serde_json::from_str(&expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_rpc_request_processor_leader_schedule() {
+        let (genesis_block, _alice) = GenesisBlock::new(10_000);
+        let bank = Bank::new(&genesis_block);
+        let leader_id = Keypair::new().pubkey();
+        bank.leader_scheduler.write().unwrap().bootstrap_leader = leader_id;
+        let tick_height = bank.tick_height() + 1;
+        let request_processor =
+            JsonRpcRequestProcessor::new(Arc::new(bank), StorageState::default());
+
+        assert_eq!(
+            request_processor.get_slot_leader().unwrap(),
+            leader_id.to_string()
+        );
+        assert_eq!(
+            request_processor.get_epoch_info().unwrap(),
+            RpcEpochInfo {
+                epoch: 0,
+                slot_index: 0,
+                slots_in_epoch: 1,
+                absolute_slot: 0,
+                tick_height,
+            }
+        );
+        assert_eq!(
+            request_processor.get_leader_schedule(0).unwrap(),
+            Some(RpcLeaderSchedule {
+                epoch: 0,
+                first_slot: 0,
+                leaders: vec![leader_id.to_string()],
+            })
+        );
+        assert_eq!(request_processor.get_leader_schedule(1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rpc_get_storage_proofs() {
+        let bob_pubkey = Keypair::new().pubkey();
+        let (io, meta, _last_id, _alice) = start_rpc_handler_with_tx(bob_pubkey);
+
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getStorageProofs","params":["{}"]}}"#,
+            bob_pubkey
+        );
+        let res = io.handle_request_sync(&req, meta.clone());
+        let expected = format!(r#"{{"jsonrpc":"2.0","result":[],"id":1}}"#);
+        let expected: Response =
+            serde_json::from_str(&expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getStoragePendingRewards","params":["{}"]}}"#,
+            bob_pubkey
+        );
+        let res = io.handle_request_sync(&req, meta);
+        let expected = format!(r#"{{"jsonrpc":"2.0","result":0,"id":1}}"#);
+        let expected: Response =
+            serde_json::from_str(&expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_get_storage_proofs() {
+        use solana_sdk::storage_program::{ProofStatus, ValidationInfo};
+
+        let replicator = Keypair::new().pubkey();
+        let other = Keypair::new().pubkey();
+        let proof = |id| ProofInfo {
+            id,
+            ..ProofInfo::default()
+        };
+        let validation = |proof_mask| ValidationInfo {
+            id: Keypair::new().pubkey(),
+            stake: 1,
+            proof_mask,
+        };
+
+        let mut state = StorageProgramState::default();
+        state.reward_proofs = vec![vec![proof(replicator), proof(other), proof(replicator)]];
+        state.reward_validations = vec![vec![validation(vec![
+            ProofStatus::Valid,
+            ProofStatus::Valid,
+            ProofStatus::NotValid,
+        ])]];
+        state.previous_proofs = vec![vec![], vec![proof(replicator)]];
+        state.proofs = vec![vec![], vec![], vec![proof(other), proof(replicator)]];
+
+        let statuses: Vec<_> = get_storage_proofs(&state, &replicator)
+            .into_iter()
+            .map(|proof| (proof.entry_height, proof.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (0, RpcStorageProofStatus::Valid),
+                (0, RpcStorageProofStatus::NotValid),
+                (ENTRIES_PER_SEGMENT, RpcStorageProofStatus::Validating),
+                (2 * ENTRIES_PER_SEGMENT, RpcStorageProofStatus::Submitted),
+            ]
+        );
+        assert!(get_storage_proofs(&state, &Keypair::new().pubkey()).is_empty());
+    }
 }
@@ -51,7 +51,20 @@ impl MockRpcClient {
                 let n = if self.addr == "airdrop" { 0 } else { 50 };
                 Value::Number(Number::from(n))
             }
+            RpcRequest::GetEpochInfo => json!({
+                "epoch": 1,
+                "slot_index": 2,
+                "slots_in_epoch": 4,
+                "absolute_slot": 3,
+                "tick_height": 21,
+            }),
             RpcRequest::GetLastId => Value::String(PUBKEY.to_string()),
+            RpcRequest::GetLeaderSchedule => json!({
+                "epoch": 1,
+                "first_slot": 1,
+                "leaders": [PUBKEY, PUBKEY, PUBKEY, PUBKEY],
+            }),
+            RpcRequest::GetSlotLeader => Value::String(PUBKEY.to_string()),
             RpcRequest::GetSignatureStatus => {
                 let str = if self.addr == "account_in_use" {
                     "AccountInUse"
@@ -10,10 +10,12 @@ use crate::jsonrpc_pubsub::{PubSubHandler, Session, SubscriptionId};
 use crate::jsonrpc_ws_server::{RequestContext, Sender, ServerBuilder};
 use crate::rpc::RpcSignatureStatus;
 use crate::service::Service;
+use bincode::deserialize;
 use bs58;
 use solana_sdk::account::Account;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
+use solana_sdk::storage_program::{self, get_segment_from_entry, StorageProgramState};
 use std::collections::HashMap;
 use std::mem;
 use std::net::SocketAddr;
@@ -124,6 +126,16 @@ build_rpc_trait! {
             #[rpc(name = "signatureUnsubscribe")]
             fn signature_unsubscribe(&self, SubscriptionId) -> Result<bool>;
         }
+        #[pubsub(name = "storageNotification")] {
+            // Get notification every time the storage program advertises a
+            // new last_id for replicators to mine with
+            #[rpc(name = "storageSubscribe")]
+            fn storage_subscribe(&self, Self::Metadata, pubsub::Subscriber<RpcStorageNotification>);
+
+            // Unsubscribe from storage notification subscription.
+            #[rpc(name = "storageUnsubscribe")]
+            fn storage_unsubscribe(&self, SubscriptionId) -> Result<bool>;
+        }
     }
 }
 
@@ -137,10 +149,30 @@ impl RpcPubSubBank {
     }
 }
 
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RpcStorageNotification {
+    /// Last id replicators sign to pick their segments and encryption keys
+    pub last_id: String,
+    pub entry_height: u64,
+    pub segment: u64,
+}
+
+impl RpcStorageNotification {
+    fn new(state: &StorageProgramState) -> Self {
+        RpcStorageNotification {
+            last_id: bs58::encode(state.id).into_string(),
+            entry_height: state.entry_height,
+            segment: get_segment_from_entry(state.entry_height) as u64,
+        }
+    }
+}
+
 pub struct RpcSubscriptions {
     account_subscriptions: RwLock<HashMap<Pubkey, HashMap<SubscriptionId, Sink<Account>>>>,
     signature_subscriptions:
         RwLock<HashMap<Signature, HashMap<SubscriptionId, Sink<RpcSignatureStatus>>>>,
+    storage_subscriptions: RwLock<HashMap<SubscriptionId, Sink<RpcStorageNotification>>>,
+    last_storage_notification: RwLock<Option<RpcStorageNotification>>,
 }
 
 impl Default for RpcSubscriptions {
@@ -148,6 +180,8 @@ impl Default for RpcSubscriptions {
         RpcSubscriptions {
             account_subscriptions: Default::default(),
             signature_subscriptions: Default::default(),
+            storage_subscriptions: Default::default(),
+            last_storage_notification: Default::default(),
         }
     }
 }
@@ -160,6 +194,9 @@ impl BankSubscriptions for RpcSubscriptions {
                 sink.notify(Ok(account.clone())).wait().unwrap();
             }
         }
+        if *pubkey == storage_program::system_id() {
+            self.check_storage(account);
+        }
     }
 
     fn check_signature(&self, signature: &Signature, bank_error: &bank::Result<()>) {
@@ -181,6 +218,28 @@ impl BankSubscriptions for RpcSubscriptions {
 }
 
 impl RpcSubscriptions {
+    /// Notify storage subscribers if the storage program advertised a new
+    /// last_id or segment
+    fn check_storage(&self, account: &Account) {
+        let state: StorageProgramState = match deserialize(&account.userdata) {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        let notification = RpcStorageNotification::new(&state);
+        {
+            let mut last_storage_notification = self.last_storage_notification.write().unwrap();
+            if last_storage_notification.as_ref() == Some(&notification) {
+                return;
+            }
+            *last_storage_notification = Some(notification.clone());
+        }
+
+        let subscriptions = self.storage_subscriptions.read().unwrap();
+        for (_bank_sub_id, sink) in subscriptions.iter() {
+            sink.notify(Ok(notification.clone())).wait().unwrap();
+        }
+    }
+
     pub fn add_account_subscription(
         &self,
         pubkey: &Pubkey,
@@ -242,6 +301,20 @@ impl RpcSubscriptions {
         });
         found
     }
+
+    pub fn add_storage_subscription(
+        &self,
+        sub_id: &SubscriptionId,
+        sink: &Sink<RpcStorageNotification>,
+    ) {
+        let mut subscriptions = self.storage_subscriptions.write().unwrap();
+        subscriptions.insert(sub_id.clone(), sink.clone());
+    }
+
+    pub fn remove_storage_subscription(&self, id: &SubscriptionId) -> bool {
+        let mut subscriptions = self.storage_subscriptions.write().unwrap();
+        subscriptions.remove(id).is_some()
+    }
 }
 
 struct RpcSolPubSubImpl {
@@ -331,6 +404,23 @@ impl RpcSolPubSubImpl {
                 .add_signature_subscription(&signature, &sub_id, &sink),
         }
     }
+
+    fn subscribe_to_storage_updates(&self, subscriber: pubsub::Subscriber<RpcStorageNotification>) {
+        let id = self.uid.fetch_add(1, atomic::Ordering::SeqCst);
+        let sub_id = SubscriptionId::Number(id as u64);
+        info!("storage_subscribe: id={:?}", sub_id);
+        let sink = subscriber.assign_id(sub_id.clone()).unwrap();
+
+        // Replicators can start mining right away if a last_id was
+        // already advertised
+        let state = self.bank.read().unwrap().bank.get_storage_program_state();
+        if state.entry_height > 0 {
+            sink.notify(Ok(RpcStorageNotification::new(&state)))
+                .wait()
+                .unwrap();
+        }
+        self.subscription.add_storage_subscription(&sub_id, &sink);
+    }
 }
 
 impl RpcSolPubSub for RpcSolPubSubImpl {
@@ -379,6 +469,27 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
             })
         }
     }
+
+    fn storage_subscribe(
+        &self,
+        _meta: Self::Metadata,
+        subscriber: pubsub::Subscriber<RpcStorageNotification>,
+    ) {
+        self.subscribe_to_storage_updates(subscriber)
+    }
+
+    fn storage_unsubscribe(&self, id: SubscriptionId) -> Result<bool> {
+        info!("storage_unsubscribe: id={:?}", id);
+        if self.subscription.remove_storage_subscription(&id) {
+            Ok(true)
+        } else {
+            Err(Error {
+                code: ErrorCode::InvalidParams,
+                message: "Invalid Request: Subscription id does not exist".into(),
+                data: None,
+            })
+        }
+    }
 }
 
 #[cfg(test)]
@@ -387,8 +498,10 @@ mod tests {
     use crate::genesis_block::GenesisBlock;
     use crate::jsonrpc_core::futures::sync::mpsc;
     use crate::jsonrpc_macros::pubsub::{Subscriber, SubscriptionId};
+    use bincode::serialize_into;
     use solana_sdk::budget_program;
     use solana_sdk::budget_transaction::BudgetTransaction;
+    use solana_sdk::hash::hash;
     use solana_sdk::signature::{Keypair, KeypairUtil};
     use solana_sdk::system_transaction::SystemTransaction;
     use std::net::{IpAddr, Ipv4Addr};
@@ -771,4 +884,57 @@ mod tests {
             .unwrap()
             .contains_key(&signature));
     }
+
+    #[test]
+    fn test_check_storage_subscribe() {
+        let (subscriber, _id_receiver, mut transport_receiver) =
+            Subscriber::new_test("storageNotification");
+        let sub_id = SubscriptionId::Number(0 as u64);
+        let sink = subscriber.assign_id(sub_id.clone()).unwrap();
+        let subscriptions = RpcSubscriptions::default();
+        subscriptions.add_storage_subscription(&sub_id, &sink);
+
+        let mut state = StorageProgramState::default();
+        state.entry_height = 32;
+        state.id = hash(&[1]);
+        let mut account = Account::new(1, 16 * 1024, storage_program::id());
+        serialize_into(&mut account.userdata[..], &state).unwrap();
+
+        subscriptions.check_account(&storage_program::system_id(), &account);
+        let string = transport_receiver.poll();
+        if let Async::Ready(Some(response)) = string.unwrap() {
+            let expected = format!(
+                r#"{{"jsonrpc":"2.0","method":"storageNotification","params":{{"result":{{"entry_height":32,"last_id":"{}","segment":2}},"subscription":0}}}}"#,
+                state.id
+            );
+            assert_eq!(expected, response);
+        }
+        assert_eq!(
+            *subscriptions.last_storage_notification.read().unwrap(),
+            Some(RpcStorageNotification {
+                last_id: state.id.to_string(),
+                entry_height: 32,
+                segment: 2,
+            })
+        );
+
+        // Proofs and validations also change the account, only a new last_id
+        // is notified
+        subscriptions.check_account(&storage_program::system_id(), &account);
+        state.entry_height = 48;
+        state.id = hash(&[2]);
+        serialize_into(&mut account.userdata[..], &state).unwrap();
+        subscriptions.check_account(&storage_program::system_id(), &account);
+        let string = transport_receiver.poll();
+        if let Async::Ready(Some(response)) = string.unwrap() {
+            let expected = format!(
+                r#"{{"jsonrpc":"2.0","method":"storageNotification","params":{{"result":{{"entry_height":48,"last_id":"{}","segment":3}},"subscription":0}}}}"#,
+                state.id
+            );
+            assert_eq!(expected, response);
+        }
+
+        assert!(subscriptions.remove_storage_subscription(&sub_id));
+        assert!(!subscriptions.remove_storage_subscription(&sub_id));
+    }
 }
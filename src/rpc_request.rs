@@ -111,8 +111,12 @@ pub enum RpcRequest {
     GetAccountInfo,
     GetBalance,
     GetConfirmationTime,
+    GetEpochInfo,
     GetLastId,
+    GetLastIdAge,
+    GetLeaderSchedule,
     GetSignatureStatus,
+    GetSlotLeader,
     GetTransactionCount,
     RequestAirdrop,
     SendTransaction,
@@ -122,6 +126,8 @@ pub enum RpcRequest {
     GetStorageMiningLastId,
     GetStorageMiningEntryHeight,
     GetStoragePubkeysForEntryHeight,
+    GetStorageProofs,
+    GetStoragePendingRewards,
 }
 
 impl RpcRequest {
@@ -132,8 +138,12 @@ impl RpcRequest {
             RpcRequest::GetAccountInfo => "getAccountInfo",
             RpcRequest::GetBalance => "getBalance",
             RpcRequest::GetConfirmationTime => "getConfirmationTime",
+            RpcRequest::GetEpochInfo => "getEpochInfo",
             RpcRequest::GetLastId => "getLastId",
+            RpcRequest::GetLastIdAge => "getLastIdAge",
+            RpcRequest::GetLeaderSchedule => "getLeaderSchedule",
             RpcRequest::GetSignatureStatus => "getSignatureStatus",
+            RpcRequest::GetSlotLeader => "getSlotLeader",
             RpcRequest::GetTransactionCount => "getTransactionCount",
             RpcRequest::RequestAirdrop => "requestAirdrop",
             RpcRequest::SendTransaction => "sendTransaction",
@@ -143,6 +153,8 @@ impl RpcRequest {
             RpcRequest::GetStorageMiningLastId => "getStorageMiningLastId",
             RpcRequest::GetStorageMiningEntryHeight => "getStorageMiningEntryHeight",
             RpcRequest::GetStoragePubkeysForEntryHeight => "getStoragePubkeysForEntryHeight",
+            RpcRequest::GetStorageProofs => "getStorageProofs",
+            RpcRequest::GetStoragePendingRewards => "getStoragePendingRewards",
         };
         let mut request = json!({
            "jsonrpc": jsonrpc,
@@ -217,10 +229,27 @@ mod tests {
         let request = test_request.build_request_json(1, None);
         assert_eq!(request["method"], "getLastId");
 
+        let test_request = RpcRequest::GetLastIdAge;
+        let request = test_request.build_request_json(1, None);
+        assert_eq!(request["method"], "getLastIdAge");
+
         let test_request = RpcRequest::GetTransactionCount;
         let request = test_request.build_request_json(1, None);
         assert_eq!(request["method"], "getTransactionCount");
 
+        let test_request = RpcRequest::GetEpochInfo;
+        let request = test_request.build_request_json(1, None);
+        assert_eq!(request["method"], "getEpochInfo");
+
+        let test_request = RpcRequest::GetLeaderSchedule;
+        let request = test_request.build_request_json(1, Some(json!([3])));
+        assert_eq!(request["method"], "getLeaderSchedule");
+        assert_eq!(request["params"], json!([3]));
+
+        let test_request = RpcRequest::GetSlotLeader;
+        let request = test_request.build_request_json(1, None);
+        assert_eq!(request["method"], "getSlotLeader");
+
         let test_request = RpcRequest::RequestAirdrop;
         let request = test_request.build_request_json(1, None);
         assert_eq!(request["method"], "requestAirdrop");
@@ -15,6 +15,7 @@ use solana_sdk::signature::Signature;
 use solana_sdk::transaction::Transaction;
 use std::io::Cursor;
 use std::mem::size_of;
+use std::str::FromStr;
 use std::sync::atomic::AtomicUsize;
 
 pub const TX_OFFSET: usize = 0;
@@ -68,49 +69,208 @@ pub fn init() {
     // stub
 }
 
-fn verify_packet(packet: &Packet) -> u8 {
-    use ring::signature;
-    use solana_sdk::pubkey::Pubkey;
-    use solana_sdk::signature::Signature;
-    use untrusted;
-
+/// Returns the signed message of the transaction in `packet` along with each
+/// (signature, pubkey) pair that signs it, or None if the packet is malformed
+fn get_packet_signed_data(packet: &Packet) -> Option<(&[u8], Vec<(&[u8], &[u8])>)> {
     let (sig_len, sig_start, msg_start, pubkey_start) = get_packet_offsets(packet, 0);
     let mut sig_start = sig_start as usize;
     let mut pubkey_start = pubkey_start as usize;
     let msg_start = msg_start as usize;
 
     if packet.meta.size <= msg_start {
-        return 0;
+        return None;
     }
 
     let msg_end = packet.meta.size;
+    let mut signers = vec![];
     for _ in 0..sig_len {
         let pubkey_end = pubkey_start as usize + size_of::<Pubkey>();
         let sig_end = sig_start as usize + size_of::<Signature>();
 
         if pubkey_end >= packet.meta.size || sig_end >= packet.meta.size {
-            return 0;
+            return None;
         }
 
+        signers.push((
+            &packet.data[sig_start..sig_end],
+            &packet.data[pubkey_start..pubkey_end],
+        ));
+        pubkey_start += size_of::<Pubkey>();
+        sig_start += size_of::<Signature>();
+    }
+    Some((&packet.data[msg_start..msg_end], signers))
+}
+
+fn verify_packet(packet: &Packet) -> u8 {
+    use ring::signature;
+    use untrusted;
+
+    if packet.meta.discard {
+        return 0;
+    }
+
+    let (message, signers) = match get_packet_signed_data(packet) {
+        Some(signed_data) => signed_data,
+        None => return 0,
+    };
+    for (signature, pubkey) in signers {
         if signature::verify(
             &signature::ED25519,
-            untrusted::Input::from(&packet.data[pubkey_start..pubkey_end]),
-            untrusted::Input::from(&packet.data[msg_start..msg_end]),
-            untrusted::Input::from(&packet.data[sig_start..sig_end]),
+            untrusted::Input::from(pubkey),
+            untrusted::Input::from(message),
+            untrusted::Input::from(signature),
         )
         .is_err()
         {
             return 0;
         }
-        pubkey_start += size_of::<Pubkey>();
-        sig_start += size_of::<Signature>();
     }
     1
 }
 
-fn verify_packet_disabled(_packet: &Packet) -> u8 {
+/// The field modulus, 2^255 - 19, little-endian
+const FIELD_MODULUS: [u8; 32] = [
+    0xed, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f,
+];
+/// The order of the base point, 2^252 + 27742317777372353535851937790883648493, little-endian
+const GROUP_ORDER: [u8; 32] = [
+    0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+];
+/// The y coordinates of the eight points of small order, see Chalkias et al., "Taming the
+/// many EdDSAs"
+const SMALL_ORDER_Y: [[u8; 32]; 5] = [
+    // 0, order 4
+    [0; 32],
+    // 1, the identity
+    [
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00,
+    ],
+    // order 8
+    [
+        0x26, 0xe8, 0x95, 0x8f, 0xc2, 0xb2, 0x27, 0xb0, 0x45, 0xc3, 0xf4, 0x89, 0xf2, 0xef, 0x98,
+        0xf0, 0xd5, 0xdf, 0xac, 0x05, 0xd3, 0xc6, 0x33, 0x39, 0xb1, 0x38, 0x02, 0x88, 0x6d, 0x53,
+        0xfc, 0x05,
+    ],
+    // order 8
+    [
+        0xc7, 0x17, 0x6a, 0x70, 0x3d, 0x4d, 0xd8, 0x4f, 0xba, 0x3c, 0x0b, 0x76, 0x0d, 0x10, 0x67,
+        0x0f, 0x2a, 0x20, 0x53, 0xfa, 0x2c, 0x39, 0xcc, 0xc6, 0x4e, 0xc7, 0xfd, 0x77, 0x92, 0xac,
+        0x03, 0x7a,
+    ],
+    // p - 1, order 2
+    [
+        0xec, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0x7f,
+    ],
+];
+
+/// Returns true if the little-endian number `a` is less than `b`
+fn is_less(a: &[u8], b: &[u8; 32]) -> bool {
+    for i in (0..32).rev() {
+        if a[i] != b[i] {
+            return a[i] < b[i];
+        }
+    }
+    false
+}
+
+/// Returns true if `point` is the canonical encoding of a point in the prime order
+/// subgroup, other than the identity
+fn is_canonical_prime_order(point: &[u8]) -> bool {
+    use curve25519_dalek::edwards::CompressedEdwardsY;
+
+    let mut y = [0u8; 32];
+    y.copy_from_slice(point);
+    y[31] &= 0x7f;
+    if !is_less(&y, &FIELD_MODULUS) || SMALL_ORDER_Y.contains(&y) {
+        return false;
+    }
+    CompressedEdwardsY::from_slice(point)
+        .decompress()
+        .map_or(false, |point| point.is_torsion_free())
+}
+
+/// Returns true if the batch equation is sure to agree with `verify_packet` on `signature`
+/// by `pubkey`.  Ring rejects an unreduced `s` and a non-canonical `R`, which the batch
+/// equation may accept.  A torsion component in `R` or `A` only cancels out of the
+/// randomly weighted batch equation some of the time, while ring's cofactorless equation
+/// always sees it.
+fn is_batchable(signature: &[u8], pubkey: &[u8]) -> bool {
+    is_less(&signature[32..], &GROUP_ORDER)
+        && is_canonical_prime_order(&signature[..32])
+        && is_canonical_prime_order(pubkey)
+}
+
+/// Verifies all the signatures of `packets` with a single batch equation, and only
+/// verifies the packets one by one if the batch fails
+fn verify_packets_batch(packets: &[Packet]) -> Vec<u8> {
+    use ed25519_dalek;
+
+    let mut rv = vec![0; packets.len()];
+    let mut messages = vec![];
+    let mut signatures = vec![];
+    let mut public_keys = vec![];
+    let mut batched = vec![];
+    for (i, packet) in packets.iter().enumerate() {
+        if packet.meta.discard {
+            continue;
+        }
+        let (message, signers) = match get_packet_signed_data(packet) {
+            Some(signed_data) => signed_data,
+            None => continue,
+        };
+        let signers: Option<Vec<_>> = signers
+            .into_iter()
+            .map(|(signature, pubkey)| {
+                if !is_batchable(signature, pubkey) {
+                    return None;
+                }
+                let signature = ed25519_dalek::Signature::from_bytes(signature).ok()?;
+                let pubkey = ed25519_dalek::PublicKey::from_bytes(pubkey).ok()?;
+                Some((signature, pubkey))
+            })
+            .collect();
+        match signers {
+            Some(signers) => {
+                for (signature, pubkey) in signers {
+                    messages.push(message);
+                    signatures.push(signature);
+                    public_keys.push(pubkey);
+                }
+                batched.push(i);
+            }
+            // Leave encodings the batch can't parse, or might judge differently, to the
+            // individual check
+            None => rv[i] = verify_packet(packet),
+        }
+    }
+
+    if messages.is_empty()
+        || ed25519_dalek::verify_batch(&messages, &signatures, &public_keys).is_ok()
+    {
+        for i in batched {
+            rv[i] = 1;
+        }
+    } else {
+        for i in batched {
+            rv[i] = verify_packet(&packets[i]);
+        }
+    }
+    rv
+}
+
+fn verify_packet_disabled(packet: &Packet) -> u8 {
     warn!("signature verification is disabled");
-    1
+    if packet.meta.discard {
+        0
+    } else {
+        1
+    }
 }
 
 fn batch_size(batches: &[SharedPackets]) -> usize {
@@ -125,6 +285,53 @@ pub fn ed25519_verify(batches: &[SharedPackets]) -> Vec<Vec<u8>> {
     ed25519_verify_cpu(batches)
 }
 
+/// How signatures are verified, picked when the node starts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VerifyMode {
+    /// Accept every signature
+    Disabled,
+    /// Verify each signature on its own
+    Cpu,
+    /// Verify chunks of `VERIFY_BATCH_SIZE` packets at once
+    CpuBatch,
+    /// Verify on the GPU, only available with `--features=cuda`
+    Cuda,
+}
+
+impl Default for VerifyMode {
+    fn default() -> Self {
+        if cfg!(feature = "cuda") {
+            VerifyMode::Cuda
+        } else {
+            VerifyMode::Cpu
+        }
+    }
+}
+
+impl FromStr for VerifyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "disabled" => Ok(VerifyMode::Disabled),
+            "cpu" => Ok(VerifyMode::Cpu),
+            "cpu-batch" => Ok(VerifyMode::CpuBatch),
+            "cuda" if cfg!(feature = "cuda") => Ok(VerifyMode::Cuda),
+            "cuda" => Err("not built with --features=cuda".to_string()),
+            _ => Err(format!("unknown signature verification mode: {}", s)),
+        }
+    }
+}
+
+pub fn ed25519_verify_with_mode(batches: &[SharedPackets], mode: VerifyMode) -> Vec<Vec<u8>> {
+    match mode {
+        VerifyMode::Disabled => ed25519_verify_disabled(batches),
+        VerifyMode::Cpu => ed25519_verify_cpu(batches),
+        VerifyMode::CpuBatch => ed25519_verify_cpu_batch(batches),
+        VerifyMode::Cuda => ed25519_verify(batches),
+    }
+}
+
 pub fn get_packet_offsets(packet: &Packet, current_offset: u32) -> (u32, u32, u32, u32) {
     // Read in the size of signatures array
     let start_offset = TX_OFFSET + size_of::<u64>();
@@ -210,6 +417,29 @@ pub fn ed25519_verify_cpu(batches: &[SharedPackets]) -> Vec<Vec<u8>> {
     rv
 }
 
+/// Batch verification pays off from a few dozen signatures, but a single bad
+/// signature sends the whole chunk through individual verification
+pub const VERIFY_BATCH_SIZE: usize = 64;
+
+pub fn ed25519_verify_cpu_batch(batches: &[SharedPackets]) -> Vec<Vec<u8>> {
+    use rayon::prelude::*;
+    let count = batch_size(batches);
+    info!("CPU batch ECDSA for {}", batch_size(batches));
+    let rv = batches
+        .into_par_iter()
+        .map(|p| {
+            p.read()
+                .unwrap()
+                .packets
+                .par_chunks(VERIFY_BATCH_SIZE)
+                .flat_map(verify_packets_batch)
+                .collect()
+        })
+        .collect();
+    inc_new_counter_info!("ed25519_verify_cpu_batch", count);
+    rv
+}
+
 pub fn ed25519_verify_disabled(batches: &[SharedPackets]) -> Vec<Vec<u8>> {
     use rayon::prelude::*;
     let count = batch_size(batches);
@@ -316,6 +546,15 @@ pub fn ed25519_verify(batches: &[SharedPackets]) -> Vec<Vec<u8>> {
             }
         }
     }
+    // Discarded packets are verified along with the rest of the batch on the GPU, so
+    // reject them here
+    for (vs, packets) in rvs.iter_mut().zip(batches) {
+        for (v, packet) in vs.iter_mut().zip(&packets.read().unwrap().packets) {
+            if packet.meta.discard {
+                *v = 0;
+            }
+        }
+    }
     inc_new_counter_info!("ed25519_verify_gpu", count);
     rvs
 }
@@ -334,9 +573,12 @@ pub fn make_packet_from_transaction(tx: Transaction) -> Packet {
 #[cfg(test)]
 mod tests {
     use crate::packet::{Packet, SharedPackets};
-    use crate::sigverify;
+    use crate::sigverify::{self, VerifyMode};
     use crate::test_tx::test_tx;
     use bincode::{deserialize, serialize};
+    use curve25519_dalek::constants::{ED25519_BASEPOINT_TABLE, EIGHT_TORSION};
+    use curve25519_dalek::scalar::Scalar;
+    use ring::digest::{digest, SHA512};
     use solana_sdk::budget_program;
     use solana_sdk::hash::Hash;
     use solana_sdk::signature::{Keypair, KeypairUtil};
@@ -534,10 +776,141 @@ mod tests {
         let mut ref_vec = vec![vec![ref_ans; n]; num_batches];
         ref_vec[0].push(0u8);
         assert_eq!(ans, ref_vec);
+        assert_eq!(sigverify::ed25519_verify_cpu_batch(&batches), ref_vec);
     }
 
     #[test]
     fn test_verify_fail() {
         test_verify_n(5, true);
     }
+
+    #[test]
+    fn test_verify_cpu_batch() {
+        let packet = sigverify::make_packet_from_transaction(test_tx());
+        let n = sigverify::VERIFY_BATCH_SIZE + 7;
+        let batches = generate_packet_vec(&packet, n, 2);
+        assert_eq!(
+            sigverify::ed25519_verify_cpu_batch(&batches),
+            vec![vec![1u8; n]; 2]
+        );
+
+        // a bad signature only fails its own packet
+        let mut bad_packet = packet.clone();
+        bad_packet.data[20] = bad_packet.data[20].wrapping_add(10);
+        batches[1].write().unwrap().packets[3] = bad_packet;
+        let mut discarded_packet = packet;
+        discarded_packet.meta.discard = true;
+        batches[1].write().unwrap().packets[n - 1] = discarded_packet;
+
+        let mut ref_vec = vec![vec![1u8; n]; 2];
+        ref_vec[1][3] = 0;
+        ref_vec[1][n - 1] = 0;
+        assert_eq!(sigverify::ed25519_verify_cpu_batch(&batches), ref_vec);
+        assert_eq!(sigverify::ed25519_verify_cpu(&batches), ref_vec);
+    }
+
+    #[test]
+    fn test_verify_cpu_batch_matches_ring() {
+        let packet = sigverify::make_packet_from_transaction(test_tx());
+        let (_, sig_start, _, pubkey_start) = sigverify::get_packet_offsets(&packet, 0);
+        let sig_start = sig_start as usize;
+        let pubkey_start = pubkey_start as usize;
+        let with = |signature: &[u8], pubkey: Option<&[u8]>| {
+            let mut packet = packet.clone();
+            packet.data[sig_start..sig_start + 64].copy_from_slice(signature);
+            if let Some(pubkey) = pubkey {
+                packet.data[pubkey_start..pubkey_start + 32].copy_from_slice(pubkey);
+            }
+            packet
+        };
+        let signature = &packet.data[sig_start..sig_start + 64];
+
+        // s + L verifies the same equation as s, but isn't reduced
+        let mut unreduced = signature.to_vec();
+        let mut carry = 0u16;
+        for (s, l) in unreduced[32..]
+            .iter_mut()
+            .zip(sigverify::GROUP_ORDER.iter())
+        {
+            let sum = u16::from(*s) + u16::from(*l) + carry;
+            *s = sum as u8;
+            carry = sum >> 8;
+        }
+        let unreduced = with(&unreduced, None);
+        assert_eq!(sigverify::verify_packet(&unreduced), 0);
+
+        // s = 0 with the identity, or other points of small order, for R and A
+        let identity = sigverify::SMALL_ORDER_Y[1];
+        let mut non_canonical_identity = sigverify::FIELD_MODULUS;
+        non_canonical_identity[0] += 1;
+        let small_order_signature = |r: &[u8; 32]| {
+            let mut signature = r.to_vec();
+            signature.extend_from_slice(&[0; 32]);
+            signature
+        };
+        let mut packets = vec![
+            unreduced,
+            with(&[0xff; 64], None),
+            with(&[0; 64], None),
+            with(&small_order_signature(&identity), Some(&identity[..])),
+            with(
+                &small_order_signature(&non_canonical_identity),
+                Some(&identity[..]),
+            ),
+        ];
+        for y in sigverify::SMALL_ORDER_Y.iter() {
+            packets.push(with(&small_order_signature(y), Some(&identity[..])));
+            packets.push(with(&small_order_signature(&identity), Some(&y[..])));
+        }
+        let mut bad_message = packet.clone();
+        bad_message.data[packet.meta.size - 1] ^= 0xff;
+        packets.push(bad_message);
+
+        // Signatures by a key with a torsion component, A + T8, or with one in R.  Ring
+        // rejects them unless the hash happens to be a multiple of 8, while a randomly
+        // weighted batch would accept about one in eight of the rejected ones.
+        let a = Scalar::from_bytes_mod_order([7; 32]);
+        let sign = |r: Scalar, r_torsion: usize, a_torsion: usize| {
+            let pubkey = (&a * &ED25519_BASEPOINT_TABLE + EIGHT_TORSION[a_torsion]).compress();
+            let big_r = (&r * &ED25519_BASEPOINT_TABLE + EIGHT_TORSION[r_torsion]).compress();
+            let unsigned = with(&[0; 64], Some(pubkey.as_bytes()));
+            let (message, _) = sigverify::get_packet_signed_data(&unsigned).unwrap();
+            let mut hashed = big_r.as_bytes().to_vec();
+            hashed.extend_from_slice(pubkey.as_bytes());
+            hashed.extend_from_slice(message);
+            let mut wide = [0u8; 64];
+            wide.copy_from_slice(digest(&SHA512, &hashed).as_ref());
+            let s = r + Scalar::from_bytes_mod_order_wide(&wide) * a;
+            let mut signature = big_r.as_bytes().to_vec();
+            signature.extend_from_slice(s.as_bytes());
+            with(&signature, Some(pubkey.as_bytes()))
+        };
+        assert_eq!(sigverify::verify_packet(&sign(Scalar::one(), 0, 0)), 1);
+        for seed in 1..=8u8 {
+            let r = Scalar::from_bytes_mod_order([seed; 32]);
+            packets.push(sign(r, 0, 1));
+            packets.push(sign(r, 1, 0));
+        }
+
+        for bad_packet in packets {
+            let expected = sigverify::verify_packet(&bad_packet);
+            assert_eq!(
+                sigverify::verify_packets_batch(&[packet.clone(), bad_packet.clone()]),
+                vec![1, expected]
+            );
+            assert_eq!(
+                sigverify::verify_packets_batch(&[bad_packet]),
+                vec![expected]
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_mode_from_str() {
+        assert_eq!("disabled".parse(), Ok(VerifyMode::Disabled));
+        assert_eq!("cpu".parse(), Ok(VerifyMode::Cpu));
+        assert_eq!("cpu-batch".parse(), Ok(VerifyMode::CpuBatch));
+        assert_eq!("cuda".parse::<VerifyMode>().is_ok(), cfg!(feature = "cuda"));
+        assert!("gpu".parse::<VerifyMode>().is_err());
+    }
 }
@@ -2,28 +2,129 @@
 //! receives a list of lists of packets and outputs the same list, but tags each
 //! top-level list with a list of booleans, telling the next stage whether the
 //! signature in that packet is valid. It assumes each packet contains one
-//! transaction. All processing is done on the CPU by default and on a GPU
-//! if the `cuda` feature is enabled with `--features=cuda`.
+//! transaction. Packets that were already seen recently are marked invalid without
+//! being verified. All processing is done on the CPU by default
+//! and on a GPU if the `cuda` feature is enabled with `--features=cuda`.
 
+use crate::bloom::Bloom;
 use crate::counter::Counter;
 
 use crate::packet::SharedPackets;
 use crate::result::{Error, Result};
 use crate::service::Service;
-use crate::sigverify;
+use crate::sigverify::{self, VerifyMode};
 use crate::streamer::{self, PacketReceiver};
 use log::Level;
 use rand::{thread_rng, Rng};
 use solana_metrics::{influxdb, submit};
+use solana_sdk::hash::{hash, Hash};
 use solana_sdk::timing;
+use std::mem;
 use std::sync::atomic::AtomicUsize;
-use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, spawn, JoinHandle};
 use std::time::Instant;
 
 pub type VerifiedPackets = Vec<(SharedPackets, Vec<u8>)>;
 
+/// Most packets a verifier takes off the queue at once. When more than this are
+/// queued, the verifier is falling behind and the oldest ones are dropped.
+pub const MAX_VERIFY_BATCH_PACKETS: usize = 100_000;
+/// Number of verified batches queued for the banking stage before the verifiers
+/// block, which in turn makes them drop more packets on their next receive
+pub const VERIFIED_QUEUE_CAPACITY: usize = 4;
+
+/// Number of packets each generation of the dedup filter holds before it's rotated
+pub const DEDUP_FILTER_CAPACITY: usize = 500_000;
+/// How long a generation of the dedup filter collects packets before it's rotated
+pub const DEDUP_FILTER_ROTATE_MS: u64 = 1000;
+/// A false positive drops a valid transaction, so keep them rare
+const DEDUP_FILTER_FALSE_RATE: f64 = 0.000_001;
+const DEDUP_FILTER_MAX_BITS: usize = 32 * 1024 * 1024;
+
+/// Remembers the hashes of the packets seen over the last one to two rotation
+/// periods. Hashes are added to the current generation, and looked up in both the
+/// current and the previous generation. Rotating drops the previous one.
+///
+/// Packets are told apart by all of their bytes rather than by their signature,
+/// which isn't verified yet: a forged packet reusing the signature of a genuine
+/// transaction would otherwise get the genuine one dropped.
+pub struct PacketDedup {
+    current: Bloom<Hash>,
+    previous: Bloom<Hash>,
+    num_current: usize,
+    capacity: usize,
+    rotate_ms: u64,
+    last_rotate: u64,
+}
+
+impl Default for PacketDedup {
+    fn default() -> Self {
+        Self::new(DEDUP_FILTER_CAPACITY, DEDUP_FILTER_ROTATE_MS)
+    }
+}
+
+impl PacketDedup {
+    pub fn new(capacity: usize, rotate_ms: u64) -> Self {
+        let new_filter = || Bloom::random(capacity, DEDUP_FILTER_FALSE_RATE, DEDUP_FILTER_MAX_BITS);
+        Self {
+            current: new_filter(),
+            previous: new_filter(),
+            num_current: 0,
+            capacity,
+            rotate_ms,
+            last_rotate: timing::timestamp(),
+        }
+    }
+
+    fn rotate(&mut self, now: u64) {
+        mem::swap(&mut self.current, &mut self.previous);
+        self.current.clear();
+        self.num_current = 0;
+        self.last_rotate = now;
+    }
+
+    /// Rotate if the current generation is full or old enough
+    pub fn maybe_rotate(&mut self, now: u64) {
+        if self.num_current >= self.capacity
+            || now.saturating_sub(self.last_rotate) >= self.rotate_ms
+        {
+            self.rotate(now);
+        }
+    }
+
+    /// Returns true if a packet hashing to `key` was already seen, and remembers it
+    /// otherwise
+    pub fn check_and_add(&mut self, key: &Hash) -> bool {
+        if self.current.contains(key) || self.previous.contains(key) {
+            return true;
+        }
+        self.current.add(key);
+        self.num_current += 1;
+        false
+    }
+
+    /// Marks every packet that was already seen as discarded, including repeats
+    /// within `batches`, and returns how many were
+    pub fn dedup_packets(&mut self, batches: &[SharedPackets]) -> usize {
+        self.maybe_rotate(timing::timestamp());
+        let mut num_dups = 0;
+        for packets in batches {
+            for packet in packets.write().unwrap().packets.iter_mut() {
+                if packet.meta.discard {
+                    continue;
+                }
+                if self.check_and_add(&hash(&packet.data[..packet.meta.size])) {
+                    packet.meta.discard = true;
+                    num_dups += 1;
+                }
+            }
+        }
+        num_dups
+    }
+}
+
 pub struct SigVerifyStage {
     thread_hdls: Vec<JoinHandle<()>>,
 }
@@ -32,32 +133,37 @@ impl SigVerifyStage {
     #[allow(clippy::new_ret_no_self)]
     pub fn new(
         packet_receiver: Receiver<SharedPackets>,
-        sigverify_disabled: bool,
+        verify_mode: VerifyMode,
     ) -> (Self, Receiver<VerifiedPackets>) {
         sigverify::init();
-        let (verified_sender, verified_receiver) = channel();
-        let thread_hdls =
-            Self::verifier_services(packet_receiver, verified_sender, sigverify_disabled);
+        let (verified_sender, verified_receiver) = sync_channel(VERIFIED_QUEUE_CAPACITY);
+        let thread_hdls = Self::verifier_services(packet_receiver, verified_sender, verify_mode);
         (Self { thread_hdls }, verified_receiver)
     }
 
-    fn verify_batch(batch: Vec<SharedPackets>, sigverify_disabled: bool) -> VerifiedPackets {
-        let r = if sigverify_disabled {
-            sigverify::ed25519_verify_disabled(&batch)
-        } else {
-            sigverify::ed25519_verify(&batch)
-        };
+    fn verify_batch(batch: Vec<SharedPackets>, verify_mode: VerifyMode) -> VerifiedPackets {
+        let r = sigverify::ed25519_verify_with_mode(&batch, verify_mode);
         batch.into_iter().zip(r).collect()
     }
 
     fn verifier(
         recvr: &Arc<Mutex<PacketReceiver>>,
-        sendr: &Arc<Mutex<Sender<VerifiedPackets>>>,
-        sigverify_disabled: bool,
+        sendr: &Arc<Mutex<SyncSender<VerifiedPackets>>>,
+        dedup: &Arc<Mutex<PacketDedup>>,
+        verify_mode: VerifyMode,
     ) -> Result<()> {
-        let (batch, len, recv_time) =
-            streamer::recv_batch(&recvr.lock().expect("'recvr' lock in fn verifier"))?;
+        let (batch, len, num_dropped, recv_time) = streamer::recv_batch(
+            &recvr.lock().expect("'recvr' lock in fn verifier"),
+            MAX_VERIFY_BATCH_PACKETS,
+        )?;
         inc_new_counter_info!("sigverify_stage-entries_received", len);
+        inc_new_counter_info!("sigverify_stage-dropped_packets", num_dropped);
+
+        let num_dups = dedup
+            .lock()
+            .expect("'dedup' lock in fn verifier")
+            .dedup_packets(&batch);
+        inc_new_counter_info!("sigverify_stage-duplicate_packets", num_dups);
 
         let now = Instant::now();
         let batch_len = batch.len();
@@ -69,12 +175,13 @@ impl SigVerifyStage {
             rand_id
         );
 
-        let verified_batch = Self::verify_batch(batch, sigverify_disabled);
+        let verified_batch = Self::verify_batch(batch, verify_mode);
         inc_new_counter_info!(
             "sigverify_stage-verified_entries_send",
             verified_batch.len()
         );
 
+        let send_start = Instant::now();
         if sendr
             .lock()
             .expect("lock in fn verify_batch in tpu")
@@ -83,6 +190,8 @@ impl SigVerifyStage {
         {
             return Err(Error::SendError);
         }
+        let send_time_ms = timing::duration_as_ms(&send_start.elapsed());
+        inc_new_counter_info!("sigverify_stage-send_blocked_ms", send_time_ms as usize);
 
         let total_time_ms = timing::duration_as_ms(&now.elapsed());
         let total_time_s = timing::duration_as_s(&now.elapsed());
@@ -104,6 +213,11 @@ impl SigVerifyStage {
             influxdb::Point::new("sigverify_stage-total_verify_time")
                 .add_field("batch_len", influxdb::Value::Integer(batch_len as i64))
                 .add_field("len", influxdb::Value::Integer(len as i64))
+                .add_field("dropped", influxdb::Value::Integer(num_dropped as i64))
+                .add_field(
+                    "send_blocked_ms",
+                    influxdb::Value::Integer(send_time_ms as i64),
+                )
                 .add_field(
                     "total_time_ms",
                     influxdb::Value::Integer(total_time_ms as i64),
@@ -116,11 +230,13 @@ impl SigVerifyStage {
 
     fn verifier_service(
         packet_receiver: Arc<Mutex<PacketReceiver>>,
-        verified_sender: Arc<Mutex<Sender<VerifiedPackets>>>,
-        sigverify_disabled: bool,
+        verified_sender: Arc<Mutex<SyncSender<VerifiedPackets>>>,
+        dedup: Arc<Mutex<PacketDedup>>,
+        verify_mode: VerifyMode,
     ) -> JoinHandle<()> {
         spawn(move || loop {
-            if let Err(e) = Self::verifier(&packet_receiver, &verified_sender, sigverify_disabled) {
+            if let Err(e) = Self::verifier(&packet_receiver, &verified_sender, &dedup, verify_mode)
+            {
                 match e {
                     Error::RecvTimeoutError(RecvTimeoutError::Disconnected) => break,
                     Error::RecvTimeoutError(RecvTimeoutError::Timeout) => (),
@@ -135,13 +251,16 @@ impl SigVerifyStage {
 
     fn verifier_services(
         packet_receiver: PacketReceiver,
-        verified_sender: Sender<VerifiedPackets>,
-        sigverify_disabled: bool,
+        verified_sender: SyncSender<VerifiedPackets>,
+        verify_mode: VerifyMode,
     ) -> Vec<JoinHandle<()>> {
         let sender = Arc::new(Mutex::new(verified_sender));
         let receiver = Arc::new(Mutex::new(packet_receiver));
+        let dedup = Arc::new(Mutex::new(PacketDedup::default()));
         (0..4)
-            .map(|_| Self::verifier_service(receiver.clone(), sender.clone(), sigverify_disabled))
+            .map(|_| {
+                Self::verifier_service(receiver.clone(), sender.clone(), dedup.clone(), verify_mode)
+            })
             .collect()
     }
 }
@@ -156,3 +275,104 @@ impl Service for SigVerifyStage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::Packet;
+    use crate::test_tx::test_tx;
+
+    fn make_batch(packets: Vec<Packet>) -> SharedPackets {
+        let batch = SharedPackets::default();
+        batch.write().unwrap().packets = packets;
+        batch
+    }
+
+    fn discarded(batch: &SharedPackets) -> Vec<bool> {
+        batch
+            .read()
+            .unwrap()
+            .packets
+            .iter()
+            .map(|packet| packet.meta.discard)
+            .collect()
+    }
+
+    #[test]
+    fn test_dedup_packets() {
+        let tx0 = sigverify::make_packet_from_transaction(test_tx());
+        let tx1 = sigverify::make_packet_from_transaction(test_tx());
+        let mut dedup = PacketDedup::default();
+
+        // repeats within a batch are discarded
+        let batch = make_batch(vec![tx0.clone(), tx1.clone(), tx0.clone()]);
+        assert_eq!(dedup.dedup_packets(&[batch.clone()]), 1);
+        assert_eq!(discarded(&batch), vec![false, false, true]);
+
+        // and so are repeats of earlier batches
+        let batch = make_batch(vec![tx1.clone()]);
+        assert_eq!(dedup.dedup_packets(&[batch.clone()]), 1);
+        assert_eq!(discarded(&batch), vec![true]);
+
+        // packets that don't hold a transaction are deduplicated all the same
+        let batch = make_batch(vec![Packet::default(), Packet::default()]);
+        assert_eq!(dedup.dedup_packets(&[batch.clone()]), 1);
+        assert_eq!(discarded(&batch), vec![false, true]);
+    }
+
+    #[test]
+    fn test_dedup_forged_signature() {
+        let genuine = sigverify::make_packet_from_transaction(test_tx());
+        // same signature, different message
+        let mut forged = genuine.clone();
+        let last = forged.meta.size - 1;
+        forged.data[last] ^= 0xff;
+        let mut dedup = PacketDedup::default();
+
+        let batch = make_batch(vec![forged, genuine]);
+        assert_eq!(dedup.dedup_packets(&[batch.clone()]), 0);
+        let verified = SigVerifyStage::verify_batch(vec![batch], VerifyMode::Cpu);
+        assert_eq!(verified[0].1, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_dedup_discarded_packets_fail_verification() {
+        let packet = sigverify::make_packet_from_transaction(test_tx());
+        let batch = make_batch(vec![packet.clone(), packet]);
+        let mut dedup = PacketDedup::default();
+        dedup.dedup_packets(&[batch.clone()]);
+
+        for verify_mode in &[
+            VerifyMode::Disabled,
+            VerifyMode::Cpu,
+            VerifyMode::CpuBatch,
+            VerifyMode::default(),
+        ] {
+            let verified = SigVerifyStage::verify_batch(vec![batch.clone()], *verify_mode);
+            assert_eq!(verified[0].1, vec![1, 0]);
+        }
+    }
+
+    #[test]
+    fn test_dedup_rotate() {
+        let key = hash(&[1u8]);
+        let mut dedup = PacketDedup::new(2, 1000);
+        let now = dedup.last_rotate;
+        assert!(!dedup.check_and_add(&key));
+        assert!(dedup.check_and_add(&key));
+
+        // still remembered after one rotation
+        dedup.maybe_rotate(now + 1000);
+        assert!(dedup.check_and_add(&key));
+
+        // forgotten after two
+        dedup.maybe_rotate(now + 2000);
+        assert!(!dedup.check_and_add(&key));
+
+        // a full generation rotates early
+        assert!(!dedup.check_and_add(&hash(&[2u8])));
+        dedup.maybe_rotate(now + 2000);
+        assert_eq!(dedup.num_current, 0);
+        assert!(dedup.check_and_add(&key));
+    }
+}
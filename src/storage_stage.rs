@@ -2,6 +2,11 @@
 // for storage mining. Replicators submit storage proofs, validator then bundles them
 // to submit its proof for mining to be rewarded.
 
+use crate::bank::Bank;
+#[cfg(all(feature = "chacha", not(feature = "cuda")))]
+use crate::chacha::chacha_cbc_encrypt_file_many_keys;
+#[cfg(feature = "chacha")]
+use crate::chacha::{chacha_cbc_encrypt_ledger_to, CHACHA_BLOCK_SIZE};
 #[cfg(all(feature = "chacha", feature = "cuda"))]
 use crate::chacha_cuda::chacha_cbc_encrypt_file_many_keys;
 use crate::client::mk_client;
@@ -16,7 +21,9 @@ use rand_chacha::ChaChaRng;
 use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, Signature};
-use solana_sdk::storage_program::{self, StorageProgram, StorageTransaction};
+use solana_sdk::storage_program::{
+    self, ProofInfo, ProofStatus, StorageProgram, StorageProgramState, StorageTransaction,
+};
 use solana_sdk::transaction::Transaction;
 use solana_sdk::vote_program;
 use std::collections::HashSet;
@@ -72,6 +79,71 @@ pub fn get_segment_from_entry(entry_height: u64) -> u64 {
     entry_height / ENTRIES_PER_SEGMENT
 }
 
+/// Offsets, in `Hash`-sized samples, that a proof for a segment encrypted
+/// with `key` into `num_chacha_blocks` blocks samples
+pub fn get_sample_offsets(key: &Signature, num_chacha_blocks: usize) -> Vec<u64> {
+    if num_chacha_blocks == 0 {
+        return vec![];
+    }
+    let mut rng_seed = [0u8; 32];
+    rng_seed.copy_from_slice(&key.as_ref()[0..32]);
+    let mut rng = ChaChaRng::from_seed(rng_seed);
+    (0..NUM_STORAGE_SAMPLES)
+        .map(|_| rng.gen_range(0, num_chacha_blocks) as u64)
+        .collect()
+}
+
+/// Check a replicator's proof for the segment starting at `entry_height`, answering the
+/// challenge `storage_last_id`.  The proof's key must be the replicator's signature of
+/// the challenge, otherwise one encryption could be resubmitted every epoch.
+pub fn verify_proof(
+    db_ledger: &Arc<DbLedger>,
+    storage_last_id: &Hash,
+    entry_height: u64,
+    proof: &ProofInfo,
+) -> ProofStatus {
+    if !proof.verify(storage_last_id, entry_height) {
+        return ProofStatus::NotValid;
+    }
+    verify_proof_samples(db_ledger, entry_height, proof)
+}
+
+/// Encrypt our copy of the segment with the proof's key and sample it
+#[cfg(feature = "chacha")]
+fn verify_proof_samples(
+    db_ledger: &Arc<DbLedger>,
+    entry_height: u64,
+    proof: &ProofInfo,
+) -> ProofStatus {
+    use crate::replicator::sample_data;
+    use std::io::Cursor;
+
+    let mut ivec = [0u8; CHACHA_BLOCK_SIZE];
+    ivec.copy_from_slice(proof.signature.as_ref());
+    let mut encrypted = vec![];
+    match chacha_cbc_encrypt_ledger_to(db_ledger, entry_height, &mut encrypted, &mut ivec) {
+        Ok(0) | Err(_) => ProofStatus::Skipped,
+        Ok(num_encrypted_bytes) => {
+            let offsets =
+                get_sample_offsets(&proof.signature, num_encrypted_bytes / CHACHA_BLOCK_SIZE);
+            match sample_data(Cursor::new(&encrypted), encrypted.len() as u64, &offsets) {
+                Ok(hash) if hash == proof.sha_state => ProofStatus::Valid,
+                _ => ProofStatus::NotValid,
+            }
+        }
+    }
+}
+
+/// Samples can't be checked without the chacha encryption
+#[cfg(not(feature = "chacha"))]
+fn verify_proof_samples(
+    _db_ledger: &Arc<DbLedger>,
+    _entry_height: u64,
+    _proof: &ProofInfo,
+) -> ProofStatus {
+    ProofStatus::Skipped
+}
+
 fn get_identity_index_from_signature(key: &Signature) -> usize {
     let rkey = key.as_ref();
     let mut res: usize = (rkey[0] as usize)
@@ -141,7 +213,9 @@ impl StorageStage {
         storage_state: &StorageState,
         storage_entry_receiver: EntryReceiver,
         db_ledger: Option<Arc<DbLedger>>,
+        bank: &Arc<Bank>,
         keypair: &Arc<Keypair>,
+        vote_account: Option<Pubkey>,
         exit: &Arc<AtomicBool>,
         entry_height: u64,
         storage_rotate_count: u64,
@@ -152,6 +226,7 @@ impl StorageStage {
         let storage_state_inner = storage_state.state.clone();
         let exit0 = exit.clone();
         let keypair0 = keypair.clone();
+        let bank = bank.clone();
 
         let (tx_sender, tx_receiver) = channel();
 
@@ -161,6 +236,7 @@ impl StorageStage {
                 let mut poh_height = 0;
                 let mut current_key = 0;
                 let mut entry_height = entry_height;
+                let mut validated_entry_height = 0;
                 loop {
                     if let Some(ref some_db_ledger) = db_ledger {
                         if let Err(e) = Self::process_entries(
@@ -180,6 +256,19 @@ impl StorageStage {
                                 _ => info!("Error from process_entries: {:?}", e),
                             }
                         }
+                        // Only validators with a staked vote account can validate proofs
+                        if let Some(vote_account) = vote_account {
+                            if let Err(e) = Self::validate_proofs(
+                                &bank,
+                                &some_db_ledger,
+                                &keypair0,
+                                &vote_account,
+                                &mut validated_entry_height,
+                                &tx_sender,
+                            ) {
+                                info!("Error from validate_proofs: {:?}", e);
+                            }
+                        }
                     }
                     if exit0.load(Ordering::Relaxed) {
                         break;
@@ -284,7 +373,12 @@ impl StorageStage {
 
         let mut rng = ChaChaRng::from_seed(seed);
 
-        state.write().unwrap().entry_height = entry_height;
+        {
+            // Replicators derive their keys from the last_id advertised above
+            let mut statew = state.write().unwrap();
+            statew.storage_last_id = entry_id;
+            statew.entry_height = entry_height;
+        }
 
         // Regenerate the answers
         let num_segments = (entry_height / ENTRIES_PER_SEGMENT) as usize;
@@ -305,10 +399,9 @@ impl StorageStage {
             samples.push(rng.gen_range(0, 10));
         }
         debug!("generated samples: {:?}", samples);
-        // TODO: cuda required to generate the reference values
-        // but if it is missing, then we need to take care not to
-        // process storage mining results.
-        #[cfg(all(feature = "chacha", feature = "cuda"))]
+        // Reference values are generated on the GPU with cuda and in
+        // parallel on the CPU otherwise, chacha is required for either.
+        #[cfg(feature = "chacha")]
         {
             // Lock the keys, since this is the IV memory,
             // it will be updated in-place by the encryption.
@@ -338,6 +431,65 @@ impl StorageStage {
         Ok(())
     }
 
+    /// Once the storage program moves on to a new epoch, check the proofs
+    /// submitted during the previous one and send a `ProofValidation` for
+    /// each segment that has any
+    fn validate_proofs(
+        bank: &Arc<Bank>,
+        db_ledger: &Arc<DbLedger>,
+        keypair: &Arc<Keypair>,
+        vote_account: &Pubkey,
+        validated_entry_height: &mut u64,
+        tx_sender: &TransactionSender,
+    ) -> Result<()> {
+        let storage_program_state = bank
+            .get_account(&storage_program::system_id())
+            .and_then(|account| deserialize::<StorageProgramState>(&account.userdata).ok());
+        let storage_program_state = match storage_program_state {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+        if storage_program_state.entry_height == *validated_entry_height {
+            return Ok(());
+        }
+        *validated_entry_height = storage_program_state.entry_height;
+
+        for (segment, proofs) in storage_program_state.previous_proofs.iter().enumerate() {
+            let entry_height = segment as u64 * ENTRIES_PER_SEGMENT;
+            let proof_mask: Vec<_> = proofs
+                .iter()
+                .map(|proof| {
+                    verify_proof(
+                        db_ledger,
+                        &storage_program_state.previous_id,
+                        entry_height,
+                        proof,
+                    )
+                })
+                .collect();
+            if proof_mask
+                .iter()
+                .all(|status| *status == ProofStatus::Skipped)
+            {
+                continue;
+            }
+
+            debug!(
+                "storage validating segment: {} proofs: {:?}",
+                segment, proof_mask
+            );
+            let tx = StorageTransaction::new_proof_validation(
+                keypair,
+                *vote_account,
+                Hash::default(),
+                entry_height,
+                proof_mask,
+            );
+            tx_sender.send(tx)?;
+        }
+        Ok(())
+    }
+
     pub fn process_entries(
         keypair: &Arc<Keypair>,
         storage_state: &Arc<RwLock<StorageStateInner>>,
@@ -432,6 +584,7 @@ impl Service for StorageStage {
 
 #[cfg(test)]
 mod tests {
+    use crate::bank::Bank;
     use crate::db_ledger::create_tmp_sample_ledger;
     use crate::db_ledger::{DbLedger, DEFAULT_SLOT_HEIGHT};
     use crate::entry::{make_tiny_test_entries, Entry};
@@ -470,7 +623,9 @@ mod tests {
             &storage_state,
             storage_entry_receiver,
             None,
+            &Arc::new(Bank::default()),
             &keypair,
+            None,
             &exit.clone(),
             0,
             STORAGE_ROTATE_TEST_COUNT,
@@ -514,7 +669,9 @@ mod tests {
             &storage_state,
             storage_entry_receiver,
             Some(Arc::new(db_ledger)),
+            &Arc::new(Bank::default()),
             &keypair,
+            None,
             &exit.clone(),
             0,
             STORAGE_ROTATE_TEST_COUNT,
@@ -545,10 +702,10 @@ mod tests {
         exit.store(true, Ordering::Relaxed);
         storage_stage.join().unwrap();
 
-        #[cfg(not(all(feature = "cuda", feature = "chacha")))]
+        #[cfg(not(feature = "chacha"))]
         assert_eq!(result, Hash::default());
 
-        #[cfg(all(feature = "cuda", feature = "chacha"))]
+        #[cfg(feature = "chacha")]
         assert_ne!(result, Hash::default());
 
         remove_dir_all(ledger_path).unwrap();
@@ -582,7 +739,9 @@ mod tests {
             &storage_state,
             storage_entry_receiver,
             Some(Arc::new(db_ledger)),
+            &Arc::new(Bank::default()),
             &keypair,
+            None,
             &exit.clone(),
             0,
             STORAGE_ROTATE_TEST_COUNT,
@@ -657,4 +816,108 @@ mod tests {
         info!("min: {} max: {}", hist_min, hist_max);
         assert_ne!(hist_min, 0);
     }
+
+    #[test]
+    fn test_verify_proof_key() {
+        use crate::storage_stage::{verify_proof, ENTRIES_PER_SEGMENT};
+        use solana_sdk::hash::hash;
+        use solana_sdk::storage_program::{segment_message, ProofInfo, ProofStatus};
+
+        let (_mint, ledger_path, _genesis_entry_height, _last_id) = create_tmp_sample_ledger(
+            "storage_stage_verify_proof_key",
+            1000,
+            1,
+            Keypair::new().pubkey(),
+            1,
+        );
+        let db_ledger = Arc::new(DbLedger::open(&ledger_path).unwrap());
+
+        let keypair = Keypair::new();
+        let storage_last_id = hash(b"storage_last_id");
+        let proof = ProofInfo {
+            id: keypair.pubkey(),
+            signature: keypair.sign_message(&segment_message(&storage_last_id, 0)),
+            sha_state: Hash::default(),
+        };
+
+        // A key derived from another challenge or segment is rejected before any sampling
+        assert_eq!(
+            verify_proof(&db_ledger, &hash(b"next_last_id"), 0, &proof),
+            ProofStatus::NotValid
+        );
+        assert_eq!(
+            verify_proof(&db_ledger, &storage_last_id, ENTRIES_PER_SEGMENT, &proof),
+            ProofStatus::NotValid
+        );
+        #[cfg(not(feature = "chacha"))]
+        assert_eq!(
+            verify_proof(&db_ledger, &storage_last_id, 0, &proof),
+            ProofStatus::Skipped
+        );
+
+        drop(db_ledger);
+        remove_dir_all(ledger_path).unwrap();
+    }
+
+    #[cfg(feature = "chacha")]
+    #[test]
+    fn test_verify_proof() {
+        use crate::chacha::{chacha_cbc_encrypt_ledger_to, CHACHA_BLOCK_SIZE};
+        use crate::replicator::sample_data;
+        use crate::storage_stage::{get_sample_offsets, verify_proof};
+        use solana_sdk::hash::hash;
+        use solana_sdk::storage_program::{segment_message, ProofInfo, ProofStatus};
+        use std::io::Cursor;
+
+        let (_mint, ledger_path, genesis_entry_height, _last_id) = create_tmp_sample_ledger(
+            "storage_stage_verify_proof",
+            1000,
+            1,
+            Keypair::new().pubkey(),
+            1,
+        );
+        let entries = make_tiny_test_entries(32);
+        let db_ledger = DbLedger::open(&ledger_path).unwrap();
+        db_ledger
+            .write_entries(DEFAULT_SLOT_HEIGHT, genesis_entry_height, &entries)
+            .unwrap();
+        let db_ledger = Arc::new(db_ledger);
+
+        let keypair = Keypair::new();
+        let storage_last_id = hash(b"storage_last_id");
+        let signature = keypair.sign_message(&segment_message(&storage_last_id, 0));
+        let mut ivec = [0u8; CHACHA_BLOCK_SIZE];
+        ivec.copy_from_slice(signature.as_ref());
+        let mut encrypted = vec![];
+        let num_bytes =
+            chacha_cbc_encrypt_ledger_to(&db_ledger, 0, &mut encrypted, &mut ivec).unwrap();
+        let offsets = get_sample_offsets(&signature, num_bytes / CHACHA_BLOCK_SIZE);
+        let sha_state =
+            sample_data(Cursor::new(&encrypted), encrypted.len() as u64, &offsets).unwrap();
+
+        let mut proof = ProofInfo {
+            id: keypair.pubkey(),
+            signature,
+            sha_state,
+        };
+        assert_eq!(
+            verify_proof(&db_ledger, &storage_last_id, 0, &proof),
+            ProofStatus::Valid
+        );
+
+        // The same encryption doesn't answer the next challenge
+        assert_eq!(
+            verify_proof(&db_ledger, &hash(b"next_last_id"), 0, &proof),
+            ProofStatus::NotValid
+        );
+
+        proof.sha_state = Hash::default();
+        assert_eq!(
+            verify_proof(&db_ledger, &storage_last_id, 0, &proof),
+            ProofStatus::NotValid
+        );
+
+        drop(db_ledger);
+        remove_dir_all(ledger_path).unwrap();
+    }
 }
@@ -1,30 +1,196 @@
 //! The `streamer` module defines a set of services for efficiently pulling data from UDP sockets.
 //!
 
-use crate::packet::{Blob, SharedBlobs, SharedPackets};
+use crate::packet::{Blob, Packets, PacketsRecycler, SharedBlobs, SharedPackets};
 use crate::result::{Error, Result};
 use solana_metrics::{influxdb, submit};
 use solana_sdk::timing::duration_as_ms;
+use std::collections::VecDeque;
+use std::mem;
 use std::net::UdpSocket;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
-use std::sync::Arc;
+use std::sync::mpsc::{Receiver, RecvError, RecvTimeoutError, SendError, Sender, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock};
 use std::thread::{Builder, JoinHandle};
 use std::time::{Duration, Instant};
 
-pub type PacketReceiver = Receiver<SharedPackets>;
-pub type PacketSender = Sender<SharedPackets>;
 pub type BlobSender = Sender<SharedBlobs>;
 pub type BlobReceiver = Receiver<SharedBlobs>;
 
+/// Most packets a packet channel holds, about four times what the signature verification
+/// threads take at once
+pub const PACKET_CHANNEL_MAX_PACKETS: usize = 400_000;
+
+struct PacketQueueState {
+    batches: VecDeque<SharedPackets>,
+    num_packets: usize,
+    /// Packets dropped since `recv_batch` last reported them
+    num_dropped: usize,
+    num_senders: usize,
+    has_receiver: bool,
+}
+
+struct PacketQueue {
+    state: Mutex<PacketQueueState>,
+    ready: Condvar,
+    max_packets: usize,
+}
+
+/// Sending half of a packet channel, see `packet_channel`
+pub struct PacketSender {
+    queue: Arc<PacketQueue>,
+}
+
+/// Receiving half of a packet channel, see `packet_channel`
+pub struct PacketReceiver {
+    queue: Arc<PacketQueue>,
+}
+
+/// Creates a channel of packet batches that holds at most `max_packets` packets.  Sending
+/// never blocks: when a batch doesn't fit, the oldest batches are dropped to make room for
+/// it, since they're the most likely to be stale by the time they're processed.  The newest
+/// batch is always kept, even if it's too big on its own.
+pub fn packet_channel(max_packets: usize) -> (PacketSender, PacketReceiver) {
+    let queue = Arc::new(PacketQueue {
+        state: Mutex::new(PacketQueueState {
+            batches: VecDeque::new(),
+            num_packets: 0,
+            num_dropped: 0,
+            num_senders: 1,
+            has_receiver: true,
+        }),
+        ready: Condvar::new(),
+        max_packets,
+    });
+    (
+        PacketSender {
+            queue: queue.clone(),
+        },
+        PacketReceiver { queue },
+    )
+}
+
+impl PacketSender {
+    /// Queue `msgs`, returns the number of older packets dropped to make room for it
+    pub fn send(
+        &self,
+        msgs: SharedPackets,
+    ) -> std::result::Result<usize, SendError<SharedPackets>> {
+        let len = msgs.read().unwrap().packets.len();
+        let mut state = self.queue.state.lock().unwrap();
+        if !state.has_receiver {
+            return Err(SendError(msgs));
+        }
+        state.batches.push_back(msgs);
+        state.num_packets += len;
+        let mut num_dropped = 0;
+        while state.num_packets > self.queue.max_packets && state.batches.len() > 1 {
+            let oldest = state.batches.pop_front().unwrap();
+            let oldest_len = oldest.read().unwrap().packets.len();
+            state.num_packets -= oldest_len;
+            num_dropped += oldest_len;
+        }
+        state.num_dropped += num_dropped;
+        drop(state);
+        self.queue.ready.notify_one();
+        Ok(num_dropped)
+    }
+}
+
+impl Clone for PacketSender {
+    fn clone(&self) -> Self {
+        self.queue.state.lock().unwrap().num_senders += 1;
+        PacketSender {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl Drop for PacketSender {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        state.num_senders -= 1;
+        if state.num_senders == 0 {
+            // wake up the receiver so it sees the channel is disconnected
+            self.queue.ready.notify_all();
+        }
+    }
+}
+
+impl PacketReceiver {
+    /// Wait up to `timeout`, or forever if it's None, until a batch is queued
+    fn wait(
+        &self,
+        timeout: Option<Duration>,
+    ) -> std::result::Result<MutexGuard<PacketQueueState>, RecvTimeoutError> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.queue.state.lock().unwrap();
+        while state.batches.is_empty() {
+            if state.num_senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            state = match deadline {
+                None => self.queue.ready.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    self.queue
+                        .ready
+                        .wait_timeout(state, deadline - now)
+                        .unwrap()
+                        .0
+                }
+            };
+        }
+        Ok(state)
+    }
+
+    fn pop(state: &mut PacketQueueState) -> Option<SharedPackets> {
+        let msgs = state.batches.pop_front()?;
+        state.num_packets -= msgs.read().unwrap().packets.len();
+        Some(msgs)
+    }
+
+    pub fn recv(&self) -> std::result::Result<SharedPackets, RecvError> {
+        let mut state = self.wait(None).map_err(|_| RecvError)?;
+        Ok(Self::pop(&mut state).unwrap())
+    }
+
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> std::result::Result<SharedPackets, RecvTimeoutError> {
+        let mut state = self.wait(Some(timeout))?;
+        Ok(Self::pop(&mut state).unwrap())
+    }
+
+    pub fn try_recv(&self) -> std::result::Result<SharedPackets, TryRecvError> {
+        let mut state = self.queue.state.lock().unwrap();
+        match Self::pop(&mut state) {
+            Some(msgs) => Ok(msgs),
+            None if state.num_senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+impl Drop for PacketReceiver {
+    fn drop(&mut self) {
+        self.queue.state.lock().unwrap().has_receiver = false;
+    }
+}
+
 fn recv_loop(
     sock: &UdpSocket,
     exit: &Arc<AtomicBool>,
     channel: &PacketSender,
+    recycler: &PacketsRecycler,
     channel_tag: &'static str,
 ) -> Result<()> {
     loop {
-        let msgs = SharedPackets::default();
+        let msgs = Arc::new(RwLock::new(Packets::new_with_recycler(recycler)));
         loop {
             // Check for exit signal, even if socket is busy
             // (for instance the leader trasaction socket)
@@ -49,16 +215,18 @@ pub fn receiver(
     sock: Arc<UdpSocket>,
     exit: Arc<AtomicBool>,
     packet_sender: PacketSender,
+    recycler: &PacketsRecycler,
     sender_tag: &'static str,
 ) -> JoinHandle<()> {
     let res = sock.set_read_timeout(Some(Duration::new(1, 0)));
     if res.is_err() {
         panic!("streamer::receiver set_read_timeout error");
     }
+    let recycler = recycler.clone();
     Builder::new()
         .name("solana-receiver".to_string())
         .spawn(move || {
-            let _ = recv_loop(&sock, &exit, &packet_sender, sender_tag);
+            let _ = recv_loop(&sock, &exit, &packet_sender, &recycler, sender_tag);
         })
         .unwrap()
 }
@@ -70,24 +238,36 @@ fn recv_send(sock: &UdpSocket, r: &BlobReceiver) -> Result<()> {
     Ok(())
 }
 
-pub fn recv_batch(recvr: &PacketReceiver) -> Result<(Vec<SharedPackets>, usize, u64)> {
+/// Receives the oldest batches of packets queued on `recvr`, up to `max_packets` packets
+/// but at least one batch, waiting up to a second for the first one.  The rest stay queued
+/// for the next call.  Returns the batches, their number of packets, the number of packets
+/// the channel dropped since the last call and the time spent receiving in ms.
+pub fn recv_batch(
+    recvr: &PacketReceiver,
+    max_packets: usize,
+) -> Result<(Vec<SharedPackets>, usize, usize, u64)> {
     let timer = Duration::new(1, 0);
-    let msgs = recvr.recv_timeout(timer)?;
+    let mut state = recvr.wait(Some(timer))?;
     let recv_start = Instant::now();
     trace!("got msgs");
-    let mut len = msgs.read().unwrap().packets.len();
-    let mut batch = vec![msgs];
-    while let Ok(more) = recvr.try_recv() {
-        trace!("got more msgs");
-        len += more.read().unwrap().packets.len();
-        batch.push(more);
-
-        if len > 100_000 {
+    let mut batch = vec![];
+    let mut len = 0;
+    while let Some(more) = state.batches.front() {
+        let more_len = more.read().unwrap().packets.len();
+        if !batch.is_empty() && len + more_len > max_packets {
             break;
         }
+        len += more_len;
+        batch.push(PacketReceiver::pop(&mut state).unwrap());
     }
+    let num_dropped = mem::replace(&mut state.num_dropped, 0);
     trace!("batch len {}", batch.len());
-    Ok((batch, len, duration_as_ms(&recv_start.elapsed())))
+    Ok((
+        batch,
+        len,
+        num_dropped,
+        duration_as_ms(&recv_start.elapsed()),
+    ))
 }
 
 pub fn responder(name: &'static str, sock: Arc<UdpSocket>, r: BlobReceiver) -> JoinHandle<()> {
@@ -135,14 +315,18 @@ pub fn blob_receiver(sock: Arc<UdpSocket>, exit: Arc<AtomicBool>, s: BlobSender)
 
 #[cfg(test)]
 mod test {
-    use crate::packet::{Blob, Packet, Packets, SharedBlob, PACKET_DATA_SIZE};
-    use crate::streamer::PacketReceiver;
-    use crate::streamer::{receiver, responder};
+    use crate::packet::{
+        Blob, Packet, Packets, PacketsRecycler, SharedBlob, SharedPackets, PACKET_DATA_SIZE,
+    };
+    use crate::result::Error;
+    use crate::streamer::{
+        packet_channel, receiver, recv_batch, responder, PacketReceiver, PACKET_CHANNEL_MAX_PACKETS,
+    };
     use std::io;
     use std::io::Write;
     use std::net::UdpSocket;
     use std::sync::atomic::{AtomicBool, Ordering};
-    use std::sync::mpsc::channel;
+    use std::sync::mpsc::{channel, RecvTimeoutError};
     use std::sync::Arc;
     use std::time::Duration;
 
@@ -172,8 +356,14 @@ mod test {
         let addr = read.local_addr().unwrap();
         let send = UdpSocket::bind("127.0.0.1:0").expect("bind");
         let exit = Arc::new(AtomicBool::new(false));
-        let (s_reader, r_reader) = channel();
-        let t_receiver = receiver(Arc::new(read), exit.clone(), s_reader, "streamer-test");
+        let (s_reader, r_reader) = packet_channel(PACKET_CHANNEL_MAX_PACKETS);
+        let t_receiver = receiver(
+            Arc::new(read),
+            exit.clone(),
+            s_reader,
+            &PacketsRecycler::default(),
+            "streamer-test",
+        );
         let t_responder = {
             let (s_responder, r_responder) = channel();
             let t_responder = responder("streamer_send_test", Arc::new(send), r_responder);
@@ -199,4 +389,74 @@ mod test {
         t_receiver.join().expect("join");
         t_responder.join().expect("join");
     }
+
+    fn make_packets(num: usize) -> SharedPackets {
+        let packets = SharedPackets::default();
+        packets
+            .write()
+            .unwrap()
+            .packets
+            .resize(num, Packet::default());
+        packets
+    }
+
+    #[test]
+    fn test_packet_channel_drops_oldest() {
+        let (s, r) = packet_channel(7);
+        let batches: Vec<_> = (1..=4).map(make_packets).collect();
+        let num_dropped: usize = batches
+            .iter()
+            .map(|packets| s.send(packets.clone()).unwrap())
+            .sum();
+        assert_eq!(num_dropped, 3);
+
+        // only the two newest batches fit
+        let (batch, len, num_dropped, _) = recv_batch(&r, 100).unwrap();
+        assert_eq!(len, 7);
+        assert_eq!(num_dropped, 3);
+        assert_eq!(batch.len(), 2);
+        assert!(Arc::ptr_eq(&batch[0], &batches[2]));
+        assert!(Arc::ptr_eq(&batch[1], &batches[3]));
+
+        // the newest batch is kept even if it's too big on its own
+        assert_eq!(s.send(make_packets(10)).unwrap(), 0);
+        let (batch, len, num_dropped, _) = recv_batch(&r, 100).unwrap();
+        assert_eq!((batch.len(), len, num_dropped), (1, 10, 0));
+
+        // the receiver is told once every sender is gone
+        let s1 = s.clone();
+        drop(s);
+        s1.send(make_packets(1)).unwrap();
+        drop(s1);
+        assert_eq!(r.recv().unwrap().read().unwrap().packets.len(), 1);
+        assert_matches!(
+            recv_batch(&r, 100),
+            Err(Error::RecvTimeoutError(RecvTimeoutError::Disconnected))
+        );
+
+        // and the senders once the receiver is
+        let (s, r) = packet_channel(7);
+        drop(r);
+        assert!(s.send(make_packets(1)).is_err());
+    }
+
+    #[test]
+    fn test_recv_batch_max_packets() {
+        let (s, r) = packet_channel(PACKET_CHANNEL_MAX_PACKETS);
+        for num in &[3, 4, 10] {
+            s.send(make_packets(*num)).unwrap();
+        }
+
+        // the rest is left for the next receive
+        let (batch, len, num_dropped, _) = recv_batch(&r, 5).unwrap();
+        assert_eq!((batch.len(), len, num_dropped), (1, 3, 0));
+        let (batch, len, _, _) = recv_batch(&r, 5).unwrap();
+        assert_eq!((batch.len(), len), (1, 4));
+        let (batch, len, _, _) = recv_batch(&r, 5).unwrap();
+        assert_eq!((batch.len(), len), (1, 10));
+        assert_matches!(
+            recv_batch(&r, 5),
+            Err(Error::RecvTimeoutError(RecvTimeoutError::Timeout))
+        );
+    }
 }
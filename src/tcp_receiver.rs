@@ -0,0 +1,418 @@
+//! The `tcp_receiver` module accepts transactions over TCP as an alternative to the TPU's
+//! UDP sockets.
+//!
+//! A client sends each transaction as a frame, a little-endian `u16` length followed by
+//! that many bytes, and the receiver answers every frame, in order, with a one byte
+//! `FrameStatus`.  Accepted frames are handed to the same channel as the UDP receivers, so
+//! they go through signature verification like any other packet.  A client therefore knows
+//! whether each transaction made it into the node, and TCP flow control slows it down
+//! instead of dropping its packets.
+//!
+//! A validator that isn't the leader forwards the frames to the leader over UDP, and answers
+//! them with `FrameStatus::Forwarded` instead, since they may still be lost on the way.
+//!
+//! Frames are limited to `PACKET_DATA_SIZE` since the rest of the pipeline works on fixed
+//! size packets.  Each connection may send at most `TCP_RECEIVER_MAX_PACKETS_PER_SECOND`
+//! frames per second, frames past that are answered with `FrameStatus::RateLimited`.  A
+//! single address may hold at most `TCP_RECEIVER_MAX_CONNECTIONS_PER_IP` connections.
+
+use crate::counter::Counter;
+use crate::packet::{Packet, Packets, SharedPackets, PACKET_DATA_SIZE};
+use crate::streamer::PacketSender;
+use byteorder::{ByteOrder, LittleEndian};
+use hashbrown::HashMap;
+use log::Level;
+use std::io::{self, Read, Write};
+use std::mem::size_of;
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{sleep, Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Maximum number of connections served at once, further connections are closed
+pub const TCP_RECEIVER_MAX_CONNECTIONS: usize = 256;
+/// Maximum number of connections served at once from a single address
+pub const TCP_RECEIVER_MAX_CONNECTIONS_PER_IP: usize = 8;
+/// Maximum number of frames accepted from a single connection per second
+pub const TCP_RECEIVER_MAX_PACKETS_PER_SECOND: usize = 2000;
+/// Connections that send nothing for this long are closed
+const TCP_RECEIVER_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Connections whose client stops reading the frame statuses for this long are closed
+const TCP_RECEIVER_WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often blocked reads wake up to check for exit
+const TCP_RECEIVER_POLL: Duration = Duration::from_millis(100);
+
+const FRAME_HEADER_SIZE: usize = size_of::<u16>();
+
+/// The receiver's answer to a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FrameStatus {
+    /// The frame was queued for signature verification
+    Accepted = 0,
+    /// The connection went over its rate limit, the frame was dropped
+    RateLimited = 1,
+    /// The frame was empty or larger than `PACKET_DATA_SIZE`, the frame was dropped
+    InvalidSize = 2,
+    /// The frame was forwarded to the leader over UDP, it may still be lost
+    Forwarded = 3,
+}
+
+impl FrameStatus {
+    fn from_u8(status: u8) -> io::Result<Self> {
+        match status {
+            0 => Ok(FrameStatus::Accepted),
+            1 => Ok(FrameStatus::RateLimited),
+            2 => Ok(FrameStatus::InvalidSize),
+            3 => Ok(FrameStatus::Forwarded),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown frame status {}", status),
+            )),
+        }
+    }
+}
+
+/// Send `data` as a frame on `stream` and wait for the receiver's answer
+pub fn send_frame(stream: &mut TcpStream, data: &[u8]) -> io::Result<FrameStatus> {
+    if data.len() > u16::max_value() as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "frame too large",
+        ));
+    }
+    let mut header = [0u8; FRAME_HEADER_SIZE];
+    LittleEndian::write_u16(&mut header, data.len() as u16);
+    stream.write_all(&header)?;
+    stream.write_all(data)?;
+    let mut status = [0u8; 1];
+    stream.read_exact(&mut status)?;
+    FrameStatus::from_u8(status[0])
+}
+
+/// Per connection budget of frames
+struct RateLimit {
+    window_start: Instant,
+    count: usize,
+}
+
+impl RateLimit {
+    fn new() -> Self {
+        RateLimit {
+            window_start: Instant::now(),
+            count: 0,
+        }
+    }
+
+    fn check(&mut self) -> bool {
+        if self.window_start.elapsed() >= Duration::from_secs(1) {
+            self.window_start = Instant::now();
+            self.count = 0;
+        }
+        self.count += 1;
+        self.count <= TCP_RECEIVER_MAX_PACKETS_PER_SECOND
+    }
+}
+
+/// Connections being served, in total and per address
+#[derive(Default)]
+struct Connections {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+impl Connections {
+    /// Count a new connection from `ip`, returns false if it's over either cap
+    fn try_add(&mut self, ip: IpAddr) -> bool {
+        let count = self.per_ip.entry(ip).or_insert(0);
+        if self.total >= TCP_RECEIVER_MAX_CONNECTIONS
+            || *count >= TCP_RECEIVER_MAX_CONNECTIONS_PER_IP
+        {
+            if *count == 0 {
+                self.per_ip.remove(&ip);
+            }
+            return false;
+        }
+        *count += 1;
+        self.total += 1;
+        true
+    }
+
+    fn remove(&mut self, ip: IpAddr) {
+        if let Some(count) = self.per_ip.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_ip.remove(&ip);
+            }
+            self.total -= 1;
+        }
+    }
+}
+
+/// Split the complete frames off the front of `buf`, turning the ones that are admitted
+/// into packets from `addr`.  Admitted frames are answered with `admitted`.  Returns the
+/// packets and the status of every frame.
+fn parse_frames(
+    buf: &mut Vec<u8>,
+    addr: &SocketAddr,
+    rate_limit: &mut RateLimit,
+    admitted: FrameStatus,
+) -> (Vec<Packet>, Vec<u8>) {
+    let mut packets = vec![];
+    let mut statuses = vec![];
+    let mut offset = 0;
+    while buf.len() - offset >= FRAME_HEADER_SIZE {
+        let len = LittleEndian::read_u16(&buf[offset..]) as usize;
+        let end = offset + FRAME_HEADER_SIZE + len;
+        if buf.len() < end {
+            break;
+        }
+        let status = if len == 0 || len > PACKET_DATA_SIZE {
+            FrameStatus::InvalidSize
+        } else if !rate_limit.check() {
+            FrameStatus::RateLimited
+        } else {
+            let mut packet = Packet::default();
+            packet.data[..len].copy_from_slice(&buf[offset + FRAME_HEADER_SIZE..end]);
+            packet.meta.size = len;
+            packet.meta.set_addr(addr);
+            packets.push(packet);
+            admitted
+        };
+        statuses.push(status as u8);
+        offset = end;
+    }
+    buf.drain(..offset);
+    (packets, statuses)
+}
+
+fn serve_connection(
+    mut stream: TcpStream,
+    addr: SocketAddr,
+    exit: &AtomicBool,
+    packet_sender: &PacketSender,
+    admitted: FrameStatus,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(TCP_RECEIVER_POLL))?;
+    stream.set_write_timeout(Some(TCP_RECEIVER_WRITE_TIMEOUT))?;
+    stream.set_nodelay(true)?;
+
+    let mut rate_limit = RateLimit::new();
+    let mut buf = Vec::with_capacity(FRAME_HEADER_SIZE + PACKET_DATA_SIZE);
+    let mut chunk = [0u8; 64 * 1024];
+    let mut last_read = Instant::now();
+    while !exit.load(Ordering::Relaxed) {
+        let len = match stream.read(&mut chunk) {
+            Ok(0) => return Ok(()),
+            Ok(len) => len,
+            Err(ref err)
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut =>
+            {
+                if last_read.elapsed() >= TCP_RECEIVER_IDLE_TIMEOUT {
+                    return Ok(());
+                }
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        last_read = Instant::now();
+        buf.extend_from_slice(&chunk[..len]);
+
+        let (packets, statuses) = parse_frames(&mut buf, &addr, &mut rate_limit, admitted);
+        let num_packets = packets.len();
+        if num_packets > 0 {
+            inc_new_counter_info!("tcp_receiver-packets", num_packets);
+            let msgs: SharedPackets = Arc::new(RwLock::new(Packets::new(packets)));
+            if packet_sender.send(msgs).is_err() {
+                // The pipeline is gone, don't tell the client its frames were accepted
+                return Ok(());
+            }
+        }
+        if statuses.len() > num_packets {
+            inc_new_counter_info!("tcp_receiver-dropped", statuses.len() - num_packets);
+        }
+        stream.write_all(&statuses)?;
+    }
+    Ok(())
+}
+
+/// Accept connections on `listener` and send the transactions they carry to
+/// `packet_sender`, until `exit` is set.  Frames that are sent on are answered with
+/// `admitted`.
+pub fn tcp_receiver(
+    listener: TcpListener,
+    exit: Arc<AtomicBool>,
+    packet_sender: PacketSender,
+    admitted: FrameStatus,
+    sender_tag: &'static str,
+) -> JoinHandle<()> {
+    listener
+        .set_nonblocking(true)
+        .expect("tcp_receiver set_nonblocking");
+    Builder::new()
+        .name(format!("solana-tcp-receiver-{}", sender_tag))
+        .spawn(move || {
+            let connections = Arc::new(Mutex::new(Connections::default()));
+            while !exit.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, addr)) => {
+                        if !connections.lock().unwrap().try_add(addr.ip()) {
+                            inc_new_counter_info!("tcp_receiver-connection_refused", 1);
+                            continue;
+                        }
+                        let connections = connections.clone();
+                        let exit = exit.clone();
+                        let packet_sender = packet_sender.clone();
+                        Builder::new()
+                            .name(format!("solana-tcp-receiver-{}-conn", sender_tag))
+                            .spawn(move || {
+                                if let Err(err) =
+                                    serve_connection(stream, addr, &exit, &packet_sender, admitted)
+                                {
+                                    debug!("tcp_receiver connection from {}: {}", addr, err);
+                                }
+                                connections.lock().unwrap().remove(addr.ip());
+                            })
+                            .unwrap();
+                    }
+                    Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        sleep(TCP_RECEIVER_POLL);
+                    }
+                    Err(err) => {
+                        warn!("tcp_receiver accept failed: {}", err);
+                        sleep(TCP_RECEIVER_POLL);
+                    }
+                }
+            }
+            // Connections notice the exit on their next read timeout
+            while connections.lock().unwrap().total > 0 {
+                sleep(TCP_RECEIVER_POLL);
+            }
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streamer::{packet_channel, PACKET_CHANNEL_MAX_PACKETS};
+
+    fn frame(data: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0u8; FRAME_HEADER_SIZE];
+        LittleEndian::write_u16(&mut buf, data.len() as u16);
+        buf.extend_from_slice(data);
+        buf
+    }
+
+    #[test]
+    fn test_parse_frames() {
+        let addr = socketaddr!("127.0.0.1:1234");
+        let mut rate_limit = RateLimit::new();
+        let mut buf = frame(&[1, 2, 3]);
+        buf.extend(frame(&[]));
+        buf.extend(frame(&[4; PACKET_DATA_SIZE + 1]));
+        let partial = frame(&[5, 6]);
+        buf.extend_from_slice(&partial[..3]);
+
+        let (packets, statuses) =
+            parse_frames(&mut buf, &addr, &mut rate_limit, FrameStatus::Accepted);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(&packets[0].data[..packets[0].meta.size], &[1, 2, 3]);
+        assert_eq!(packets[0].meta.addr(), addr);
+        assert_eq!(
+            statuses,
+            vec![
+                FrameStatus::Accepted as u8,
+                FrameStatus::InvalidSize as u8,
+                FrameStatus::InvalidSize as u8
+            ]
+        );
+        // the incomplete frame is kept for the next read
+        assert_eq!(buf, &partial[..3]);
+        buf.extend_from_slice(&partial[3..]);
+        let (packets, statuses) =
+            parse_frames(&mut buf, &addr, &mut rate_limit, FrameStatus::Accepted);
+        assert_eq!(&packets[0].data[..packets[0].meta.size], &[5, 6]);
+        assert_eq!(statuses, vec![FrameStatus::Accepted as u8]);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let addr = socketaddr!("127.0.0.1:1234");
+        let mut rate_limit = RateLimit::new();
+        let mut buf = vec![];
+        for _ in 0..=TCP_RECEIVER_MAX_PACKETS_PER_SECOND {
+            buf.extend(frame(&[1]));
+        }
+        let (packets, statuses) =
+            parse_frames(&mut buf, &addr, &mut rate_limit, FrameStatus::Accepted);
+        assert_eq!(packets.len(), TCP_RECEIVER_MAX_PACKETS_PER_SECOND);
+        assert_eq!(*statuses.last().unwrap(), FrameStatus::RateLimited as u8);
+    }
+
+    #[test]
+    fn test_connections() {
+        let ip = socketaddr!("127.0.0.1:1234").ip();
+        let other_ip = socketaddr!("127.0.0.2:1234").ip();
+        let mut connections = Connections::default();
+        for _ in 0..TCP_RECEIVER_MAX_CONNECTIONS_PER_IP {
+            assert!(connections.try_add(ip));
+        }
+        assert!(!connections.try_add(ip));
+        assert!(connections.try_add(other_ip));
+        connections.remove(ip);
+        assert!(connections.try_add(ip));
+
+        connections.remove(other_ip);
+        assert_eq!(connections.total, TCP_RECEIVER_MAX_CONNECTIONS_PER_IP);
+        assert!(!connections.per_ip.contains_key(&other_ip));
+    }
+
+    #[test]
+    fn test_forwarded_status() {
+        let addr = socketaddr!("127.0.0.1:1234");
+        let mut rate_limit = RateLimit::new();
+        let mut buf = frame(&[1, 2, 3]);
+        let (packets, statuses) =
+            parse_frames(&mut buf, &addr, &mut rate_limit, FrameStatus::Forwarded);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(statuses, vec![FrameStatus::Forwarded as u8]);
+    }
+
+    #[test]
+    fn test_tcp_receiver() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let exit = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = packet_channel(PACKET_CHANNEL_MAX_PACKETS);
+        let t_receiver = tcp_receiver(
+            listener,
+            exit.clone(),
+            sender,
+            FrameStatus::Accepted,
+            "test",
+        );
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        assert_eq!(
+            send_frame(&mut stream, &[7; 10]).unwrap(),
+            FrameStatus::Accepted
+        );
+        assert_eq!(
+            send_frame(&mut stream, &[7; PACKET_DATA_SIZE + 1]).unwrap(),
+            FrameStatus::InvalidSize
+        );
+        let msgs = receiver.recv().unwrap();
+        let msgs = msgs.read().unwrap();
+        assert_eq!(msgs.packets.len(), 1);
+        assert_eq!(&msgs.packets[0].data[..msgs.packets[0].meta.size], &[7; 10]);
+        assert_eq!(msgs.packets[0].meta.addr(), stream.local_addr().unwrap());
+
+        exit.store(true, Ordering::Relaxed);
+        t_receiver.join().unwrap();
+    }
+}
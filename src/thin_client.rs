@@ -9,6 +9,7 @@ use crate::fullnode::{Fullnode, FullnodeConfig};
 use crate::gossip_service::GossipService;
 use crate::packet::PACKET_DATA_SIZE;
 use crate::result::{Error, Result};
+use crate::rpc::{RpcEpochInfo, RpcLeaderSchedule};
 use crate::rpc_request::{RpcClient, RpcRequest, RpcRequestHandler};
 use bincode::serialize_into;
 use bs58;
@@ -20,7 +21,7 @@ use solana_metrics::influxdb;
 use solana_sdk::account::Account;
 use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signature::{Keypair, KeypairUtil, Signature};
 use solana_sdk::system_transaction::SystemTransaction;
 use solana_sdk::timing;
 use solana_sdk::transaction::Transaction;
@@ -262,6 +263,58 @@ impl ThinClient {
         self.last_id.expect("some last_id")
     }
 
+    /// Request the current epoch, slot and tick height from the server.
+    pub fn get_epoch_info(&mut self) -> io::Result<RpcEpochInfo> {
+        trace!("get_epoch_info");
+        let value = self
+            .rpc_client
+            .make_rpc_request(1, RpcRequest::GetEpochInfo, None)
+            .map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("get_epoch_info failed: {:?}", err),
+                )
+            })?;
+        serde_json::from_value(value)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "bad epoch info"))
+    }
+
+    /// Request the number of ticks since `last_id` was registered.  Returns None if
+    /// the server no longer tracks it.
+    pub fn get_last_id_age(&mut self, last_id: &Hash) -> io::Result<Option<u64>> {
+        trace!("get_last_id_age {}", last_id);
+        let params = json!([format!("{}", last_id)]);
+        let value = self
+            .rpc_client
+            .make_rpc_request(1, RpcRequest::GetLastIdAge, Some(params))
+            .map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("get_last_id_age failed: {:?}", err),
+                )
+            })?;
+        serde_json::from_value(value)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "bad last_id age"))
+    }
+
+    /// Request the leader of each slot in `epoch`.  Returns None if the server
+    /// doesn't know the schedule for that epoch yet.
+    pub fn get_leader_schedule(&mut self, epoch: u64) -> io::Result<Option<RpcLeaderSchedule>> {
+        trace!("get_leader_schedule {}", epoch);
+        let params = json!([epoch]);
+        let value = self
+            .rpc_client
+            .make_rpc_request(1, RpcRequest::GetLeaderSchedule, Some(params))
+            .map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("get_leader_schedule failed: {:?}", err),
+                )
+            })?;
+        serde_json::from_value(value)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "bad leader schedule"))
+    }
+
     pub fn submit_poll_balance_metrics(elapsed: &Duration) {
         solana_metrics::submit(
             influxdb::Point::new("thinclient")
@@ -360,9 +413,10 @@ impl Drop for ThinClient {
 
 pub fn poll_gossip_for_leader(leader_gossip: SocketAddr, timeout: Option<u64>) -> Result<NodeInfo> {
     let exit = Arc::new(AtomicBool::new(false));
-    let (node, gossip_socket) = ClusterInfo::spy_node();
+    let keypair = Arc::new(Keypair::new());
+    let (node, gossip_socket) = ClusterInfo::spy_node(keypair.pubkey());
     let my_addr = gossip_socket.local_addr().unwrap();
-    let cluster_info = Arc::new(RwLock::new(ClusterInfo::new(node)));
+    let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_keypair(node, keypair)));
     let gossip_service =
         GossipService::new(&cluster_info.clone(), None, gossip_socket, exit.clone());
 
@@ -6,18 +6,19 @@ use crate::banking_stage::{BankingStage, BankingStageReturnType};
 use crate::broadcast_service::BroadcastService;
 use crate::cluster_info::ClusterInfo;
 use crate::cluster_info_vote_listener::ClusterInfoVoteListener;
+use crate::erasure::ErasureConfig;
 use crate::fetch_stage::FetchStage;
 use crate::fullnode::TpuRotationSender;
 use crate::poh_service::Config;
 use crate::service::Service;
+use crate::sigverify::VerifyMode;
 use crate::sigverify_stage::SigVerifyStage;
-use crate::streamer::BlobSender;
+use crate::streamer::{packet_channel, BlobSender, PACKET_CHANNEL_MAX_PACKETS};
 use crate::tpu_forwarder::TpuForwarder;
 use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
-use std::net::UdpSocket;
+use std::net::{TcpListener, UdpSocket};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::channel;
 use std::sync::{Arc, RwLock};
 use std::thread;
 
@@ -78,22 +79,25 @@ impl Tpu {
         bank: &Arc<Bank>,
         tick_duration: Config,
         transactions_sockets: Vec<UdpSocket>,
+        transactions_listener: TcpListener,
         broadcast_socket: UdpSocket,
         cluster_info: Arc<RwLock<ClusterInfo>>,
         entry_height: u64,
-        sigverify_disabled: bool,
+        sigverify_mode: VerifyMode,
         max_tick_height: Option<u64>,
         last_entry_id: &Hash,
         leader_id: Pubkey,
         is_leader: bool,
         to_validator_sender: &TpuRotationSender,
         blob_sender: &BlobSender,
+        erasure_config: ErasureConfig,
     ) -> Self {
         let exit = Arc::new(AtomicBool::new(false));
         let tpu_mode = if is_leader {
-            let (packet_sender, packet_receiver) = channel();
+            let (packet_sender, packet_receiver) = packet_channel(PACKET_CHANNEL_MAX_PACKETS);
             let fetch_stage = FetchStage::new_with_sender(
                 transactions_sockets,
+                transactions_listener,
                 exit.clone(),
                 &packet_sender.clone(),
             );
@@ -101,7 +105,7 @@ impl Tpu {
                 ClusterInfoVoteListener::new(exit.clone(), cluster_info.clone(), packet_sender);
 
             let (sigverify_stage, verified_receiver) =
-                SigVerifyStage::new(packet_receiver, sigverify_disabled);
+                SigVerifyStage::new(packet_receiver, sigverify_mode);
 
             let (banking_stage, entry_receiver) = BankingStage::new(
                 &bank,
@@ -114,7 +118,6 @@ impl Tpu {
             );
 
             let broadcast_service = BroadcastService::new(
-                bank.clone(),
                 broadcast_socket,
                 cluster_info,
                 entry_height,
@@ -123,6 +126,7 @@ impl Tpu {
                 max_tick_height,
                 exit.clone(),
                 blob_sender,
+                erasure_config,
             );
 
             let svcs = LeaderServices::new(
@@ -134,7 +138,8 @@ impl Tpu {
             );
             TpuMode::Leader(svcs)
         } else {
-            let tpu_forwarder = TpuForwarder::new(transactions_sockets, cluster_info);
+            let tpu_forwarder =
+                TpuForwarder::new(transactions_sockets, transactions_listener, cluster_info);
             let svcs = ForwarderServices::new(tpu_forwarder);
             TpuMode::Forwarder(svcs)
         };
@@ -148,6 +153,7 @@ impl Tpu {
     pub fn switch_to_forwarder(
         &mut self,
         transactions_sockets: Vec<UdpSocket>,
+        transactions_listener: TcpListener,
         cluster_info: Arc<RwLock<ClusterInfo>>,
     ) {
         match &self.tpu_mode {
@@ -158,7 +164,8 @@ impl Tpu {
                 svcs.tpu_forwarder.close();
             }
         }
-        let tpu_forwarder = TpuForwarder::new(transactions_sockets, cluster_info);
+        let tpu_forwarder =
+            TpuForwarder::new(transactions_sockets, transactions_listener, cluster_info);
         self.tpu_mode = TpuMode::Forwarder(ForwarderServices::new(tpu_forwarder));
     }
 
@@ -168,15 +175,17 @@ impl Tpu {
         bank: &Arc<Bank>,
         tick_duration: Config,
         transactions_sockets: Vec<UdpSocket>,
+        transactions_listener: TcpListener,
         broadcast_socket: UdpSocket,
         cluster_info: Arc<RwLock<ClusterInfo>>,
-        sigverify_disabled: bool,
+        sigverify_mode: VerifyMode,
         max_tick_height: Option<u64>,
         entry_height: u64,
         last_entry_id: &Hash,
         leader_id: Pubkey,
         to_validator_sender: &TpuRotationSender,
         blob_sender: &BlobSender,
+        erasure_config: ErasureConfig,
     ) {
         match &self.tpu_mode {
             TpuMode::Leader(svcs) => {
@@ -187,9 +196,10 @@ impl Tpu {
             }
         }
         self.exit = Arc::new(AtomicBool::new(false));
-        let (packet_sender, packet_receiver) = channel();
+        let (packet_sender, packet_receiver) = packet_channel(PACKET_CHANNEL_MAX_PACKETS);
         let fetch_stage = FetchStage::new_with_sender(
             transactions_sockets,
+            transactions_listener,
             self.exit.clone(),
             &packet_sender.clone(),
         );
@@ -197,7 +207,7 @@ impl Tpu {
             ClusterInfoVoteListener::new(self.exit.clone(), cluster_info.clone(), packet_sender);
 
         let (sigverify_stage, verified_receiver) =
-            SigVerifyStage::new(packet_receiver, sigverify_disabled);
+            SigVerifyStage::new(packet_receiver, sigverify_mode);
 
         let (banking_stage, entry_receiver) = BankingStage::new(
             &bank,
@@ -210,7 +220,6 @@ impl Tpu {
         );
 
         let broadcast_service = BroadcastService::new(
-            bank.clone(),
             broadcast_socket,
             cluster_info,
             entry_height,
@@ -219,6 +228,7 @@ impl Tpu {
             max_tick_height,
             self.exit.clone(),
             blob_sender,
+            erasure_config,
         );
 
         let svcs = LeaderServices::new(
@@ -0,0 +1,338 @@
+//! The `tpu_client` module sends transactions straight to the TPUs of the
+//! current and upcoming leaders.  A transaction sent just before a leader
+//! rotation then still reaches the node that ends up processing it, instead of
+//! being dropped by a leader whose slot has already ended.
+
+use crate::cluster_info::ClusterInfo;
+use crate::contact_info::ContactInfo;
+use crate::counter::Counter;
+use crate::last_id_queue::MAX_ENTRY_IDS;
+use crate::packet::PACKET_DATA_SIZE;
+use crate::rpc::{RpcEpochInfo, RpcLeaderSchedule};
+use crate::thin_client::ThinClient;
+use bincode::serialize;
+use bs58;
+use log::Level;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+use std::io;
+use std::mem;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, RwLock};
+
+/// Number of leaders, starting with the current one, that each transaction is sent to
+pub const DEFAULT_FANOUT_SLOTS: u64 = 4;
+
+/// Leaders only accept transactions whose last_id is younger than this many ticks
+pub const MAX_LAST_ID_AGE: u64 = MAX_ENTRY_IDS as u64 / 2;
+
+/// The leaders of a contiguous run of slots
+#[derive(Default)]
+struct LeaderScheduleCache {
+    first_slot: u64,
+    leaders: Vec<Pubkey>,
+    last_epoch: Option<u64>,
+}
+
+impl LeaderScheduleCache {
+    fn last_slot(&self) -> u64 {
+        self.first_slot + self.leaders.len() as u64
+    }
+
+    fn contains(&self, slot: u64) -> bool {
+        slot >= self.first_slot && slot < self.last_slot()
+    }
+
+    /// Append the schedule of the epoch after the cached one, or replace the cache if
+    /// `schedule` doesn't follow on from it
+    fn insert(&mut self, schedule: &RpcLeaderSchedule) -> io::Result<()> {
+        let leaders = schedule
+            .leaders
+            .iter()
+            .map(|id| parse_pubkey(id))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        if self.leaders.is_empty() || schedule.first_slot != self.last_slot() {
+            self.first_slot = schedule.first_slot;
+            self.leaders.clear();
+        }
+        self.leaders.extend(leaders);
+        self.last_epoch = Some(schedule.epoch);
+        Ok(())
+    }
+
+    /// The distinct leaders of the `num_slots` slots starting at `slot`, in the order
+    /// they lead
+    fn get_leaders(&self, slot: u64, num_slots: u64) -> Vec<Pubkey> {
+        let mut leaders: Vec<Pubkey> = vec![];
+        for slot in slot..slot + num_slots {
+            if !self.contains(slot) {
+                break;
+            }
+            let leader = self.leaders[(slot - self.first_slot) as usize];
+            if !leaders.contains(&leader) {
+                leaders.push(leader);
+            }
+        }
+        leaders
+    }
+}
+
+fn parse_pubkey(input: &str) -> io::Result<Pubkey> {
+    let pubkey_vec = bs58::decode(input)
+        .into_vec()
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "invalid leader id"))?;
+    if pubkey_vec.len() != mem::size_of::<Pubkey>() {
+        Err(io::Error::new(io::ErrorKind::Other, "invalid leader id"))
+    } else {
+        Ok(Pubkey::new(&pubkey_vec))
+    }
+}
+
+/// A client that follows the leader schedule over RPC and finds the leaders' TPU
+/// addresses through gossip
+pub struct TpuClient {
+    thin_client: ThinClient,
+    cluster_info: Arc<RwLock<ClusterInfo>>,
+    send_socket: UdpSocket,
+    fanout_slots: u64,
+    leader_schedule_cache: LeaderScheduleCache,
+}
+
+impl TpuClient {
+    /// Create a TpuClient that queries the RPC server behind `thin_client` and looks up
+    /// leaders in `cluster_info`, which the caller keeps up to date with gossip.
+    pub fn new(
+        thin_client: ThinClient,
+        cluster_info: Arc<RwLock<ClusterInfo>>,
+        fanout_slots: u64,
+    ) -> io::Result<Self> {
+        assert!(fanout_slots > 0);
+        Ok(TpuClient {
+            thin_client,
+            cluster_info,
+            send_socket: UdpSocket::bind("0.0.0.0:0")?,
+            fanout_slots,
+            leader_schedule_cache: LeaderScheduleCache::default(),
+        })
+    }
+
+    pub fn thin_client(&mut self) -> &mut ThinClient {
+        &mut self.thin_client
+    }
+
+    /// Send a signed Transaction to the current and upcoming leaders. This method
+    /// does not wait for a response.
+    pub fn send_transaction(&mut self, tx: &Transaction) -> io::Result<Signature> {
+        let epoch_info = self.thin_client.get_epoch_info()?;
+        self.send_to_leaders(tx, &epoch_info)?;
+        Ok(tx.signatures[0])
+    }
+
+    /// Send a signed Transaction to the current and upcoming leaders until it is
+    /// confirmed, or until its last_id is too old for any leader to accept it.
+    pub fn send_and_confirm_transaction(&mut self, tx: &Transaction) -> io::Result<Signature> {
+        let signature = tx.signatures[0];
+        loop {
+            match self.thin_client.get_last_id_age(&tx.last_id)? {
+                Some(age) if age < MAX_LAST_ID_AGE => (),
+                _ => {
+                    // The last send may still have landed before the last_id expired
+                    if self.thin_client.check_signature(&signature) {
+                        return Ok(signature);
+                    }
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "last_id expired before the transaction was confirmed",
+                    ));
+                }
+            }
+
+            let epoch_info = self.thin_client.get_epoch_info()?;
+            self.send_to_leaders(tx, &epoch_info)?;
+            if self.thin_client.poll_for_signature(&signature).is_ok() {
+                return Ok(signature);
+            }
+            info!(
+                "transaction {} not confirmed by slot {}, resending",
+                signature, epoch_info.absolute_slot
+            );
+        }
+    }
+
+    fn send_to_leaders(&mut self, tx: &Transaction, epoch_info: &RpcEpochInfo) -> io::Result<()> {
+        let buf = serialize(tx).expect("serialize Transaction in send_to_leaders");
+        if buf.len() > PACKET_DATA_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "transaction doesn't fit in a packet",
+            ));
+        }
+
+        let tpu_addrs = self.leader_tpu_addrs(epoch_info)?;
+        if tpu_addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "no TPU address known for any upcoming leader",
+            ));
+        }
+        inc_new_counter_info!("tpu_client-send_to_leaders", tpu_addrs.len());
+        for tpu_addr in tpu_addrs {
+            self.send_socket.send_to(&buf, &tpu_addr)?;
+        }
+        Ok(())
+    }
+
+    /// TPU addresses of the current leader and the next `fanout_slots - 1` leaders,
+    /// falling back to the leader gossip knows about if none of them can be found
+    fn leader_tpu_addrs(&mut self, epoch_info: &RpcEpochInfo) -> io::Result<Vec<SocketAddr>> {
+        self.update_leader_schedule(epoch_info)?;
+        let leaders = self
+            .leader_schedule_cache
+            .get_leaders(epoch_info.absolute_slot, self.fanout_slots);
+
+        let cluster_info = self.cluster_info.read().unwrap();
+        let mut tpu_addrs: Vec<_> = leaders
+            .iter()
+            .filter_map(|id| cluster_info.lookup(*id))
+            .map(|node_info| node_info.tpu)
+            .filter(ContactInfo::is_valid_address)
+            .collect();
+        if tpu_addrs.is_empty() {
+            if let Some(leader_data) = cluster_info.leader_data() {
+                tpu_addrs.push(leader_data.tpu);
+            }
+        }
+        Ok(tpu_addrs)
+    }
+
+    fn update_leader_schedule(&mut self, epoch_info: &RpcEpochInfo) -> io::Result<()> {
+        let cache = &mut self.leader_schedule_cache;
+        if !cache.contains(epoch_info.absolute_slot) {
+            if let Some(schedule) = self.thin_client.get_leader_schedule(epoch_info.epoch)? {
+                cache.insert(&schedule)?;
+            }
+        }
+
+        // Fetch the next epoch's schedule once the fanout reaches past the cached one
+        let last_fanout_slot = epoch_info.absolute_slot + self.fanout_slots - 1;
+        if cache.contains(epoch_info.absolute_slot)
+            && !cache.contains(last_fanout_slot)
+            && cache.last_epoch == Some(epoch_info.epoch)
+        {
+            if let Some(schedule) = self.thin_client.get_leader_schedule(epoch_info.epoch + 1)? {
+                cache.insert(&schedule)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mk_client;
+    use crate::cluster_info::NodeInfo;
+    use crate::thin_client::new_fullnode;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::signature::{Keypair, KeypairUtil};
+    use solana_sdk::system_transaction::SystemTransaction;
+    use std::fs::remove_dir_all;
+
+    fn schedule(epoch: u64, first_slot: u64, leaders: &[Pubkey]) -> RpcLeaderSchedule {
+        RpcLeaderSchedule {
+            epoch,
+            first_slot,
+            leaders: leaders.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_leader_schedule_cache() {
+        let leader0 = Keypair::new().pubkey();
+        let leader1 = Keypair::new().pubkey();
+        let leader2 = Keypair::new().pubkey();
+
+        let mut cache = LeaderScheduleCache::default();
+        assert!(!cache.contains(0));
+        assert!(cache.get_leaders(0, 4).is_empty());
+
+        cache
+            .insert(&schedule(1, 1, &[leader0, leader1, leader0]))
+            .unwrap();
+        assert!(!cache.contains(0));
+        assert!(cache.contains(1));
+        assert!(cache.contains(3));
+        assert!(!cache.contains(4));
+        assert_eq!(cache.get_leaders(1, 1), vec![leader0]);
+
+        // Leaders are only sent to once, and slots past the cache are ignored
+        assert_eq!(cache.get_leaders(1, 3), vec![leader0, leader1]);
+        assert_eq!(cache.get_leaders(2, 4), vec![leader1, leader0]);
+
+        // The next epoch extends the cache
+        cache
+            .insert(&schedule(2, 4, &[leader2, leader2, leader2]))
+            .unwrap();
+        assert_eq!(cache.last_epoch, Some(2));
+        assert_eq!(cache.get_leaders(2, 4), vec![leader1, leader0, leader2]);
+
+        // A schedule that doesn't follow on replaces it
+        cache.insert(&schedule(4, 10, &[leader1])).unwrap();
+        assert!(!cache.contains(4));
+        assert_eq!(cache.get_leaders(10, 4), vec![leader1]);
+
+        let mut bad_schedule = schedule(5, 11, &[leader0]);
+        bad_schedule.leaders[0] = "deadbeef".to_string();
+        assert!(cache.insert(&bad_schedule).is_err());
+    }
+
+    #[test]
+    fn test_tpu_client_send_and_confirm() {
+        solana_logger::setup();
+        let (server, leader_data, alice, ledger_path) = new_fullnode("tpu_client");
+        let bob_pubkey = Keypair::new().pubkey();
+
+        // The leader's TPU is only known through gossip
+        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new(NodeInfo::new_localhost(
+            Keypair::new().pubkey(),
+            0,
+        ))));
+        cluster_info
+            .write()
+            .unwrap()
+            .insert_info(leader_data.clone());
+
+        let mut client =
+            TpuClient::new(mk_client(&leader_data), cluster_info, DEFAULT_FANOUT_SLOTS).unwrap();
+        let last_id = client.thin_client().get_last_id();
+        let tx = SystemTransaction::new_account(&alice, bob_pubkey, 500, last_id, 0);
+        let signature = client.send_and_confirm_transaction(&tx).unwrap();
+        assert_eq!(signature, tx.signatures[0]);
+        assert_eq!(
+            client.thin_client().poll_get_balance(&bob_pubkey).unwrap(),
+            500
+        );
+
+        // A last_id the cluster doesn't know has already expired
+        let tx = SystemTransaction::new_account(&alice, bob_pubkey, 500, Hash::default(), 0);
+        assert!(client.send_and_confirm_transaction(&tx).is_err());
+
+        let tx = Transaction::new(
+            &alice,
+            &[],
+            Pubkey::default(),
+            &vec![0u8; PACKET_DATA_SIZE],
+            last_id,
+            0,
+        );
+        assert_eq!(
+            client.send_transaction(&tx).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        server.close().unwrap();
+        remove_dir_all(ledger_path).unwrap();
+    }
+}
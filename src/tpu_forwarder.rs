@@ -5,14 +5,16 @@
 use crate::cluster_info::ClusterInfo;
 use crate::contact_info::ContactInfo;
 use crate::counter::Counter;
-use crate::result::Result;
+use crate::packet::{PacketsRecycler, PACKETS_RECYCLER_LIMIT};
+use crate::result::{Error, Result};
 use crate::service::Service;
-use crate::streamer::{self, PacketReceiver};
+use crate::streamer::{self, PacketReceiver, PACKET_CHANNEL_MAX_PACKETS};
+use crate::tcp_receiver::{tcp_receiver, FrameStatus};
 use log::Level;
 use solana_sdk::pubkey::Pubkey;
-use std::net::{SocketAddr, UdpSocket};
+use std::net::{SocketAddr, TcpListener, UdpSocket};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::mpsc::channel;
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::{Arc, RwLock};
 use std::thread::{self, Builder, JoinHandle};
 
@@ -32,32 +34,47 @@ pub struct TpuForwarder {
 }
 
 impl TpuForwarder {
-    fn forward(receiver: &PacketReceiver, cluster_info: &Arc<RwLock<ClusterInfo>>) -> Result<()> {
+    fn forward(
+        receiver: &PacketReceiver,
+        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        exit: &Arc<AtomicBool>,
+    ) -> Result<()> {
         let socket = UdpSocket::bind("0.0.0.0:0")?;
 
         let my_id = cluster_info.read().unwrap().id();
 
-        loop {
-            let msgs = receiver.recv()?;
+        while !exit.load(Ordering::Relaxed) {
+            let (batch, len, num_dropped, _) =
+                match streamer::recv_batch(receiver, PACKET_CHANNEL_MAX_PACKETS) {
+                    Ok(received) => received,
+                    Err(Error::RecvTimeoutError(RecvTimeoutError::Timeout)) => continue,
+                    Err(e) => return Err(e),
+                };
 
-            inc_new_counter_info!(
-                "tpu_forwarder-msgs_received",
-                msgs.read().unwrap().packets.len()
-            );
+            inc_new_counter_info!("tpu_forwarder-msgs_received", len);
+            inc_new_counter_info!("tpu_forwarder-dropped_packets", num_dropped);
 
             let send_addr = get_forwarding_addr(cluster_info.read().unwrap().leader_data(), &my_id);
 
             if let Some(send_addr) = send_addr {
-                msgs.write().unwrap().set_addr(&send_addr);
-                msgs.read().unwrap().send_to(&socket)?;
+                for msgs in batch {
+                    msgs.write().unwrap().set_addr(&send_addr);
+                    msgs.read().unwrap().send_to(&socket)?;
+                }
             }
         }
+        Ok(())
     }
 
-    pub fn new(sockets: Vec<UdpSocket>, cluster_info: Arc<RwLock<ClusterInfo>>) -> Self {
+    pub fn new(
+        sockets: Vec<UdpSocket>,
+        tcp_listener: TcpListener,
+        cluster_info: Arc<RwLock<ClusterInfo>>,
+    ) -> Self {
         let exit = Arc::new(AtomicBool::new(false));
-        let (sender, receiver) = channel();
+        let (sender, receiver) = streamer::packet_channel(PACKET_CHANNEL_MAX_PACKETS);
 
+        let recycler = PacketsRecycler::new("tpu-forwarder", PACKETS_RECYCLER_LIMIT);
         let mut thread_hdls: Vec<_> = sockets
             .into_iter()
             .map(|socket| {
@@ -65,15 +82,24 @@ impl TpuForwarder {
                     Arc::new(socket),
                     exit.clone(),
                     sender.clone(),
+                    &recycler,
                     "tpu-forwarder",
                 )
             })
             .collect();
-
+        thread_hdls.push(tcp_receiver(
+            tcp_listener,
+            exit.clone(),
+            sender.clone(),
+            FrameStatus::Forwarded,
+            "tpu-forwarder",
+        ));
+
+        let exit_ = exit.clone();
         let thread_hdl = Builder::new()
             .name("solana-tpu_forwarder".to_string())
             .spawn(move || {
-                let _ignored = Self::forward(&receiver, &cluster_info);
+                let _ignored = Self::forward(&receiver, &cluster_info, &exit_);
             })
             .unwrap();
 
@@ -103,7 +129,9 @@ impl Service for TpuForwarder {
 mod tests {
     use super::*;
     use crate::contact_info::ContactInfo;
+    use crate::packet::{Packet, SharedPackets};
     use solana_sdk::signature::{Keypair, KeypairUtil};
+    use std::time::Duration;
 
     #[test]
     fn test_get_forwarding_addr() {
@@ -125,4 +153,42 @@ mod tests {
         let leader_data = ContactInfo::new_localhost(alice_id, 0);
         assert!(get_forwarding_addr(Some(&leader_data), &my_id,).is_some());
     }
+
+    #[test]
+    fn test_forward_after_idle() {
+        let leader_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        leader_socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut leader = ContactInfo::new_localhost(Keypair::new().pubkey(), 0);
+        leader.tpu = leader_socket.local_addr().unwrap();
+        let mut cluster_info =
+            ClusterInfo::new(ContactInfo::new_localhost(Keypair::new().pubkey(), 0));
+        cluster_info.insert_info(leader.clone());
+        cluster_info.set_leader(leader.id);
+        let cluster_info = Arc::new(RwLock::new(cluster_info));
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = streamer::packet_channel(PACKET_CHANNEL_MAX_PACKETS);
+        let t_forward = {
+            let exit = exit.clone();
+            thread::spawn(move || TpuForwarder::forward(&receiver, &cluster_info, &exit))
+        };
+
+        // outlast `recv_batch`'s timeout before sending anything
+        thread::sleep(Duration::from_millis(1500));
+        let msgs = SharedPackets::default();
+        let mut packet = Packet::default();
+        packet.data[..3].copy_from_slice(&[1, 2, 3]);
+        packet.meta.size = 3;
+        msgs.write().unwrap().packets.push(packet);
+        sender.send(msgs).unwrap();
+
+        let mut buf = [0u8; 8];
+        let (size, _) = leader_socket.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..size], &[1, 2, 3]);
+
+        exit.store(true, Ordering::Relaxed);
+        t_forward.join().unwrap().unwrap();
+    }
 }
@@ -59,6 +59,7 @@ impl Tvu {
     /// * `bank` - The bank state.
     /// * `entry_height` - Initial ledger height
     /// * `last_entry_id` - Hash of the last entry
+    /// * `tick_hash_count` - Hashes rolled since the last tick of the ledger
     /// * `cluster_info` - The cluster_info state.
     /// * `sockets` - My fetch, repair, and restransmit sockets
     /// * `db_ledger` - the ledger itself
@@ -68,6 +69,7 @@ impl Tvu {
         bank: &Arc<Bank>,
         entry_height: u64,
         last_entry_id: Hash,
+        tick_hash_count: u64,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         sockets: Sockets,
         db_ledger: Arc<DbLedger>,
@@ -117,6 +119,9 @@ impl Tvu {
         let l_entry_height = Arc::new(RwLock::new(entry_height));
         let l_last_entry_id = Arc::new(RwLock::new(last_entry_id));
 
+        let vote_account = voting_keypair
+            .as_ref()
+            .map(|voting_keypair| voting_keypair.pubkey());
         let (replay_stage, ledger_entry_receiver) = ReplayStage::new(
             keypair.pubkey(),
             voting_keypair,
@@ -126,6 +131,7 @@ impl Tvu {
             exit.clone(),
             l_entry_height.clone(),
             l_last_entry_id.clone(),
+            tick_hash_count,
             to_leader_sender,
             entry_stream,
         );
@@ -134,7 +140,9 @@ impl Tvu {
             storage_state,
             ledger_entry_receiver,
             Some(db_ledger),
+            &bank,
             &keypair,
+            vote_account,
             &exit.clone(),
             entry_height,
             storage_rotate_count,
@@ -297,6 +305,7 @@ pub mod tests {
             &bank,
             0,
             cur_hash,
+            0,
             &cref1,
             {
                 Sockets {
@@ -279,8 +279,10 @@ pub fn default_window() -> Window {
 
 #[cfg(test)]
 mod test {
-    use crate::packet::{Blob, Packet, Packets, SharedBlob, PACKET_DATA_SIZE};
-    use crate::streamer::{receiver, responder, PacketReceiver};
+    use crate::packet::{Blob, Packet, Packets, PacketsRecycler, SharedBlob, PACKET_DATA_SIZE};
+    use crate::streamer::{
+        packet_channel, receiver, responder, PacketReceiver, PACKET_CHANNEL_MAX_PACKETS,
+    };
     use crate::window::{calculate_max_repair, new_window, Window, WindowUtil};
     use solana_sdk::pubkey::Pubkey;
     use std::io;
@@ -317,11 +319,12 @@ mod test {
         let addr = read.local_addr().unwrap();
         let send = UdpSocket::bind("127.0.0.1:0").expect("bind");
         let exit = Arc::new(AtomicBool::new(false));
-        let (s_reader, r_reader) = channel();
+        let (s_reader, r_reader) = packet_channel(PACKET_CHANNEL_MAX_PACKETS);
         let t_receiver = receiver(
             Arc::new(read),
             exit.clone(),
             s_reader,
+            &PacketsRecycler::default(),
             "window-streamer-test",
         );
         let t_responder = {
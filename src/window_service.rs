@@ -49,6 +49,26 @@ fn repair_backoff(last: &mut u64, times: &mut usize, consumed: u64) -> bool {
     thread_rng().gen_range(0, *times as u64) == 0
 }
 
+// Advertise the slots of the current epoch that are complete in the ledger, which are all
+// the slots before the one the next blob to consume is in
+fn push_epoch_slots(
+    cluster_info: &Arc<RwLock<ClusterInfo>>,
+    leader_scheduler: &Arc<RwLock<LeaderScheduler>>,
+    consumed_slot: u64,
+) {
+    if consumed_slot == 0 {
+        return;
+    }
+    let last_complete_slot = consumed_slot - 1;
+    let (epoch, first_slot) = {
+        let leader_scheduler = leader_scheduler.read().unwrap();
+        let epoch = leader_scheduler.slot_to_epoch(last_complete_slot);
+        (epoch, leader_scheduler.first_slot_in_epoch(epoch))
+    };
+    let slots = (first_slot..=last_complete_slot).collect();
+    cluster_info.write().unwrap().push_epoch_slots(epoch, slots);
+}
+
 #[allow(clippy::too_many_arguments)]
 fn recv_window(
     db_ledger: &Arc<DbLedger>,
@@ -136,6 +156,7 @@ pub fn window_service(
         .spawn(move || {
             let mut tick_height_ = tick_height;
             let mut last = entry_height;
+            let mut last_consumed_slot = 0;
             let mut times = 0;
             let id = cluster_info.read().unwrap().id();
             trace!("{}: RECV_WINDOW started", id);
@@ -170,6 +191,11 @@ pub fn window_service(
                     let received = meta.received;
                     let consumed = meta.consumed;
 
+                    if meta.consumed_slot != last_consumed_slot {
+                        last_consumed_slot = meta.consumed_slot;
+                        push_epoch_slots(&cluster_info, &leader_scheduler, last_consumed_slot);
+                    }
+
                     // Consumed should never be bigger than received
                     assert!(consumed <= received);
                     if received == consumed {
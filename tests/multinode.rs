@@ -1085,7 +1085,7 @@ fn test_leader_to_validator_transition() {
 
     // Check the ledger to make sure it's the right height, we should've
     // transitioned after tick_height == bootstrap_height
-    let (bank, _, _) = Fullnode::new_bank_from_ledger(
+    let (bank, _, _, _) = Fullnode::new_bank_from_ledger(
         &leader_ledger_path,
         Arc::new(RwLock::new(LeaderScheduler::default())),
     );
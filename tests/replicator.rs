@@ -5,7 +5,6 @@ extern crate log;
 #[macro_use]
 extern crate serde_json;
 
-use bincode::deserialize;
 use solana::client::mk_client;
 use solana::cluster_info::{ClusterInfo, Node, NodeInfo};
 use solana::db_ledger::DbLedger;
@@ -13,7 +12,7 @@ use solana::db_ledger::{create_tmp_sample_ledger, get_tmp_ledger_path, tmp_copy_
 use solana::entry::Entry;
 use solana::fullnode::{Fullnode, FullnodeConfig};
 use solana::leader_scheduler::LeaderScheduler;
-use solana::replicator::Replicator;
+use solana::replicator::{Replicator, ReplicatorConfig};
 use solana::storage_stage::STORAGE_ROTATE_TEST_COUNT;
 use solana::streamer::blob_receiver;
 use solana::voting_keypair::VotingKeypair;
@@ -102,7 +101,7 @@ fn test_replicator_startup() {
             sleep(Duration::from_millis(200));
         }
 
-        let replicator_keypair = Keypair::new();
+        let replicator_keypair = Arc::new(Keypair::new());
 
         info!("giving replicator tokens..");
 
@@ -132,6 +131,7 @@ fn test_replicator_startup() {
             &leader_info,
             &replicator_keypair,
             None,
+            &ReplicatorConfig::default(),
         )
         .unwrap();
 
@@ -166,7 +166,7 @@ fn test_replicator_startup() {
                 for b in blobs {
                     let br = b.read().unwrap();
                     assert!(br.index() == repair_index);
-                    let entry: Entry = deserialize(&br.data()[..br.meta.size]).unwrap();
+                    let entry = Entry::from_blob(&br).unwrap();
                     info!("entry: {:?}", entry);
                     assert_ne!(entry.id, Hash::default());
                     received_blob = true;
@@ -230,7 +230,7 @@ fn test_replicator_startup_leader_hang() {
     let leader_ledger_path = "replicator_test_leader_ledger";
 
     {
-        let replicator_keypair = Keypair::new();
+        let replicator_keypair = Arc::new(Keypair::new());
 
         info!("starting replicator node");
         let replicator_node = Node::new_localhost_with_pubkey(replicator_keypair.pubkey());
@@ -244,6 +244,7 @@ fn test_replicator_startup_leader_hang() {
             &leader_info,
             &replicator_keypair,
             Some(Duration::from_secs(3)),
+            &ReplicatorConfig::default(),
         );
 
         assert!(replicator_res.is_err());
@@ -307,7 +308,7 @@ fn test_replicator_startup_ledger_hang() {
         );
 
         info!("starting replicator node");
-        let bad_keys = Keypair::new();
+        let bad_keys = Arc::new(Keypair::new());
         let mut replicator_node = Node::new_localhost_with_pubkey(bad_keys.pubkey());
 
         // Pass bad TVU sockets to prevent successful ledger download
@@ -321,6 +322,7 @@ fn test_replicator_startup_ledger_hang() {
             &leader_info,
             &bad_keys,
             Some(Duration::from_secs(3)),
+            &ReplicatorConfig::default(),
         );
 
         assert!(replicator_res.is_err());
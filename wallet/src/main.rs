@@ -195,6 +195,17 @@ fn main() -> Result<(), Box<dyn error::Error>> {
         .subcommand(
             SubCommand::with_name("get-transaction-count").about("Get current transaction count"),
         )
+        .subcommand(
+            SubCommand::with_name("leader-schedule")
+                .about("Get the leader of each slot in an epoch")
+                .arg(
+                    Arg::with_name("epoch")
+                        .index(1)
+                        .value_name("EPOCH")
+                        .takes_value(true)
+                        .help("The epoch to get the schedule of [default: current epoch]"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("pay")
                 .about("Send a payment")
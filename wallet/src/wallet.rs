@@ -4,7 +4,7 @@ use chrono::prelude::*;
 use clap::ArgMatches;
 use serde_json;
 use serde_json::json;
-use solana::rpc::{RpcSignatureStatus, RPC_PORT};
+use solana::rpc::{RpcEpochInfo, RpcLeaderSchedule, RpcSignatureStatus, RPC_PORT};
 #[cfg(test)]
 use solana::rpc_mock::{request_airdrop_transaction, MockRpcClient as RpcClient};
 #[cfg(not(test))]
@@ -41,6 +41,8 @@ pub enum WalletCommand {
     Confirm(Signature),
     Deploy(String),
     GetTransactionCount,
+    // LeaderSchedule(epoch), defaulting to the current epoch
+    LeaderSchedule(Option<u64>),
     // Pay(tokens, to, timestamp, timestamp_pubkey, witness(es), cancelable)
     Pay(
         u64,
@@ -165,6 +167,13 @@ pub fn parse_command(
                 .to_string(),
         )),
         ("get-transaction-count", Some(_matches)) => Ok(WalletCommand::GetTransactionCount),
+        ("leader-schedule", Some(leader_schedule_matches)) => {
+            let epoch = match leader_schedule_matches.value_of("epoch") {
+                Some(epoch) => Some(epoch.parse()?),
+                None => None,
+            };
+            Ok(WalletCommand::LeaderSchedule(epoch))
+        }
         ("pay", Some(pay_matches)) => {
             let tokens = pay_matches.value_of("tokens").unwrap().parse()?;
             let to = if pay_matches.is_present("to") {
@@ -499,6 +508,39 @@ pub fn process_command(config: &WalletConfig) -> Result<String, Box<dyn error::E
                 ))?,
             }
         }
+        // Print the leader of each slot in an epoch
+        WalletCommand::LeaderSchedule(epoch) => {
+            let epoch = match epoch {
+                Some(epoch) => epoch,
+                None => get_epoch_info(&rpc_client)?.epoch,
+            };
+            let params = json!([epoch]);
+            let schedule = rpc_client.retry_make_rpc_request(
+                1,
+                &RpcRequest::GetLeaderSchedule,
+                Some(params),
+                5,
+            )?;
+            if schedule.is_null() {
+                Err(WalletError::RpcRequestError(format!(
+                    "No leader schedule for epoch {}",
+                    epoch
+                )))?
+            }
+            let schedule: RpcLeaderSchedule = serde_json::from_value(schedule).map_err(|_| {
+                WalletError::RpcRequestError("Received bad leader schedule".to_string())
+            })?;
+
+            let mut output = format!("Epoch {} leader schedule:", schedule.epoch);
+            for (i, leader) in schedule.leaders.iter().enumerate() {
+                output.push_str(&format!(
+                    "\n  {:>10}  {}",
+                    schedule.first_slot + i as u64,
+                    leader
+                ));
+            }
+            Ok(output)
+        }
         // If client has positive balance, pay tokens to another address
         WalletCommand::Pay(tokens, to, timestamp, timestamp_pubkey, ref witnesses, cancelable) => {
             let last_id = get_last_id(&rpc_client)?;
@@ -672,6 +714,13 @@ fn get_last_id(rpc_client: &RpcClient) -> Result<Hash, Box<dyn error::Error>> {
     Ok(Hash::new(&last_id_vec))
 }
 
+fn get_epoch_info(rpc_client: &RpcClient) -> Result<RpcEpochInfo, Box<dyn error::Error>> {
+    let result = rpc_client.retry_make_rpc_request(1, &RpcRequest::GetEpochInfo, None, 5)?;
+    let epoch_info = serde_json::from_value(result)
+        .map_err(|_| WalletError::RpcRequestError("Received bad epoch info".to_string()))?;
+    Ok(epoch_info)
+}
+
 fn send_tx(rpc_client: &RpcClient, tx: &Transaction) -> Result<String, Box<dyn error::Error>> {
     let serialized = serialize(tx).unwrap();
     let params = json!([serialized]);
@@ -888,6 +937,17 @@ mod tests {
                 SubCommand::with_name("get-transaction-count")
                     .about("Get current transaction count"),
             )
+            .subcommand(
+                SubCommand::with_name("leader-schedule")
+                    .about("Get the leader of each slot in an epoch")
+                    .arg(
+                        Arg::with_name("epoch")
+                            .index(1)
+                            .value_name("EPOCH")
+                            .takes_value(true)
+                            .help("The epoch to get the schedule of [default: current epoch]"),
+                    ),
+            )
             .subcommand(
                 SubCommand::with_name("pay")
                     .about("Send a payment")
@@ -1041,6 +1101,28 @@ mod tests {
             WalletCommand::Deploy("/Users/test/program.o".to_string())
         );
 
+        // Test LeaderSchedule Subcommand
+        let test_leader_schedule = test_commands
+            .clone()
+            .get_matches_from(vec!["test", "leader-schedule"]);
+        assert_eq!(
+            parse_command(pubkey, &test_leader_schedule).unwrap(),
+            WalletCommand::LeaderSchedule(None)
+        );
+        let test_leader_schedule =
+            test_commands
+                .clone()
+                .get_matches_from(vec!["test", "leader-schedule", "7"]);
+        assert_eq!(
+            parse_command(pubkey, &test_leader_schedule).unwrap(),
+            WalletCommand::LeaderSchedule(Some(7))
+        );
+        let test_bad_epoch =
+            test_commands
+                .clone()
+                .get_matches_from(vec!["test", "leader-schedule", "seven"]);
+        assert!(parse_command(pubkey, &test_bad_epoch).is_err());
+
         // Test Simple Pay Subcommand
         let test_pay =
             test_commands
@@ -1189,6 +1271,15 @@ mod tests {
         config.command = WalletCommand::GetTransactionCount;
         assert_eq!(process_command(&config).unwrap(), "1234");
 
+        let schedule = format!(
+            "Epoch 1 leader schedule:\n           1  {0}\n           2  {0}\n           3  {0}\n           4  {0}",
+            PUBKEY
+        );
+        config.command = WalletCommand::LeaderSchedule(None);
+        assert_eq!(process_command(&config).unwrap(), schedule);
+        config.command = WalletCommand::LeaderSchedule(Some(1));
+        assert_eq!(process_command(&config).unwrap(), schedule);
+
         let bob_pubkey = Keypair::new().pubkey();
         config.command = WalletCommand::Pay(10, bob_pubkey, None, None, None, None);
         let signature = process_command(&config);
@@ -1277,6 +1368,12 @@ mod tests {
         config.command = WalletCommand::GetTransactionCount;
         assert!(process_command(&config).is_err());
 
+        config.command = WalletCommand::LeaderSchedule(None);
+        assert!(process_command(&config).is_err());
+
+        config.command = WalletCommand::LeaderSchedule(Some(1));
+        assert!(process_command(&config).is_err());
+
         config.command = WalletCommand::Pay(10, bob_pubkey, None, None, None, None);
         assert!(process_command(&config).is_err());
 